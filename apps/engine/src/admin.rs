@@ -0,0 +1,123 @@
+//! Local admin/control HTTP server for a running Connect daemon.
+//!
+//! Exposes read-only status (`GET /status`) and nudges (`POST /flush`,
+//! `POST /spool/replay`) so a background daemon can be inspected or kicked
+//! without restarting it or scraping log files. Bound to `--admin-addr`
+//! (disabled unless that flag is given).
+//!
+//! `GET /status` opens its own short-lived SQLite connection — safe
+//! alongside the daemon's connection since `state/db.rs` enables WAL mode.
+//! The POST endpoints can't run the scan/ship themselves (only the daemon's
+//! tokio task owns the `ShipperClient`), so they just enqueue a command onto
+//! a channel that the main loop drains on its next iteration.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use tokio::sync::mpsc;
+
+use crate::state::db::open_db;
+use crate::state::file_state::FileState;
+use crate::state::spool::Spool;
+
+/// Commands the admin server asks the daemon's main loop to run.
+#[derive(Debug, Clone, Copy)]
+pub enum AdminCommand {
+    Flush,
+    SpoolReplay,
+}
+
+/// Start the admin HTTP server on its own OS thread.
+pub fn serve(addr: &str, db_path: Option<PathBuf>, cmd_tx: mpsc::UnboundedSender<AdminCommand>) -> Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let bound_addr = listener.local_addr()?;
+
+    std::thread::Builder::new()
+        .name("admin-http".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => handle_connection(stream, db_path.as_deref(), &cmd_tx),
+                    Err(e) => tracing::debug!("admin listener accept error: {}", e),
+                }
+            }
+        })?;
+
+    tracing::info!("Admin API listening on http://{}", bound_addr);
+    Ok(())
+}
+
+fn handle_connection(
+    mut stream: std::net::TcpStream,
+    db_path: Option<&std::path::Path>,
+    cmd_tx: &mpsc::UnboundedSender<AdminCommand>,
+) {
+    let mut request_line = String::new();
+    {
+        let mut reader = BufReader::new(&stream);
+        if reader.read_line(&mut request_line).is_err() {
+            return;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+
+    let (status, body) = match (method, path) {
+        ("GET", "/status") => match status_json(db_path) {
+            Ok(body) => ("200 OK", body),
+            Err(e) => (
+                "500 Internal Server Error",
+                serde_json::json!({ "error": e.to_string() }).to_string(),
+            ),
+        },
+        ("POST", "/flush") => {
+            let _ = cmd_tx.send(AdminCommand::Flush);
+            ("202 Accepted", r#"{"status":"flush triggered"}"#.to_string())
+        }
+        ("POST", "/spool/replay") => {
+            let _ = cmd_tx.send(AdminCommand::SpoolReplay);
+            ("202 Accepted", r#"{"status":"spool replay triggered"}"#.to_string())
+        }
+        _ => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn status_json(db_path: Option<&std::path::Path>) -> Result<String> {
+    let conn = open_db(db_path)?;
+    let file_state = FileState::new(&conn);
+    let spool = Spool::new(&conn);
+
+    let files_json: Vec<_> = file_state
+        .list_all()?
+        .into_iter()
+        .map(|f| {
+            serde_json::json!({
+                "path": f.path,
+                "provider": f.provider,
+                "acked_offset": f.acked_offset,
+                "queued_offset": f.queued_offset,
+                "lag_bytes": f.queued_offset.saturating_sub(f.acked_offset),
+                "session_id": f.session_id,
+            })
+        })
+        .collect();
+
+    let summary = serde_json::json!({
+        "tracked_files": files_json,
+        "spool_pending": spool.pending_count()?,
+        "spool_bytes": spool.total_size()?,
+    });
+    Ok(serde_json::to_string_pretty(&summary)?)
+}
@@ -2,12 +2,15 @@
 
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use crossbeam_channel::bounded;
 use rayon::prelude::*;
 
 use crate::pipeline;
 use crate::pipeline::compressor::CompressionAlgo;
+use crate::progress::ProgressSender;
+use crate::state::jobs::{JobFileState, Jobs};
 
 pub struct BenchResult {
     pub files_processed: usize,
@@ -55,6 +58,7 @@ impl BenchResult {
 
 /// Per-file result collected from parallel workers.
 struct FileResult {
+    path: PathBuf,
     bytes: u64,
     events: usize,
     parse_secs: f64,
@@ -138,6 +142,108 @@ pub fn run_benchmark_with(files: &[PathBuf], compress: bool, algo: CompressionAl
     }
 }
 
+/// Run benchmark sequentially, resumable via the `job_name` job (see
+/// `state::jobs`): files already marked `Done` from a prior run are skipped,
+/// and a file that fails to parse is recorded `Failed` with its error rather
+/// than silently skipped. Reports progress over `progress` instead of the
+/// periodic `eprintln!` the non-resumable variants use.
+pub fn run_benchmark_resumable(
+    conn: &rusqlite::Connection,
+    job_name: &str,
+    files: &[PathBuf],
+    compress: bool,
+    algo: CompressionAlgo,
+    progress: Option<&ProgressSender>,
+) -> anyhow::Result<BenchResult> {
+    let jobs = Jobs::new(conn);
+    let path_strs: Vec<String> = files
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    let job_id = jobs.start(job_name, &path_strs)?;
+    let pending = jobs.pending_paths(job_id)?;
+    let already_done = path_strs.len() - pending.len();
+
+    let overall_start = Instant::now();
+    let mut total_bytes: u64 = 0;
+    let mut total_events: usize = 0;
+    let mut parse_time: f64 = 0.0;
+    let mut compress_time: f64 = 0.0;
+    let mut files_ok: usize = 0;
+
+    for (i, path_str) in pending.iter().enumerate() {
+        let path = PathBuf::from(path_str);
+        let file_size = match std::fs::metadata(&path) {
+            Ok(m) => m.len(),
+            Err(e) => {
+                jobs.set_state(job_id, path_str, &JobFileState::Failed(e.to_string()))?;
+                if let Some(tx) = progress {
+                    tx.send_modify(|p| p.last_error = Some((path_str.clone(), e.to_string())));
+                }
+                continue;
+            }
+        };
+
+        let parse_start = Instant::now();
+        let result = match pipeline::parser::parse_session_file(&path, 0) {
+            Ok(r) => r,
+            Err(e) => {
+                jobs.set_state(job_id, path_str, &JobFileState::Failed(e.to_string()))?;
+                if let Some(tx) = progress {
+                    tx.send_modify(|p| p.last_error = Some((path_str.clone(), e.to_string())));
+                }
+                continue;
+            }
+        };
+        parse_time += parse_start.elapsed().as_secs_f64();
+
+        if compress && !result.events.is_empty() {
+            let compress_start = Instant::now();
+            let source_path = path.to_string_lossy();
+            let _ = pipeline::compressor::build_and_compress_with(
+                &result.metadata.session_id,
+                &result.events,
+                &result.metadata,
+                &source_path,
+                "claude",
+                algo,
+            );
+            compress_time += compress_start.elapsed().as_secs_f64();
+        }
+
+        total_bytes += file_size;
+        total_events += result.events.len();
+        files_ok += 1;
+        jobs.set_state(job_id, path_str, &JobFileState::Done)?;
+
+        if let Some(tx) = progress {
+            let elapsed = overall_start.elapsed().as_secs_f64().max(0.001);
+            let mb = total_bytes as f64 / 1_048_576.0;
+            let done = already_done + i + 1;
+            tx.send_modify(|p| {
+                p.files_done = done;
+                p.bytes_done = total_bytes;
+                p.events_done = total_events;
+                p.throughput_mb_s = mb / elapsed;
+            });
+        }
+    }
+
+    let total_seconds = overall_start.elapsed().as_secs_f64();
+
+    Ok(BenchResult {
+        files_processed: files_ok,
+        total_bytes,
+        total_events,
+        parse_seconds: parse_time,
+        compress_seconds: compress_time,
+        total_seconds,
+        peak_rss_mb: get_rss_mb(),
+        parallel: false,
+        workers: 1,
+    })
+}
+
 /// Run benchmark with rayon parallel file processing.
 pub fn run_benchmark_parallel(files: &[PathBuf], compress: bool, workers: usize) -> BenchResult {
     run_benchmark_parallel_with(files, compress, workers, CompressionAlgo::Gzip)
@@ -209,6 +315,7 @@ pub fn run_benchmark_parallel_with(files: &[PathBuf], compress: bool, workers: u
             }
 
             Some(FileResult {
+                path: path.clone(),
                 bytes: file_size,
                 events: event_count,
                 parse_secs,
@@ -239,6 +346,173 @@ pub fn run_benchmark_parallel_with(files: &[PathBuf], compress: bool, workers: u
     }
 }
 
+/// Run benchmark with rayon parallel file processing, streaming per-file
+/// results to a dedicated aggregator thread over a bounded channel instead of
+/// collecting every `FileResult` into a `Vec` first (what
+/// `run_benchmark_parallel_with` does, at the cost of peak memory scaling
+/// with file count and nothing being reported until the whole run finishes).
+///
+/// The aggregator buffers the first `BUFFER_WINDOW` worth of results, sorted
+/// by path, so the earliest output reads in a stable order even though rayon
+/// workers finish out of order; once that window elapses it switches to
+/// printing each result live as it arrives.
+pub fn run_benchmark_streaming(
+    files: &[PathBuf],
+    compress: bool,
+    workers: usize,
+    algo: CompressionAlgo,
+) -> BenchResult {
+    const BUFFER_WINDOW: Duration = Duration::from_millis(500);
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(workers)
+        .build_global()
+        .ok();
+
+    let overall_start = Instant::now();
+    let total_files = files.len();
+    let (tx, rx) = bounded::<FileResult>(workers * 4);
+
+    let aggregator = std::thread::spawn(move || {
+        let mut total_bytes: u64 = 0;
+        let mut total_events: usize = 0;
+        let mut parse_seconds: f64 = 0.0;
+        let mut compress_seconds: f64 = 0.0;
+        let mut files_processed: usize = 0;
+
+        let window_start = Instant::now();
+        let mut buffer: Vec<FileResult> = Vec::new();
+        let mut streaming = false;
+
+        for result in rx.iter() {
+            if !streaming {
+                buffer.push(result);
+                if window_start.elapsed() < BUFFER_WINDOW {
+                    continue;
+                }
+                buffer.sort_by(|a, b| a.path.cmp(&b.path));
+                streaming = true;
+                for buffered in buffer.drain(..) {
+                    files_processed += 1;
+                    total_bytes += buffered.bytes;
+                    total_events += buffered.events;
+                    parse_seconds += buffered.parse_secs;
+                    compress_seconds += buffered.compress_secs;
+                    print_streaming_line(&buffered, files_processed, total_files, total_bytes, overall_start);
+                }
+                continue;
+            }
+
+            files_processed += 1;
+            total_bytes += result.bytes;
+            total_events += result.events;
+            parse_seconds += result.parse_secs;
+            compress_seconds += result.compress_secs;
+            print_streaming_line(&result, files_processed, total_files, total_bytes, overall_start);
+        }
+
+        // The whole run finished inside the buffering window (small corpus) —
+        // flush it now, still sorted by path.
+        if !buffer.is_empty() {
+            buffer.sort_by(|a, b| a.path.cmp(&b.path));
+            for buffered in buffer.drain(..) {
+                files_processed += 1;
+                total_bytes += buffered.bytes;
+                total_events += buffered.events;
+                parse_seconds += buffered.parse_secs;
+                compress_seconds += buffered.compress_secs;
+                print_streaming_line(&buffered, files_processed, total_files, total_bytes, overall_start);
+            }
+        }
+
+        let total_seconds = overall_start.elapsed().as_secs_f64();
+        BenchResult {
+            files_processed,
+            total_bytes,
+            total_events,
+            parse_seconds,
+            compress_seconds,
+            total_seconds,
+            peak_rss_mb: get_rss_mb(),
+            parallel: true,
+            workers,
+        }
+    });
+
+    files.par_iter().for_each(|path| {
+        let file_size = match std::fs::metadata(path) {
+            Ok(m) => m.len(),
+            Err(_) => return,
+        };
+
+        let parse_start = Instant::now();
+        let result = match pipeline::parser::parse_session_file(path, 0) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+        let parse_secs = parse_start.elapsed().as_secs_f64();
+
+        let compress_secs = if compress && !result.events.is_empty() {
+            let compress_start = Instant::now();
+            let source_path = path.to_string_lossy();
+            let _ = pipeline::compressor::build_and_compress_with(
+                &result.metadata.session_id,
+                &result.events,
+                &result.metadata,
+                &source_path,
+                "claude",
+                algo,
+            );
+            compress_start.elapsed().as_secs_f64()
+        } else {
+            0.0
+        };
+
+        // Send blocks if the aggregator falls behind, which is the point: it
+        // bounds memory to `workers * 4` in-flight results instead of the
+        // whole corpus.
+        let _ = tx.send(FileResult {
+            path: path.clone(),
+            bytes: file_size,
+            events: result.events.len(),
+            parse_secs,
+            compress_secs,
+        });
+    });
+
+    drop(tx);
+    aggregator.join().unwrap_or(BenchResult {
+        files_processed: 0,
+        total_bytes: 0,
+        total_events: 0,
+        parse_seconds: 0.0,
+        compress_seconds: 0.0,
+        total_seconds: overall_start.elapsed().as_secs_f64(),
+        peak_rss_mb: get_rss_mb(),
+        parallel: true,
+        workers,
+    })
+}
+
+fn print_streaming_line(
+    result: &FileResult,
+    files_done: usize,
+    total_files: usize,
+    total_bytes: u64,
+    overall_start: Instant,
+) {
+    let elapsed = overall_start.elapsed().as_secs_f64().max(0.001);
+    let mb = total_bytes as f64 / 1_048_576.0;
+    eprintln!(
+        "  [{}/{}] {} ({} events, {:.1} MB/s overall)",
+        files_done,
+        total_files,
+        result.path.display(),
+        result.events,
+        mb / elapsed,
+    );
+}
+
 /// Discover all JSONL session files under ~/.claude/projects/
 pub fn discover_session_files() -> Vec<PathBuf> {
     let home = std::env::var("HOME").unwrap_or_else(|_| "/Users/davidrose".to_string());
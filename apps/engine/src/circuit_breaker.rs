@@ -0,0 +1,223 @@
+//! Circuit breaker guarding outbound shipping against a down server.
+//!
+//! Sits between the spool drain loop and `ShipTarget`: `ConsecutiveErrorTracker`
+//! already counts ship failures for logging, but nothing previously stopped
+//! the daemon from hammering the server on every batch while it's down. This
+//! breaker adds the missing throttle, with three states:
+//! - `Closed`: normal operation, requests pass through.
+//! - `Open`: requests are rejected immediately (no network call) until a
+//!   cooldown elapses. The cooldown follows the same exponential bounds as
+//!   the spool's own retry backoff (5s → 3600s, see `state::spool`), growing
+//!   each time the breaker reopens.
+//! - `HalfOpen`: exactly one probe request is allowed through; success closes
+//!   the breaker, failure reopens it with a longer cooldown.
+//!
+//! The daemon's ship paths (watcher/resync/fallback-scan) all gate on
+//! `is_offline()`, which is `true` for both `Open` and `HalfOpen` — so the
+//! probe that actually drives `Open` → `HalfOpen` → `Closed`/`Open` has to
+//! come from somewhere that calls `allow_request()` directly rather than
+//! going through that same gate. `daemon::worker::HealthCheckWorker` is that
+//! caller: its periodic ping *is* the breaker's probe (see
+//! `daemon::OfflineState::allow_probe`/`mark_probe_failed`), so a real ship
+//! attempt is gated by `is_offline()` the whole time and the breaker only
+//! flips back to `Closed` once the health check's own probe succeeds.
+
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+
+/// Consecutive failures before the breaker opens.
+const FAILURE_THRESHOLD: u32 = 5;
+
+/// Cooldown bounds — same as `state::spool`'s exponential backoff.
+const COOLDOWN_BASE_SECS: f64 = 5.0;
+const COOLDOWN_MAX_SECS: f64 = 3600.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl BreakerState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half_open",
+        }
+    }
+}
+
+struct Inner {
+    state: BreakerState,
+    cooldown_until: Option<DateTime<Utc>>,
+    consecutive_failures: u32,
+    /// Times the breaker has opened since the last close — widens each
+    /// reopen's cooldown along the same curve as `Spool::mark_failed`.
+    open_count: u32,
+}
+
+/// Shared circuit breaker — cheap to clone, one instance per daemon shared
+/// across its async tasks.
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl CircuitBreaker {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                state: BreakerState::Closed,
+                cooldown_until: None,
+                consecutive_failures: 0,
+                open_count: 0,
+            })),
+        }
+    }
+
+    /// Whether a ship attempt should proceed. While `Open`, rejects until the
+    /// cooldown elapses, then flips to `HalfOpen` and allows exactly one
+    /// probe through (subsequent calls reject until that probe is recorded).
+    pub fn allow_request(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            BreakerState::Closed => true,
+            BreakerState::HalfOpen => false,
+            BreakerState::Open => {
+                let ready = inner.cooldown_until.map_or(true, |until| Utc::now() >= until);
+                if ready {
+                    inner.state = BreakerState::HalfOpen;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    /// Record the outcome of a request `allow_request()` permitted.
+    pub fn record(&self, success: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if success {
+            inner.state = BreakerState::Closed;
+            inner.cooldown_until = None;
+            inner.consecutive_failures = 0;
+            inner.open_count = 0;
+            return;
+        }
+
+        inner.consecutive_failures += 1;
+        let should_open = match inner.state {
+            BreakerState::HalfOpen => true, // the probe failed — reopen
+            _ => inner.consecutive_failures >= FAILURE_THRESHOLD,
+        };
+        if should_open {
+            let cooldown_secs =
+                (COOLDOWN_BASE_SECS * 2.0_f64.powi(inner.open_count as i32)).min(COOLDOWN_MAX_SECS);
+            inner.cooldown_until = Some(Utc::now() + chrono::Duration::seconds(cooldown_secs as i64));
+            inner.open_count += 1;
+            inner.state = BreakerState::Open;
+        }
+    }
+
+    /// Current state, for heartbeat/status reporting.
+    pub fn state(&self) -> BreakerState {
+        self.inner.lock().unwrap().state
+    }
+
+    /// True unless the breaker is `Closed` — used to gate the watcher,
+    /// fallback scan, and spool replay the same way `OfflineState` did.
+    pub fn is_offline(&self) -> bool {
+        !matches!(self.state(), BreakerState::Closed)
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closed_allows_requests() {
+        let b = CircuitBreaker::new();
+        assert_eq!(b.state(), BreakerState::Closed);
+        assert!(b.allow_request());
+    }
+
+    #[test]
+    fn test_opens_after_threshold_failures() {
+        let b = CircuitBreaker::new();
+        for _ in 0..FAILURE_THRESHOLD - 1 {
+            b.record(false);
+            assert_eq!(b.state(), BreakerState::Closed, "should stay closed below threshold");
+        }
+        b.record(false);
+        assert_eq!(b.state(), BreakerState::Open);
+        assert!(!b.allow_request(), "open breaker should reject immediately");
+    }
+
+    #[test]
+    fn test_success_resets_to_closed() {
+        let b = CircuitBreaker::new();
+        for _ in 0..FAILURE_THRESHOLD {
+            b.record(false);
+        }
+        assert_eq!(b.state(), BreakerState::Open);
+        // Can't wait out the cooldown in a unit test, but a later probe
+        // success should still reset counters once allowed through.
+        b.record(true);
+        assert_eq!(b.state(), BreakerState::Closed);
+        assert!(b.allow_request());
+    }
+
+    #[test]
+    fn test_half_open_allows_single_probe() {
+        let b = CircuitBreaker::new();
+        for _ in 0..FAILURE_THRESHOLD {
+            b.record(false);
+        }
+        // Force the cooldown to have already elapsed.
+        {
+            let mut inner = b.inner.lock().unwrap();
+            inner.cooldown_until = Some(Utc::now() - chrono::Duration::seconds(1));
+        }
+        assert!(b.allow_request(), "cooldown elapsed, first probe should be allowed");
+        assert_eq!(b.state(), BreakerState::HalfOpen);
+        assert!(!b.allow_request(), "a second concurrent probe should be rejected");
+    }
+
+    #[test]
+    fn test_failed_probe_reopens_breaker() {
+        let b = CircuitBreaker::new();
+        for _ in 0..FAILURE_THRESHOLD {
+            b.record(false);
+        }
+        {
+            let mut inner = b.inner.lock().unwrap();
+            inner.cooldown_until = Some(Utc::now() - chrono::Duration::seconds(1));
+        }
+        assert!(b.allow_request());
+        assert_eq!(b.state(), BreakerState::HalfOpen);
+
+        b.record(false);
+        assert_eq!(b.state(), BreakerState::Open, "failed probe should reopen");
+    }
+
+    #[test]
+    fn test_is_offline_matches_state() {
+        let b = CircuitBreaker::new();
+        assert!(!b.is_offline());
+        for _ in 0..FAILURE_THRESHOLD {
+            b.record(false);
+        }
+        assert!(b.is_offline());
+    }
+}
@@ -16,8 +16,82 @@ pub struct ShipperConfig {
     pub workers: usize,
     pub max_batch_bytes: u64,
     pub timeout_seconds: u64,
-    pub max_retries_429: u32,
+    /// Retry budget for transient failures — 429 (honoring `Retry-After`),
+    /// 5xx, and connect errors — before `ShipperClient::ship` gives up and
+    /// returns to the caller for spooling (see `shipper::ship_and_record`).
+    pub max_retries_transient: u32,
     pub base_backoff_seconds: f64,
+    /// Hex-encoded X25519 public key of the payload recipient. When set,
+    /// compressed payloads are sealed (see `pipeline::crypto`) before POST
+    /// so an untrusted relay only ever sees ciphertext.
+    pub recipient_key: Option<String>,
+    /// Global gitignore-style exclusion patterns, applied on top of any
+    /// per-provider-root `.longhouseignore` files (see `crate::ignore`).
+    pub ignore_patterns: Vec<String>,
+    /// Whether to chunk files with content-defined chunking and skip
+    /// re-uploading chunks already recorded as seen (see
+    /// `pipeline::chunker` and `state::chunks::ChunkStore`).
+    pub chunk_dedup: bool,
+    /// Target average chunk size in bytes for content-defined chunking.
+    pub target_chunk_bytes: usize,
+    /// Hard ceiling on a single chunk's size in bytes.
+    pub max_chunk_bytes: usize,
+    /// Fraction of shipped events that parse errors in the last hour may
+    /// reach before the heartbeat's `elevated_parse_error_rate` flag trips
+    /// (see `heartbeat::HeartbeatPayload`). A corrupted or upgraded log
+    /// format tends to fail every parse, not a random few, so a fairly low
+    /// default catches that early without tripping on occasional bad lines.
+    pub elevated_parse_error_ratio: f64,
+    /// Max files coalesced into one `shipper::ship_batch` HTTP request,
+    /// alongside `max_batch_bytes` — whichever limit is hit first closes
+    /// the group.
+    pub max_batch_items: usize,
+    /// When `true`, a spooled byte range's content is copied into the
+    /// `spool_queue` row itself (see `state::spool::Spool::with_owned_blobs`)
+    /// instead of just recording the range as a pointer into the source
+    /// file. Costs DB space proportional to what's actually spooled, but
+    /// survives the source file being rotated, truncated, or deleted before
+    /// replay gets to it. Off by default since most installs never spool
+    /// against a file whose lifetime outlives its own content.
+    pub spool_owned_blobs: bool,
+    /// Self-throttle knob (0-10) for background catch-up passes — the
+    /// resync worker (`resync::run_resync_pass`) and `shipper::full_scan`
+    /// (daemon startup and the fallback-scan worker): after each unit of
+    /// work, the pass sleeps `tranquility * last_op_duration` before moving
+    /// to the next one. 0 disables the pacing sleep; higher values trade
+    /// catch-up speed for leaving more headroom for live shipping. The
+    /// live event-driven `shipper::ship_batch` call always passes 0
+    /// regardless of this setting.
+    pub tranquility: u8,
+    /// PEM-encoded client certificate for mutual TLS, paired with
+    /// `client_key_path`. Both must be set to enable mTLS (see
+    /// `shipping::client::ShipperClient::with_compression`).
+    pub client_cert_path: Option<PathBuf>,
+    /// PEM-encoded private key matching `client_cert_path`.
+    pub client_key_path: Option<PathBuf>,
+    /// PEM-encoded custom CA bundle to trust in addition to the system
+    /// roots — for gateways fronted by a private CA.
+    pub ca_bundle_path: Option<PathBuf>,
+    /// Endpoint to POST the current token to for a fresh one when ingest
+    /// returns 401/403 (see `shipping::client::ShipperClient::refresh_token`),
+    /// or on a proactive background schedule before the old one expires.
+    /// `None` means a 401/403 is terminal, same as before this field existed.
+    pub token_refresh_url: Option<String>,
+    /// Path to a zstd dictionary trained by the `train-dict` subcommand (see
+    /// `pipeline::dictionary`). When set, the daemon loads it once at
+    /// startup and compresses live payloads against it instead of plain
+    /// zstd/gzip — a missing or unreadable file just logs a warning and
+    /// falls back to `algo`, same as `None` here.
+    pub dictionary_path: Option<PathBuf>,
+    /// Budget, in uncompressed bytes, for a single `IngestPayload` built by
+    /// `shipper::prepare_file` (see `pipeline::compressor::build_batches`). A
+    /// session whose new events exceed this in one pass is split into several
+    /// `ShipItem`s, each with its own source byte range, so a failed upload
+    /// can be retried without re-sending events an earlier batch already
+    /// landed. Deliberately larger than `max_batch_bytes` (which bounds a
+    /// batch of *compressed, already-shipped* files) since this bounds one
+    /// session's *uncompressed* payload before it's ever sent.
+    pub max_uncompressed_event_bytes: usize,
 }
 
 impl Default for ShipperConfig {
@@ -29,8 +103,23 @@ impl Default for ShipperConfig {
             workers: num_cpus::get(),
             max_batch_bytes: 5 * 1024 * 1024, // 5 MB
             timeout_seconds: 60,
-            max_retries_429: 3,
+            max_retries_transient: 3,
             base_backoff_seconds: 1.0,
+            recipient_key: None,
+            ignore_patterns: Vec::new(),
+            chunk_dedup: true,
+            target_chunk_bytes: crate::pipeline::chunker::DEFAULT_TARGET_SIZE,
+            max_chunk_bytes: crate::pipeline::chunker::DEFAULT_MAX_SIZE,
+            elevated_parse_error_ratio: 0.1,
+            max_batch_items: 50,
+            spool_owned_blobs: false,
+            tranquility: 2,
+            client_cert_path: None,
+            client_key_path: None,
+            ca_bundle_path: None,
+            token_refresh_url: None,
+            dictionary_path: None,
+            max_uncompressed_event_bytes: 10 * 1024 * 1024, // 10 MB
         }
     }
 }
@@ -72,6 +161,82 @@ impl ShipperConfig {
             }
         }
 
+        // Env var override for the encryption recipient key
+        if let Ok(key) = std::env::var("LONGHOUSE_RECIPIENT_KEY") {
+            if !key.is_empty() {
+                config.recipient_key = Some(key);
+            }
+        }
+
+        // Comma-separated gitignore-style patterns, e.g. "archive,*-scratch"
+        if let Ok(patterns) = std::env::var("LONGHOUSE_IGNORE") {
+            config.ignore_patterns = patterns
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+
+        // Env var overrides for content-defined chunking
+        if let Ok(flag) = std::env::var("LONGHOUSE_CHUNK_DEDUP") {
+            config.chunk_dedup = flag != "0" && !flag.eq_ignore_ascii_case("false");
+        }
+        if let Ok(n) = std::env::var("LONGHOUSE_TARGET_CHUNK_BYTES").and_then(|v| {
+            v.parse::<usize>()
+                .map_err(|_| std::env::VarError::NotPresent)
+        }) {
+            config.target_chunk_bytes = n;
+        }
+        if let Ok(n) = std::env::var("LONGHOUSE_MAX_CHUNK_BYTES").and_then(|v| {
+            v.parse::<usize>()
+                .map_err(|_| std::env::VarError::NotPresent)
+        }) {
+            config.max_chunk_bytes = n;
+        }
+
+        if let Ok(flag) = std::env::var("LONGHOUSE_SPOOL_OWNED_BLOBS") {
+            config.spool_owned_blobs = flag != "0" && !flag.eq_ignore_ascii_case("false");
+        }
+
+        if let Ok(n) = std::env::var("LONGHOUSE_TRANQUILITY").and_then(|v| {
+            v.parse::<u8>().map_err(|_| std::env::VarError::NotPresent)
+        }) {
+            config.tranquility = n.min(10);
+        }
+
+        if let Ok(p) = std::env::var("LONGHOUSE_CLIENT_CERT") {
+            if !p.is_empty() {
+                config.client_cert_path = Some(PathBuf::from(p));
+            }
+        }
+        if let Ok(p) = std::env::var("LONGHOUSE_CLIENT_KEY") {
+            if !p.is_empty() {
+                config.client_key_path = Some(PathBuf::from(p));
+            }
+        }
+        if let Ok(p) = std::env::var("LONGHOUSE_CA_BUNDLE") {
+            if !p.is_empty() {
+                config.ca_bundle_path = Some(PathBuf::from(p));
+            }
+        }
+        if let Ok(url) = std::env::var("LONGHOUSE_TOKEN_REFRESH_URL") {
+            if !url.is_empty() {
+                config.token_refresh_url = Some(url);
+            }
+        }
+        if let Ok(p) = std::env::var("LONGHOUSE_DICTIONARY_PATH") {
+            if !p.is_empty() {
+                config.dictionary_path = Some(PathBuf::from(p));
+            }
+        }
+        if let Ok(n) = std::env::var("LONGHOUSE_MAX_UNCOMPRESSED_EVENT_BYTES").and_then(|v| {
+            v.parse::<usize>()
+                .map_err(|_| std::env::VarError::NotPresent)
+        }) {
+            config.max_uncompressed_event_bytes = n;
+        }
+
         Ok(config)
     }
 
@@ -99,6 +264,60 @@ impl ShipperConfig {
         }
         self
     }
+
+    /// Override the encryption recipient key (see `--recipient-key`).
+    pub fn with_recipient_key(mut self, recipient_key: Option<&str>) -> Self {
+        if let Some(k) = recipient_key {
+            self.recipient_key = Some(k.to_string());
+        }
+        self
+    }
+
+    /// Override the resync worker's tranquility knob (see `--tranquility` /
+    /// `Self::tranquility`), clamped to 0-10.
+    pub fn with_tranquility(mut self, tranquility: Option<u8>) -> Self {
+        if let Some(t) = tranquility {
+            self.tranquility = t.min(10);
+        }
+        self
+    }
+
+    /// Override the mutual-TLS client cert/key pair and/or custom CA bundle
+    /// (see `--client-cert`/`--client-key`/`--ca-bundle`). Each is only
+    /// overridden when `Some`.
+    pub fn with_mtls(
+        mut self,
+        client_cert: Option<&Path>,
+        client_key: Option<&Path>,
+        ca_bundle: Option<&Path>,
+    ) -> Self {
+        if let Some(p) = client_cert {
+            self.client_cert_path = Some(p.to_path_buf());
+        }
+        if let Some(p) = client_key {
+            self.client_key_path = Some(p.to_path_buf());
+        }
+        if let Some(p) = ca_bundle {
+            self.ca_bundle_path = Some(p.to_path_buf());
+        }
+        self
+    }
+
+    /// Override the token-refresh endpoint (see `--token-refresh-url`).
+    pub fn with_token_refresh_url(mut self, url: Option<&str>) -> Self {
+        if let Some(u) = url {
+            self.token_refresh_url = Some(u.to_string());
+        }
+        self
+    }
+
+    /// Override the trained-dictionary path (see `--dictionary-path`).
+    pub fn with_dictionary_path(mut self, path: Option<&Path>) -> Self {
+        if let Some(p) = path {
+            self.dictionary_path = Some(p.to_path_buf());
+        }
+        self
+    }
 }
 
 /// Resolve `~/.claude/` or `CLAUDE_CONFIG_DIR`.
@@ -7,68 +7,149 @@
 //! - 0% CPU when idle (blocked on kernel filesystem events)
 //! - Single-threaded tokio runtime (current_thread)
 
+pub(crate) mod worker;
+
 use std::time::{Duration, Instant};
 
 use anyhow::Result;
+use rand::Rng;
 
+use crate::admin::{self, AdminCommand};
+use crate::circuit_breaker::CircuitBreaker;
 use crate::config::ShipperConfig;
 use crate::discovery::{self, ProviderConfig};
 use crate::error_tracker::ConsecutiveErrorTracker;
-use crate::heartbeat;
+use crate::ignore::IgnoreMatcher;
+use crate::metrics::Metrics;
 use crate::pipeline::compressor::CompressionAlgo;
+use crate::pipeline::crypto::RecipientKey;
+use crate::pipeline::dictionary::{self, Dictionary};
+use crate::resync;
 use crate::shipper;
 use crate::shipping::client::ShipperClient;
+use crate::shutdown::ShutdownToken;
 use crate::state::db::open_db;
 use crate::state::file_state::FileState;
 use crate::state::spool::Spool;
-use crate::watcher::SessionWatcher;
+use crate::watcher::{self, SessionWatcher};
+use worker::{
+    FallbackScanWorker, HealthCheckWorker, HeartbeatWorker, PruneWorker, SpoolReplayWorker, WorkerContext, WorkerRegistry,
+};
 
 /// Configuration for the connect daemon.
 pub struct ConnectConfig {
+    /// Also carries `tranquility` (see `ShipperConfig::tranquility`), the
+    /// self-throttle knob shared by the initial/fallback `shipper::full_scan`
+    /// passes and `resync::run_resync_pass` — 0 for the live event-driven
+    /// `ship_batch` call in this module's own `ship_batch` helper, since that
+    /// path should never wait on a knob meant for background catch-up.
     pub shipper_config: ShipperConfig,
     pub algo: CompressionAlgo,
     pub flush_interval: Duration,
     pub fallback_scan_secs: u64,
     pub spool_replay_secs: u64,
+    /// How often to re-check for unacked file gaps that stalled (no new
+    /// writes to re-trigger a normal ship) and re-run the prepare/ship
+    /// pipeline against them — see `resync::run_resync_pass`.
+    pub resync_secs: u64,
+    /// Base delay for the offline health-check backoff (see
+    /// `health_check_backoff`) — the first retry after going offline waits
+    /// around this long.
+    pub health_backoff_base: Duration,
+    /// Upper bound the health-check backoff is capped at, however many
+    /// consecutive failures have piled up.
+    pub health_backoff_max: Duration,
+    pub metrics_addr: Option<String>,
+    pub admin_addr: Option<String>,
+    /// Deadline for the shutdown drain (flush the watcher cookie, ship
+    /// whatever it observed, replay the spool) once the first SIGINT/SIGTERM
+    /// arrives — see `run`'s shutdown-signal arm. A second signal before the
+    /// deadline forces an immediate exit regardless of drain progress.
+    pub shutdown_grace: Duration,
+}
+
+/// Default `health_backoff_base` / `health_backoff_max` / `shutdown_grace`
+/// for callers that don't need to tune reconnection/shutdown behavior.
+impl ConnectConfig {
+    pub const DEFAULT_HEALTH_BACKOFF_BASE: Duration = Duration::from_secs(1);
+    pub const DEFAULT_HEALTH_BACKOFF_MAX: Duration = Duration::from_secs(5 * 60);
+    pub const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
 }
 
-/// Offline / connectivity state.
+/// Offline / connectivity state. `is_offline` derives entirely from the
+/// shared `CircuitBreaker` (see `circuit_breaker`) rather than being set ad
+/// hoc — this wrapper only adds the wall-clock "how long were we down"
+/// bookkeeping for the recovery log line.
 struct OfflineState {
-    is_offline: bool,
+    breaker: CircuitBreaker,
     offline_since: Option<Instant>,
-    consecutive_failures: u32,
 }
 
 impl OfflineState {
-    fn new() -> Self {
+    fn new(breaker: CircuitBreaker) -> Self {
         Self {
-            is_offline: false,
+            breaker,
             offline_since: None,
-            consecutive_failures: 0,
         }
     }
 
-    fn mark_offline(&mut self) {
-        if !self.is_offline {
-            self.is_offline = true;
+    fn is_offline(&self) -> bool {
+        self.breaker.is_offline()
+    }
+
+    /// Note that we've just observed a connect error (the breaker's own
+    /// state transition already happened inside `ship_and_record` /
+    /// `full_scan`) — starts the down-time clock if it isn't running yet.
+    /// Returns `true` the first time this fires for the current outage.
+    fn note_connect_error(&mut self) -> bool {
+        if self.offline_since.is_none() {
             self.offline_since = Some(Instant::now());
+            true
+        } else {
+            false
         }
-        self.consecutive_failures += 1;
     }
 
+    /// Whether `HealthCheckWorker` should spend a real network call on a
+    /// probe right now — delegates to `CircuitBreaker::allow_request` so the
+    /// health check *is* the breaker's own `Open` → `HalfOpen` probe instead
+    /// of a second, independent prober racing past the breaker's cooldown.
+    /// `false` while `Open`'s cooldown hasn't elapsed yet, or while a probe
+    /// from an earlier tick is still outstanding.
+    fn allow_probe(&self) -> bool {
+        self.breaker.allow_request()
+    }
+
+    /// Call after a successful health-check probe. Returns the outage
+    /// duration if we were offline.
     fn mark_online(&mut self) -> Option<Duration> {
-        if self.is_offline {
-            let duration = self.offline_since.map(|t| t.elapsed());
-            self.is_offline = false;
-            self.offline_since = None;
-            self.consecutive_failures = 0;
-            duration
-        } else {
-            None
-        }
+        self.breaker.record(true);
+        self.offline_since.take().map(|t| t.elapsed())
+    }
+
+    /// Call after a failed health-check probe — reopens the breaker (with a
+    /// fresh, wider cooldown) so the next `allow_probe()` doesn't stay stuck
+    /// rejecting forever in `HalfOpen` (`CircuitBreaker::allow_request`
+    /// returns `false` unconditionally while `HalfOpen`, by design: only the
+    /// one in-flight probe gets to decide the next transition).
+    fn mark_probe_failed(&mut self) {
+        self.breaker.record(false);
     }
 }
 
+/// Next health-check delay after `consecutive_failures` probe failures:
+/// `min(base * 2^(failures-1), max_cap)`, then full jitter — sampled
+/// uniformly from `[0, computed]` — so daemons that go offline at the same
+/// moment don't all reconnect in lockstep (same idea as
+/// `state::spool::BackoffStrategy::FullJitter`).
+fn health_check_backoff(base: Duration, max_cap: Duration, consecutive_failures: u32) -> Duration {
+    let exponent = consecutive_failures.saturating_sub(1).min(32);
+    let computed = base
+        .saturating_mul(1u32.checked_shl(exponent).unwrap_or(u32::MAX))
+        .min(max_cap);
+    computed.mul_f64(rand::thread_rng().gen::<f64>())
+}
+
 /// Run the connect daemon. This function blocks until shutdown signal.
 pub async fn run(config: ConnectConfig) -> Result<()> {
     let start = Instant::now();
@@ -77,7 +158,8 @@ pub async fn run(config: ConnectConfig) -> Result<()> {
     let conn = open_db(config.shipper_config.db_path.as_deref())?;
 
     // 2. Startup recovery
-    let recovered = shipper::run_startup_recovery(&conn)?;
+    let spool_owned_blobs = config.shipper_config.spool_owned_blobs;
+    let recovered = shipper::run_startup_recovery(&conn, spool_owned_blobs)?;
     if recovered > 0 {
         tracing::info!("Recovered {} unacked file gaps into spool", recovered);
     }
@@ -92,10 +174,59 @@ pub async fn run(config: ConnectConfig) -> Result<()> {
         }
     }
 
+    // 2c. Create metrics early so the HTTP client can record per-attempt
+    // outcomes/latency/backoff (see `shipping::client::ShipperClient::with_metrics`).
+    let metrics = Metrics::new();
+    if let Some(addr) = config.metrics_addr.as_deref() {
+        metrics.serve(addr)?;
+    }
+
     // 3. Create HTTP client
-    let client = ShipperClient::with_compression(&config.shipper_config, config.algo)?;
+    let client = ShipperClient::with_compression(&config.shipper_config, config.algo)?
+        .with_metrics(metrics.clone());
     tracing::info!("Shipping to: {}", client.ingest_url());
 
+    // 3b. Parse the encryption recipient key, if configured
+    let recipient_key = config
+        .shipper_config
+        .recipient_key
+        .as_deref()
+        .map(RecipientKey::from_hex)
+        .transpose()?;
+    let recipient_key = recipient_key.as_ref();
+
+    // 3c. Discovery walker concurrency mirrors the ship worker count.
+    let discovery_config = discovery::DiscoveryConfig {
+        workers: config.shipper_config.workers,
+    };
+
+    // 3d. Content-defined chunking knobs, for dedup manifest reporting.
+    let chunk_dedup = config.shipper_config.chunk_dedup;
+    let chunker_params = crate::pipeline::chunker::ChunkerParams::new(
+        config.shipper_config.target_chunk_bytes,
+        config.shipper_config.max_chunk_bytes,
+    );
+
+    // 3e. Trained zstd dictionary (see `config::ShipperConfig::dictionary_path`),
+    // loaded once up front rather than per payload. A missing/unreadable
+    // file just logs a warning and falls back to plain `config.algo` — same
+    // as never configuring one.
+    let loaded_dictionary: Option<Dictionary> = config
+        .shipper_config
+        .dictionary_path
+        .as_deref()
+        .and_then(|path| match dictionary::load(path) {
+            Ok(dict) => {
+                tracing::info!("Loaded compression dictionary {} from {}", dict.id, path.display());
+                Some(dict)
+            }
+            Err(e) => {
+                tracing::warn!("Failed to load dictionary from {}: {} — falling back to plain compression", path.display(), e);
+                None
+            }
+        });
+    let dictionary = loaded_dictionary.as_ref();
+
     // 4. Discover providers
     let providers = discovery::get_providers();
     if providers.is_empty() {
@@ -106,52 +237,83 @@ pub async fn run(config: ConnectConfig) -> Result<()> {
         tracing::info!("Provider {}: {}", p.name, p.root.display());
     }
 
-    // 5. Create error tracker (shared across all ship operations)
+    // 4b. Gather .longhouseignore patterns once so compilation isn't repeated per event.
+    let ignore = IgnoreMatcher::gather(&providers, &config.shipper_config.ignore_patterns)?;
+
+    // 5. Create error tracker and circuit breaker (shared across all ship operations)
     let tracker = ConsecutiveErrorTracker::new();
+    let breaker = CircuitBreaker::new();
+
+    // 5c. Start the admin/control API, if enabled
+    let (admin_tx, mut admin_rx) = tokio::sync::mpsc::unbounded_channel::<AdminCommand>();
+    if let Some(addr) = config.admin_addr.as_deref() {
+        admin::serve(addr, config.shipper_config.db_path.clone(), admin_tx)?;
+    }
 
     // 6. Initial full scan (catch up on anything missed while stopped)
     tracing::info!("Running initial full scan...");
-    let (files, events) = shipper::full_scan(&providers, &conn, &client, config.algo, Some(&tracker)).await?;
+    let scan = shipper::full_scan(&providers, &conn, &client, config.algo, Some(&tracker), Some(&breaker), recipient_key, &discovery_config, &ignore, chunk_dedup, &chunker_params, config.shipper_config.max_batch_items, config.shipper_config.max_batch_bytes, spool_owned_blobs, config.shipper_config.tranquility, None, dictionary, config.shipper_config.max_uncompressed_event_bytes).await?;
+    metrics.record_shipped(scan.files as u64, scan.events as u64, 0);
+    if scan.had_connect_error {
+        metrics.record_failure("connect_error");
+        tracing::warn!("Initial scan hit a connect error — circuit breaker will gate the next attempt");
+    }
     tracing::info!(
         "Initial scan: shipped {} files, {} events in {:.1}s",
-        files,
-        events,
+        scan.files,
+        scan.events,
         start.elapsed().as_secs_f64()
     );
 
     // 7. Replay any pending spool entries
-    let (spool_ok, spool_fail) = shipper::replay_spool_batch(&conn, &client, config.algo, 100).await?;
+    let (spool_ok, spool_fail) = shipper::replay_spool_batch(&conn, &client, config.algo, 100, Some(&breaker), recipient_key).await?;
+    if spool_fail > 0 {
+        metrics.record_failure("spool_replay");
+    }
     if spool_ok > 0 || spool_fail > 0 {
         tracing::info!("Spool replay: {} shipped, {} failed", spool_ok, spool_fail);
     }
+    metrics.set_spool_depth(Spool::new(&conn).pending_count()? as u64);
 
     // 8. Start file watcher
-    let mut watcher = SessionWatcher::new(&providers)?;
+    let mut watcher = SessionWatcher::new(&providers, &ignore)?;
     tracing::info!("Daemon ready — watching for file changes (flush interval: {:?})", config.flush_interval);
 
     // 9. Main event loop
     let fallback_interval = Duration::from_secs(config.fallback_scan_secs.max(10));
     let spool_interval = Duration::from_secs(config.spool_replay_secs.max(5));
-    let health_check_interval = Duration::from_secs(60);
+    let resync_interval = Duration::from_secs(config.resync_secs.max(60));
     let prune_interval = Duration::from_secs(24 * 3600);
     let heartbeat_interval = Duration::from_secs(5 * 60);
-
-    let mut fallback_timer = tokio::time::interval(fallback_interval);
-    fallback_timer.tick().await; // consume first immediate tick
-
-    let mut spool_timer = tokio::time::interval(spool_interval);
-    spool_timer.tick().await; // consume first immediate tick
-
-    let mut health_timer = tokio::time::interval(health_check_interval);
-    health_timer.tick().await; // consume first immediate tick
-
-    let mut prune_timer = tokio::time::interval(prune_interval);
-    prune_timer.tick().await; // consume first immediate tick
-
-    let mut heartbeat_timer = tokio::time::interval(heartbeat_interval);
-    heartbeat_timer.tick().await; // consume first immediate tick
-
-    let mut offline = OfflineState::new();
+    // Only meaningful when `token_refresh_url` is configured — proactively
+    // rotates the bearer token ahead of expiry (see
+    // `shipping::client::ShipperClient::refresh_token`) instead of waiting
+    // for a 401/403 to trigger the reactive path.
+    let token_refresh_interval = Duration::from_secs(15 * 60);
+
+    let mut resync_timer = tokio::time::interval(resync_interval);
+    resync_timer.tick().await; // consume first immediate tick
+
+    let mut token_refresh_timer = tokio::time::interval(token_refresh_interval);
+    token_refresh_timer.tick().await; // consume first immediate tick
+
+    // The fallback scan, spool replay, prune, heartbeat, and health-check
+    // jobs used to each be a hard-coded `tokio::time::interval` plus
+    // `select!` arm; they're now `DaemonWorker`s driven from the single
+    // branch below, with their live status folded into the heartbeat (see
+    // `worker::WorkerSnapshot`). Resync and the primary file-watch path stay
+    // inline — resync isn't one of the five originally-hard-coded timers,
+    // and the watcher's `next_batch`/shutdown-drain flow is too tightly
+    // wound through `OfflineState` to generalize without a disproportionate
+    // rewrite.
+    let mut workers = WorkerRegistry::new();
+    workers.register(Box::new(FallbackScanWorker::new(fallback_interval)));
+    workers.register(Box::new(SpoolReplayWorker::new(spool_interval)));
+    workers.register(Box::new(PruneWorker::new(prune_interval)));
+    workers.register(Box::new(HeartbeatWorker::new(heartbeat_interval)));
+    workers.register(Box::new(HealthCheckWorker::new(config.health_backoff_base, config.health_backoff_max)));
+
+    let mut offline = OfflineState::new(breaker.clone());
     let mut last_ship_at: Option<String> = None;
 
     // Resolve claude dir for status file
@@ -167,38 +329,67 @@ pub async fn run(config: ConnectConfig) -> Result<()> {
         tokio::select! {
             biased;
 
-            // Shutdown signals
+            // First shutdown signal: stop accepting new watcher batches (we
+            // no longer poll that arm once we've left this `select!` for the
+            // drain sub-loop below) and run a bounded final pass — flush the
+            // watcher cookie, ship whatever it observed, and replay the
+            // spool — under `config.shutdown_grace`. A second signal forces
+            // an immediate exit instead of waiting the grace period out.
             _ = shutdown_signal() => {
-                tracing::info!("Shutdown signal received, exiting gracefully...");
-                break;
-            }
-
-            // Health check when offline (every 60s)
-            _ = health_timer.tick(), if offline.is_offline => {
-                match client.health_check().await {
-                    Ok(true) => {
-                        if let Some(duration) = offline.mark_online() {
-                            tracing::info!(
-                                "Back online after {:.0}s — resuming shipping",
-                                duration.as_secs_f64()
-                            );
+                tracing::info!("Shutdown signal received, draining in-flight changes (grace: {:?})...", config.shutdown_grace);
+                let shutdown_token = ShutdownToken::new();
+                let drain = async {
+                    if let Err(e) = watcher.flush_cookie().await {
+                        tracing::warn!("Failed to flush watcher cookie on shutdown: {}", e);
+                    }
+                    let paths = watcher.drain_ready();
+                    if !paths.is_empty() {
+                        tracing::info!("Shipping {} file(s) observed during shutdown drain", paths.len());
+                        ship_batch(&paths, &providers, &conn, &client, &tracker, &breaker, &metrics, recipient_key, chunk_dedup, &chunker_params, config.shipper_config.max_batch_items, config.shipper_config.max_batch_bytes, spool_owned_blobs, Some(&shutdown_token), dictionary, config.shipper_config.max_uncompressed_event_bytes).await;
+                    }
+                    if !shutdown_token.is_cancelled() {
+                        match shipper::replay_spool_batch(&conn, &client, config.algo, 100, Some(&breaker), recipient_key).await {
+                            Ok((ok, fail)) => {
+                                if ok > 0 || fail > 0 {
+                                    tracing::info!("Shutdown spool replay: {} shipped, {} failed", ok, fail);
+                                }
+                            }
+                            Err(e) => tracing::warn!("Shutdown spool replay error: {}", e),
                         }
                     }
-                    _ => {
-                        tracing::debug!("Still offline (health check failed)");
+                };
+                tokio::pin!(drain);
+                let mut grace_elapsed = false;
+                loop {
+                    tokio::select! {
+                        _ = &mut drain => {
+                            tracing::info!("Graceful drain complete, exiting...");
+                            break;
+                        }
+                        _ = tokio::time::sleep(config.shutdown_grace), if !grace_elapsed => {
+                            grace_elapsed = true;
+                            shutdown_token.cancel();
+                            tracing::warn!("Shutdown grace period ({:?}) elapsed — finishing in-flight request, then exiting", config.shutdown_grace);
+                        }
+                        _ = shutdown_signal() => {
+                            tracing::warn!("Second shutdown signal received — forcing immediate exit");
+                            break;
+                        }
                     }
                 }
+                break;
             }
 
             // File change events (primary path) — skip when offline
-            batch = watcher.next_batch(config.flush_interval), if !offline.is_offline => {
+            batch = watcher.next_batch(config.flush_interval), if !offline.is_offline() => {
                 match batch {
                     Some(paths) if !paths.is_empty() => {
-                        let (had_connect_error, shipped_events) = ship_batch(&paths, &providers, &conn, &client, config.algo, &tracker).await;
+                        let (had_connect_error, shipped_events) = ship_batch(&paths, &providers, &conn, &client, &tracker, &breaker, &metrics, recipient_key, chunk_dedup, &chunker_params, config.shipper_config.max_batch_items, config.shipper_config.max_batch_bytes, spool_owned_blobs, None, dictionary, config.shipper_config.max_uncompressed_event_bytes).await;
                         if had_connect_error {
-                            offline.mark_offline();
+                            offline.note_connect_error();
+                            metrics.record_failure("connect_error");
                             tracing::warn!(
-                                "Connection error — entering offline mode, will retry every 60s"
+                                "Connection error — entering offline mode, will retry with backoff"
                             );
                         } else if shipped_events > 0 {
                             last_ship_at = Some(chrono::Utc::now().to_rfc3339());
@@ -212,65 +403,107 @@ pub async fn run(config: ConnectConfig) -> Result<()> {
                 }
             }
 
-            // Periodic full scan (catch missed events) — skip when offline
-            _ = fallback_timer.tick(), if !offline.is_offline => {
-                tracing::debug!("Running fallback full scan...");
-                match shipper::full_scan(&providers, &conn, &client, config.algo, Some(&tracker)).await {
-                    Ok((f, e)) => {
-                        if f > 0 {
-                            tracing::info!("Fallback scan: shipped {} files, {} events", f, e);
-                        }
-                    }
-                    Err(e) => {
-                        // Check if it's a connection error → go offline
-                        let msg = e.to_string();
-                        if msg.contains("connect") || msg.contains("ConnectError") {
-                            offline.mark_offline();
-                            tracing::warn!("Fallback scan connect error — entering offline mode");
-                        } else {
-                            tracing::warn!("Fallback scan error: {}", e);
-                        }
-                    }
-                }
+            // Run whichever background workers (fallback scan, spool
+            // replay, prune, heartbeat, health check) are due — see
+            // `worker::WorkerRegistry`.
+            _ = tokio::time::sleep_until(workers.next_deadline()) => {
+                let mut ctx = WorkerContext {
+                    conn: &conn,
+                    client: &client,
+                    breaker: &breaker,
+                    tracker: &tracker,
+                    metrics: &metrics,
+                    offline: &mut offline,
+                    watcher: &mut watcher,
+                    providers: &providers,
+                    discovery_config: &discovery_config,
+                    ignore: &ignore,
+                    recipient_key,
+                    dictionary,
+                    algo: config.algo,
+                    chunk_dedup,
+                    chunker_params: &chunker_params,
+                    max_batch_items: config.shipper_config.max_batch_items,
+                    max_batch_bytes: config.shipper_config.max_batch_bytes,
+                    owned_blobs: spool_owned_blobs,
+                    max_uncompressed_bytes: config.shipper_config.max_uncompressed_event_bytes,
+                    claude_dir: &claude_dir,
+                    last_ship_at: &last_ship_at,
+                    elevated_parse_error_ratio: config.shipper_config.elevated_parse_error_ratio,
+                    tranquility: config.shipper_config.tranquility,
+                    worker_snapshots: Vec::new(),
+                };
+                workers.run_due(&mut ctx).await;
             }
 
-            // Spool replay (retry failed shipments) — skip when offline
-            _ = spool_timer.tick(), if !offline.is_offline => {
-                match shipper::replay_spool_batch(&conn, &client, config.algo, 50).await {
-                    Ok((ok, fail)) => {
-                        if ok > 0 || fail > 0 {
-                            tracing::info!("Spool replay: {} shipped, {} failed", ok, fail);
+            // Background resync: catch unacked gaps whose spool entry
+            // stalled (backed off, went dead, or didn't survive a restart)
+            // with no new writes left to re-trigger a normal ship — skip
+            // when offline.
+            _ = resync_timer.tick(), if !offline.is_offline() => {
+                match resync::run_resync_pass(&conn, &client, config.algo, recipient_key, chunk_dedup, &chunker_params, spool_owned_blobs, config.shipper_config.tranquility, Some(&breaker), dictionary, config.shipper_config.max_uncompressed_event_bytes).await {
+                    Ok((had_connect_error, resynced)) => {
+                        if had_connect_error {
+                            offline.note_connect_error();
+                            metrics.record_failure("connect_error");
+                            tracing::warn!("Resync pass connect error — entering offline mode");
+                        } else if resynced > 0 {
+                            tracing::info!("Resync pass: recovered {} stalled file(s)", resynced);
                         }
                     }
-                    Err(e) => tracing::warn!("Spool replay error: {}", e),
+                    Err(e) => tracing::warn!("Resync pass error: {}", e),
                 }
             }
 
-            // Daily: prune stale file_state entries
-            _ = prune_timer.tick() => {
-                let fs = FileState::new(&conn);
-                match fs.prune_stale(30) {
-                    Ok(n) if n > 0 => tracing::info!("Daily prune: removed {} stale file_state entries", n),
-                    Ok(_) => {}
-                    Err(e) => tracing::warn!("Daily prune error: {}", e),
+            // Proactive token refresh, ahead of expiry (see
+            // ShipperClient::refresh_token) — a no-op when
+            // token_refresh_url isn't configured.
+            _ = token_refresh_timer.tick(), if config.shipper_config.token_refresh_url.is_some() => {
+                if let Err(e) = client.refresh_token().await {
+                    tracing::warn!("Proactive token refresh failed: {}", e);
                 }
             }
 
-            // Periodic heartbeat
-            _ = heartbeat_timer.tick() => {
-                let spool = Spool::new(&conn);
-                let stats = heartbeat::HeartbeatStats {
-                    spool: &spool,
-                    tracker: &tracker,
-                    is_offline: offline.is_offline,
-                    last_ship_at: last_ship_at.clone(),
-                };
-                let payload = heartbeat::HeartbeatPayload::build(&stats);
-                heartbeat::write_status_file(&payload, &claude_dir);
-                if !offline.is_offline {
-                    if let Err(e) = heartbeat::send_heartbeat(&client, &payload).await {
-                        tracing::debug!("Heartbeat send failed: {}", e);
+            // Admin API nudges (manual flush / spool replay)
+            cmd = admin_rx.recv() => {
+                match cmd {
+                    Some(AdminCommand::Flush) => {
+                        tracing::info!("Admin-triggered flush: running scan-and-ship cycle...");
+                        // An operator asking for a flush wants it now, not
+                        // paced behind the catch-up knob — same reasoning
+                        // as the event-driven `ship_batch` call below.
+                        match shipper::full_scan(&providers, &conn, &client, config.algo, Some(&tracker), Some(&breaker), recipient_key, &discovery_config, &ignore, chunk_dedup, &chunker_params, config.shipper_config.max_batch_items, config.shipper_config.max_batch_bytes, spool_owned_blobs, 0, None, dictionary, config.shipper_config.max_uncompressed_event_bytes).await {
+                            Ok(scan) => {
+                                metrics.record_shipped(scan.files as u64, scan.events as u64, 0);
+                                if scan.had_connect_error {
+                                    offline.note_connect_error();
+                                    metrics.record_failure("connect_error");
+                                    tracing::warn!("Admin flush connect error — entering offline mode");
+                                }
+                                tracing::info!("Admin flush: shipped {} files, {} events", scan.files, scan.events);
+                            }
+                            Err(e) => {
+                                metrics.record_failure("admin_flush_error");
+                                tracing::warn!("Admin flush error: {}", e);
+                            }
+                        }
                     }
+                    Some(AdminCommand::SpoolReplay) => {
+                        tracing::info!("Admin-triggered spool replay...");
+                        match shipper::replay_spool_batch(&conn, &client, config.algo, 100, Some(&breaker), recipient_key).await {
+                            Ok((ok, fail)) => {
+                                if fail > 0 {
+                                    metrics.record_failure("spool_replay");
+                                }
+                                tracing::info!("Admin spool replay: {} shipped, {} failed", ok, fail);
+                            }
+                            Err(e) => tracing::warn!("Admin spool replay error: {}", e),
+                        }
+                        if let Ok(depth) = Spool::new(&conn).pending_count() {
+                            metrics.set_spool_depth(depth as u64);
+                        }
+                    }
+                    None => {} // channel closed (no admin API enabled)
                 }
             }
         }
@@ -281,21 +514,49 @@ pub async fn run(config: ConnectConfig) -> Result<()> {
 }
 
 /// Ship a batch of changed file paths.
+///
+/// Prepares every path first, then hands the whole set to
+/// `shipper::ship_batch` so they go out coalesced into as few HTTP
+/// round-trips as `config.shipper_config.max_batch_items`/`max_batch_bytes`
+/// allow, instead of one request per file.
+///
+/// `shutdown`, if set, is checked between groups inside `shipper::ship_batch`
+/// — used by the shutdown-drain pass (see `run`) so it can stop picking up
+/// new groups once its grace deadline elapses; `None` on the live path.
+///
 /// Returns (had_connect_error, total_events_shipped).
+#[allow(clippy::too_many_arguments)]
 async fn ship_batch(
     paths: &[std::path::PathBuf],
     providers: &[ProviderConfig],
     conn: &rusqlite::Connection,
     client: &ShipperClient,
-    algo: CompressionAlgo,
     tracker: &ConsecutiveErrorTracker,
+    breaker: &CircuitBreaker,
+    metrics: &Metrics,
+    recipient_key: Option<&RecipientKey>,
+    chunk_dedup: bool,
+    chunker_params: &crate::pipeline::chunker::ChunkerParams,
+    max_batch_items: usize,
+    max_batch_bytes: u64,
+    owned_blobs: bool,
+    shutdown: Option<&ShutdownToken>,
+    dictionary: Option<&Dictionary>,
+    max_uncompressed_bytes: usize,
 ) -> (bool, usize) {
     let batch_start = Instant::now();
-    let mut shipped = 0usize;
-    let mut events = 0usize;
-    let mut had_connect_error = false;
+    let mut bytes = 0u64;
+    let mut items = Vec::with_capacity(paths.len());
 
     for path in paths {
+        // Defense in depth: the watcher's notify callback already intercepts
+        // cookie files before they ever reach a channel consumer, but
+        // `ship_batch` takes a plain path slice, so guard against one
+        // reaching it by some other route too.
+        if watcher::is_cookie_file(path) {
+            continue;
+        }
+
         let provider = match discovery::provider_for_path(path, providers) {
             Some(p) => p,
             None => {
@@ -304,27 +565,40 @@ async fn ship_batch(
             }
         };
 
-        match shipper::prepare_file(path, provider, algo, conn) {
-            Ok(Some(item)) => {
-                match shipper::ship_and_record(item, client, conn, Some(tracker)).await {
-                    Ok((e, is_connect_err)) => {
-                        if is_connect_err {
-                            had_connect_error = true;
-                        } else if e > 0 {
-                            shipped += 1;
-                            events += e;
-                        }
-                    }
-                    Err(e) => {
-                        // Unexpected error (not a ShipResult variant)
-                        if tracker.record_error() {
-                            tracing::warn!("Error shipping {}: {}", path.display(), e);
-                        }
-                    }
+        // Pick an algorithm from the server-negotiated candidates and this
+        // file's pending size before compressing, rather than always using
+        // the daemon's static default — `prepare_file` re-derives the exact
+        // same offset/size itself, so this is a second cheap stat + SQLite
+        // lookup, not duplicated compression work.
+        let path_str = path.to_string_lossy().to_string();
+        let pending_len = FileState::new(conn)
+            .get_offset(&path_str)
+            .ok()
+            .zip(std::fs::metadata(path).ok())
+            .map(|(offset, metadata)| metadata.len().saturating_sub(offset) as usize)
+            .unwrap_or(0);
+        let chosen_algo = client.choose_algo(pending_len);
+
+        let prepare_start = Instant::now();
+        match shipper::prepare_file(path, provider, chosen_algo, conn, recipient_key, chunk_dedup, chunker_params, dictionary, max_uncompressed_bytes) {
+            Ok(prepared) => {
+                for item in prepared {
+                    let new_bytes = item.new_offset - item.offset;
+                    // `item.algo` rather than `chosen_algo`: when `dictionary`
+                    // is set, `prepare_file` compresses against it instead
+                    // and reports zstd regardless of what was negotiated.
+                    client.record_compression_outcome(
+                        item.algo,
+                        new_bytes as usize,
+                        item.compressed.len(),
+                        prepare_start.elapsed(),
+                    );
+                    bytes += new_bytes;
+                    items.push(item);
                 }
             }
-            Ok(None) => {} // no new content
             Err(e) => {
+                metrics.record_failure("prepare_error");
                 if tracker.record_error() {
                     tracing::warn!("Error preparing {}: {}", path.display(), e);
                 }
@@ -332,7 +606,45 @@ async fn ship_batch(
         }
     }
 
-    if shipped > 0 {
+    if items.is_empty() {
+        return (false, 0);
+    }
+
+    // 0: the pacing knob is for catch-up passes (`full_scan`/`resync`), not
+    // this live path — see `shipper::ship_batch`.
+    let result = shipper::ship_batch(
+        items,
+        client,
+        conn,
+        Some(tracker),
+        Some(breaker),
+        max_batch_items,
+        max_batch_bytes,
+        owned_blobs,
+        0,
+        shutdown,
+    )
+    .await;
+    metrics.observe_ship_latency(batch_start.elapsed());
+
+    let (shipped, events, had_connect_error) = match result {
+        Ok((files, events, had_connect_error)) => {
+            if had_connect_error {
+                metrics.record_failure("connect_error");
+            }
+            (files, events, had_connect_error)
+        }
+        Err(e) => {
+            metrics.record_failure("unexpected_error");
+            if tracker.record_error() {
+                tracing::warn!("Error shipping batch: {}", e);
+            }
+            (0, 0, false)
+        }
+    };
+
+    if events > 0 {
+        metrics.record_shipped(shipped as u64, events as u64, bytes);
         tracing::info!(
             "Shipped {} files ({} events) in {:.0}ms",
             shipped,
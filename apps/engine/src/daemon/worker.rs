@@ -0,0 +1,643 @@
+//! Background-worker subsystem for the connect daemon's periodic jobs.
+//!
+//! The main loop used to hard-code one `tokio::time::interval` plus one
+//! `select!` arm per periodic job (fallback scan, spool replay, prune,
+//! heartbeat, health check), which made their status invisible between log
+//! lines. Each job now implements `DaemonWorker` and registers into a
+//! `WorkerRegistry`, which drives them all from a single `select!` branch in
+//! `daemon::run` and records per-worker state behind a cheap `Arc` handle —
+//! the same pattern `error_tracker::ConsecutiveErrorTracker` uses — so
+//! `HeartbeatPayload` can report which subsystems are healthy, stuck, or
+//! last failed.
+//!
+//! The primary file-watch path and the resync timer stay in the main
+//! `select!` loop rather than here — see `daemon::run`.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::circuit_breaker::CircuitBreaker;
+use crate::discovery::{self, ProviderConfig};
+use crate::error_tracker::ConsecutiveErrorTracker;
+use crate::heartbeat;
+use crate::ignore::IgnoreMatcher;
+use crate::metrics::Metrics;
+use crate::pipeline::compressor::CompressionAlgo;
+use crate::pipeline::crypto::RecipientKey;
+use crate::pipeline::dictionary::Dictionary;
+use crate::shipper;
+use crate::shipping::client::ShipperClient;
+use crate::state::file_state::FileState;
+use crate::state::parse_errors::ParseErrorLog;
+use crate::state::spool::Spool;
+use crate::watcher::SessionWatcher;
+
+use super::{health_check_backoff, OfflineState};
+
+/// What a worker's `tick` accomplished, for the registry's status snapshot.
+pub enum WorkerOutcome {
+    /// Ran and completed normally.
+    Success,
+    /// Skipped — e.g. offline, or nothing due yet. Doesn't touch
+    /// last-success/last-error, so an idle tick can't paper over a real
+    /// failure or manufacture a fake recovery.
+    Idle,
+    /// Ran and failed; the string is a short, human-readable reason.
+    Failure(String),
+}
+
+/// Shared state needed by more than one worker's `tick`, assembled fresh by
+/// `daemon::run` before each `WorkerRegistry::run_due` call.
+pub struct WorkerContext<'a> {
+    pub conn: &'a rusqlite::Connection,
+    pub client: &'a ShipperClient,
+    pub breaker: &'a CircuitBreaker,
+    pub tracker: &'a ConsecutiveErrorTracker,
+    pub metrics: &'a Metrics,
+    pub offline: &'a mut OfflineState,
+    pub watcher: &'a mut SessionWatcher,
+    pub providers: &'a [ProviderConfig],
+    pub discovery_config: &'a discovery::DiscoveryConfig,
+    pub ignore: &'a IgnoreMatcher,
+    pub recipient_key: Option<&'a RecipientKey>,
+    /// Trained zstd dictionary (see `config::ShipperConfig::dictionary_path`),
+    /// loaded once at daemon startup. `None` when unconfigured or unreadable
+    /// — `full_scan`/`prepare_file` fall back to plain `algo` either way.
+    pub dictionary: Option<&'a Dictionary>,
+    pub algo: CompressionAlgo,
+    pub chunk_dedup: bool,
+    pub chunker_params: &'a crate::pipeline::chunker::ChunkerParams,
+    pub max_batch_items: usize,
+    pub max_batch_bytes: u64,
+    pub owned_blobs: bool,
+    /// Budget for a single `IngestPayload` built by `shipper::prepare_file`
+    /// (see `config::ShipperConfig::max_uncompressed_event_bytes`) — only the
+    /// fallback scan uses this directly.
+    pub max_uncompressed_bytes: usize,
+    pub claude_dir: &'a std::path::Path,
+    pub last_ship_at: &'a Option<String>,
+    pub elevated_parse_error_ratio: f64,
+    /// Self-throttle knob for `shipper::full_scan` (see
+    /// `config::ShipperConfig::tranquility`) — only the fallback scan uses
+    /// this; it's the same full-history catch-up pass as the daemon's
+    /// initial scan.
+    pub tranquility: u8,
+    /// Snapshot of every registered worker as of the start of this
+    /// `run_due` call — populated by the registry so `HeartbeatWorker` can
+    /// embed it in `HeartbeatPayload` without borrowing the registry itself.
+    pub worker_snapshots: Vec<WorkerSnapshot>,
+}
+
+/// A periodic daemon job. `interval` is read again after every `tick`, so a
+/// worker with adaptive timing (see `HealthCheckWorker`) can shrink or widen
+/// its own cadence based on state `tick` just updated.
+///
+/// `?Send`: `WorkerContext` carries a `&rusqlite::Connection`, and
+/// `Connection` isn't `Sync`, so a boxed `tick` future can't be `Send`
+/// either — fine, since the daemon runs on a single-threaded `current_thread`
+/// executor (see `daemon`'s module doc) and never needs to move this future
+/// across threads.
+#[async_trait(?Send)]
+pub trait DaemonWorker {
+    fn name(&self) -> &'static str;
+    fn interval(&self) -> Duration;
+    async fn tick(&mut self, ctx: &mut WorkerContext<'_>) -> WorkerOutcome;
+}
+
+/// Point-in-time status for one worker, serialized into the heartbeat.
+#[derive(Debug, Serialize, Clone)]
+pub struct WorkerSnapshot {
+    pub name: &'static str,
+    /// "idle" | "running" | "ok" | "error".
+    pub state: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub running_for_secs: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error_at: Option<String>,
+    pub run_count: u64,
+}
+
+struct HandleInner {
+    name: &'static str,
+    running: AtomicBool,
+    run_count: AtomicU64,
+    running_since: Mutex<Option<std::time::Instant>>,
+    last_success_at: Mutex<Option<DateTime<Utc>>>,
+    last_error: Mutex<Option<(DateTime<Utc>, String)>>,
+}
+
+/// Cheap-clone handle onto one worker's status — mirrors
+/// `ConsecutiveErrorTracker`'s `Arc`-wrapped-atomics shape.
+#[derive(Clone)]
+pub struct WorkerHandle {
+    inner: Arc<HandleInner>,
+}
+
+impl WorkerHandle {
+    fn new(name: &'static str) -> Self {
+        Self {
+            inner: Arc::new(HandleInner {
+                name,
+                running: AtomicBool::new(false),
+                run_count: AtomicU64::new(0),
+                running_since: Mutex::new(None),
+                last_success_at: Mutex::new(None),
+                last_error: Mutex::new(None),
+            }),
+        }
+    }
+
+    fn mark_running(&self) {
+        self.inner.running.store(true, Ordering::Relaxed);
+        *self.inner.running_since.lock().unwrap() = Some(std::time::Instant::now());
+    }
+
+    fn mark_outcome(&self, outcome: &WorkerOutcome) {
+        self.inner.running.store(false, Ordering::Relaxed);
+        *self.inner.running_since.lock().unwrap() = None;
+        match outcome {
+            WorkerOutcome::Idle => {}
+            WorkerOutcome::Success => {
+                self.inner.run_count.fetch_add(1, Ordering::Relaxed);
+                *self.inner.last_success_at.lock().unwrap() = Some(Utc::now());
+            }
+            WorkerOutcome::Failure(reason) => {
+                self.inner.run_count.fetch_add(1, Ordering::Relaxed);
+                *self.inner.last_error.lock().unwrap() = Some((Utc::now(), reason.clone()));
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> WorkerSnapshot {
+        let running = self.inner.running.load(Ordering::Relaxed);
+        let running_for_secs = self
+            .inner
+            .running_since
+            .lock()
+            .unwrap()
+            .map(|since| since.elapsed().as_secs_f64());
+        let last_success_at = self.inner.last_success_at.lock().unwrap().clone();
+        let last_error = self.inner.last_error.lock().unwrap().clone();
+
+        let state = if running {
+            "running"
+        } else {
+            match (&last_success_at, &last_error) {
+                (_, Some((err_at, _))) if last_success_at.map_or(true, |ok_at| *err_at > ok_at) => "error",
+                (Some(_), _) => "ok",
+                (None, None) => "idle",
+            }
+        };
+
+        WorkerSnapshot {
+            name: self.inner.name,
+            state,
+            running_for_secs,
+            last_success_at: last_success_at.map(|t| t.to_rfc3339()),
+            last_error: last_error.as_ref().map(|(_, msg)| msg.clone()),
+            last_error_at: last_error.map(|(at, _)| at.to_rfc3339()),
+            run_count: self.inner.run_count.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct RegisteredWorker {
+    worker: Box<dyn DaemonWorker>,
+    handle: WorkerHandle,
+    next_tick: tokio::time::Instant,
+}
+
+/// Drives every registered `DaemonWorker` from one `select!` branch instead
+/// of one hard-coded timer per job.
+#[derive(Default)]
+pub struct WorkerRegistry {
+    workers: Vec<RegisteredWorker>,
+}
+
+impl WorkerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a worker, returning the handle its status can be read from.
+    /// The first tick fires one full `interval()` out, matching the
+    /// "consume the first immediate tick" convention the daemon's other
+    /// timers use.
+    pub fn register(&mut self, worker: Box<dyn DaemonWorker>) -> WorkerHandle {
+        let handle = WorkerHandle::new(worker.name());
+        let next_tick = tokio::time::Instant::now() + worker.interval();
+        self.workers.push(RegisteredWorker {
+            worker,
+            handle: handle.clone(),
+            next_tick,
+        });
+        handle
+    }
+
+    /// Soonest deadline across all registered workers — what the main
+    /// loop's `select!` branch sleeps until.
+    pub fn next_deadline(&self) -> tokio::time::Instant {
+        self.workers
+            .iter()
+            .map(|w| w.next_tick)
+            .min()
+            .unwrap_or_else(tokio::time::Instant::now)
+    }
+
+    /// Tick every worker whose deadline has elapsed, then reschedule it from
+    /// its own (possibly just-changed) `interval()` — mirrors
+    /// `tokio::time::interval` not stalling the others if one handler is
+    /// slow or errors.
+    pub async fn run_due(&mut self, ctx: &mut WorkerContext<'_>) {
+        ctx.worker_snapshots = self.snapshot();
+        let now = tokio::time::Instant::now();
+        for w in &mut self.workers {
+            if w.next_tick > now {
+                continue;
+            }
+            w.handle.mark_running();
+            let outcome = w.worker.tick(ctx).await;
+            w.handle.mark_outcome(&outcome);
+            w.next_tick = tokio::time::Instant::now() + w.worker.interval();
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<WorkerSnapshot> {
+        self.workers.iter().map(|w| w.handle.snapshot()).collect()
+    }
+}
+
+/// Re-runs `shipper::full_scan` on a timer to catch filesystem events the
+/// primary watcher missed (e.g. a burst that exceeded `notify`'s internal
+/// queue). Idle while offline.
+pub struct FallbackScanWorker {
+    interval: Duration,
+}
+
+impl FallbackScanWorker {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval }
+    }
+}
+
+#[async_trait(?Send)]
+impl DaemonWorker for FallbackScanWorker {
+    fn name(&self) -> &'static str {
+        "fallback_scan"
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    async fn tick(&mut self, ctx: &mut WorkerContext<'_>) -> WorkerOutcome {
+        if ctx.offline.is_offline() {
+            return WorkerOutcome::Idle;
+        }
+        tracing::debug!("Running fallback full scan...");
+        ctx.watcher.reestablish_watches(ctx.providers);
+        match shipper::full_scan(
+            ctx.providers,
+            ctx.conn,
+            ctx.client,
+            ctx.algo,
+            Some(ctx.tracker),
+            Some(ctx.breaker),
+            ctx.recipient_key,
+            ctx.discovery_config,
+            ctx.ignore,
+            ctx.chunk_dedup,
+            ctx.chunker_params,
+            ctx.max_batch_items,
+            ctx.max_batch_bytes,
+            ctx.owned_blobs,
+            ctx.tranquility,
+            None,
+            ctx.dictionary,
+            ctx.max_uncompressed_bytes,
+        )
+        .await
+        {
+            Ok(scan) => {
+                ctx.metrics.record_shipped(scan.files as u64, scan.events as u64, 0);
+                if scan.had_connect_error {
+                    ctx.offline.note_connect_error();
+                    ctx.metrics.record_failure("connect_error");
+                    tracing::warn!("Fallback scan connect error — entering offline mode");
+                    return WorkerOutcome::Failure("connect error".to_string());
+                }
+                if scan.files > 0 {
+                    tracing::info!("Fallback scan: shipped {} files, {} events", scan.files, scan.events);
+                }
+                WorkerOutcome::Success
+            }
+            Err(e) => {
+                ctx.metrics.record_failure("fallback_scan_error");
+                tracing::warn!("Fallback scan error: {}", e);
+                WorkerOutcome::Failure(e.to_string())
+            }
+        }
+    }
+}
+
+/// Retries spooled (previously-failed) shipments on a timer. Idle while
+/// offline.
+pub struct SpoolReplayWorker {
+    interval: Duration,
+}
+
+impl SpoolReplayWorker {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval }
+    }
+}
+
+#[async_trait(?Send)]
+impl DaemonWorker for SpoolReplayWorker {
+    fn name(&self) -> &'static str {
+        "spool_replay"
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    async fn tick(&mut self, ctx: &mut WorkerContext<'_>) -> WorkerOutcome {
+        if ctx.offline.is_offline() {
+            return WorkerOutcome::Idle;
+        }
+        let outcome = match shipper::replay_spool_batch(ctx.conn, ctx.client, ctx.algo, 50, Some(ctx.breaker), ctx.recipient_key).await {
+            Ok((ok, fail)) => {
+                if fail > 0 {
+                    ctx.metrics.record_failure("spool_replay");
+                }
+                if ok > 0 || fail > 0 {
+                    tracing::info!("Spool replay: {} shipped, {} failed", ok, fail);
+                }
+                if fail > 0 {
+                    WorkerOutcome::Failure(format!("{} entries failed", fail))
+                } else {
+                    WorkerOutcome::Success
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Spool replay error: {}", e);
+                WorkerOutcome::Failure(e.to_string())
+            }
+        };
+        if let Ok(depth) = Spool::new(ctx.conn).pending_count() {
+            ctx.metrics.set_spool_depth(depth as u64);
+        }
+        outcome
+    }
+}
+
+/// Prunes stale `file_state` entries (files deleted from disk, >30 days
+/// old) once a day. Runs regardless of connectivity — it's local
+/// housekeeping, not shipping.
+pub struct PruneWorker {
+    interval: Duration,
+}
+
+impl PruneWorker {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval }
+    }
+}
+
+#[async_trait(?Send)]
+impl DaemonWorker for PruneWorker {
+    fn name(&self) -> &'static str {
+        "prune"
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    async fn tick(&mut self, ctx: &mut WorkerContext<'_>) -> WorkerOutcome {
+        let fs = FileState::new(ctx.conn);
+        match fs.prune_stale(30) {
+            Ok(n) => {
+                if n > 0 {
+                    tracing::info!("Daily prune: removed {} stale file_state entries", n);
+                }
+                WorkerOutcome::Success
+            }
+            Err(e) => {
+                tracing::warn!("Daily prune error: {}", e);
+                WorkerOutcome::Failure(e.to_string())
+            }
+        }
+    }
+}
+
+/// Builds and emits the periodic heartbeat (status file + POST). Runs
+/// regardless of connectivity — the POST is simply skipped while offline,
+/// same as before.
+pub struct HeartbeatWorker {
+    interval: Duration,
+}
+
+impl HeartbeatWorker {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval }
+    }
+}
+
+#[async_trait(?Send)]
+impl DaemonWorker for HeartbeatWorker {
+    fn name(&self) -> &'static str {
+        "heartbeat"
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    async fn tick(&mut self, ctx: &mut WorkerContext<'_>) -> WorkerOutcome {
+        let spool = Spool::new(ctx.conn);
+        let parse_errors = ParseErrorLog::new(ctx.conn);
+        let file_state = FileState::new(ctx.conn);
+        match file_state.get_unacked_files() {
+            Ok(unacked) => {
+                let gap_bytes: u64 = unacked.iter().map(|f| f.queued_offset.saturating_sub(f.acked_offset)).sum();
+                let total = file_state.count().unwrap_or(0) as u64;
+                ctx.metrics.set_file_state_gauges(total, unacked.len() as u64, gap_bytes);
+            }
+            Err(e) => tracing::warn!("file_state gauge scan error: {}", e),
+        }
+
+        let stats = heartbeat::HeartbeatStats {
+            spool: &spool,
+            tracker: ctx.tracker,
+            breaker: ctx.breaker,
+            parse_errors: &parse_errors,
+            events_shipped_total: ctx.metrics.events_shipped_total(),
+            elevated_parse_error_ratio: ctx.elevated_parse_error_ratio,
+            is_offline: ctx.offline.is_offline(),
+            last_ship_at: ctx.last_ship_at.clone(),
+        };
+        let mut payload = heartbeat::HeartbeatPayload::build(&stats);
+        payload.workers = std::mem::take(&mut ctx.worker_snapshots);
+        ctx.metrics.record_heartbeat(&payload);
+        heartbeat::write_status_file(&payload, ctx.claude_dir);
+
+        if !ctx.offline.is_offline() {
+            if let Err(e) = heartbeat::send_heartbeat(ctx.client, &payload).await {
+                tracing::debug!("Heartbeat send failed: {}", e);
+                return WorkerOutcome::Failure(e.to_string());
+            }
+        }
+        WorkerOutcome::Success
+    }
+}
+
+/// How often the health-check worker polls `is_offline()` while healthy —
+/// cheap (an atomic read behind a mutex), but non-zero, trading a little of
+/// the daemon's "0% CPU when idle" budget for not needing every other
+/// worker to reach in and reset this one's schedule the moment it notices a
+/// connect error.
+const HEALTH_IDLE_POLL: Duration = Duration::from_secs(30);
+
+/// Probes the server while offline, with the same exponential-plus-jitter
+/// backoff as before (`health_check_backoff`) — idle (at `HEALTH_IDLE_POLL`
+/// cadence) while online.
+pub struct HealthCheckWorker {
+    base: Duration,
+    max: Duration,
+    consecutive_failures: u32,
+}
+
+impl HealthCheckWorker {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            consecutive_failures: 0,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl DaemonWorker for HealthCheckWorker {
+    fn name(&self) -> &'static str {
+        "health_check"
+    }
+
+    fn interval(&self) -> Duration {
+        if self.consecutive_failures == 0 {
+            HEALTH_IDLE_POLL
+        } else {
+            health_check_backoff(self.base, self.max, self.consecutive_failures)
+        }
+    }
+
+    async fn tick(&mut self, ctx: &mut WorkerContext<'_>) -> WorkerOutcome {
+        if !ctx.offline.is_offline() {
+            self.consecutive_failures = 0;
+            return WorkerOutcome::Idle;
+        }
+        // Gate the real network call on the breaker's own cooldown instead
+        // of just this worker's timer — this probe *is* the breaker's
+        // `Open` → `HalfOpen` probe (see `OfflineState::allow_probe`), so a
+        // tick landing before the cooldown elapses does nothing this round.
+        if !ctx.offline.allow_probe() {
+            return WorkerOutcome::Idle;
+        }
+        match ctx.client.health_check().await {
+            Ok(true) => {
+                self.consecutive_failures = 0;
+                if let Some(duration) = ctx.offline.mark_online() {
+                    tracing::info!("Back online after {:.0}s — resuming shipping", duration.as_secs_f64());
+                }
+                WorkerOutcome::Success
+            }
+            _ => {
+                self.consecutive_failures += 1;
+                // Reopens the breaker so the next tick's `allow_probe()`
+                // isn't stuck rejecting forever in `HalfOpen`.
+                ctx.offline.mark_probe_failed();
+                tracing::debug!(
+                    "Still offline (health check failed, retry {} in {:.1}s)",
+                    self.consecutive_failures,
+                    health_check_backoff(self.base, self.max, self.consecutive_failures).as_secs_f64()
+                );
+                WorkerOutcome::Failure("still offline".to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle_starts_idle() {
+        let handle = WorkerHandle::new("test");
+        let snap = handle.snapshot();
+        assert_eq!(snap.state, "idle");
+        assert_eq!(snap.run_count, 0);
+    }
+
+    #[test]
+    fn test_handle_success_marks_ok() {
+        let handle = WorkerHandle::new("test");
+        handle.mark_running();
+        assert_eq!(handle.snapshot().state, "running");
+        handle.mark_outcome(&WorkerOutcome::Success);
+        let snap = handle.snapshot();
+        assert_eq!(snap.state, "ok");
+        assert_eq!(snap.run_count, 1);
+        assert!(snap.last_success_at.is_some());
+    }
+
+    #[test]
+    fn test_handle_failure_marks_error() {
+        let handle = WorkerHandle::new("test");
+        handle.mark_running();
+        handle.mark_outcome(&WorkerOutcome::Failure("boom".to_string()));
+        let snap = handle.snapshot();
+        assert_eq!(snap.state, "error");
+        assert_eq!(snap.run_count, 1);
+        assert_eq!(snap.last_error.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn test_handle_idle_outcome_does_not_count_as_a_run() {
+        let handle = WorkerHandle::new("test");
+        handle.mark_running();
+        handle.mark_outcome(&WorkerOutcome::Idle);
+        let snap = handle.snapshot();
+        assert_eq!(snap.state, "idle");
+        assert_eq!(snap.run_count, 0);
+    }
+
+    #[test]
+    fn test_handle_recovers_from_error_to_ok() {
+        let handle = WorkerHandle::new("test");
+        handle.mark_outcome(&WorkerOutcome::Failure("boom".to_string()));
+        assert_eq!(handle.snapshot().state, "error");
+        handle.mark_outcome(&WorkerOutcome::Success);
+        assert_eq!(handle.snapshot().state, "ok");
+    }
+
+    #[test]
+    fn test_registry_next_deadline_is_soonest() {
+        let mut registry = WorkerRegistry::new();
+        registry.register(Box::new(PruneWorker::new(Duration::from_secs(100))));
+        registry.register(Box::new(PruneWorker::new(Duration::from_secs(5))));
+        let now = tokio::time::Instant::now();
+        assert!(registry.next_deadline() <= now + Duration::from_secs(6));
+    }
+}
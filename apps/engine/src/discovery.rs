@@ -4,9 +4,29 @@
 //! Replaces the Claude-only `bench::discover_session_files()`.
 
 use std::path::PathBuf;
+use std::time::SystemTime;
 
+use crossbeam_channel::bounded;
 use walkdir::WalkDir;
 
+use crate::ignore::IgnoreMatcher;
+
+/// Knobs for `discover_all_files_parallel`.
+pub struct DiscoveryConfig {
+    /// Number of walker threads the provider roots' subdirectories are
+    /// split across. Reuses `ShipperConfig::workers` so discovery and
+    /// shipping scale together.
+    pub workers: usize,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            workers: num_cpus::get(),
+        }
+    }
+}
+
 /// Configuration for a session provider.
 pub struct ProviderConfig {
     pub name: &'static str,
@@ -45,40 +65,108 @@ pub fn get_providers() -> Vec<ProviderConfig> {
         .collect()
 }
 
-/// Discover all session files across all providers.
-///
-/// Returns `(path, provider_name)` tuples sorted by modification time (newest first).
-pub fn discover_all_files(providers: &[ProviderConfig]) -> Vec<(PathBuf, &'static str)> {
-    let mut files = Vec::new();
+/// One provider subtree a walker thread is responsible for: either an
+/// immediate child directory of a provider root (recursed fully), or the
+/// root itself walked one level deep (to pick up files that sit directly
+/// in the root, outside any subdirectory).
+struct WalkUnit {
+    path: PathBuf,
+    provider: &'static str,
+    extension: &'static str,
+    max_depth: Option<usize>,
+}
 
+/// Discover all session files across all providers, stat-ing each matching
+/// file exactly once.
+///
+/// Splits each provider root into per-subdirectory work units and walks
+/// them across `config.workers` threads (fd's `WalkParallel` model, minus
+/// the directory-level work-stealing — our provider trees are shallow
+/// enough that a static split across top-level subdirectories keeps all
+/// workers busy). Each worker pushes `(path, provider, modified, len)`
+/// tuples into a bounded channel as it finds them; a single collector
+/// thread drains it into the final, newest-first-sorted `Vec`. Paths
+/// matched by `ignore` are skipped before the `metadata()` stat.
+pub fn discover_all_files_parallel(
+    providers: &[ProviderConfig],
+    config: &DiscoveryConfig,
+    ignore: &IgnoreMatcher,
+) -> Vec<(PathBuf, &'static str, SystemTime, u64)> {
+    let mut units = Vec::new();
     for provider in providers {
-        for entry in WalkDir::new(&provider.root)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            let path = entry.path();
-            if path.extension().map_or(false, |ext| ext == provider.extension) {
-                if let Ok(meta) = path.metadata() {
-                    if meta.len() > 0 {
-                        files.push((path.to_path_buf(), provider.name));
-                    }
+        // Files directly under the root (not in any subdirectory).
+        units.push(WalkUnit {
+            path: provider.root.clone(),
+            provider: provider.name,
+            extension: provider.extension,
+            max_depth: Some(1),
+        });
+        if let Ok(entries) = std::fs::read_dir(&provider.root) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                    units.push(WalkUnit {
+                        path: entry.path(),
+                        provider: provider.name,
+                        extension: provider.extension,
+                        max_depth: None,
+                    });
                 }
             }
         }
     }
 
-    // Sort by modification time descending (newest first)
-    files.sort_by(|a, b| {
-        let ma = std::fs::metadata(&a.0)
-            .and_then(|m| m.modified())
-            .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-        let mb = std::fs::metadata(&b.0)
-            .and_then(|m| m.modified())
-            .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-        mb.cmp(&ma)
+    let worker_count = config.workers.max(1).min(units.len().max(1));
+    let (tx, rx) = bounded::<(PathBuf, &'static str, SystemTime, u64)>(4096);
+
+    let collector = std::thread::spawn(move || {
+        let mut files = Vec::new();
+        while let Ok(item) = rx.recv() {
+            files.push(item);
+        }
+        files
+    });
+
+    std::thread::scope(|scope| {
+        let chunk_size = (units.len() + worker_count - 1) / worker_count;
+        for chunk in units.chunks(chunk_size.max(1)) {
+            let tx = tx.clone();
+            scope.spawn(move || {
+                for unit in chunk {
+                    let mut walker = WalkDir::new(&unit.path).follow_links(false);
+                    if let Some(depth) = unit.max_depth {
+                        walker = walker.max_depth(depth);
+                    }
+                    for entry in walker.into_iter().filter_map(|e| e.ok()) {
+                        let path = entry.path();
+                        if !path.extension().map_or(false, |ext| ext == unit.extension) {
+                            continue;
+                        }
+                        if ignore.is_ignored(path) {
+                            continue;
+                        }
+                        let Ok(meta) = entry.metadata() else {
+                            continue;
+                        };
+                        if meta.len() == 0 {
+                            continue;
+                        }
+                        let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                        if tx.send((path.to_path_buf(), unit.provider, modified, meta.len())).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+        drop(tx);
     });
 
+    let mut files = collector.join().unwrap_or_default();
+
+    // Sort by the mtime captured during the walk — avoids re-stating every
+    // file a second time just to order them.
+    files.sort_by(|a, b| b.2.cmp(&a.2));
+
     files
 }
 
@@ -8,10 +8,16 @@ use std::path::PathBuf;
 use anyhow::Result;
 use serde::Serialize;
 
+use crate::circuit_breaker::CircuitBreaker;
+use crate::daemon::worker::WorkerSnapshot;
 use crate::error_tracker::ConsecutiveErrorTracker;
 use crate::shipping::client::ShipperClient;
+use crate::state::parse_errors::ParseErrorLog;
 use crate::state::spool::Spool;
 
+/// Sliding window over which `parse_error_count_1h` is computed.
+const PARSE_ERROR_WINDOW: std::time::Duration = std::time::Duration::from_secs(3600);
+
 const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Heartbeat payload sent to the server and written locally.
@@ -22,16 +28,37 @@ pub struct HeartbeatPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_ship_at: Option<String>,
     pub spool_pending_count: usize,
+    pub spool_total_count: usize,
+    pub spool_dead_count: usize,
     pub parse_error_count_1h: u32,
     pub consecutive_ship_failures: u32,
     pub disk_free_bytes: u64,
     pub is_offline: bool,
+    pub breaker_state: String,
+    /// True when recent parse errors make up an outsized share of shipped
+    /// events — usually means a corrupted or upgraded log format, not just
+    /// the occasional bad line. See `ShipperConfig::elevated_parse_error_ratio`.
+    pub elevated_parse_error_rate: bool,
+    /// Live status of each background worker (fallback scan, spool replay,
+    /// prune, heartbeat, health check) as of this tick — see
+    /// `daemon::worker::WorkerRegistry`. Populated by the heartbeat worker
+    /// itself; empty for payloads built outside that path (e.g. the tests
+    /// below).
+    #[serde(default)]
+    pub workers: Vec<WorkerSnapshot>,
 }
 
 /// Stats needed to build a heartbeat.
 pub struct HeartbeatStats<'a> {
     pub spool: &'a Spool<'a>,
     pub tracker: &'a ConsecutiveErrorTracker,
+    pub breaker: &'a CircuitBreaker,
+    pub parse_errors: &'a ParseErrorLog<'a>,
+    /// Lifetime successfully-shipped event count (see
+    /// `Metrics::events_shipped_total`), the denominator for the parse
+    /// error ratio guard.
+    pub events_shipped_total: u64,
+    pub elevated_parse_error_ratio: f64,
     pub is_offline: bool,
     pub last_ship_at: Option<String>,
 }
@@ -39,18 +66,34 @@ pub struct HeartbeatStats<'a> {
 impl HeartbeatPayload {
     pub fn build(stats: &HeartbeatStats<'_>) -> Self {
         let spool_pending_count = stats.spool.pending_count().unwrap_or(0);
+        let spool_total_count = stats.spool.total_size().unwrap_or(0);
+        let spool_dead_count = stats.spool.dead_count().unwrap_or(0);
         let consecutive_ship_failures = stats.tracker.consecutive_count();
         let disk_free_bytes = get_disk_free();
+        let parse_error_count_1h = stats.parse_errors.count_since(PARSE_ERROR_WINDOW).unwrap_or(0);
+
+        // With no shipped events yet, any parse error at all is elevated;
+        // otherwise compare against the configured ratio.
+        let elevated_parse_error_rate = if stats.events_shipped_total == 0 {
+            parse_error_count_1h > 0
+        } else {
+            (parse_error_count_1h as f64 / stats.events_shipped_total as f64) > stats.elevated_parse_error_ratio
+        };
 
         HeartbeatPayload {
             version: ENGINE_VERSION.to_string(),
             daemon_pid: std::process::id(),
             last_ship_at: stats.last_ship_at.clone(),
             spool_pending_count,
-            parse_error_count_1h: 0, // placeholder — not tracked per-hour yet
+            spool_total_count,
+            spool_dead_count,
+            parse_error_count_1h,
             consecutive_ship_failures,
             disk_free_bytes,
             is_offline: stats.is_offline,
+            breaker_state: stats.breaker.state().as_str().to_string(),
+            elevated_parse_error_rate,
+            workers: Vec::new(),
         }
     }
 }
@@ -123,10 +166,15 @@ mod tests {
             daemon_pid: 12345,
             last_ship_at: Some("2026-02-18T10:00:00Z".to_string()),
             spool_pending_count: 5,
+            spool_total_count: 5,
+            spool_dead_count: 0,
             parse_error_count_1h: 0,
             consecutive_ship_failures: 2,
             disk_free_bytes: 1_000_000_000,
             is_offline: false,
+            breaker_state: "closed".to_string(),
+            elevated_parse_error_rate: false,
+            workers: Vec::new(),
         };
 
         // Must serialize correctly
@@ -148,10 +196,15 @@ mod tests {
             daemon_pid: 1,
             last_ship_at: None,
             spool_pending_count: 0,
+            spool_total_count: 0,
+            spool_dead_count: 0,
             parse_error_count_1h: 0,
             consecutive_ship_failures: 0,
             disk_free_bytes: 0,
             is_offline: true,
+            breaker_state: "open".to_string(),
+            elevated_parse_error_rate: true,
+            workers: Vec::new(),
         };
 
         let json = serde_json::to_string(&payload).unwrap();
@@ -160,4 +213,41 @@ mod tests {
         // last_ship_at should be omitted when None
         assert!(parsed.get("last_ship_at").is_none() || parsed["last_ship_at"].is_null());
     }
+
+    #[test]
+    fn test_build_flags_elevated_parse_error_rate() {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let conn = crate::state::db::open_db(Some(tmp.path())).unwrap();
+        let spool = Spool::new(&conn);
+        let tracker = ConsecutiveErrorTracker::new();
+        let breaker = CircuitBreaker::new();
+        let parse_errors = ParseErrorLog::new(&conn);
+        for _ in 0..5 {
+            parse_errors.record("claude", "/a.jsonl", "parse_error", "bad json").unwrap();
+        }
+
+        // 5 errors against 10 shipped events is a 50% rate — above the
+        // default 10% ratio, so the flag should trip.
+        let stats = HeartbeatStats {
+            spool: &spool,
+            tracker: &tracker,
+            breaker: &breaker,
+            parse_errors: &parse_errors,
+            events_shipped_total: 10,
+            elevated_parse_error_ratio: 0.1,
+            is_offline: false,
+            last_ship_at: None,
+        };
+        let payload = HeartbeatPayload::build(&stats);
+        assert_eq!(payload.parse_error_count_1h, 5);
+        assert!(payload.elevated_parse_error_rate);
+
+        // Same 5 errors against 1000 shipped events is well under 10%.
+        let stats = HeartbeatStats {
+            events_shipped_total: 1000,
+            ..stats
+        };
+        let payload = HeartbeatPayload::build(&stats);
+        assert!(!payload.elevated_parse_error_rate);
+    }
 }
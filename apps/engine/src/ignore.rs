@@ -0,0 +1,168 @@
+//! gitignore-style exclusion for discovery and the filesystem watcher.
+//!
+//! Users park archived or throwaway project folders under provider roots
+//! (e.g. `~/.claude/projects`) that they don't want shipped or watched.
+//! `IgnoreMatcher` compiles a `.longhouseignore` file per provider root
+//! (walking upward from the root so a single ignore file near `$HOME` can
+//! cover every provider) plus `ShipperConfig::ignore_patterns`, into one
+//! `GlobSet` gathered once at startup — the way watchexec batches its
+//! ignore-file gathering, so pattern compilation isn't repeated per event.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use globset::{Glob, GlobSetBuilder};
+
+use crate::discovery::ProviderConfig;
+
+const IGNORE_FILE_NAME: &str = ".longhouseignore";
+
+/// A compiled set of gitignore-style patterns, last-match-wins.
+#[derive(Clone)]
+pub struct IgnoreMatcher {
+    set: globset::GlobSet,
+    /// Parallel to `set`'s glob indices: true if the pattern was a `!negation`.
+    negated: Vec<bool>,
+}
+
+impl IgnoreMatcher {
+    /// No patterns — every path passes.
+    pub fn empty() -> Self {
+        Self {
+            set: GlobSetBuilder::new().build().expect("empty GlobSet builds"),
+            negated: Vec::new(),
+        }
+    }
+
+    /// Gather `.longhouseignore` files for each provider root (walking
+    /// upward toward the filesystem root so a shared ignore file closer to
+    /// `$HOME` covers every provider) plus `global_patterns`, and compile
+    /// them into a single matcher.
+    pub fn gather(providers: &[ProviderConfig], global_patterns: &[String]) -> Result<Self> {
+        let mut lines = Vec::new();
+        for pattern in global_patterns {
+            lines.push((pattern.clone(), false));
+        }
+
+        let mut visited = HashSet::new();
+        for provider in providers {
+            // Outermost first, so a file nearer the provider root is added
+            // later and wins under `is_ignored`'s last-match rule.
+            for dir in ancestors_of(&provider.root).into_iter().rev() {
+                if !visited.insert(dir.clone()) {
+                    continue;
+                }
+                let ignore_file = dir.join(IGNORE_FILE_NAME);
+                if let Ok(contents) = std::fs::read_to_string(&ignore_file) {
+                    lines.extend(parse_ignore_file(&contents));
+                }
+            }
+        }
+
+        Self::compile(lines)
+    }
+
+    fn compile(lines: Vec<(String, bool)>) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        let mut negated = Vec::with_capacity(lines.len());
+        for (pattern, is_negated) in lines {
+            for glob in glob_patterns(&pattern) {
+                builder.add(Glob::new(&glob)?);
+                negated.push(is_negated);
+            }
+        }
+        Ok(Self {
+            set: builder.build()?,
+            negated,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.negated.is_empty()
+    }
+
+    /// True if `path` should be excluded from discovery/watching. Matches
+    /// gitignore's last-pattern-wins rule: of all patterns that match, the
+    /// one added last (furthest from `$HOME`, i.e. most specific) decides.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        match self.set.matches(path).into_iter().max() {
+            Some(idx) => !self.negated[idx],
+            None => false,
+        }
+    }
+}
+
+/// `path` and each of its ancestor directories, nearest first.
+fn ancestors_of(path: &Path) -> Vec<PathBuf> {
+    path.ancestors().map(Path::to_path_buf).collect()
+}
+
+fn parse_ignore_file(contents: &str) -> Vec<(String, bool)> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| match line.strip_prefix('!') {
+            Some(rest) => (rest.to_string(), true),
+            None => (line.to_string(), false),
+        })
+        .collect()
+}
+
+/// Translate a gitignore-style line into globs matched against a full
+/// path: `**/{pattern}` matches the entry itself and `**/{pattern}/**`
+/// everything beneath it (globset's trailing `/**` does not match the entry
+/// itself), so this also covers directory-only patterns. Every pattern
+/// matches at any depth; a `/` in it does not anchor it to the ignore
+/// file's directory.
+fn glob_patterns(pattern: &str) -> [String; 2] {
+    let pattern = pattern.trim_end_matches('/');
+    [format!("**/{pattern}"), format!("**/{pattern}/**")]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(patterns: &[&str]) -> IgnoreMatcher {
+        IgnoreMatcher::compile(patterns.iter().map(|p| (p.to_string(), false)).collect()).unwrap()
+    }
+
+    #[test]
+    fn test_bare_pattern_matches_at_any_depth() {
+        let m = matcher(&["archive"]);
+        assert!(m.is_ignored(Path::new("/home/user/.claude/projects/archive/session.jsonl")));
+        assert!(!m.is_ignored(Path::new("/home/user/.claude/projects/active/session.jsonl")));
+    }
+
+    #[test]
+    fn test_negation_overrides_earlier_ignore() {
+        let m = IgnoreMatcher::compile(vec![
+            ("archive/*".to_string(), false),
+            ("archive/keep.jsonl".to_string(), true),
+        ])
+        .unwrap();
+        assert!(m.is_ignored(Path::new("/projects/archive/old.jsonl")));
+        assert!(!m.is_ignored(Path::new("/projects/archive/keep.jsonl")));
+    }
+
+    #[test]
+    fn test_empty_matcher_ignores_nothing() {
+        let m = IgnoreMatcher::empty();
+        assert!(m.is_empty());
+        assert!(!m.is_ignored(Path::new("/anything")));
+    }
+
+    #[test]
+    fn test_parse_ignore_file_skips_comments_and_blanks() {
+        let parsed = parse_ignore_file("# comment\n\narchive\n!archive/keep\n");
+        assert_eq!(
+            parsed,
+            vec![
+                ("archive".to_string(), false),
+                ("archive/keep".to_string(), true),
+            ]
+        );
+    }
+}
@@ -1,26 +1,38 @@
+mod admin;
 mod bench;
+mod circuit_breaker;
 mod config;
 mod daemon;
 mod discovery;
 mod error_tracker;
 mod heartbeat;
+mod ignore;
+mod metrics;
 mod outbox;
 mod pipeline;
+mod progress;
+mod resync;
 mod shipper;
 mod shipping;
+mod shutdown;
 mod state;
+mod tail;
 mod watcher;
+mod workunit;
 
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use clap::{Parser, Subcommand};
+use crossbeam_channel::bounded;
 use rayon::prelude::*;
 
 use config::ShipperConfig;
 use pipeline::compressor::CompressionAlgo;
 use shipping::client::{ShipResult, ShipperClient};
+use shipping::target::ShipTarget;
 use state::db::open_db;
 use state::file_state::FileState;
 use state::spool::Spool;
@@ -29,7 +41,8 @@ fn parse_compression_algo(s: &str) -> anyhow::Result<CompressionAlgo> {
     match s.to_lowercase().as_str() {
         "gzip" | "gz" => Ok(CompressionAlgo::Gzip),
         "zstd" | "zstandard" => Ok(CompressionAlgo::Zstd),
-        _ => anyhow::bail!("Unknown compression: {}. Use 'gzip' or 'zstd'", s),
+        "lz4" => Ok(CompressionAlgo::Lz4),
+        _ => anyhow::bail!("Unknown compression: {}. Use 'gzip', 'zstd', or 'lz4'", s),
     }
 }
 
@@ -58,6 +71,34 @@ enum Commands {
         /// Also build + gzip-compress the ingest payload
         #[arg(long)]
         compress: bool,
+
+        /// Parse via mmap + rayon-sharded concurrent decoding instead of the
+        /// single-threaded streaming path (helps on multi-GB files)
+        #[arg(long)]
+        mmap: bool,
+
+        /// Path to a trained dictionary (see `train-dict`). With --compress,
+        /// also reports the dictionary-compressed size alongside the plain
+        /// zstd/gzip size for comparison.
+        #[arg(long)]
+        dict: Option<PathBuf>,
+    },
+
+    /// Train a zstd dictionary from a sample of local session files, for use
+    /// with `connect --dictionary-path` and `parse --dict` (see
+    /// `pipeline::dictionary`)
+    TrainDict {
+        /// Where to write the trained dictionary
+        #[arg(long, default_value = "longhouse.dict")]
+        out: PathBuf,
+
+        /// Maximum number of sample session files to train on
+        #[arg(long, default_value = "200")]
+        max_samples: usize,
+
+        /// Maximum dictionary size in bytes
+        #[arg(long, default_value = "112640")]
+        max_size: usize,
     },
 
     /// Run multi-file benchmark (compare against Python profiling baselines)
@@ -78,9 +119,27 @@ enum Commands {
         #[arg(long, default_value = "0")]
         workers: usize,
 
-        /// Compression algorithm: gzip (default) or zstd
+        /// Compression algorithm: gzip (default), zstd, or lz4
         #[arg(long, default_value = "gzip")]
         compression: String,
+
+        /// Run as a resumable, crash-safe job (see `state::jobs`): restarting
+        /// with the same --level/--compression skips files already done and
+        /// records parse failures instead of silently skipping them.
+        #[arg(long)]
+        resume: bool,
+
+        /// State store address for --resume (same grammar as `ship --db`);
+        /// defaults to the shipper's own state DB.
+        #[arg(long)]
+        db: Option<String>,
+
+        /// With --parallel, stream each file's result to an aggregator thread
+        /// as workers finish instead of collecting them all into memory first
+        /// (see `bench::run_benchmark_streaming`) — bounded memory and live
+        /// per-file output for very large corpora.
+        #[arg(long)]
+        streaming: bool,
     },
 
     /// Daemon mode: watch for file changes and ship incrementally
@@ -93,11 +152,13 @@ enum Commands {
         #[arg(long)]
         token: Option<String>,
 
-        /// SQLite DB path override
+        /// State store address: a bare filesystem path (SQLite, default),
+        /// or `sqlite://...` (`sled://...` and `postgres://...` reserved for
+        /// future backends)
         #[arg(long)]
-        db: Option<PathBuf>,
+        db: Option<String>,
 
-        /// Compression algorithm: gzip (default) or zstd
+        /// Compression algorithm: zstd (default), gzip, or lz4
         #[arg(long, default_value = "zstd")]
         compression: String,
 
@@ -113,9 +174,70 @@ enum Commands {
         #[arg(long, default_value = "30")]
         spool_replay_secs: u64,
 
+        /// Background resync interval in seconds: how often to re-check for
+        /// unacked file gaps that stalled (no new writes to re-trigger a
+        /// normal ship) and re-run the prepare/ship pipeline against them
+        #[arg(long, default_value = "600")]
+        resync_secs: u64,
+
+        /// Self-throttle knob (0-10) for the background resync worker
+        /// (default: from LONGHOUSE_TRANQUILITY, else 2). After each
+        /// stalled file's gap is resynced, the pass sleeps
+        /// `tranquility * last_op_duration` before moving to the next one.
+        #[arg(long)]
+        tranquility: Option<u8>,
+
         /// Log directory for rolling log files (default: ~/.claude/logs, or LONGHOUSE_LOG_DIR env)
         #[arg(long)]
         log_dir: Option<PathBuf>,
+
+        /// Expose Prometheus metrics at http://<addr>/metrics (e.g. 127.0.0.1:9090)
+        #[arg(long)]
+        metrics_addr: Option<String>,
+
+        /// Expose the admin/control API (GET /status, POST /flush, POST /spool/replay)
+        /// at this address, e.g. 127.0.0.1:9091 (default: disabled)
+        #[arg(long)]
+        admin_addr: Option<String>,
+
+        /// Hex-encoded X25519 recipient public key (default: from
+        /// LONGHOUSE_RECIPIENT_KEY). When set, payloads are sealed (see
+        /// `pipeline::crypto`) before POST.
+        #[arg(long)]
+        recipient_key: Option<String>,
+
+        /// PEM client certificate for mutual TLS (default: from
+        /// LONGHOUSE_CLIENT_CERT). Must be paired with --client-key.
+        #[arg(long)]
+        client_cert: Option<PathBuf>,
+
+        /// PEM private key matching --client-cert (default: from
+        /// LONGHOUSE_CLIENT_KEY).
+        #[arg(long)]
+        client_key: Option<PathBuf>,
+
+        /// PEM CA bundle to trust in addition to the system roots (default:
+        /// from LONGHOUSE_CA_BUNDLE).
+        #[arg(long)]
+        ca_bundle: Option<PathBuf>,
+
+        /// Endpoint to POST the current token to for a fresh one on 401/403
+        /// (default: from LONGHOUSE_TOKEN_REFRESH_URL). Omit to treat
+        /// 401/403 as terminal.
+        #[arg(long)]
+        token_refresh_url: Option<String>,
+
+        /// Seconds to wait for the shutdown drain (flush watcher cookie,
+        /// ship observed files, replay spool) before exiting anyway on
+        /// SIGINT/SIGTERM. A second signal exits immediately regardless.
+        #[arg(long, default_value = "10")]
+        shutdown_grace_secs: u64,
+
+        /// Path to a trained zstd dictionary (see `train-dict`, default:
+        /// from LONGHOUSE_DICTIONARY_PATH). When set, payloads compress
+        /// against it instead of plain `--compression`.
+        #[arg(long)]
+        dictionary_path: Option<PathBuf>,
     },
 
     /// One-shot: scan all provider sessions and ship new events
@@ -128,9 +250,11 @@ enum Commands {
         #[arg(long)]
         token: Option<String>,
 
-        /// SQLite DB path override
+        /// State store address: a bare filesystem path (SQLite, default),
+        /// or `sqlite://...` (`sled://...` and `postgres://...` reserved for
+        /// future backends)
         #[arg(long)]
-        db: Option<PathBuf>,
+        db: Option<String>,
 
         /// Ship a single file instead of scanning all providers
         #[arg(long)]
@@ -152,9 +276,43 @@ enum Commands {
         #[arg(long)]
         json: bool,
 
-        /// Compression algorithm: gzip (default) or zstd
+        /// Compression algorithm: gzip (default), zstd, or lz4
         #[arg(long, default_value = "gzip")]
         compression: String,
+
+        /// Group up to N files per HTTP request via ShipperClient::ship_batch
+        /// (default 1 = one request per file, the original behavior)
+        #[arg(long, default_value = "1")]
+        batch_size: usize,
+
+        /// Hex-encoded X25519 recipient public key (default: from
+        /// LONGHOUSE_RECIPIENT_KEY). When set, payloads are sealed (see
+        /// `pipeline::crypto`) before POST.
+        #[arg(long)]
+        recipient_key: Option<String>,
+
+        /// Number of concurrent ship-pipeline upload workers for live HTTP
+        /// shipping (default: num_cpus). Files are parsed+compressed on the
+        /// rayon pool while these workers upload earlier ones, via
+        /// `run_ship_pipeline`.
+        #[arg(long, default_value = "0")]
+        ship_workers: usize,
+    },
+
+    /// Generate an X25519 keypair for `--recipient-key` payload encryption
+    Keygen,
+
+    /// Follow one session file and print newly appended events as they're
+    /// written (see `tail::SessionTail`) — a live-rendering UI's entry point
+    /// into the same offset-resume parsing the shipper uses, independent of
+    /// `connect`'s whole-directory watch.
+    Follow {
+        /// Path to the session JSONL file to follow
+        path: PathBuf,
+
+        /// Print each event's full JSON instead of a one-line summary
+        #[arg(long)]
+        dump_events: bool,
     },
 }
 
@@ -242,15 +400,28 @@ fn main() -> anyhow::Result<()> {
             flush_ms,
             fallback_scan_secs,
             spool_replay_secs,
+            resync_secs,
+            tranquility,
             log_dir: _,
+            metrics_addr,
+            admin_addr,
+            recipient_key,
+            client_cert,
+            client_key,
+            ca_bundle,
+            token_refresh_url,
+            shutdown_grace_secs,
+            dictionary_path,
         } => {
             let algo = parse_compression_algo(&compression)?;
-            let shipper_config = ShipperConfig::from_env()?.with_overrides(
-                url.as_deref(),
-                token.as_deref(),
-                db.as_deref(),
-                None,
-            );
+            let db_path = state::store::resolve_sqlite_path(db.as_deref())?;
+            let shipper_config = ShipperConfig::from_env()?
+                .with_overrides(url.as_deref(), token.as_deref(), db_path.as_deref(), None)
+                .with_recipient_key(recipient_key.as_deref())
+                .with_tranquility(tranquility)
+                .with_mtls(client_cert.as_deref(), client_key.as_deref(), ca_bundle.as_deref())
+                .with_token_refresh_url(token_refresh_url.as_deref())
+                .with_dictionary_path(dictionary_path.as_deref());
 
             let connect_config = daemon::ConnectConfig {
                 shipper_config,
@@ -258,6 +429,12 @@ fn main() -> anyhow::Result<()> {
                 flush_interval: std::time::Duration::from_millis(flush_ms),
                 fallback_scan_secs,
                 spool_replay_secs,
+                resync_secs,
+                health_backoff_base: daemon::ConnectConfig::DEFAULT_HEALTH_BACKOFF_BASE,
+                health_backoff_max: daemon::ConnectConfig::DEFAULT_HEALTH_BACKOFF_MAX,
+                metrics_addr,
+                admin_addr,
+                shutdown_grace: std::time::Duration::from_secs(shutdown_grace_secs),
             };
 
             // Use current_thread runtime for minimal resource usage
@@ -271,8 +448,17 @@ fn main() -> anyhow::Result<()> {
             offset,
             dump_events,
             compress,
+            mmap,
+            dict,
+        } => {
+            cmd_parse(&path, offset, dump_events, compress, mmap, dict.as_deref())?;
+        }
+        Commands::TrainDict {
+            out,
+            max_samples,
+            max_size,
         } => {
-            cmd_parse(&path, offset, dump_events, compress)?;
+            cmd_train_dict(&out, max_samples, max_size)?;
         }
         Commands::Bench {
             level,
@@ -280,9 +466,22 @@ fn main() -> anyhow::Result<()> {
             parallel,
             workers,
             compression,
+            resume,
+            db,
+            streaming,
         } => {
             let algo = parse_compression_algo(&compression)?;
-            cmd_bench(&level, compress, parallel, workers, algo)?;
+            let db_path = state::store::resolve_sqlite_path(db.as_deref())?;
+            cmd_bench(
+                &level,
+                compress,
+                parallel,
+                workers,
+                algo,
+                resume,
+                db_path.as_deref(),
+                streaming,
+            )?;
         }
         Commands::Ship {
             url,
@@ -294,8 +493,12 @@ fn main() -> anyhow::Result<()> {
             dry_run,
             json,
             compression,
+            batch_size,
+            recipient_key,
+            ship_workers,
         } => {
             let algo = parse_compression_algo(&compression)?;
+            let db_path = state::store::resolve_sqlite_path(db.as_deref())?;
             // Build tokio runtime for async HTTP
             let rt = tokio::runtime::Runtime::new()?;
             if let Some(path) = file.as_ref() {
@@ -304,23 +507,36 @@ fn main() -> anyhow::Result<()> {
                     provider.as_deref(),
                     url.as_deref(),
                     token.as_deref(),
-                    db.as_deref(),
+                    db_path.as_deref(),
                     dry_run,
                     json,
                     algo,
+                    recipient_key.as_deref(),
                 ))?;
             } else {
                 rt.block_on(cmd_ship(
                     url.as_deref(),
                     token.as_deref(),
-                    db.as_deref(),
+                    db_path.as_deref(),
                     workers,
                     dry_run,
                     json,
                     algo,
+                    batch_size.max(1),
+                    recipient_key.as_deref(),
+                    ship_workers,
                 ))?;
             }
         }
+        Commands::Keygen => {
+            let (secret_hex, public_hex) = pipeline::crypto::generate_keypair();
+            println!("public key  (--recipient-key): {}", public_hex);
+            println!("secret key  (keep this OFFLINE, server-side only): {}", secret_hex);
+        }
+        Commands::Follow { path, dump_events } => {
+            let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+            rt.block_on(cmd_follow(&path, dump_events))?;
+        }
     }
 
     Ok(())
@@ -338,16 +554,27 @@ async fn cmd_ship(
     dry_run: bool,
     json_output: bool,
     algo: CompressionAlgo,
+    batch_size: usize,
+    recipient_key: Option<&str>,
+    ship_workers: usize,
 ) -> anyhow::Result<()> {
     let start = Instant::now();
 
     // Load config
-    let config = ShipperConfig::from_env()?.with_overrides(
-        url,
-        token,
-        db_path,
-        if workers > 0 { Some(workers) } else { None },
-    );
+    let config = ShipperConfig::from_env()?
+        .with_overrides(
+            url,
+            token,
+            db_path,
+            if workers > 0 { Some(workers) } else { None },
+        )
+        .with_recipient_key(recipient_key);
+    let recipient_key = config
+        .recipient_key
+        .as_deref()
+        .map(pipeline::crypto::RecipientKey::from_hex)
+        .transpose()?;
+    let recipient_key = recipient_key.as_ref();
 
     if !json_output {
         eprintln!("Shipping to: {}", config.api_url);
@@ -362,7 +589,7 @@ async fn cmd_ship(
     // Startup recovery: re-enqueue gaps (queued > acked)
     {
         let file_state = FileState::new(&conn);
-        let spool = Spool::new(&conn);
+        let spool = Spool::with_owned_blobs(&conn, config.spool_owned_blobs);
         let unacked = file_state.get_unacked_files()?;
         for f in &unacked {
             tracing::info!(
@@ -444,11 +671,14 @@ async fn cmd_ship(
 
     // Create HTTP client (unless dry run)
     let client = if !dry_run {
-        Some(ShipperClient::with_compression(&config, algo)?)
+        Some(Arc::new(ShipperClient::with_compression(&config, algo)?))
     } else {
         None
     };
 
+    // Worker count for the concurrent ship pipeline (live shipping only).
+    let ship_worker_count = if ship_workers > 0 { ship_workers } else { num_cpus::get() };
+
     // Configure rayon thread pool
     let num_workers = if workers > 0 { workers } else { num_cpus::get() };
     rayon::ThreadPoolBuilder::new()
@@ -464,12 +694,69 @@ async fn cmd_ship(
         );
     }
 
+    let total_files = files_to_ship.len();
+    let mut files_shipped = 0usize;
+    let mut events_shipped = 0usize;
+    let mut bytes_shipped = 0u64;
+    let mut files_failed = 0usize;
+    let mut files_skipped = 0usize;
+    let mut worker_stats: Vec<ShipWorkerStats> = Vec::new();
+
+    // The bounded concurrent pipeline (see `run_ship_pipeline`) overlaps
+    // per-file compression with the network upload of earlier files, and is
+    // the default live-shipping path. `--ship-workers 1` instead takes the
+    // original embarrassingly-parallel-compress-then-batch-POST path below,
+    // which still honors `--batch-size`.
+    if !dry_run && ship_worker_count > 1 {
+        let files: Vec<PathBuf> = files_to_ship.iter().map(|(p, _)| p.clone()).collect();
+        let client = Arc::clone(client.as_ref().unwrap());
+        let db_path_owned = config.db_path.clone();
+        let recipient_key_owned = recipient_key.copied();
+        let rt_handle = tokio::runtime::Handle::current();
+
+        let chunk_dedup = config.chunk_dedup;
+        let chunker_params =
+            pipeline::chunker::ChunkerParams::new(config.target_chunk_bytes, config.max_chunk_bytes);
+
+        let (fs, es, bs, ff, stats) = tokio::task::spawn_blocking(move || {
+            run_ship_pipeline(
+                files,
+                "claude".to_string(),
+                algo,
+                db_path_owned,
+                client,
+                ship_worker_count,
+                recipient_key_owned,
+                rt_handle,
+                chunk_dedup,
+                chunker_params,
+                config.spool_owned_blobs,
+                config.max_uncompressed_event_bytes,
+            )
+        })
+        .await??;
+
+        files_shipped = fs;
+        events_shipped = es;
+        bytes_shipped = bs;
+        files_failed = ff;
+        files_skipped = total_files - fs - ff;
+        worker_stats = stats;
+
+        if !json_output {
+            eprintln!(
+                "Shipped with {} pipeline workers in {:.1}s",
+                ship_worker_count,
+                start.elapsed().as_secs_f64()
+            );
+        }
+    } else {
+
     // Phase 1: Parse + compress in parallel (CPU-bound, embarrassingly parallel)
     // Collect results for sequential state writes + HTTP shipping.
     let files_done = AtomicUsize::new(0);
     let bytes_done = AtomicU64::new(0);
     let events_done = AtomicUsize::new(0);
-    let total_files = files_to_ship.len();
 
     struct ShipItem {
         path_str: String,
@@ -516,7 +803,20 @@ async fn cmd_ship(
                 algo,
             ) {
                 Ok(c) => {
-                    if dry_run { Vec::new() } else { c }
+                    if dry_run {
+                        Vec::new()
+                    } else {
+                        match recipient_key {
+                            Some(key) => match pipeline::crypto::seal(&c, key) {
+                                Ok(sealed) => sealed,
+                                Err(e) => {
+                                    tracing::warn!("Encrypt failed {}: {}", path_str, e);
+                                    return None;
+                                }
+                            },
+                            None => c,
+                        }
+                    }
                 }
                 Err(e) => {
                     tracing::warn!("Compress failed {}: {}", path_str, e);
@@ -561,12 +861,6 @@ async fn cmd_ship(
     }
 
     // Phase 2: Sequential state writes + HTTP shipping
-    let mut files_shipped = 0usize;
-    let mut events_shipped = 0usize;
-    let mut bytes_shipped = 0u64;
-    let mut files_failed = 0usize;
-    let mut files_skipped = 0usize;
-
     if dry_run {
         // Batch all state writes in a single transaction (8000+ writes → ~10ms)
         conn.execute_batch("BEGIN")?;
@@ -594,102 +888,138 @@ async fn cmd_ship(
 
     // Live HTTP shipping (skip if dry run — already handled above)
     if !dry_run {
-    for item in ship_items {
-        let item = match item {
-            Some(item) => item,
+    let items: Vec<ShipItem> = ship_items
+        .into_iter()
+        .filter_map(|item| match item {
+            Some(item) => Some(item),
             None => {
                 files_skipped += 1;
-                continue;
+                None
             }
-        };
+        })
+        .collect();
 
-        // Ship via HTTP
+    for group in items.chunks(batch_size.max(1)) {
         let client = client.as_ref().unwrap();
-        let result = client.ship(item.compressed).await;
-
-        match result {
-            ShipResult::Ok(_) => {
-                file_state.set_offset(
-                    &item.path_str,
-                    item.new_offset,
-                    &item.session_id,
-                    &item.session_id,
-                    "claude",
-                )?;
-                files_shipped += 1;
-                events_shipped += item.event_count;
-                bytes_shipped += item.new_offset - item.offset;
-            }
-            ShipResult::RateLimited | ShipResult::ServerError(_, _) | ShipResult::ConnectError(_) => {
-                let spool = Spool::new(&conn);
-                file_state.set_queued_offset(
-                    &item.path_str,
-                    item.new_offset,
-                    "claude",
-                    &item.session_id,
-                    &item.session_id,
-                )?;
-                spool.enqueue(
-                    "claude",
-                    &item.path_str,
-                    item.offset,
-                    item.new_offset,
-                    Some(&item.session_id),
-                )?;
-                files_failed += 1;
-
-                let err_msg = match &result {
-                    ShipResult::RateLimited => "rate limited".to_string(),
-                    ShipResult::ServerError(code, body) => format!("{}:{}", code, &body[..body.len().min(200)]),
-                    ShipResult::ConnectError(e) => e.clone(),
-                    _ => unreachable!(),
-                };
-                tracing::warn!("Failed to ship {}: {}", item.path_str, err_msg);
-            }
-            ShipResult::ClientError(code, body) => {
-                tracing::error!(
-                    "Client error shipping {}: {} {}",
-                    item.path_str,
-                    code,
-                    &body[..body.len().min(200)]
-                );
-                file_state.set_offset(
-                    &item.path_str,
-                    item.new_offset,
-                    &item.session_id,
-                    &item.session_id,
-                    "claude",
-                )?;
-                files_skipped += 1;
+        let results = if group.len() == 1 {
+            vec![client.ship(group[0].compressed.clone(), algo).await]
+        } else {
+            client
+                .ship_batch(
+                    group.iter().map(|item| item.compressed.clone()).collect(),
+                    algo,
+                )
+                .await
+        };
+
+        for (item, result) in group.iter().zip(results) {
+            match result {
+                ShipResult::Ok(_) => {
+                    file_state.set_offset(
+                        &item.path_str,
+                        item.new_offset,
+                        &item.session_id,
+                        &item.session_id,
+                        "claude",
+                    )?;
+                    files_shipped += 1;
+                    events_shipped += item.event_count;
+                    bytes_shipped += item.new_offset - item.offset;
+                }
+                ShipResult::RateLimited | ShipResult::ServerError(_, _) | ShipResult::ConnectError(_) => {
+                    let spool = Spool::with_owned_blobs(&conn, config.spool_owned_blobs);
+                    file_state.set_queued_offset(
+                        &item.path_str,
+                        item.new_offset,
+                        "claude",
+                        &item.session_id,
+                        &item.session_id,
+                    )?;
+                    spool.enqueue(
+                        "claude",
+                        &item.path_str,
+                        item.offset,
+                        item.new_offset,
+                        Some(&item.session_id),
+                    )?;
+                    files_failed += 1;
+
+                    let err_msg = match &result {
+                        ShipResult::RateLimited => "rate limited".to_string(),
+                        ShipResult::ServerError(code, body) => format!("{}:{}", code, &body[..body.len().min(200)]),
+                        ShipResult::ConnectError(e) => e.clone(),
+                        _ => unreachable!(),
+                    };
+                    tracing::warn!("Failed to ship {}: {}", item.path_str, err_msg);
+                }
+                ShipResult::ClientError(code, body) => {
+                    tracing::error!(
+                        "Client error shipping {}: {} {}",
+                        item.path_str,
+                        code,
+                        &body[..body.len().min(200)]
+                    );
+                    file_state.set_offset(
+                        &item.path_str,
+                        item.new_offset,
+                        &item.session_id,
+                        &item.session_id,
+                        "claude",
+                    )?;
+                    files_skipped += 1;
+                }
             }
         }
     }
     } // end if !dry_run
+    } // end else (legacy embarrassingly-parallel path)
 
     // Replay spool (if not dry run)
     let mut spool_replayed = 0usize;
     if !dry_run {
-        let spool = Spool::new(&conn);
+        let spool = Spool::with_owned_blobs(&conn, config.spool_owned_blobs);
         let pending = spool.dequeue_batch(100)?;
         if !pending.is_empty() && !json_output {
             eprintln!("Replaying {} spool entries...", pending.len());
         }
+        let replay_span = workunit::Span::root("spool.replay_batch", std::sync::Arc::new(workunit::LogSink));
+        replay_span.add_rows(pending.len() as u64);
         let client = client.as_ref().unwrap();
         for entry in &pending {
-            // Re-read and re-parse the source file range
-            let path = PathBuf::from(&entry.file_path);
-            if !path.exists() {
-                tracing::warn!("Spool file missing: {}", entry.file_path);
-                spool.mark_failed_with_max(entry.id, "file missing", 0)?;
-                continue;
-            }
-
-            let parse_result = match pipeline::parser::parse_session_file(&path, entry.start_offset) {
-                Ok(r) => r,
-                Err(e) => {
-                    spool.mark_failed(entry.id, &e.to_string())?;
+            // Re-read and re-parse the source file range — or, for an
+            // owned-blob entry, the bytes already copied into the row.
+            let parse_result = if entry.has_payload {
+                match spool.read_payload(entry.id) {
+                    Ok(bytes) => {
+                        let session_id = entry.session_id.clone().unwrap_or_else(|| {
+                            PathBuf::from(&entry.file_path)
+                                .file_stem()
+                                .and_then(|s| s.to_str())
+                                .unwrap_or("unknown")
+                                .to_string()
+                        });
+                        pipeline::parser::parse_bytes(&bytes, entry.start_offset, &session_id)
+                    }
+                    Err(e) => {
+                        spool.mark_failed(entry.id, &e.to_string(), Some(&replay_span))?;
+                        continue;
+                    }
+                }
+            } else {
+                let path = PathBuf::from(&entry.file_path);
+                if !path.exists() {
+                    tracing::warn!("Spool file missing: {}", entry.file_path);
+                    spool.mark_failed_with_max(entry.id, "file missing", 0, Some(&replay_span))?;
                     continue;
                 }
+
+                match pipeline::parser::parse_session_file(&path, entry.start_offset) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        spool.mark_failed(entry.id, &e.to_string(), Some(&replay_span))?;
+                        continue;
+                    }
+                }
             };
 
             if parse_result.events.is_empty() {
@@ -706,7 +1036,7 @@ async fn cmd_ship(
                 algo,
             )?;
 
-            match client.ship(compressed).await {
+            match client.ship(compressed, algo).await {
                 ShipResult::Ok(_) => {
                     spool.mark_shipped(entry.id)?;
                     file_state.set_acked_offset(&entry.file_path, entry.end_offset)?;
@@ -717,10 +1047,10 @@ async fn cmd_ship(
                     break;
                 }
                 ShipResult::RateLimited | ShipResult::ServerError(_, _) => {
-                    spool.mark_failed(entry.id, "server error during replay")?;
+                    spool.mark_failed(entry.id, "server error during replay", Some(&replay_span))?;
                 }
                 ShipResult::ClientError(code, _) => {
-                    spool.mark_failed_with_max(entry.id, &format!("client error {}", code), 0)?;
+                    spool.mark_failed_with_max(entry.id, &format!("client error {}", code), 0, Some(&replay_span))?;
                 }
             }
         }
@@ -730,13 +1060,31 @@ async fn cmd_ship(
         if cleaned > 0 {
             tracing::info!("Cleaned {} old spool entries", cleaned);
         }
+
+        // Reclaim any rows stranded behind an already-advanced acked
+        // watermark (see `Spool::checkpoint`).
+        let checkpoint = spool.checkpoint(&file_state)?;
+        if checkpoint.reclaimed > 0 {
+            tracing::info!("Checkpoint reclaimed {} stranded spool entries", checkpoint.reclaimed);
+        }
     }
 
     let total_elapsed = start.elapsed();
 
+    // Concurrency utilization: fraction of (wall time * worker count) that
+    // workers spent actually blocked on network I/O, vs. idle waiting on the
+    // channel. 1.0 means every worker was shipping for the entire run.
+    let concurrency_utilization = if worker_stats.is_empty() {
+        None
+    } else {
+        let busy_total: f64 = worker_stats.iter().map(|w| w.busy_secs).sum();
+        let capacity = total_elapsed.as_secs_f64() * worker_stats.len() as f64;
+        Some(if capacity > 0.0 { busy_total / capacity } else { 0.0 })
+    };
+
     if json_output {
         let spool = Spool::new(&conn);
-        let summary = serde_json::json!({
+        let mut summary = serde_json::json!({
             "status": "ok",
             "files_scanned": all_files.len(),
             "files_shipped": files_shipped,
@@ -750,6 +1098,19 @@ async fn cmd_ship(
             "throughput_mb_s": bytes_shipped as f64 / 1_048_576.0 / total_elapsed.as_secs_f64(),
             "dry_run": dry_run,
         });
+        if !worker_stats.is_empty() {
+            summary["ship_workers"] = serde_json::json!(worker_stats
+                .iter()
+                .map(|w| serde_json::json!({
+                    "worker_id": w.worker_id,
+                    "items_shipped": w.items_shipped,
+                    "events_shipped": w.events_shipped,
+                    "bytes_shipped": w.bytes_shipped,
+                    "busy_seconds": w.busy_secs,
+                }))
+                .collect::<Vec<_>>());
+            summary["concurrency_utilization"] = serde_json::json!(concurrency_utilization);
+        }
         println!("{}", serde_json::to_string_pretty(&summary)?);
     } else {
         eprintln!("\n=== Ship Results ===");
@@ -769,11 +1130,165 @@ async fn cmd_ship(
                 bytes_shipped as f64 / 1_048_576.0 / total_elapsed.as_secs_f64()
             );
         }
+        if let Some(util) = concurrency_utilization {
+            eprintln!("Concurrency utilization: {:.0}%", util * 100.0);
+            for w in &worker_stats {
+                eprintln!(
+                    "  worker {}: {} files, {:.2} MB, {:.1}s busy",
+                    w.worker_id,
+                    w.items_shipped,
+                    w.bytes_shipped as f64 / 1_048_576.0,
+                    w.busy_secs
+                );
+            }
+        }
     }
 
     Ok(())
 }
 
+/// Per-worker throughput stats for the bounded ship pipeline (see
+/// `run_ship_pipeline`), surfaced in the final JSON/text summary.
+struct ShipWorkerStats {
+    worker_id: usize,
+    items_shipped: usize,
+    events_shipped: usize,
+    bytes_shipped: u64,
+    busy_secs: f64,
+}
+
+/// Bounded producer/consumer ship pipeline.
+///
+/// Producer threads run `shipper::prepare_file` (CPU-bound parse+compress)
+/// and feed completed items through a bounded crossbeam channel; `ship_workers`
+/// consumer threads drain it and run `shipper::ship_and_record` (IO-bound
+/// POST), so compression of the next file overlaps the network upload of the
+/// current one. The bounded channel capacity keeps memory flat regardless of
+/// corpus size. Runs on its own threads (not the tokio runtime) since
+/// producers are CPU-bound and consumers block on `rt_handle` per item.
+///
+/// Failed/spooled and skipped (client-error) items aren't distinguishable
+/// from `ship_and_record`'s return value alone, so both are folded into the
+/// returned `files_failed` count.
+fn run_ship_pipeline(
+    files: Vec<PathBuf>,
+    provider: String,
+    algo: CompressionAlgo,
+    db_path: Option<PathBuf>,
+    client: Arc<ShipperClient>,
+    ship_workers: usize,
+    recipient_key: Option<pipeline::crypto::RecipientKey>,
+    rt_handle: tokio::runtime::Handle,
+    chunk_dedup: bool,
+    chunker_params: pipeline::chunker::ChunkerParams,
+    owned_blobs: bool,
+    max_uncompressed_bytes: usize,
+) -> anyhow::Result<(usize, usize, u64, usize, Vec<ShipWorkerStats>)> {
+    let ship_workers = ship_workers.max(1);
+    let channel_capacity = ship_workers * 4;
+    let (tx, rx) = bounded::<shipper::ShipItem>(channel_capacity);
+    let items_sent = Arc::new(AtomicUsize::new(0));
+
+    let producer_db_path = db_path.clone();
+    let producer_items_sent = Arc::clone(&items_sent);
+    let producer = std::thread::spawn(move || {
+        files.par_iter().for_each(|path| {
+            let conn = match open_db(producer_db_path.as_deref()) {
+                Ok(c) => c,
+                Err(e) => {
+                    tracing::warn!("ship pipeline producer: failed to open state DB: {}", e);
+                    return;
+                }
+            };
+            match shipper::prepare_file(
+                path,
+                &provider,
+                algo,
+                &conn,
+                recipient_key.as_ref(),
+                chunk_dedup,
+                &chunker_params,
+                None,
+                max_uncompressed_bytes,
+            ) {
+                Ok(prepared) => {
+                    for item in prepared {
+                        producer_items_sent.fetch_add(1, Ordering::Relaxed);
+                        let _ = tx.send(item);
+                    }
+                }
+                Err(e) => tracing::warn!("ship pipeline producer: {}: {}", path.display(), e),
+            }
+        });
+        // `tx` drops here once every file has been processed, closing the
+        // channel so consumers exit their `recv` loop.
+    });
+
+    let mut consumers = Vec::with_capacity(ship_workers);
+    for worker_id in 0..ship_workers {
+        let rx = rx.clone();
+        let client = Arc::clone(&client);
+        let db_path = db_path.clone();
+        let rt_handle = rt_handle.clone();
+        consumers.push(std::thread::spawn(
+            move || -> anyhow::Result<ShipWorkerStats> {
+                let conn = open_db(db_path.as_deref())?;
+                let mut stats = ShipWorkerStats {
+                    worker_id,
+                    items_shipped: 0,
+                    events_shipped: 0,
+                    bytes_shipped: 0,
+                    busy_secs: 0.0,
+                };
+                while let Ok(item) = rx.recv() {
+                    let bytes = item.new_offset - item.offset;
+                    let busy_start = Instant::now();
+                    let (events, _is_connect_error) = rt_handle.block_on(shipper::ship_and_record(
+                        item,
+                        client.as_ref(),
+                        &conn,
+                        None,
+                        None,
+                        owned_blobs,
+                        None,
+                    ))?;
+                    stats.busy_secs += busy_start.elapsed().as_secs_f64();
+                    if events > 0 {
+                        stats.items_shipped += 1;
+                        stats.events_shipped += events;
+                        stats.bytes_shipped += bytes;
+                    }
+                }
+                Ok(stats)
+            },
+        ));
+    }
+
+    producer.join().expect("ship pipeline producer thread panicked");
+
+    let mut worker_stats = Vec::with_capacity(ship_workers);
+    let mut files_shipped = 0usize;
+    let mut events_shipped = 0usize;
+    let mut bytes_shipped = 0u64;
+    for consumer in consumers {
+        let stats = consumer
+            .join()
+            .expect("ship pipeline consumer thread panicked")?;
+        files_shipped += stats.items_shipped;
+        events_shipped += stats.events_shipped;
+        bytes_shipped += stats.bytes_shipped;
+        worker_stats.push(stats);
+    }
+
+    // Items sent into the channel but not counted as shipped by any worker
+    // were spooled (transient failure) or skipped (client error) inside
+    // `ship_and_record`.
+    let total_sent = items_sent.load(Ordering::Relaxed);
+    let files_failed = total_sent.saturating_sub(files_shipped);
+
+    Ok((files_shipped, events_shipped, bytes_shipped, files_failed, worker_stats))
+}
+
 fn detect_provider_for_file(
     path: &std::path::Path,
     provider_override: Option<&str>,
@@ -811,6 +1326,7 @@ async fn cmd_ship_file(
     dry_run: bool,
     json_output: bool,
     algo: CompressionAlgo,
+    recipient_key: Option<&str>,
 ) -> anyhow::Result<()> {
     if !path.exists() {
         anyhow::bail!("File not found: {}", path.display());
@@ -818,7 +1334,15 @@ async fn cmd_ship_file(
 
     let provider = detect_provider_for_file(path, provider_override)?;
 
-    let config = ShipperConfig::from_env()?.with_overrides(url, token, db_path, None);
+    let config = ShipperConfig::from_env()?
+        .with_overrides(url, token, db_path, None)
+        .with_recipient_key(recipient_key);
+    let recipient_key = config
+        .recipient_key
+        .as_deref()
+        .map(pipeline::crypto::RecipientKey::from_hex)
+        .transpose()?;
+    let recipient_key = recipient_key.as_ref();
 
     if !json_output {
         eprintln!("Shipping file: {}", path.display());
@@ -830,17 +1354,45 @@ async fn cmd_ship_file(
 
     let conn = open_db(config.db_path.as_deref())?;
 
-    let prepared = shipper::prepare_file(path, &provider, algo, &conn)?;
-    let item = match prepared {
-        Some(item) => item,
-        None => {
-            println!("No new events");
-            return Ok(());
-        }
-    };
+    let chunker_params =
+        pipeline::chunker::ChunkerParams::new(config.target_chunk_bytes, config.max_chunk_bytes);
+    let prepared = shipper::prepare_file(
+        path,
+        &provider,
+        algo,
+        &conn,
+        recipient_key,
+        config.chunk_dedup,
+        &chunker_params,
+        None,
+        config.max_uncompressed_event_bytes,
+    )?;
+    if prepared.is_empty() {
+        println!("No new events");
+        return Ok(());
+    }
+    // A single file rarely splits into more than one batch (see
+    // `compressor::build_batches`), but when it does, ship every batch in
+    // order before returning — same as the watcher/resync/full-scan paths.
+    for item in prepared {
+        ship_prepared_item(item, path, &conn, &config, algo, dry_run, json_output).await?;
+    }
 
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn ship_prepared_item(
+    item: shipper::ShipItem,
+    path: &std::path::Path,
+    conn: &rusqlite::Connection,
+    config: &ShipperConfig,
+    algo: CompressionAlgo,
+    dry_run: bool,
+    json_output: bool,
+) -> anyhow::Result<()> {
     if dry_run {
-        let file_state = FileState::new(&conn);
+        let file_state = FileState::new(conn);
         file_state.set_offset(
             &item.path_str,
             item.new_offset,
@@ -849,23 +1401,68 @@ async fn cmd_ship_file(
             &item.provider,
         )?;
 
+        // Computed even for HTTP targets (which ignore it) so dry-run output
+        // is consistent regardless of which `ShipTarget` is configured.
+        let object_key = shipping::s3::manifest_key(&item.session_id, &item.compressed);
+
         if json_output {
             let summary = serde_json::json!({
                 "status": "ok",
                 "file": item.path_str,
                 "events_shipped": item.event_count,
+                "object_key": object_key,
                 "dry_run": true,
             });
             println!("{}", serde_json::to_string_pretty(&summary)?);
         } else {
             println!("Shipped {} events", item.event_count);
+            println!("Object key: {}", object_key);
         }
         return Ok(());
     }
 
-    let client = ShipperClient::with_compression(&config, algo)?;
+    let manifest_summary = serde_json::json!({
+        "chunks": item.chunk_manifest.entries.len(),
+        "unseen_bytes": item.chunk_manifest.unseen_bytes,
+        "total_bytes": item.chunk_manifest.total_bytes,
+    });
+
+    // Every chunk in this range has already been accepted by the server
+    // under a previous shipment (e.g. the file was rewritten back to
+    // already-seen content) — advance local offsets without re-POSTing.
+    if item.chunk_manifest.total_bytes > 0 && item.chunk_manifest.unseen_bytes == 0 {
+        let file_state = FileState::new(conn);
+        file_state.set_offset(
+            &item.path_str,
+            item.new_offset,
+            &item.session_id,
+            &item.session_id,
+            &item.provider,
+        )?;
+
+        if json_output {
+            let summary = serde_json::json!({
+                "status": "ok",
+                "file": item.path_str,
+                "events_shipped": item.event_count,
+                "dry_run": false,
+                "deduped": true,
+                "chunk_manifest": manifest_summary,
+            });
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        } else {
+            println!(
+                "Skipped upload: all {} chunks already shipped ({} events advanced locally)",
+                item.chunk_manifest.entries.len(),
+                item.event_count
+            );
+        }
+        return Ok(());
+    }
+
+    let client = shipping::target::resolve(config, algo).await?;
     let (events_shipped, _is_connect_err) =
-        shipper::ship_and_record(item, &client, &conn, None).await?;
+        shipper::ship_and_record(item, client.as_ref(), conn, None, None, config.spool_owned_blobs, None).await?;
 
     if json_output {
         let summary = serde_json::json!({
@@ -873,6 +1470,7 @@ async fn cmd_ship_file(
             "file": path.display().to_string(),
             "events_shipped": events_shipped,
             "dry_run": false,
+            "chunk_manifest": manifest_summary,
         });
         println!("{}", serde_json::to_string_pretty(&summary)?);
     } else {
@@ -886,19 +1484,32 @@ async fn cmd_ship_file(
 // parse subcommand
 // ---------------------------------------------------------------------------
 
-fn cmd_parse(path: &PathBuf, offset: u64, dump_events: bool, compress: bool) -> anyhow::Result<()> {
+fn cmd_parse(
+    path: &PathBuf,
+    offset: u64,
+    dump_events: bool,
+    compress: bool,
+    use_mmap: bool,
+    dict: Option<&std::path::Path>,
+) -> anyhow::Result<()> {
     let start = Instant::now();
 
     let file_size = std::fs::metadata(path)?.len();
     eprintln!(
-        "Parsing {} ({:.2} MB) from offset {}",
+        "Parsing {} ({:.2} MB) from offset {}{}",
         path.display(),
         file_size as f64 / 1_048_576.0,
-        offset
+        offset,
+        if use_mmap { " (mmap, sharded)" } else { "" }
     );
 
     let parse_start = Instant::now();
-    let result = pipeline::parser::parse_session_file(path, offset)?;
+    let result = if use_mmap {
+        let shard_count = num_cpus::get();
+        pipeline::parser::parse_session_file_sharded(path, offset, shard_count)?
+    } else {
+        pipeline::parser::parse_session_file(path, offset)?
+    };
     let parse_elapsed = parse_start.elapsed();
 
     eprintln!(
@@ -930,7 +1541,7 @@ fn cmd_parse(path: &PathBuf, offset: u64, dump_events: bool, compress: bool) ->
     if compress {
         let compress_start = Instant::now();
         let source_path = path.to_string_lossy();
-        let compressed = pipeline::compressor::build_and_compress(
+        let encoded = pipeline::compressor::build_and_compress(
             "test-session-id",
             &result.events,
             &result.metadata,
@@ -939,23 +1550,32 @@ fn cmd_parse(path: &PathBuf, offset: u64, dump_events: bool, compress: bool) ->
         )?;
         let compress_elapsed = compress_start.elapsed();
 
-        // Calculate uncompressed size for ratio
-        let payload = pipeline::compressor::build_payload(
-            "test-session-id",
-            &result.events,
-            &result.metadata,
-            &source_path,
-            "claude",
-        );
-        let uncompressed = serde_json::to_vec(&payload)?;
-
         eprintln!(
             "Compressed: {:.2} MB JSON → {:.2} MB gzip ({:.1}x ratio) in {:.3}s",
-            uncompressed.len() as f64 / 1_048_576.0,
-            compressed.len() as f64 / 1_048_576.0,
-            uncompressed.len() as f64 / compressed.len() as f64,
+            encoded.uncompressed_bytes as f64 / 1_048_576.0,
+            encoded.compressed_bytes as f64 / 1_048_576.0,
+            encoded.uncompressed_bytes as f64 / encoded.compressed_bytes as f64,
             compress_elapsed.as_secs_f64()
         );
+
+        if let Some(dict_path) = dict {
+            let dictionary = pipeline::dictionary::load(dict_path)?;
+            let dict_compressed = pipeline::compressor::build_and_compress_with_dictionary(
+                "test-session-id",
+                &result.events,
+                &result.metadata,
+                &source_path,
+                "claude",
+                &dictionary,
+            )?;
+            eprintln!(
+                "Compressed with dictionary {}: {:.2} MB JSON → {:.2} MB zstd ({:.1}x ratio)",
+                dictionary.id,
+                encoded.uncompressed_bytes as f64 / 1_048_576.0,
+                dict_compressed.len() as f64 / 1_048_576.0,
+                encoded.uncompressed_bytes as f64 / dict_compressed.len() as f64,
+            );
+        }
     }
 
     let bytes_processed = file_size - offset;
@@ -990,11 +1610,88 @@ fn cmd_parse(path: &PathBuf, offset: u64, dump_events: bool, compress: bool) ->
     Ok(())
 }
 
+// ---------------------------------------------------------------------------
+// follow subcommand
+// ---------------------------------------------------------------------------
+
+async fn cmd_follow(path: &std::path::Path, dump_events: bool) -> anyhow::Result<()> {
+    eprintln!("Following {} (Ctrl-C to stop)", path.display());
+
+    let mut follower = tail::SessionTail::open(path)?;
+    while let Some(events) = follower.next_events().await? {
+        for event in events {
+            if dump_events {
+                println!("{}", serde_json::to_string(&event)?);
+            } else {
+                println!(
+                    "[{}] {:?}: {}",
+                    event.timestamp.to_rfc3339(),
+                    event.role,
+                    event.content_text.as_deref().unwrap_or("")
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// train-dict subcommand
+// ---------------------------------------------------------------------------
+
+fn cmd_train_dict(out: &PathBuf, max_samples: usize, max_size: usize) -> anyhow::Result<()> {
+    let files = bench::discover_session_files();
+    anyhow::ensure!(!files.is_empty(), "no session files found to train on");
+
+    let mut samples = Vec::new();
+    for path in files.iter().take(max_samples) {
+        let result = match pipeline::parser::parse_session_file(path, 0) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let source_path = path.to_string_lossy();
+        let payload = pipeline::compressor::build_payload(
+            "train-sample",
+            &result.events,
+            &result.metadata,
+            &source_path,
+            "claude",
+        );
+        samples.push(serde_json::to_vec(&payload)?);
+    }
+
+    eprintln!("Training dictionary from {} samples...", samples.len());
+    let dictionary = pipeline::dictionary::train(&samples, max_size)?;
+    pipeline::dictionary::save(&dictionary, out)?;
+
+    eprintln!(
+        "Wrote {} byte dictionary (id {}) to {}",
+        dictionary.bytes.len(),
+        dictionary.id,
+        out.display()
+    );
+
+    Ok(())
+}
+
 // ---------------------------------------------------------------------------
 // bench subcommand
 // ---------------------------------------------------------------------------
 
-fn cmd_bench(level: &str, compress: bool, parallel: bool, workers: usize, algo: CompressionAlgo) -> anyhow::Result<()> {
+fn cmd_bench(
+    level: &str,
+    compress: bool,
+    parallel: bool,
+    workers: usize,
+    algo: CompressionAlgo,
+    resume: bool,
+    db_path: Option<&std::path::Path>,
+    streaming: bool,
+) -> anyhow::Result<()> {
     eprintln!("Discovering session files...");
     let all_files = bench::discover_session_files();
     eprintln!("Found {} non-empty JSONL files", all_files.len());
@@ -1053,7 +1750,14 @@ fn cmd_bench(level: &str, compress: bool, parallel: bool, workers: usize, algo:
         if compress { "yes" } else { "parse-only" }
     );
 
-    let result = if parallel {
+    let result = if resume {
+        if parallel {
+            eprintln!("--resume runs sequentially (job tracking is per-file, not per-worker)");
+        }
+        cmd_bench_resumable(&files, compress, algo, level, &compression_label(algo), db_path)?
+    } else if parallel && streaming {
+        bench::run_benchmark_streaming(&files, compress, num_workers, algo)
+    } else if parallel {
         bench::run_benchmark_parallel_with(&files, compress, num_workers, algo)
     } else {
         bench::run_benchmark_with(&files, compress, algo)
@@ -1062,3 +1766,73 @@ fn cmd_bench(level: &str, compress: bool, parallel: bool, workers: usize, algo:
 
     Ok(())
 }
+
+/// A short, filesystem/SQLite-safe label for `algo`, used to key resumable
+/// job names (so e.g. `--compression zstd` and `--compression gzip` runs of
+/// the same `--level` don't share — and clobber — job progress).
+fn compression_label(algo: CompressionAlgo) -> String {
+    format!("{:?}", algo).to_lowercase()
+}
+
+/// Run the bench harness as a resumable job (see `state::jobs`), printing
+/// live progress from the `progress` channel (see `crate::progress`) instead
+/// of the periodic `eprintln!` the non-resumable path uses.
+fn cmd_bench_resumable(
+    files: &[PathBuf],
+    compress: bool,
+    algo: CompressionAlgo,
+    level: &str,
+    compression_label: &str,
+    db_path: Option<&std::path::Path>,
+) -> anyhow::Result<bench::BenchResult> {
+    let conn = open_db(db_path)?;
+    let job_name = format!("bench-{}-{}", level.to_uppercase(), compression_label);
+
+    let (tx, rx) = progress::channel(files.len());
+    let done = Arc::new(AtomicBool::new(false));
+    let printer_done = Arc::clone(&done);
+    let printer = std::thread::spawn(move || {
+        let mut last_printed = 0usize;
+        let mut last_error_printed: Option<(String, String)> = None;
+        while !printer_done.load(Ordering::Relaxed) {
+            let snapshot = rx.borrow().clone();
+            if snapshot.files_done != last_printed {
+                eprintln!(
+                    "  [{}/{}] {:.1} MB, {} events, {:.1} MB/s",
+                    snapshot.files_done,
+                    snapshot.files_total,
+                    snapshot.bytes_done as f64 / 1_048_576.0,
+                    snapshot.events_done,
+                    snapshot.throughput_mb_s,
+                );
+                last_printed = snapshot.files_done;
+            }
+            if snapshot.last_error.is_some() && snapshot.last_error != last_error_printed {
+                if let Some((path, err)) = &snapshot.last_error {
+                    eprintln!("  SKIP {}: {}", path, err);
+                }
+                last_error_printed = snapshot.last_error.clone();
+            }
+            std::thread::sleep(Duration::from_millis(250));
+        }
+    });
+
+    let result = bench::run_benchmark_resumable(&conn, &job_name, files, compress, algo, Some(&tx));
+    done.store(true, Ordering::Relaxed);
+    printer.join().ok();
+
+    // Re-`start` is idempotent (see `Jobs::start`) — just a cheap way to get
+    // the job id back for a completion summary.
+    let jobs = state::jobs::Jobs::new(&conn);
+    let path_strs: Vec<String> = files.iter().map(|p| p.to_string_lossy().to_string()).collect();
+    if let Ok(job_id) = jobs.start(&job_name, &path_strs) {
+        if let Ok((pending, done_count, failed)) = jobs.counts(job_id) {
+            eprintln!(
+                "Job '{}': {} done, {} failed, {} still pending",
+                job_name, done_count, failed, pending
+            );
+        }
+    }
+
+    result
+}
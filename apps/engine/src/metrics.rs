@@ -0,0 +1,479 @@
+//! Prometheus text-format metrics for the Connect daemon.
+//!
+//! Counters and gauges are plain atomics behind an `Arc` so the same handle
+//! can be cloned into the watcher loop and the HTTP exposition thread without
+//! any locking on the hot path — the same cheap-clone pattern used by
+//! `ConsecutiveErrorTracker` in `error_tracker.rs`.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::heartbeat::HeartbeatPayload;
+
+/// Upper bounds (inclusive) of each `longhouse_ship_request_latency_ms`
+/// bucket, in Prometheus's usual cumulative-histogram sense — the last one
+/// stands in for `+Inf`.
+const REQUEST_LATENCY_BUCKETS_MS: [u64; 8] = [50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+#[derive(Default)]
+struct Inner {
+    files_shipped_total: AtomicU64,
+    events_shipped_total: AtomicU64,
+    /// Uncompressed source bytes shipped (see `ShipperConfig`-driven callers
+    /// of `record_shipped`). `bytes_compressed_total` below is the
+    /// corresponding on-the-wire figure.
+    bytes_shipped_total: AtomicU64,
+    bytes_compressed_total: AtomicU64,
+    ship_failures_total: Mutex<HashMap<String, u64>>,
+    /// Per-attempt outcome, keyed by `ShipResult` variant name ("ok",
+    /// "rate_limited", "server_error", "client_error", "connect_error") —
+    /// finer-grained than `ship_failures_total`'s ad hoc reason labels,
+    /// recorded once per `ShipperClient::ship`/`ship_batch` item.
+    ship_outcomes_total: Mutex<HashMap<String, u64>>,
+    /// 429 responses that triggered a retry (not counting the final give-up).
+    ship_rate_limit_retries_total: AtomicU64,
+    /// Cumulative time slept across every transient-failure backoff
+    /// (429, 5xx, connect error), in milliseconds.
+    ship_backoff_ms_total: AtomicU64,
+    spool_depth: AtomicU64,
+    ship_latency_count: AtomicU64,
+    ship_latency_sum_ms: AtomicU64,
+    /// Per-HTTP-request latency histogram (one observation per attempt,
+    /// including retried ones) — see `REQUEST_LATENCY_BUCKETS_MS`. Index `i`
+    /// counts requests whose latency fell in `(buckets[i-1], buckets[i]]`
+    /// (or `[0, buckets[0]]` for `i == 0`); `render()` turns this into the
+    /// cumulative counts Prometheus histograms expect.
+    request_latency_buckets: Mutex<[u64; REQUEST_LATENCY_BUCKETS_MS.len()]>,
+    request_latency_count: AtomicU64,
+    request_latency_sum_ms: AtomicU64,
+    /// Total `file_state` rows tracked (see `FileState::count`).
+    file_state_total: AtomicU64,
+    /// Files where `queued_offset > acked_offset` (see
+    /// `FileState::get_unacked_files`) — nonzero means shipping is behind.
+    file_state_unacked: AtomicU64,
+    /// `sum(queued_offset - acked_offset)` across unacked files — the size
+    /// of the backlog, not just its file count.
+    file_state_gap_bytes: AtomicU64,
+    /// Most recently built heartbeat payload, reused so the `/metrics`
+    /// gauges below never diverge from what `heartbeat::HeartbeatStats`
+    /// reports to `~/.claude/engine-status.json` and the server.
+    latest_heartbeat: Mutex<Option<HeartbeatPayload>>,
+}
+
+/// Cheap-clone handle to the daemon's metrics. Safe to share across the
+/// watcher loop, the periodic scan/replay tasks, and the exposition server.
+#[derive(Clone, Default)]
+pub struct Metrics(Arc<Inner>);
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self(Arc::new(Inner::default()))
+    }
+
+    /// Record a successful ship of `files`/`events`/`bytes`.
+    pub fn record_shipped(&self, files: u64, events: u64, bytes: u64) {
+        self.0.files_shipped_total.fetch_add(files, Ordering::Relaxed);
+        self.0.events_shipped_total.fetch_add(events, Ordering::Relaxed);
+        self.0.bytes_shipped_total.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Record a failed ship attempt, bucketed by a short reason label
+    /// (e.g. "rate_limited", "server_error", "connect_error").
+    pub fn record_failure(&self, reason: &str) {
+        let mut failures = self.0.ship_failures_total.lock().unwrap();
+        *failures.entry(reason.to_string()).or_insert(0) += 1;
+    }
+
+    /// Update the current spool depth gauge (call after enqueue/dequeue).
+    pub fn set_spool_depth(&self, depth: u64) {
+        self.0.spool_depth.store(depth, Ordering::Relaxed);
+    }
+
+    /// Lifetime total of successfully shipped events, for the heartbeat's
+    /// elevated-parse-error-rate ratio (see `heartbeat::HeartbeatStats`).
+    pub fn events_shipped_total(&self) -> u64 {
+        self.0.events_shipped_total.load(Ordering::Relaxed)
+    }
+
+    /// Snapshot the latest heartbeat payload so `render()` can expose its
+    /// fields as gauges without recomputing them.
+    pub fn record_heartbeat(&self, payload: &HeartbeatPayload) {
+        *self.0.latest_heartbeat.lock().unwrap() = Some(payload.clone());
+    }
+
+    /// Record one observation for the `ShipperClient::ship` latency histogram.
+    pub fn observe_ship_latency(&self, elapsed: Duration) {
+        self.0.ship_latency_count.fetch_add(1, Ordering::Relaxed);
+        self.0
+            .ship_latency_sum_ms
+            .fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Record one ship attempt's outcome, keyed by its `ShipResult` variant
+    /// (see `shipping::client::outcome_label`) — called once per item per
+    /// `ShipperClient::ship`/`ship_batch` attempt.
+    pub fn record_ship_outcome(&self, outcome: &str) {
+        let mut outcomes = self.0.ship_outcomes_total.lock().unwrap();
+        *outcomes.entry(outcome.to_string()).or_insert(0) += 1;
+    }
+
+    /// Record that a 429 response triggered a retry (not the final give-up,
+    /// which is already counted via `record_ship_outcome("rate_limited")`).
+    pub fn record_rate_limit_retry(&self) {
+        self.0.ship_rate_limit_retries_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Add to the cumulative backoff-sleep total (429, 5xx, or connect-error
+    /// retries all count).
+    pub fn record_backoff(&self, slept: Duration) {
+        self.0
+            .ship_backoff_ms_total
+            .fetch_add(slept.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Add to the compressed (on-the-wire) bytes shipped counter.
+    pub fn record_bytes_compressed(&self, bytes: u64) {
+        self.0.bytes_compressed_total.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Record one observation for the `longhouse_ship_request_latency_ms`
+    /// histogram — the actual HTTP round trip, one observation per attempt
+    /// (so a retried request contributes multiple points).
+    pub fn observe_request_latency(&self, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        self.0.request_latency_count.fetch_add(1, Ordering::Relaxed);
+        self.0.request_latency_sum_ms.fetch_add(ms, Ordering::Relaxed);
+        let mut buckets = self.0.request_latency_buckets.lock().unwrap();
+        let idx = REQUEST_LATENCY_BUCKETS_MS
+            .iter()
+            .position(|&le| ms <= le)
+            .unwrap_or(REQUEST_LATENCY_BUCKETS_MS.len() - 1);
+        buckets[idx] += 1;
+    }
+
+    /// Update the `file_state` gauges (see `state::file_state::FileState`) —
+    /// total tracked files, how many are unacked, and the aggregate byte gap
+    /// across them. Call periodically (e.g. alongside the heartbeat), not
+    /// per-file, since these are whole-table scans.
+    pub fn set_file_state_gauges(&self, total: u64, unacked: u64, gap_bytes: u64) {
+        self.0.file_state_total.store(total, Ordering::Relaxed);
+        self.0.file_state_unacked.store(unacked, Ordering::Relaxed);
+        self.0.file_state_gap_bytes.store(gap_bytes, Ordering::Relaxed);
+    }
+
+    /// Render all metrics in Prometheus text exposition format.
+    fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP longhouse_files_shipped_total Session files successfully shipped.\n");
+        out.push_str("# TYPE longhouse_files_shipped_total counter\n");
+        out.push_str(&format!(
+            "longhouse_files_shipped_total {}\n",
+            self.0.files_shipped_total.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP longhouse_events_shipped_total Parsed events successfully shipped.\n");
+        out.push_str("# TYPE longhouse_events_shipped_total counter\n");
+        out.push_str(&format!(
+            "longhouse_events_shipped_total {}\n",
+            self.0.events_shipped_total.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP longhouse_bytes_shipped_total Source bytes successfully shipped.\n");
+        out.push_str("# TYPE longhouse_bytes_shipped_total counter\n");
+        out.push_str(&format!(
+            "longhouse_bytes_shipped_total {}\n",
+            self.0.bytes_shipped_total.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP longhouse_bytes_compressed_total On-the-wire (compressed) bytes shipped.\n");
+        out.push_str("# TYPE longhouse_bytes_compressed_total counter\n");
+        out.push_str(&format!(
+            "longhouse_bytes_compressed_total {}\n",
+            self.0.bytes_compressed_total.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP longhouse_ship_failures_total Ship attempts that did not succeed, by reason.\n");
+        out.push_str("# TYPE longhouse_ship_failures_total counter\n");
+        let failures = self.0.ship_failures_total.lock().unwrap();
+        if failures.is_empty() {
+            out.push_str("longhouse_ship_failures_total{reason=\"none\"} 0\n");
+        }
+        for (reason, count) in failures.iter() {
+            out.push_str(&format!(
+                "longhouse_ship_failures_total{{reason=\"{}\"}} {}\n",
+                reason, count
+            ));
+        }
+        drop(failures);
+
+        out.push_str("# HELP longhouse_ship_outcomes_total Ship attempts by ShipResult variant.\n");
+        out.push_str("# TYPE longhouse_ship_outcomes_total counter\n");
+        let outcomes = self.0.ship_outcomes_total.lock().unwrap();
+        if outcomes.is_empty() {
+            out.push_str("longhouse_ship_outcomes_total{outcome=\"none\"} 0\n");
+        }
+        for (outcome, count) in outcomes.iter() {
+            out.push_str(&format!(
+                "longhouse_ship_outcomes_total{{outcome=\"{}\"}} {}\n",
+                outcome, count
+            ));
+        }
+        drop(outcomes);
+
+        out.push_str("# HELP longhouse_ship_rate_limit_retries_total 429 responses that triggered a retry.\n");
+        out.push_str("# TYPE longhouse_ship_rate_limit_retries_total counter\n");
+        out.push_str(&format!(
+            "longhouse_ship_rate_limit_retries_total {}\n",
+            self.0.ship_rate_limit_retries_total.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP longhouse_ship_backoff_ms_total Cumulative time slept across all transient-failure backoffs, in milliseconds.\n");
+        out.push_str("# TYPE longhouse_ship_backoff_ms_total counter\n");
+        out.push_str(&format!(
+            "longhouse_ship_backoff_ms_total {}\n",
+            self.0.ship_backoff_ms_total.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP longhouse_spool_depth Pending entries currently held in the retry spool.\n");
+        out.push_str("# TYPE longhouse_spool_depth gauge\n");
+        out.push_str(&format!(
+            "longhouse_spool_depth {}\n",
+            self.0.spool_depth.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP longhouse_ship_latency_ms_sum Cumulative ShipperClient::ship latency, in milliseconds.\n");
+        out.push_str("# TYPE longhouse_ship_latency_ms summary\n");
+        out.push_str(&format!(
+            "longhouse_ship_latency_ms_sum {}\n",
+            self.0.ship_latency_sum_ms.load(Ordering::Relaxed)
+        ));
+        out.push_str(&format!(
+            "longhouse_ship_latency_ms_count {}\n",
+            self.0.ship_latency_count.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP longhouse_ship_request_latency_ms HTTP round-trip latency per ship attempt, including retries.\n");
+        out.push_str("# TYPE longhouse_ship_request_latency_ms histogram\n");
+        let buckets = self.0.request_latency_buckets.lock().unwrap();
+        let mut cumulative = 0u64;
+        for (i, &le) in REQUEST_LATENCY_BUCKETS_MS.iter().enumerate() {
+            cumulative += buckets[i];
+            out.push_str(&format!(
+                "longhouse_ship_request_latency_ms_bucket{{le=\"{}\"}} {}\n",
+                le, cumulative
+            ));
+        }
+        out.push_str(&format!(
+            "longhouse_ship_request_latency_ms_bucket{{le=\"+Inf\"}} {}\n",
+            cumulative
+        ));
+        drop(buckets);
+        out.push_str(&format!(
+            "longhouse_ship_request_latency_ms_sum {}\n",
+            self.0.request_latency_sum_ms.load(Ordering::Relaxed)
+        ));
+        out.push_str(&format!(
+            "longhouse_ship_request_latency_ms_count {}\n",
+            self.0.request_latency_count.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP longhouse_file_state_total Rows tracked in file_state.\n");
+        out.push_str("# TYPE longhouse_file_state_total gauge\n");
+        out.push_str(&format!(
+            "longhouse_file_state_total {}\n",
+            self.0.file_state_total.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP longhouse_file_state_unacked Tracked files where queued_offset > acked_offset.\n");
+        out.push_str("# TYPE longhouse_file_state_unacked gauge\n");
+        out.push_str(&format!(
+            "longhouse_file_state_unacked {}\n",
+            self.0.file_state_unacked.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP longhouse_file_state_gap_bytes Sum of (queued_offset - acked_offset) across unacked files.\n");
+        out.push_str("# TYPE longhouse_file_state_gap_bytes gauge\n");
+        out.push_str(&format!(
+            "longhouse_file_state_gap_bytes {}\n",
+            self.0.file_state_gap_bytes.load(Ordering::Relaxed)
+        ));
+
+        let heartbeat = self.0.latest_heartbeat.lock().unwrap();
+        if let Some(payload) = heartbeat.as_ref() {
+            out.push_str("# HELP longhouse_spool_pending Spool entries awaiting retry.\n");
+            out.push_str("# TYPE longhouse_spool_pending gauge\n");
+            out.push_str(&format!("longhouse_spool_pending {}\n", payload.spool_pending_count));
+
+            out.push_str("# HELP longhouse_spool_total Spool entries of any status.\n");
+            out.push_str("# TYPE longhouse_spool_total gauge\n");
+            out.push_str(&format!("longhouse_spool_total {}\n", payload.spool_total_count));
+
+            out.push_str("# HELP longhouse_spool_dead Spool entries given up on after exhausting retries.\n");
+            out.push_str("# TYPE longhouse_spool_dead gauge\n");
+            out.push_str(&format!("longhouse_spool_dead {}\n", payload.spool_dead_count));
+
+            out.push_str("# HELP longhouse_consecutive_ship_failures Current consecutive ship failure streak.\n");
+            out.push_str("# TYPE longhouse_consecutive_ship_failures gauge\n");
+            out.push_str(&format!(
+                "longhouse_consecutive_ship_failures {}\n",
+                payload.consecutive_ship_failures
+            ));
+
+            out.push_str("# HELP longhouse_disk_free_bytes Free bytes on the filesystem containing ~/.claude.\n");
+            out.push_str("# TYPE longhouse_disk_free_bytes gauge\n");
+            out.push_str(&format!("longhouse_disk_free_bytes {}\n", payload.disk_free_bytes));
+
+            out.push_str("# HELP longhouse_parse_errors_1h Parse errors recorded in the last hour.\n");
+            out.push_str("# TYPE longhouse_parse_errors_1h gauge\n");
+            out.push_str(&format!("longhouse_parse_errors_1h {}\n", payload.parse_error_count_1h));
+
+            out.push_str("# HELP longhouse_is_offline Whether the daemon currently considers itself offline (1) or not (0).\n");
+            out.push_str("# TYPE longhouse_is_offline gauge\n");
+            out.push_str(&format!("longhouse_is_offline {}\n", payload.is_offline as u8));
+        }
+        drop(heartbeat);
+
+        out
+    }
+
+    /// Start serving `/metrics` in Prometheus text format on `addr`.
+    ///
+    /// Runs a tiny hand-rolled HTTP server on its own OS thread rather than
+    /// pulling in a web framework — exposition is infrequent and doesn't need
+    /// the tokio runtime.
+    pub fn serve(&self, addr: &str) -> Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        let bound_addr = listener.local_addr()?;
+        let metrics = self.clone();
+
+        std::thread::Builder::new()
+            .name("metrics-http".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => metrics.handle_connection(stream),
+                        Err(e) => tracing::debug!("metrics listener accept error: {}", e),
+                    }
+                }
+            })?;
+
+        tracing::info!("Metrics exposed at http://{}/metrics", bound_addr);
+        Ok(())
+    }
+
+    fn handle_connection(&self, mut stream: std::net::TcpStream) {
+        let mut request_line = String::new();
+        {
+            let mut reader = BufReader::new(&stream);
+            if reader.read_line(&mut request_line).is_err() {
+                return;
+            }
+        }
+
+        let body = self.render();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_includes_all_metrics() {
+        let metrics = Metrics::new();
+        metrics.record_shipped(2, 10, 4096);
+        metrics.record_failure("rate_limited");
+        metrics.set_spool_depth(3);
+        metrics.observe_ship_latency(Duration::from_millis(50));
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("longhouse_files_shipped_total 2"));
+        assert!(rendered.contains("longhouse_events_shipped_total 10"));
+        assert!(rendered.contains("longhouse_bytes_shipped_total 4096"));
+        assert!(rendered.contains("longhouse_ship_failures_total{reason=\"rate_limited\"} 1"));
+        assert!(rendered.contains("longhouse_spool_depth 3"));
+        assert!(rendered.contains("longhouse_ship_latency_ms_sum 50"));
+        assert!(rendered.contains("longhouse_ship_latency_ms_count 1"));
+    }
+
+    #[test]
+    fn test_render_includes_heartbeat_gauges_once_recorded() {
+        let metrics = Metrics::new();
+        assert!(!metrics.render().contains("longhouse_spool_pending"));
+
+        let payload = HeartbeatPayload {
+            version: "0.1.0".to_string(),
+            daemon_pid: 1,
+            last_ship_at: None,
+            spool_pending_count: 2,
+            spool_total_count: 7,
+            spool_dead_count: 1,
+            parse_error_count_1h: 3,
+            consecutive_ship_failures: 4,
+            disk_free_bytes: 123,
+            is_offline: true,
+            breaker_state: "open".to_string(),
+            elevated_parse_error_rate: false,
+            workers: Vec::new(),
+        };
+        metrics.record_heartbeat(&payload);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("longhouse_spool_pending 2"));
+        assert!(rendered.contains("longhouse_spool_total 7"));
+        assert!(rendered.contains("longhouse_spool_dead 1"));
+        assert!(rendered.contains("longhouse_consecutive_ship_failures 4"));
+        assert!(rendered.contains("longhouse_disk_free_bytes 123"));
+        assert!(rendered.contains("longhouse_parse_errors_1h 3"));
+        assert!(rendered.contains("longhouse_is_offline 1"));
+    }
+
+    #[test]
+    fn test_render_includes_shipper_client_metrics() {
+        let metrics = Metrics::new();
+        metrics.record_ship_outcome("ok");
+        metrics.record_ship_outcome("ok");
+        metrics.record_ship_outcome("rate_limited");
+        metrics.record_rate_limit_retry();
+        metrics.record_backoff(Duration::from_millis(200));
+        metrics.record_bytes_compressed(512);
+        metrics.observe_request_latency(Duration::from_millis(40));
+        metrics.observe_request_latency(Duration::from_millis(300));
+        metrics.set_file_state_gauges(10, 3, 4096);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("longhouse_ship_outcomes_total{outcome=\"ok\"} 2"));
+        assert!(rendered.contains("longhouse_ship_outcomes_total{outcome=\"rate_limited\"} 1"));
+        assert!(rendered.contains("longhouse_ship_rate_limit_retries_total 1"));
+        assert!(rendered.contains("longhouse_ship_backoff_ms_total 200"));
+        assert!(rendered.contains("longhouse_bytes_compressed_total 512"));
+        assert!(rendered.contains("longhouse_ship_request_latency_ms_bucket{le=\"50\"} 1"));
+        assert!(rendered.contains("longhouse_ship_request_latency_ms_bucket{le=\"500\"} 2"));
+        assert!(rendered.contains("longhouse_ship_request_latency_ms_bucket{le=\"+Inf\"} 2"));
+        assert!(rendered.contains("longhouse_ship_request_latency_ms_count 2"));
+        assert!(rendered.contains("longhouse_file_state_total 10"));
+        assert!(rendered.contains("longhouse_file_state_unacked 3"));
+        assert!(rendered.contains("longhouse_file_state_gap_bytes 4096"));
+    }
+
+    #[test]
+    fn test_clone_shares_state() {
+        let metrics = Metrics::new();
+        let clone = metrics.clone();
+        clone.record_shipped(1, 1, 1);
+        assert!(metrics.render().contains("longhouse_files_shipped_total 1"));
+    }
+}
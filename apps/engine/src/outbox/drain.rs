@@ -0,0 +1,713 @@
+//! Generic drain loop over a [`Store`] of queued [`QueueEntry`] files.
+//!
+//! Claude Code hooks write small JSON files instead of calling the API
+//! directly, eliminating network I/O from the hook hot path so hooks can run
+//! as `async: false` without risking stalls. A drain pass reads every ready
+//! file a [`Store`] has, coalesces duplicates per each entry's own
+//! [`CoalescePolicy`], POSTs the survivors through a [`Poster`] (up to
+//! [`DRAIN_CONCURRENCY`] in flight at once, so one slow endpoint doesn't
+//! stall every other session's POST), and deletes files on success. Presence
+//! is the one queue kind wired up today (see [`run`]/[`drain_outbox`]),
+//! registered as a latest-wins queue over [`PresenceEntry`] — a second event
+//! kind just needs its own `QueueEntry` impl and parser function to route
+//! through the same [`drain`].
+//!
+//! [`run`] drives this event-driven: a `notify` watcher on the outbox
+//! directory wakes the loop when a ready file lands, a short debounce window
+//! collapses a burst of writes into one drain, and a slow fallback timer
+//! (`STALE_SECS / 2`) keeps stale-file cleanup running even with no new
+//! writes or if the watcher itself becomes unavailable. Files that fail to
+//! POST are kept and retried with per-key exponential backoff (see
+//! [`BackoffState`]); files older than `STALE_SECS` are deleted without
+//! posting (presence is ephemeral).
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use rand::Rng;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use super::queue::{parse_presence_entry, CoalescePolicy, QueueEntry};
+use super::store::{is_ready_file_name, FileStore, Store};
+use crate::shipping::client::ShipperClient;
+
+/// Maximum age for a queued file before it is considered stale and deleted.
+const STALE_SECS: u64 = 600; // 10 minutes
+
+/// How long to wait after the first watcher wake, accumulating further
+/// wakes, before running a single drain — collapses a burst of hook writes
+/// (e.g. several presence updates flushed together) into one pass.
+const DEBOUNCE_PAUSE: Duration = Duration::from_millis(150);
+
+/// Base backoff in seconds before the first retry after a POST failure.
+const BACKOFF_BASE_SECS: f64 = 1.0;
+
+/// Maximum backoff in seconds, regardless of attempt count.
+const BACKOFF_CAP_SECS: f64 = 60.0;
+
+/// Max POST attempts for a single key's coalesced entry before it's dropped
+/// outright — presence (and anything else this drains today) is ephemeral,
+/// so retrying forever just means hammering a downed server with stale data.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Max number of POSTs a single drain pass has in flight at once. Bounds
+/// concurrent sockets when many sessions are active, while letting drain
+/// time scale with the slowest request instead of the sum of all of them.
+const DRAIN_CONCURRENCY: usize = 8;
+
+/// Per-key retry bookkeeping carried across drain passes, keyed by
+/// [`QueueEntry::dedup_key`].
+#[derive(Debug, Default, Clone)]
+pub struct BackoffState {
+    entries: HashMap<String, BackoffEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct BackoffEntry {
+    next_eligible: SystemTime,
+    attempt: u32,
+}
+
+impl BackoffState {
+    fn is_eligible(&self, key: &str, now: SystemTime) -> bool {
+        match self.entries.get(key) {
+            Some(entry) => now >= entry.next_eligible,
+            None => true,
+        }
+    }
+
+    /// Record a POST failure and schedule the next retry with exponential
+    /// backoff plus full jitter. Returns `true` once `key` has exhausted
+    /// `MAX_ATTEMPTS` — the caller should drop its files rather than keep
+    /// them for a retry that will never come.
+    fn record_failure(&mut self, key: &str, now: SystemTime) -> bool {
+        let attempt = self.entries.get(key).map_or(0, |e| e.attempt) + 1;
+        if attempt >= MAX_ATTEMPTS {
+            self.entries.remove(key);
+            return true;
+        }
+
+        let ceiling = (BACKOFF_BASE_SECS * 2f64.powi(attempt as i32 - 1)).min(BACKOFF_CAP_SECS);
+        let jittered = rand::thread_rng().gen_range(0.0..=ceiling);
+        self.entries.insert(
+            key.to_string(),
+            BackoffEntry {
+                next_eligible: now + Duration::from_secs_f64(jittered),
+                attempt,
+            },
+        );
+        false
+    }
+
+    fn record_success(&mut self, key: &str) {
+        self.entries.remove(key);
+    }
+}
+
+/// A destination a drain pass can POST a queue entry's (possibly coalesced)
+/// bytes to. Exists so `drain`'s tests don't need a real `ShipperClient`
+/// built from a live `ShipperConfig` — see `tests::FakePoster`.
+#[async_trait]
+pub trait Poster: Send + Sync {
+    async fn post(&self, endpoint: &str, bytes: Vec<u8>) -> Result<()>;
+
+    /// Whether this destination is currently compatible with what `drain`
+    /// would send it — checked once per pass before coalescing/POSTing so an
+    /// incompatible server doesn't receive a payload it can't parse.
+    /// Defaults to `true` so test doubles (`FakePoster` and friends) don't
+    /// need to opt in.
+    async fn is_compatible(&self) -> bool {
+        true
+    }
+}
+
+#[async_trait]
+impl Poster for ShipperClient {
+    async fn post(&self, endpoint: &str, bytes: Vec<u8>) -> Result<()> {
+        self.post_json(endpoint, bytes).await
+    }
+
+    async fn is_compatible(&self) -> bool {
+        self.capabilities().await.is_compatible()
+    }
+}
+
+/// Start a `notify` watcher on `dir` that sends on `tx` whenever a ready
+/// file is created or renamed into place (the hook's atomic-write pattern:
+/// write to a dotfile, then rename to its final ready name).
+fn start_watcher(dir: &Path, tx: mpsc::Sender<()>) -> notify::Result<RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else { return };
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            return;
+        }
+        let hit = event.paths.iter().any(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(is_ready_file_name)
+        });
+        if hit {
+            let _ = tx.try_send(());
+        }
+    })?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+/// One pending queue entry awaiting a decision, grouped by
+/// [`QueueEntry::dedup_key`] while a drain pass runs.
+struct Candidate<E> {
+    id: String,
+    modified: SystemTime,
+    entry: E,
+}
+
+/// Drain every ready file `store` has through `poster`, routing via `parse`.
+///
+/// Returns `(sent, kept, backoff)`:
+/// - `sent`: number of POSTs that succeeded (one per coalesced group)
+/// - `kept`: number of entries still queued afterward (POST failed and
+///   retries remain, or skipped because `backoff` says this key isn't
+///   eligible yet)
+/// - `backoff`: updated retry bookkeeping — pass this back in on the next
+///   drain pass so backoff state survives across ticks.
+pub async fn drain<E: QueueEntry>(
+    store: &dyn Store,
+    poster: &dyn Poster,
+    parse: fn(Vec<u8>) -> Option<E>,
+    mut backoff: BackoffState,
+    stale_secs: u64,
+) -> (usize, usize, BackoffState) {
+    let now = SystemTime::now();
+
+    // Group survivors by dedup_key; entries that are stale or fail to parse
+    // are dropped up front and never make it into a group.
+    let mut by_key: HashMap<String, Vec<Candidate<E>>> = HashMap::new();
+    for file in store.list_ready() {
+        let age = now.duration_since(file.modified).unwrap_or_default();
+        if age > Duration::from_secs(stale_secs) {
+            store.remove(&file.id);
+            continue;
+        }
+
+        let id = file.id.clone();
+        match parse(file.bytes) {
+            Some(entry) => {
+                by_key.entry(entry.dedup_key().to_owned()).or_default().push(Candidate {
+                    id,
+                    modified: file.modified,
+                    entry,
+                });
+            }
+            None => {
+                // Malformed — delete to avoid indefinite retry.
+                store.remove(&id);
+            }
+        }
+    }
+
+    if !poster.is_compatible().await {
+        // Server is on an incompatible major version — refuse to POST and
+        // keep every surviving file for a later pass. Stale/malformed
+        // cleanup above has already happened since it doesn't depend on the
+        // server being reachable.
+        let kept = by_key.values().map(Vec::len).sum();
+        return (0, kept, backoff);
+    }
+
+    let mut sent = 0usize;
+    let mut kept = 0usize;
+
+    // Coalesce each group and decide backoff eligibility up front — both are
+    // synchronous bookkeeping against `backoff`/`store`, done before any
+    // POST is issued so the concurrent phase below never needs a shared
+    // `&mut BackoffState`.
+    let mut jobs: Vec<(String, String, Vec<u8>, Vec<String>)> = Vec::new();
+    for (key, mut candidates) in by_key {
+        let policy = candidates[0].entry.coalesce_policy();
+        let endpoint = candidates[0].entry.endpoint().to_owned();
+
+        let survivors: Vec<Candidate<E>> = match policy {
+            CoalescePolicy::LatestWins => {
+                candidates.sort_by_key(|c| c.modified);
+                let winner = candidates.pop().expect("group is never empty");
+                // Older duplicates are never sent.
+                for stale in candidates {
+                    store.remove(&stale.id);
+                }
+                vec![winner]
+            }
+            CoalescePolicy::Append => candidates,
+        };
+
+        let body = match policy {
+            CoalescePolicy::LatestWins => survivors[0].entry.bytes().to_vec(),
+            CoalescePolicy::Append => {
+                let values: Vec<serde_json::Value> = survivors
+                    .iter()
+                    .filter_map(|c| serde_json::from_slice(c.entry.bytes()).ok())
+                    .collect();
+                serde_json::to_vec(&values).unwrap_or_default()
+            }
+        };
+
+        if !backoff.is_eligible(&key, now) {
+            // Still backing off this key — leave the survivors for a later
+            // pass rather than hammering a server we know just failed.
+            kept += survivors.len();
+            continue;
+        }
+
+        let ids = survivors.into_iter().map(|c| c.id).collect();
+        jobs.push((key, endpoint, body, ids));
+    }
+
+    // POST every eligible group concurrently, capped at DRAIN_CONCURRENCY
+    // in flight at once — drain time then scales with the slowest request
+    // rather than the sum of all of them, without opening unbounded sockets.
+    let outcomes: Vec<(String, Vec<String>, Result<()>)> = stream::iter(jobs)
+        .map(|(key, endpoint, body, ids)| async move {
+            let result = poster.post(&endpoint, body).await;
+            (key, ids, result)
+        })
+        .buffer_unordered(DRAIN_CONCURRENCY)
+        .collect()
+        .await;
+
+    for (key, ids, result) in outcomes {
+        match result {
+            Ok(_) => {
+                for id in &ids {
+                    store.remove(id);
+                }
+                backoff.record_success(&key);
+                sent += 1;
+            }
+            Err(_) => {
+                if backoff.record_failure(&key, now) {
+                    // Exhausted MAX_ATTEMPTS — drop rather than retry forever.
+                    for id in &ids {
+                        store.remove(id);
+                    }
+                } else {
+                    // Keep for retry once `next_eligible` passes.
+                    kept += ids.len();
+                }
+            }
+        }
+    }
+
+    (sent, kept, backoff)
+}
+
+/// Drain all ready presence events from the outbox directory.
+pub async fn drain_outbox(
+    dir: &Path,
+    client: &ShipperClient,
+    backoff: BackoffState,
+) -> (usize, usize, BackoffState) {
+    let store = FileStore::new(dir);
+    drain(&store, client, parse_presence_entry, backoff, STALE_SECS).await
+}
+
+/// Run the drain loop forever over `dir`, routing through `parse` and
+/// `poster`: event-driven off a filesystem watcher with a [`DEBOUNCE_PAUSE`]
+/// debounce, plus a fallback tick every `stale_secs / 2` so stale-file
+/// cleanup still happens with no new writes. If the watcher can't be
+/// started, or its channel ever closes, this falls back to the fallback
+/// timer alone rather than stopping.
+async fn run_queue<E: QueueEntry>(
+    dir: &Path,
+    poster: &dyn Poster,
+    parse: fn(Vec<u8>) -> Option<E>,
+    stale_secs: u64,
+) -> ! {
+    let _ = std::fs::create_dir_all(dir);
+    let store = FileStore::new(dir);
+
+    let (tx, mut rx) = mpsc::channel::<()>(1);
+    let watcher = start_watcher(dir, tx)
+        .inspect_err(|e| tracing::warn!("outbox watcher unavailable ({e}), polling only"))
+        .ok();
+    let mut watcher_alive = watcher.is_some();
+    // Keep the watcher alive for the loop's lifetime even after the branch
+    // below stops reading from it — dropping it would stop delivery.
+    let _watcher = watcher;
+
+    let fallback_interval = Duration::from_secs((stale_secs / 2).max(1));
+    let mut fallback_timer = tokio::time::interval(fallback_interval);
+    fallback_timer.tick().await; // consume first immediate tick
+
+    let mut backoff = BackoffState::default();
+
+    loop {
+        tokio::select! {
+            woken = rx.recv(), if watcher_alive => {
+                if woken.is_none() {
+                    watcher_alive = false;
+                    continue;
+                }
+                // Debounce: keep absorbing further wakes until the pause
+                // window passes quietly, then drain once.
+                let deadline = Instant::now() + DEBOUNCE_PAUSE;
+                loop {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(deadline) => break,
+                        more = rx.recv() => if more.is_none() {
+                            watcher_alive = false;
+                            break;
+                        },
+                    }
+                }
+                let (_, _, next_backoff) = drain(&store, poster, parse, backoff, stale_secs).await;
+                backoff = next_backoff;
+            }
+            _ = fallback_timer.tick() => {
+                let (_, _, next_backoff) = drain(&store, poster, parse, backoff, stale_secs).await;
+                backoff = next_backoff;
+            }
+        }
+    }
+}
+
+/// Run the presence outbox drain loop forever (see [`run_queue`]).
+pub async fn run(dir: &Path, client: &ShipperClient) -> ! {
+    run_queue(dir, client, parse_presence_entry, STALE_SECS).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outbox::store::MemoryStore;
+    use std::sync::Mutex;
+
+    /// A `Poster` double that records calls and returns a fixed outcome —
+    /// stands in for `ShipperClient` so these tests don't need a live
+    /// `ShipperConfig` or a real HTTP server.
+    struct FakePoster {
+        ok: bool,
+        calls: Mutex<Vec<(String, Vec<u8>)>>,
+    }
+
+    impl FakePoster {
+        fn new(ok: bool) -> Self {
+            Self { ok, calls: Mutex::new(Vec::new()) }
+        }
+
+        fn call_count(&self) -> usize {
+            self.calls.lock().unwrap().len()
+        }
+    }
+
+    #[async_trait]
+    impl Poster for FakePoster {
+        async fn post(&self, endpoint: &str, bytes: Vec<u8>) -> Result<()> {
+            self.calls.lock().unwrap().push((endpoint.to_owned(), bytes));
+            if self.ok {
+                Ok(())
+            } else {
+                anyhow::bail!("simulated failure")
+            }
+        }
+    }
+
+    fn presence_bytes(session_id: &str, state: &str) -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "session_id": session_id,
+            "state": state,
+            "tool_name": "",
+            "cwd": "/tmp"
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_drain_success_deletes_entry() {
+        let store = MemoryStore::new();
+        store.put("prs.A.json", presence_bytes("sess-1", "idle"), SystemTime::now());
+        let poster = FakePoster::new(true);
+
+        let (sent, kept, _) = drain(&store, &poster, parse_presence_entry, BackoffState::default(), STALE_SECS).await;
+
+        assert_eq!(sent, 1);
+        assert_eq!(kept, 0);
+        assert!(!store.contains("prs.A.json"));
+        assert_eq!(poster.call_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_drain_failure_keeps_entry() {
+        let store = MemoryStore::new();
+        store.put("prs.A.json", presence_bytes("sess-1", "idle"), SystemTime::now());
+        let poster = FakePoster::new(false);
+
+        let (sent, kept, _) = drain(&store, &poster, parse_presence_entry, BackoffState::default(), STALE_SECS).await;
+
+        assert_eq!(sent, 0);
+        assert_eq!(kept, 1);
+        assert!(store.contains("prs.A.json"), "entry must be kept when POST fails");
+    }
+
+    /// A `Poster` that holds each call open briefly and tracks the peak
+    /// number of calls in flight at once, to verify `drain` actually
+    /// overlaps POSTs instead of running them strictly one at a time.
+    struct ConcurrencyTrackingPoster {
+        in_flight: std::sync::atomic::AtomicUsize,
+        peak: std::sync::atomic::AtomicUsize,
+    }
+
+    impl ConcurrencyTrackingPoster {
+        fn new() -> Self {
+            Self {
+                in_flight: std::sync::atomic::AtomicUsize::new(0),
+                peak: std::sync::atomic::AtomicUsize::new(0),
+            }
+        }
+
+        fn peak(&self) -> usize {
+            self.peak.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl Poster for ConcurrencyTrackingPoster {
+        async fn post(&self, _endpoint: &str, _bytes: Vec<u8>) -> Result<()> {
+            use std::sync::atomic::Ordering;
+            let now_in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now_in_flight, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_posts_concurrently_up_to_the_cap() {
+        let store = MemoryStore::new();
+        // More sessions than DRAIN_CONCURRENCY, each its own dedup key, so
+        // every one needs its own POST rather than coalescing away.
+        for i in 0..(DRAIN_CONCURRENCY * 2) {
+            store.put(
+                &format!("prs.{i}.json"),
+                presence_bytes(&format!("sess-{i}"), "idle"),
+                SystemTime::now(),
+            );
+        }
+        let poster = ConcurrencyTrackingPoster::new();
+
+        let (sent, kept, _) = drain(&store, &poster, parse_presence_entry, BackoffState::default(), STALE_SECS).await;
+
+        assert_eq!(sent, DRAIN_CONCURRENCY * 2);
+        assert_eq!(kept, 0);
+        assert!(poster.peak() > 1, "POSTs should overlap, not run strictly sequentially");
+        assert!(
+            poster.peak() <= DRAIN_CONCURRENCY,
+            "at most {DRAIN_CONCURRENCY} POSTs should be in flight at once, saw {}",
+            poster.peak()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drain_coalesces_same_dedup_key() {
+        let store = MemoryStore::new();
+        let t0 = SystemTime::now();
+        store.put("prs.A.json", presence_bytes("sess-multi", "thinking"), t0);
+        store.put("prs.B.json", presence_bytes("sess-multi", "running"), t0 + Duration::from_millis(10));
+        store.put("prs.C.json", presence_bytes("sess-multi", "idle"), t0 + Duration::from_millis(20));
+        let poster = FakePoster::new(true);
+
+        let (sent, kept, _) = drain(&store, &poster, parse_presence_entry, BackoffState::default(), STALE_SECS).await;
+
+        assert_eq!(sent, 1, "3 entries for same session → 1 POST");
+        assert_eq!(kept, 0);
+        assert_eq!(store.len(), 0, "all 3 files removed after coalescing");
+        assert_eq!(poster.call_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_drain_deletes_invalid_json() {
+        let store = MemoryStore::new();
+        store.put("prs.bad.json", b"not valid json!!!".to_vec(), SystemTime::now());
+        let poster = FakePoster::new(true);
+
+        let (sent, kept, _) = drain(&store, &poster, parse_presence_entry, BackoffState::default(), STALE_SECS).await;
+
+        assert_eq!(sent, 0);
+        assert_eq!(kept, 0);
+        assert!(!store.contains("prs.bad.json"));
+        assert_eq!(poster.call_count(), 0, "malformed entries are never POSTed");
+    }
+
+    #[tokio::test]
+    async fn test_drain_deletes_stale_entry_without_posting() {
+        let store = MemoryStore::new();
+        let old = SystemTime::now() - Duration::from_secs(STALE_SECS + 60);
+        store.put("prs.old.json", presence_bytes("sess-stale", "idle"), old);
+        let poster = FakePoster::new(true);
+
+        let (sent, kept, _) = drain(&store, &poster, parse_presence_entry, BackoffState::default(), STALE_SECS).await;
+
+        assert_eq!(sent, 0);
+        assert_eq!(kept, 0);
+        assert!(!store.contains("prs.old.json"));
+        assert_eq!(poster.call_count(), 0, "stale entries are deleted, not posted");
+    }
+
+    #[tokio::test]
+    async fn test_drain_backoff_delays_retry_then_drops_after_max_attempts() {
+        let store = MemoryStore::new();
+        store.put("prs.A.json", presence_bytes("sess-backoff", "thinking"), SystemTime::now());
+        let poster = FakePoster::new(false);
+
+        let (sent, kept, backoff) = drain(&store, &poster, parse_presence_entry, BackoffState::default(), STALE_SECS).await;
+        assert_eq!(sent, 0);
+        assert_eq!(kept, 1);
+
+        // Immediately draining again must skip the POST — still in backoff.
+        let (sent, kept, mut backoff) = drain(&store, &poster, parse_presence_entry, backoff, STALE_SECS).await;
+        assert_eq!(sent, 0);
+        assert_eq!(kept, 1, "entry kept while still in backoff window");
+        assert_eq!(poster.call_count(), 1, "no new POST attempt while ineligible");
+
+        // Force eligibility each round rather than sleeping through a
+        // jittered window that can be up to BACKOFF_CAP_SECS.
+        for _ in 0..MAX_ATTEMPTS {
+            for entry in backoff.entries.values_mut() {
+                entry.next_eligible = SystemTime::UNIX_EPOCH;
+            }
+            let (_, _, next_backoff) = drain(&store, &poster, parse_presence_entry, backoff, STALE_SECS).await;
+            backoff = next_backoff;
+        }
+
+        assert!(!store.contains("prs.A.json"), "entry dropped once MAX_ATTEMPTS is exhausted");
+        assert!(backoff.entries.is_empty(), "backoff bookkeeping for a dropped key must not linger");
+    }
+
+    /// A `Poster` that reports itself incompatible, so tests can verify
+    /// `drain` refuses to POST without needing a real capability handshake.
+    struct IncompatiblePoster;
+
+    #[async_trait]
+    impl Poster for IncompatiblePoster {
+        async fn post(&self, _endpoint: &str, _bytes: Vec<u8>) -> Result<()> {
+            panic!("drain must not POST to an incompatible destination");
+        }
+
+        async fn is_compatible(&self) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_skips_posting_when_poster_is_incompatible() {
+        let store = MemoryStore::new();
+        store.put("prs.A.json", presence_bytes("sess-1", "idle"), SystemTime::now());
+        let poster = IncompatiblePoster;
+
+        let (sent, kept, _) = drain(&store, &poster, parse_presence_entry, BackoffState::default(), STALE_SECS).await;
+
+        assert_eq!(sent, 0);
+        assert_eq!(kept, 1);
+        assert!(store.contains("prs.A.json"), "entry must be kept, not dropped, on incompatibility");
+    }
+
+    #[tokio::test]
+    async fn test_watcher_fires_on_ready_file_creation() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, mut rx) = mpsc::channel::<()>(1);
+        let _watcher = start_watcher(dir.path(), tx).unwrap();
+
+        // A dotfile write alone must not wake the drain loop — only the
+        // rename into a ready name should.
+        let tmp = dir.path().join(".tmp.XYZ");
+        std::fs::write(&tmp, b"{}").unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(300), rx.recv()).await.is_err(),
+            "a dotfile write alone must not wake the drain loop"
+        );
+
+        let final_path = dir.path().join("prs.XYZ.json");
+        std::fs::rename(&tmp, &final_path).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("watcher should wake on ready-file rename")
+            .expect("channel should still be open");
+    }
+
+    // -----------------------------------------------------------------------
+    // One integration test against a real `ShipperClient`, confirming the
+    // `Poster` impl actually reaches the configured endpoint. Everything
+    // else above exercises `drain`'s own logic through `FakePoster` +
+    // `MemoryStore`, with no live config or real HTTP server required.
+    // -----------------------------------------------------------------------
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_drain_outbox_posts_through_real_shipper_client() {
+        use crate::config::ShipperConfig;
+        use crate::pipeline::compressor::CompressionAlgo;
+        use std::sync::Arc;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let paths: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+        let paths_clone = paths.clone();
+        let server = tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else { break };
+                let mut buf = vec![0u8; 4096];
+                let mut total = 0;
+                loop {
+                    let n = socket.read(&mut buf[total..]).await.unwrap_or(0);
+                    if n == 0 {
+                        break;
+                    }
+                    total += n;
+                    if buf[..total].windows(4).any(|w| w == b"\r\n\r\n") {
+                        break;
+                    }
+                }
+                let head = String::from_utf8_lossy(&buf[..total]).into_owned();
+                let path = head
+                    .lines()
+                    .next()
+                    .and_then(|l| l.split_whitespace().nth(1))
+                    .unwrap_or("/")
+                    .to_string();
+                paths_clone.lock().unwrap().push(path);
+                let resp = "HTTP/1.1 204 No Content\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+                let _ = socket.write_all(resp.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("prs.A.json"),
+            presence_bytes("sess-real", "idle"),
+        )
+        .unwrap();
+
+        let url = format!("http://{}", addr);
+        let cfg = ShipperConfig::default().with_overrides(Some(&url), None, None, None);
+        let client = ShipperClient::with_compression(&cfg, CompressionAlgo::Gzip).unwrap();
+
+        let (sent, kept, _) = drain_outbox(dir.path(), &client, BackoffState::default()).await;
+        assert_eq!(sent, 1);
+        assert_eq!(kept, 0);
+
+        server.abort();
+        let logged = paths.lock().unwrap().clone();
+        assert_eq!(
+            logged,
+            vec!["/api/capabilities".to_string(), "/api/agents/presence".to_string()]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! What a drain pass needs to know about one parsed queue entry: where it
+//! ships to, and how duplicates sharing the same logical identity coalesce.
+
+use serde_json::Value;
+
+/// How entries sharing a [`QueueEntry::dedup_key`] combine before POSTing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoalescePolicy {
+    /// Only the most-recently-written entry survives; older duplicates are
+    /// dropped without ever being sent. Presence uses this — only the
+    /// latest state per session matters.
+    LatestWins,
+    /// All entries sharing the key are batched into one POST of a JSON
+    /// array, rather than collapsed to one — for events that are each
+    /// independently meaningful (e.g. batchable telemetry records).
+    Append,
+}
+
+/// A parsed, queued event a drain pass can ship.
+pub trait QueueEntry {
+    /// API path this entry POSTs to.
+    fn endpoint(&self) -> &str;
+
+    /// Coalescing key — entries sharing this key fold together per
+    /// [`Self::coalesce_policy`].
+    fn dedup_key(&self) -> &str;
+
+    /// How entries sharing `dedup_key` combine before POSTing.
+    fn coalesce_policy(&self) -> CoalescePolicy;
+
+    /// The raw bytes to POST for this single entry (before any
+    /// [`CoalescePolicy::Append`] batching).
+    fn bytes(&self) -> &[u8];
+}
+
+/// A presence heartbeat written by a Claude Code hook. Only the latest
+/// write per session matters, so duplicates coalesce to the newest.
+pub struct PresenceEntry {
+    pub session_id: String,
+    pub bytes: Vec<u8>,
+}
+
+impl QueueEntry for PresenceEntry {
+    fn endpoint(&self) -> &str {
+        "/api/agents/presence"
+    }
+
+    fn dedup_key(&self) -> &str {
+        &self.session_id
+    }
+
+    fn coalesce_policy(&self) -> CoalescePolicy {
+        CoalescePolicy::LatestWins
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Parse one presence file's raw bytes into a [`PresenceEntry`], validating
+/// it carries a non-empty `session_id` — the only field `drain` needs to
+/// coalesce duplicate writes for the same session. `None` on malformed JSON
+/// or a missing/empty id, both of which `drain` treats as a reason to
+/// delete the file without retrying.
+pub fn parse_presence_entry(bytes: Vec<u8>) -> Option<PresenceEntry> {
+    let val: Value = serde_json::from_slice(&bytes).ok()?;
+    let session_id = val
+        .get("session_id")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())?
+        .to_owned();
+    Some(PresenceEntry { session_id, bytes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_presence_entry_requires_session_id() {
+        assert!(parse_presence_entry(br#"{"session_id":"s1"}"#.to_vec()).is_some());
+        assert!(parse_presence_entry(br#"{"session_id":""}"#.to_vec()).is_none());
+        assert!(parse_presence_entry(br#"{}"#.to_vec()).is_none());
+        assert!(parse_presence_entry(b"not json".to_vec()).is_none());
+    }
+
+    #[test]
+    fn test_presence_entry_is_latest_wins() {
+        let entry = PresenceEntry {
+            session_id: "s1".into(),
+            bytes: b"{}".to_vec(),
+        };
+        assert_eq!(entry.endpoint(), "/api/agents/presence");
+        assert_eq!(entry.dedup_key(), "s1");
+        assert_eq!(entry.coalesce_policy(), CoalescePolicy::LatestWins);
+    }
+}
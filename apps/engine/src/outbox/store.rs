@@ -0,0 +1,164 @@
+//! Pluggable backing store for queued outbox files.
+//!
+//! [`FileStore`] is the real backend — the `~/.claude/outbox/`-style
+//! directory the drain loop has always read. [`MemoryStore`] exists purely
+//! so `drain`'s tests don't need a real directory on disk, matching how
+//! [`super::drain::Poster`] lets them avoid a real `ShipperClient` too.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Whether `file_name` is a ready file a drain pass will act on — shared
+/// between [`FileStore::list_ready`] and the watcher's event filter (see
+/// `drain::start_watcher`) so the two can't drift apart.
+pub fn is_ready_file_name(file_name: &str) -> bool {
+    file_name.ends_with(".json") && !file_name.starts_with('.')
+}
+
+/// One queued file, as seen by a drain pass, before it's parsed into a
+/// [`super::queue::QueueEntry`].
+pub struct ReadyFile {
+    pub id: String,
+    pub bytes: Vec<u8>,
+    pub modified: SystemTime,
+}
+
+/// A backing store a drain pass reads queued files from and deletes them
+/// from once handled.
+pub trait Store: Send + Sync {
+    /// All files currently ready to drain. An entry that can't be read or
+    /// stat'd (disappeared mid-scan, permissions) is simply omitted rather
+    /// than erroring the whole pass.
+    fn list_ready(&self) -> Vec<ReadyFile>;
+
+    /// Remove a file by `id` — after a successful POST, a dropped
+    /// coalescing duplicate, or an exhausted-retries drop.
+    fn remove(&self, id: &str);
+}
+
+/// The real backend: a directory of `prs.*.json`-style files (the current
+/// `~/.claude/outbox/`), matched by [`is_ready_file_name`].
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: &Path) -> Self {
+        Self { dir: dir.to_path_buf() }
+    }
+}
+
+impl Store for FileStore {
+    fn list_ready(&self) -> Vec<ReadyFile> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        entries
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.path();
+                let file_name = path.file_name()?.to_str()?;
+                if !is_ready_file_name(file_name) {
+                    return None;
+                }
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                let bytes = std::fs::read(&path).ok()?;
+                Some(ReadyFile {
+                    id: file_name.to_owned(),
+                    bytes,
+                    modified,
+                })
+            })
+            .collect()
+    }
+
+    fn remove(&self, id: &str) {
+        let _ = std::fs::remove_file(self.dir.join(id));
+    }
+}
+
+/// An in-process backend for tests — avoids both a real directory and the
+/// filesystem-mtime quirks (coarse resolution, clock skew) a disk-backed
+/// store has to tolerate when ordering duplicates.
+#[derive(Default)]
+pub struct MemoryStore {
+    files: Mutex<HashMap<String, (Vec<u8>, SystemTime)>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Seed a file as though it had just been written at `modified`.
+    pub fn put(&self, id: &str, bytes: Vec<u8>, modified: SystemTime) {
+        self.files.lock().unwrap().insert(id.to_owned(), (bytes, modified));
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.files.lock().unwrap().contains_key(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.lock().unwrap().len()
+    }
+}
+
+impl Store for MemoryStore {
+    fn list_ready(&self) -> Vec<ReadyFile> {
+        self.files
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, (bytes, modified))| ReadyFile {
+                id: id.clone(),
+                bytes: bytes.clone(),
+                modified: *modified,
+            })
+            .collect()
+    }
+
+    fn remove(&self, id: &str) {
+        self.files.lock().unwrap().remove(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ready_file_name() {
+        assert!(is_ready_file_name("prs.ABC123.json"));
+        assert!(!is_ready_file_name(".tmp.ABC123"));
+        assert!(!is_ready_file_name(".tmp.ABC123.json"));
+        assert!(!is_ready_file_name("prs.ABC123.txt"));
+    }
+
+    #[test]
+    fn test_memory_store_round_trips() {
+        let store = MemoryStore::new();
+        store.put("a", b"{}".to_vec(), SystemTime::now());
+        assert_eq!(store.list_ready().len(), 1);
+        assert!(store.contains("a"));
+
+        store.remove("a");
+        assert!(store.list_ready().is_empty());
+        assert!(!store.contains("a"));
+    }
+
+    #[test]
+    fn test_file_store_filters_to_ready_names() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".tmp.ABC"), b"{}").unwrap();
+        std::fs::write(dir.path().join("prs.ABC.json"), b"{}").unwrap();
+
+        let store = FileStore::new(dir.path());
+        let ready = store.list_ready();
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].id, "prs.ABC.json");
+    }
+}
@@ -0,0 +1,10 @@
+pub mod adaptive_compression;
+pub mod chunker;
+pub mod compressor;
+pub mod crypto;
+pub mod dictionary;
+pub mod msgpack;
+pub mod parse_cache;
+pub mod parser;
+pub mod session_format;
+pub mod stats;
@@ -0,0 +1,234 @@
+//! Adaptive compression algorithm selection.
+//!
+//! Three signals feed `ShipperClient::choose_algo`: a size heuristic (this
+//! module's [`choose_for_size`]), the set of algorithms the server actually
+//! advertises (parsed from its `Accept-Encoding` response header by
+//! [`negotiate_server_support`], called from `ShipperClient::health_check`),
+//! and a rolling per-algorithm bytes-saved-per-millisecond rate learned from
+//! real outcomes ([`AdaptiveCompressor`]). The size heuristic is the floor —
+//! it's right even cold, before any server round-trip or history exists.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::compressor::CompressionAlgo;
+
+/// Below this many bytes, an encoder's framing overhead outweighs the
+/// bandwidth it would save — heartbeat-sized payloads ship uncompressed.
+const SMALL_PAYLOAD_BYTES: usize = 2 * 1024;
+
+/// Above this many bytes, zstd's better ratio is worth its slower encode
+/// than gzip's; below it, gzip's cheaper encode wins on the shipper's mostly
+/// small session payloads.
+const LARGE_PAYLOAD_BYTES: usize = 64 * 1024;
+
+/// Pick an algorithm by payload size alone, ignoring server negotiation or
+/// learned history — the floor every other signal in this module layers on
+/// top of (see `AdaptiveCompressor::choose`).
+pub fn choose_for_size(len: usize) -> CompressionAlgo {
+    if len < SMALL_PAYLOAD_BYTES {
+        CompressionAlgo::Identity
+    } else if len < LARGE_PAYLOAD_BYTES {
+        CompressionAlgo::Gzip
+    } else {
+        CompressionAlgo::Zstd
+    }
+}
+
+/// Parse an `Accept-Encoding` header value into the subset of
+/// `CompressionAlgo` the server advertises support for, in the header's own
+/// listed order. Q-values aren't parsed — every algorithm we'd ever pick is
+/// one we trust equally, so a bare name is all that matters.
+pub fn negotiate_server_support(accept_encoding: &str) -> Vec<CompressionAlgo> {
+    accept_encoding
+        .split(',')
+        .filter_map(|token| {
+            let name = token.split(';').next().unwrap_or("").trim();
+            match name {
+                "gzip" => Some(CompressionAlgo::Gzip),
+                "zstd" => Some(CompressionAlgo::Zstd),
+                "br" => Some(CompressionAlgo::Brotli),
+                "lz4" => Some(CompressionAlgo::Lz4),
+                "identity" | "*" => Some(CompressionAlgo::Identity),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Samples kept per algorithm — enough to smooth over one-off outliers (a
+/// single huge or tiny payload) without reacting too slowly to a real shift,
+/// e.g. the link actually getting slower.
+const WINDOW_SIZE: usize = 20;
+
+/// Rolling window of recent (bytes_saved, millis) samples for one algorithm.
+#[derive(Default)]
+struct AlgoWindow {
+    samples: Vec<(i64, u64)>,
+}
+
+impl AlgoWindow {
+    fn push(&mut self, bytes_saved: i64, millis: u64) {
+        if self.samples.len() >= WINDOW_SIZE {
+            self.samples.remove(0);
+        }
+        self.samples.push((bytes_saved, millis));
+    }
+
+    /// Bytes saved per millisecond spent compressing, averaged over the
+    /// window. `None` with no samples yet.
+    fn rate(&self) -> Option<f64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let total_saved: i64 = self.samples.iter().map(|(saved, _)| saved).sum();
+        let total_millis: u64 = self.samples.iter().map(|(_, millis)| millis).sum();
+        Some(total_saved as f64 / total_millis.max(1) as f64)
+    }
+}
+
+/// Learns which compression algorithm is actually paying off on this link,
+/// from real `record`ed outcomes, and falls back to `choose_for_size` for
+/// any algorithm (or every algorithm, cold) with no history yet.
+pub struct AdaptiveCompressor {
+    windows: Mutex<HashMap<CompressionAlgo, AlgoWindow>>,
+}
+
+impl AdaptiveCompressor {
+    pub fn new() -> Self {
+        Self {
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record one compression outcome: `original_len` bytes compressed to
+    /// `compressed_len` bytes in `elapsed`, using `algo`.
+    pub fn record(&self, algo: CompressionAlgo, original_len: usize, compressed_len: usize, elapsed: Duration) {
+        let saved = original_len as i64 - compressed_len as i64;
+        let millis = elapsed.as_millis() as u64;
+        self.windows
+            .lock()
+            .unwrap()
+            .entry(algo)
+            .or_default()
+            .push(saved, millis);
+    }
+
+    /// Pick the best algorithm among `candidates` for a payload of
+    /// `payload_len` bytes: whichever has the highest learned
+    /// bytes-saved-per-ms rate among those with any history, or
+    /// `choose_for_size`'s pick (clamped to `candidates`) when none do yet.
+    pub fn choose(&self, candidates: &[CompressionAlgo], payload_len: usize) -> CompressionAlgo {
+        if candidates.is_empty() {
+            return choose_for_size(payload_len);
+        }
+
+        let by_size = choose_for_size(payload_len);
+        let fallback = if candidates.contains(&by_size) {
+            by_size
+        } else {
+            candidates[0]
+        };
+
+        let windows = self.windows.lock().unwrap();
+        let mut best: Option<(CompressionAlgo, f64)> = None;
+        for &algo in candidates {
+            if let Some(rate) = windows.get(&algo).and_then(AlgoWindow::rate) {
+                if best.map_or(true, |(_, best_rate)| rate > best_rate) {
+                    best = Some((algo, rate));
+                }
+            }
+        }
+        best.map(|(algo, _)| algo).unwrap_or(fallback)
+    }
+}
+
+impl Default for AdaptiveCompressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_for_size_small_is_identity() {
+        assert_eq!(choose_for_size(100), CompressionAlgo::Identity);
+    }
+
+    #[test]
+    fn test_choose_for_size_medium_is_gzip() {
+        assert_eq!(choose_for_size(10 * 1024), CompressionAlgo::Gzip);
+    }
+
+    #[test]
+    fn test_choose_for_size_large_is_zstd() {
+        assert_eq!(choose_for_size(200 * 1024), CompressionAlgo::Zstd);
+    }
+
+    #[test]
+    fn test_negotiate_server_support_parses_known_tokens() {
+        let algos = negotiate_server_support("gzip, br, zstd");
+        assert_eq!(
+            algos,
+            vec![CompressionAlgo::Gzip, CompressionAlgo::Brotli, CompressionAlgo::Zstd]
+        );
+    }
+
+    #[test]
+    fn test_negotiate_server_support_parses_lz4() {
+        let algos = negotiate_server_support("lz4, gzip");
+        assert_eq!(algos, vec![CompressionAlgo::Lz4, CompressionAlgo::Gzip]);
+    }
+
+    #[test]
+    fn test_negotiate_server_support_ignores_unknown_tokens() {
+        let algos = negotiate_server_support("gzip, compress, sdch");
+        assert_eq!(algos, vec![CompressionAlgo::Gzip]);
+    }
+
+    #[test]
+    fn test_negotiate_server_support_handles_q_values() {
+        let algos = negotiate_server_support("gzip;q=1.0, identity;q=0.5");
+        assert_eq!(algos, vec![CompressionAlgo::Gzip, CompressionAlgo::Identity]);
+    }
+
+    #[test]
+    fn test_adaptive_compressor_falls_back_to_size_heuristic_with_no_history() {
+        let adaptive = AdaptiveCompressor::new();
+        let candidates = [CompressionAlgo::Gzip, CompressionAlgo::Zstd];
+        assert_eq!(adaptive.choose(&candidates, 10 * 1024), CompressionAlgo::Gzip);
+    }
+
+    #[test]
+    fn test_adaptive_compressor_empty_candidates_uses_size_heuristic() {
+        let adaptive = AdaptiveCompressor::new();
+        assert_eq!(adaptive.choose(&[], 10 * 1024), CompressionAlgo::Gzip);
+    }
+
+    #[test]
+    fn test_adaptive_compressor_prefers_higher_saved_per_ms_rate() {
+        let adaptive = AdaptiveCompressor::new();
+        // Gzip: saves 1000 bytes in 10ms => 100 bytes/ms.
+        adaptive.record(CompressionAlgo::Gzip, 2000, 1000, Duration::from_millis(10));
+        // Zstd: saves 1500 bytes in 5ms => 300 bytes/ms, clearly better.
+        adaptive.record(CompressionAlgo::Zstd, 2000, 500, Duration::from_millis(5));
+
+        let candidates = [CompressionAlgo::Gzip, CompressionAlgo::Zstd];
+        assert_eq!(adaptive.choose(&candidates, 10 * 1024), CompressionAlgo::Zstd);
+    }
+
+    #[test]
+    fn test_adaptive_compressor_ignores_candidates_without_history() {
+        let adaptive = AdaptiveCompressor::new();
+        adaptive.record(CompressionAlgo::Brotli, 2000, 1000, Duration::from_millis(10));
+
+        // Brotli isn't a candidate here, so its history shouldn't matter —
+        // falls back to the size heuristic among gzip/zstd.
+        let candidates = [CompressionAlgo::Gzip, CompressionAlgo::Zstd];
+        assert_eq!(adaptive.choose(&candidates, 10 * 1024), CompressionAlgo::Gzip);
+    }
+}
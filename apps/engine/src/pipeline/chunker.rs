@@ -0,0 +1,223 @@
+//! Content-defined chunking (FastCDC-style) for chunk-level dedup.
+//!
+//! Splits a byte range into variable-size chunks using a gear-hash rolling
+//! hash so boundaries are stable under small edits — inserting or deleting
+//! bytes only perturbs chunks near the edit, not the whole tail of the file
+//! (unlike fixed-size chunking). Chunking always runs on raw,
+//! pre-compression bytes so boundaries stay reproducible across runs
+//! regardless of which `CompressionAlgo` is in play.
+
+use std::sync::OnceLock;
+
+/// Default target average chunk size, matched by `ShipperConfig::target_chunk_bytes`.
+pub const DEFAULT_TARGET_SIZE: usize = 64 * 1024;
+/// Default hard ceiling, matched by `ShipperConfig::max_chunk_bytes`.
+pub const DEFAULT_MAX_SIZE: usize = 256 * 1024;
+
+/// Boundary-test parameters derived from a target/max chunk size. `min_size`
+/// suppresses boundary tests until a quarter of the target has accumulated;
+/// `mask_s`/`mask_l` are gear-hash masks biasing boundaries toward the
+/// target before backing off to force a cut by `max_size`.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkerParams {
+    min_size: usize,
+    normal_size: usize,
+    max_size: usize,
+    mask_s: u64,
+    mask_l: u64,
+}
+
+impl Default for ChunkerParams {
+    fn default() -> Self {
+        Self::new(DEFAULT_TARGET_SIZE, DEFAULT_MAX_SIZE)
+    }
+}
+
+impl ChunkerParams {
+    /// `target_size` is the average chunk size once normalized; `max_size`
+    /// is the hard ceiling a chunk is force-cut at.
+    pub fn new(target_size: usize, max_size: usize) -> Self {
+        let target_size = target_size.max(1);
+        let bits = usize::BITS - 1 - target_size.leading_zeros();
+        Self {
+            min_size: (target_size / 4).max(1),
+            normal_size: target_size,
+            max_size: max_size.max(target_size),
+            // Stricter mask before the target (more required zero bits, rarer
+            // match) biases boundaries away from the low end of the range;
+            // looser after (fewer bits) biases them back down before max_size.
+            mask_s: (1u64 << (bits + 2)) - 1,
+            mask_l: (1u64 << bits.saturating_sub(2).max(1)) - 1,
+        }
+    }
+}
+
+/// Gear table: 256 pseudo-random u64s, one per byte value, used to mix each
+/// byte into the rolling hash. Lazily built once via a splitmix64 generator
+/// rather than hand-written as a literal array.
+fn gear_table() -> &'static [u64; 256] {
+    static TABLE: OnceLock<[u64; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0u64; 256];
+        let mut seed: u64 = 0x9E3779B97F4A7C15;
+        for slot in table.iter_mut() {
+            seed = seed.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            z ^= z >> 31;
+            *slot = z;
+        }
+        table
+    })
+}
+
+/// Find the end offset (exclusive) of the next chunk starting at `start`.
+fn next_chunk_end(data: &[u8], start: usize, params: &ChunkerParams) -> usize {
+    let len = data.len();
+    let min_end = (start + params.min_size).min(len);
+    let normal_end = (start + params.normal_size).min(len);
+    let max_end = (start + params.max_size).min(len);
+
+    if min_end >= len {
+        return len;
+    }
+
+    let table = gear_table();
+    let mut hash: u64 = 0;
+    let mut i = min_end;
+
+    while i < normal_end {
+        hash = (hash << 1).wrapping_add(table[data[i] as usize]);
+        if hash & params.mask_s == 0 {
+            return i + 1;
+        }
+        i += 1;
+    }
+    while i < max_end {
+        hash = (hash << 1).wrapping_add(table[data[i] as usize]);
+        if hash & params.mask_l == 0 {
+            return i + 1;
+        }
+        i += 1;
+    }
+    max_end
+}
+
+/// Split `data` into content-defined (start, end) byte ranges, in order,
+/// using `ChunkerParams::default()`.
+pub fn chunk_ranges(data: &[u8]) -> Vec<(usize, usize)> {
+    chunk_ranges_with(data, &ChunkerParams::default())
+}
+
+/// As `chunk_ranges`, with explicit boundary parameters (see `ShipperConfig`'s
+/// `target_chunk_bytes`/`max_chunk_bytes`).
+pub fn chunk_ranges_with(data: &[u8], params: &ChunkerParams) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = 0usize;
+    let len = data.len();
+    while start < len {
+        let end = next_chunk_end(data, start, params);
+        ranges.push((start, end));
+        start = end;
+    }
+    ranges
+}
+
+/// A single content-defined chunk: its blake3 hash and byte range within the
+/// source buffer it was cut from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub hash: [u8; 32],
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Chunk `data` and hash each piece with blake3, using `ChunkerParams::default()`.
+pub fn chunk_and_hash(data: &[u8]) -> Vec<Chunk> {
+    chunk_and_hash_with(data, &ChunkerParams::default())
+}
+
+/// As `chunk_and_hash`, with explicit boundary parameters.
+pub fn chunk_and_hash_with(data: &[u8], params: &ChunkerParams) -> Vec<Chunk> {
+    chunk_ranges_with(data, params)
+        .into_iter()
+        .map(|(start, end)| Chunk {
+            hash: *blake3::hash(&data[start..end]).as_bytes(),
+            start,
+            end,
+        })
+        .collect()
+}
+
+/// Render a chunk hash as lowercase hex, the form stored in the `chunks` table
+/// and sent in the manifest.
+pub fn hash_hex(hash: &[u8; 32]) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_ranges_cover_whole_input() {
+        let data = vec![0u8; 500_000];
+        let ranges = chunk_ranges(&data);
+        assert!(!ranges.is_empty());
+        assert_eq!(ranges.first().unwrap().0, 0);
+        assert_eq!(ranges.last().unwrap().1, data.len());
+        // Contiguous, no gaps or overlaps
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+        }
+    }
+
+    #[test]
+    fn test_chunk_sizes_bounded() {
+        let data: Vec<u8> = (0..500_000u32).map(|i| (i % 251) as u8).collect();
+        let ranges = chunk_ranges(&data);
+        for (start, end) in &ranges {
+            let size = end - start;
+            // Every chunk, the final one included, respects the ceiling.
+            assert!(size <= DEFAULT_MAX_SIZE, "chunk exceeded max size: {}", size);
+        }
+    }
+
+    #[test]
+    fn test_custom_params_respect_max_size() {
+        let params = ChunkerParams::new(16 * 1024, 64 * 1024);
+        let data: Vec<u8> = (0..500_000u32).map(|i| (i % 251) as u8).collect();
+        let ranges = chunk_ranges_with(&data, &params);
+        for (start, end) in &ranges {
+            assert!(end - start <= 64 * 1024);
+        }
+    }
+
+    #[test]
+    fn test_insertion_only_perturbs_local_chunks() {
+        let base: Vec<u8> = (0..300_000u32).map(|i| (i % 197) as u8).collect();
+        let mut edited = base.clone();
+        edited.splice(150_000..150_000, vec![9u8; 37]);
+
+        let base_chunks = chunk_and_hash(&base);
+        let edited_chunks = chunk_and_hash(&edited);
+
+        let base_hashes: std::collections::HashSet<_> =
+            base_chunks.iter().map(|c| c.hash).collect();
+        let edited_hashes: std::collections::HashSet<_> =
+            edited_chunks.iter().map(|c| c.hash).collect();
+
+        let unchanged = base_hashes.intersection(&edited_hashes).count();
+        // Most chunks should survive a small local edit — not a full re-chunk.
+        assert!(unchanged as f64 > base_chunks.len() as f64 * 0.5);
+    }
+
+    #[test]
+    fn test_hash_hex_format() {
+        let chunk = &chunk_and_hash(b"hello world")[0];
+        let hex = hash_hex(&chunk.hash);
+        assert_eq!(hex.len(), 64);
+        assert!(hex.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+}
@@ -4,6 +4,8 @@
 //! `GzEncoder`, so the full JSON is never materialized in memory.
 //! This eliminates the 79% gzip bottleneck from the Python version.
 
+use std::cell::RefCell;
+use std::io::Write;
 use std::sync::OnceLock;
 
 use flate2::write::GzEncoder;
@@ -12,6 +14,34 @@ use serde::Serialize;
 
 use super::parser::{ParsedEvent, SessionMetadata};
 
+/// `Write` adapter that tallies every byte passed through it before
+/// forwarding to `inner` — wrapped around the writer `serde_json::to_writer`
+/// targets so `build_and_compress` can report the uncompressed payload size
+/// for free during its single streaming pass, without ever materializing
+/// the JSON to measure it directly.
+struct CountingWriter<W> {
+    inner: W,
+    count: usize,
+}
+
+impl<W> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 /// Cached hostname — called once, reused for all payloads.
 fn cached_hostname() -> &'static str {
     static HOSTNAME: OnceLock<String> = OnceLock::new();
@@ -47,6 +77,16 @@ pub struct IngestPayload<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ended_at: Option<String>,
     pub provider_session_id: &'a str,
+    /// Set when the payload was zstd-compressed against a shared trained
+    /// dictionary (see `pipeline::dictionary`), so the server can pick the
+    /// matching dictionary to decompress with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dictionary_id: Option<u32>,
+    /// Set when shipped from the retry spool (see `state::spool::SpoolEntry`),
+    /// so the server can dedupe idempotently if a crash lands between a
+    /// successful `put` and `Spool::mark_shipped`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spool_seq: Option<i64>,
     pub events: Vec<EventIngest<'a>>,
 }
 
@@ -139,30 +179,335 @@ pub fn build_payload<'a>(
         started_at,
         ended_at,
         provider_session_id: &metadata.session_id,
+        dictionary_id: None,
+        spool_seq: None,
         events: event_ingests,
     }
 }
 
+/// Split events into one or more size-bounded `IngestPayload`s instead of
+/// one that can blow past request-size limits. Each batch shares the same
+/// session header as `build_payload` (`id`, `provider`,
+/// `provider_session_id`, `started_at`/`ended_at`, ...) but carries only a
+/// contiguous slice of `events` — since `events` arrives in source order,
+/// that slice is also a contiguous `source_offset` range, so a failed batch
+/// can be re-shipped independently of the others without re-sending events
+/// another batch already landed.
+///
+/// Packs greedily: events are added to the running batch until the next one
+/// would push its serialized size over `max_uncompressed_bytes`, then a new
+/// batch starts. A single event over budget on its own still gets shipped
+/// alone rather than dropped. An empty `events` slice still yields one
+/// header-only batch, matching `build_payload`'s behavior for an
+/// empty-event session.
+pub fn build_batches<'a>(
+    session_id: &'a str,
+    events: &'a [ParsedEvent],
+    metadata: &'a SessionMetadata,
+    source_path: &'a str,
+    provider: &'a str,
+    max_uncompressed_bytes: usize,
+) -> Vec<IngestPayload<'a>> {
+    batch_ranges(events, source_path, max_uncompressed_bytes)
+        .into_iter()
+        .map(|(start, end)| {
+            build_payload(session_id, &events[start..end], metadata, source_path, provider)
+        })
+        .collect()
+}
+
+/// Index ranges of `events` for `build_batches`' greedy packing — shared
+/// with `shipper::prepare_file`, which needs the same ranges to slice each
+/// batch's own source byte range (`source_offset` of `events[end]`, or EOF
+/// for the last range) alongside its `IngestPayload`.
+///
+/// A multi-event line (several events sharing one `source_offset` — e.g. a
+/// `tool_use` and the `tool_result` answering it land on the same line) is
+/// never split across two ranges, so a batch's byte range always lands on a
+/// real JSONL line boundary: once a line's first event is in the running
+/// batch, every other event from that same line is forced in too regardless
+/// of budget. An empty `events` slice still yields one empty range, matching
+/// `build_payload`'s header-only-payload behavior for an empty-event
+/// session.
+pub(crate) fn batch_ranges(
+    events: &[ParsedEvent],
+    source_path: &str,
+    max_uncompressed_bytes: usize,
+) -> Vec<(usize, usize)> {
+    if events.is_empty() {
+        return vec![(0, 0)];
+    }
+
+    let sizes: Vec<usize> = events
+        .iter()
+        .map(|e| event_ingest_size(e, source_path))
+        .collect();
+
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < events.len() {
+        let mut end = start + 1;
+        let mut running = sizes[start];
+        while end < events.len() {
+            let same_line = events[end].source_offset == events[end - 1].source_offset;
+            if !same_line && running + sizes[end] > max_uncompressed_bytes {
+                break;
+            }
+            running += sizes[end];
+            end += 1;
+        }
+        ranges.push((start, end));
+        start = end;
+    }
+    ranges
+}
+
+/// Serialized size of a single event, for `build_batches`' greedy packing.
+/// Approximate (ignores the header fields shared by the whole payload and
+/// JSON array punctuation) but cheap and consistent across events, which is
+/// all a packing heuristic needs.
+fn event_ingest_size(e: &ParsedEvent, source_path: &str) -> usize {
+    let role = match e.role {
+        super::parser::Role::User => "user",
+        super::parser::Role::Assistant => "assistant",
+        super::parser::Role::Tool => "tool",
+    };
+    let ingest = EventIngest {
+        role,
+        content_text: e.content_text.as_deref(),
+        tool_name: e.tool_name.as_deref(),
+        tool_input_json: e.tool_input_json.as_ref(),
+        tool_output_text: e.tool_output_text.as_deref(),
+        timestamp: e.timestamp.to_rfc3339(),
+        source_path,
+        source_offset: e.source_offset,
+        raw_json: e.raw_line.as_deref(),
+    };
+    serde_json::to_vec(&ingest).map(|v| v.len()).unwrap_or(0)
+}
+
+/// Outcome of a `build_and_compress` pass — bundles the compressed bytes
+/// with the byte accounting (`uncompressed_bytes`/`compressed_bytes`) and
+/// `event_count` a caller would otherwise have to re-serialize the payload
+/// to recover, so it can emit ratio/throughput metrics or enforce an
+/// upload-size budget directly off this single streaming pass.
+#[derive(Debug)]
+pub struct EncodeResult {
+    pub payload: Vec<u8>,
+    pub uncompressed_bytes: usize,
+    pub compressed_bytes: usize,
+    pub event_count: usize,
+    pub codec: CompressionAlgo,
+}
+
 /// Build an IngestPayload and stream-compress it to gzip bytes.
 ///
 /// This is THE key optimization: `serde_json::to_writer` writes JSON tokens
 /// directly into the `GzEncoder`'s write buffer. At no point is the full
-/// JSON string materialized in memory.
+/// JSON string materialized in memory. `CountingWriter` sits between the
+/// two so `uncompressed_bytes` is still recovered for free in that same
+/// pass.
 pub fn build_and_compress(
     session_id: &str,
     events: &[ParsedEvent],
     metadata: &SessionMetadata,
     source_path: &str,
     provider: &str,
-) -> anyhow::Result<Vec<u8>> {
+) -> anyhow::Result<EncodeResult> {
     let payload = build_payload(session_id, events, metadata, source_path, provider);
+    let event_count = events.len();
 
-    // Stream serialize directly into gzip compressor
-    let mut gz = GzEncoder::new(Vec::with_capacity(64 * 1024), Compression::fast());
-    serde_json::to_writer(&mut gz, &payload)?;
-    let compressed = gz.finish()?;
+    // Stream serialize directly into gzip compressor, counting the
+    // uncompressed bytes passed through along the way.
+    let gz = GzEncoder::new(Vec::with_capacity(64 * 1024), Compression::fast());
+    let mut counting = CountingWriter::new(gz);
+    serde_json::to_writer(&mut counting, &payload)?;
+    let uncompressed_bytes = counting.count;
+    let compressed = counting.inner.finish()?;
+    let compressed_bytes = compressed.len();
 
-    Ok(compressed)
+    Ok(EncodeResult {
+        payload: compressed,
+        uncompressed_bytes,
+        compressed_bytes,
+        event_count,
+        codec: CompressionAlgo::Gzip,
+    })
+}
+
+/// Async counterpart to `build_and_compress`, for callers already running on
+/// the shipper's Tokio executor. Requires the `async-compression` crate's
+/// `tokio` feature (plus its `gzip`/`zstd` features) so
+/// `GzipEncoder`/`ZstdEncoder` implement `tokio::io::AsyncWrite`.
+///
+/// `serde_json` only serializes into a synchronous `Write`, so unlike
+/// `build_and_compress` this can't stream JSON tokens straight into the
+/// encoder — the payload is serialized to an in-memory buffer first. What it
+/// still buys over serializing and `spawn_blocking`-ing the sync path: the
+/// compress itself runs as a sequence of small `poll_write` steps rather than
+/// one uninterrupted call on a worker thread, so the executor can interleave
+/// another payload's network upload between them.
+///
+/// Deliberately not wired into `shipper::prepare_file` yet: every current
+/// caller is either plain sync code calling straight into
+/// `compressor::build_and_compress_with` (the daemon's single-threaded
+/// watcher loop, `resync::run_resync_pass`) or a `rayon`/`std::thread` worker
+/// with no Tokio executor to hand the `poll_write` steps to at all
+/// (`run_ship_pipeline`'s producer threads in `main.rs`). Swapping
+/// `prepare_file` itself onto this path would mean making it `async` and
+/// reworking every one of those call sites' threading model, not just
+/// swapping a compressor call — this function stays ready for the day the
+/// prepare stage runs on the same executor as the upload.
+pub async fn build_and_compress_async(
+    session_id: &str,
+    events: &[ParsedEvent],
+    metadata: &SessionMetadata,
+    source_path: &str,
+    provider: &str,
+    algo: CompressionAlgo,
+) -> anyhow::Result<EncodeResult> {
+    use tokio::io::AsyncWriteExt;
+
+    let payload = build_payload(session_id, events, metadata, source_path, provider);
+    let event_count = events.len();
+    let uncompressed = serde_json::to_vec(&payload)?;
+    let uncompressed_bytes = uncompressed.len();
+
+    let compressed = match algo {
+        CompressionAlgo::Gzip => {
+            let mut encoder =
+                async_compression::tokio::write::GzipEncoder::new(Vec::with_capacity(64 * 1024));
+            encoder.write_all(&uncompressed).await?;
+            encoder.shutdown().await?;
+            encoder.into_inner()
+        }
+        CompressionAlgo::Zstd => {
+            let mut encoder =
+                async_compression::tokio::write::ZstdEncoder::new(Vec::with_capacity(64 * 1024));
+            encoder.write_all(&uncompressed).await?;
+            encoder.shutdown().await?;
+            encoder.into_inner()
+        }
+        CompressionAlgo::Identity => uncompressed.clone(),
+        other => anyhow::bail!("build_and_compress_async only supports gzip, zstd, or identity, got {other:?}"),
+    };
+    let compressed_bytes = compressed.len();
+
+    Ok(EncodeResult {
+        payload: compressed,
+        uncompressed_bytes,
+        compressed_bytes,
+        event_count,
+        codec: algo,
+    })
+}
+
+/// Per-thread libdeflate state for `gzip_with_libdeflate` — the serialize
+/// buffer and the `Compressor` itself are both expensive enough to allocate
+/// (the compressor builds internal Huffman/hash tables sized to its level)
+/// that reusing them across calls on the same thread is the entire point of
+/// this codec path. Rebuilt only when a call asks for a different `level`
+/// than what's cached.
+struct LibdeflateState {
+    level: u32,
+    buf: Vec<u8>,
+    compressor: libdeflater::Compressor,
+}
+
+thread_local! {
+    static LIBDEFLATE_STATE: RefCell<Option<LibdeflateState>> = const { RefCell::new(None) };
+}
+
+/// Gzip-compress an already-built `IngestPayload` with `libdeflater`'s
+/// hand-tuned deflate, which runs notably faster than `flate2`/miniz at a
+/// comparable ratio. Single-shot rather than streaming: `payload` is
+/// serialized into a reusable thread-local buffer first, then compressed in
+/// one call. Shared by `build_and_compress_libdeflate` (builds its own
+/// payload) and `build_and_compress_with`'s/`build_and_compress_with_seq`'s
+/// `Gzip` arms (reuse the payload they already built), so the shipper's hot
+/// path and the standalone API can't drift onto two different backends.
+///
+/// `level` is libdeflate's 1-12 compression level (1 fastest, 12 best
+/// ratio). Returns `(compressed, uncompressed_bytes)`.
+fn gzip_with_libdeflate(payload: &IngestPayload, level: u32) -> anyhow::Result<(Vec<u8>, usize)> {
+    LIBDEFLATE_STATE.with(|cell| {
+        let mut state = cell.borrow_mut();
+        let stale = !matches!(&*state, Some(s) if s.level == level);
+        if stale {
+            let compressor = libdeflater::Compressor::new(
+                libdeflater::CompressionLvl::new(level as i32)
+                    .map_err(|_| anyhow::anyhow!("invalid libdeflate level: {level}"))?,
+            );
+            *state = Some(LibdeflateState {
+                level,
+                buf: Vec::with_capacity(64 * 1024),
+                compressor,
+            });
+        }
+        let state = state.as_mut().expect("just initialized above if stale");
+
+        state.buf.clear();
+        serde_json::to_writer(&mut state.buf, payload)?;
+        let uncompressed_bytes = state.buf.len();
+
+        let mut compressed = vec![0u8; state.compressor.gzip_compress_bound(uncompressed_bytes)];
+        let compressed_bytes = state
+            .compressor
+            .gzip_compress(&state.buf, &mut compressed)
+            .map_err(|e| anyhow::anyhow!("libdeflate gzip compress failed: {e:?}"))?;
+        compressed.truncate(compressed_bytes);
+
+        Ok((compressed, uncompressed_bytes))
+    })
+}
+
+/// Above this estimated uncompressed size, `Gzip` falls back to
+/// `build_and_compress`'s streaming `GzEncoder` instead of libdeflate's
+/// single-shot path — `gzip_with_libdeflate` holds the whole serialized
+/// payload in memory, which stops being the right tradeoff for a truly huge
+/// session.
+const LIBDEFLATE_MAX_BYTES: usize = 8 * 1024 * 1024;
+
+/// Default libdeflate level for the `Gzip` common-case path — matches
+/// `flate2::Compression::fast()`'s speed-over-ratio intent, since this is
+/// just a faster backend for the same tradeoff, not a ratio upgrade.
+const LIBDEFLATE_GZIP_LEVEL: u32 = 1;
+
+/// Build an IngestPayload and gzip-compress it with `libdeflater`'s
+/// hand-tuned deflate, which runs notably faster than `build_and_compress`'s
+/// `flate2`/miniz backend at a comparable ratio — worth it on the shipper's
+/// hot path, called once per session.
+///
+/// Single-shot rather than streaming: the payload is serialized into a
+/// reusable thread-local buffer first, then compressed in one call. For the
+/// truly-large session where holding the full JSON in memory is
+/// undesirable, `build_and_compress`'s streaming `GzEncoder` path is still
+/// the right choice.
+///
+/// `level` is libdeflate's 1-12 compression level (1 fastest, 12 best
+/// ratio) — exposed so a caller under CPU pressure can trade ratio for
+/// speed the same way `build_and_compress_with`'s `algo` choice does.
+pub fn build_and_compress_libdeflate(
+    session_id: &str,
+    events: &[ParsedEvent],
+    metadata: &SessionMetadata,
+    source_path: &str,
+    provider: &str,
+    level: u32,
+) -> anyhow::Result<EncodeResult> {
+    let payload = build_payload(session_id, events, metadata, source_path, provider);
+    let event_count = events.len();
+
+    let (compressed, uncompressed_bytes) = gzip_with_libdeflate(&payload, level)?;
+    let compressed_bytes = compressed.len();
+
+    Ok(EncodeResult {
+        payload: compressed,
+        uncompressed_bytes,
+        compressed_bytes,
+        event_count,
+        codec: CompressionAlgo::Gzip,
+    })
 }
 
 /// Compress an already-built payload to gzip bytes (for benchmarking).
@@ -173,6 +518,212 @@ pub fn compress_payload(payload: &IngestPayload<'_>) -> anyhow::Result<Vec<u8>>
     Ok(compressed)
 }
 
+/// Wire compression algorithm for a shipped payload.
+///
+/// `Zstd` is a better fit than gzip for the many small session payloads the
+/// shipper sends (low per-payload overhead, optional shared dictionary via
+/// `build_and_compress_with_dictionary`). `Brotli` trades slower encoding
+/// for a better ratio than either, worth it when `pipeline::adaptive_compression`
+/// has the CPU budget and the server advertises `br` support. `Lz4` sits at
+/// the other end — worse ratio than gzip but an order of magnitude cheaper
+/// to encode, the right call when the ingest endpoint itself is CPU-bound
+/// rather than the link bandwidth-constrained. `Identity` skips compression
+/// entirely — the right call for payloads small enough (e.g. a
+/// heartbeat-sized batch) that an encoder's framing overhead would erase or
+/// reverse the savings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionAlgo {
+    Gzip,
+    Zstd,
+    Brotli,
+    Lz4,
+    Identity,
+}
+
+/// `Content-Encoding` header value for the given algorithm.
+pub fn content_encoding(algo: CompressionAlgo) -> &'static str {
+    match algo {
+        CompressionAlgo::Gzip => "gzip",
+        CompressionAlgo::Zstd => "zstd",
+        CompressionAlgo::Brotli => "br",
+        CompressionAlgo::Lz4 => "lz4",
+        CompressionAlgo::Identity => "identity",
+    }
+}
+
+/// Default zstd compression level — fast enough for the shipper's hot path
+/// while still beating gzip's ratio on JSON payloads.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Brotli quality (0-11) — middling rather than max, since brotli's own
+/// encode time grows steeply above this for the ratio gain it buys on the
+/// shipper's mostly-text JSON payloads.
+const BROTLI_QUALITY: u32 = 6;
+
+/// Brotli window size in bits (10-24) — the default `lgwin`, large enough
+/// to catch cross-event repetition in a session's worth of tool output.
+const BROTLI_LGWIN: u32 = 22;
+
+/// Internal buffer size brotli's writer uses between calls into the
+/// underlying `Vec` — unrelated to `lgwin`, just an I/O chunk size.
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// Build an IngestPayload and stream-compress it with the requested
+/// algorithm.
+///
+/// `Gzip` is the shipper's hot-path codec (`shipper::prepare_file` calls
+/// this once per session), so it doesn't stream into `GzEncoder` like the
+/// other algorithms here: below `LIBDEFLATE_MAX_BYTES` it compresses via
+/// `gzip_with_libdeflate` instead, which runs notably faster than
+/// `flate2`/miniz at a comparable ratio (see `build_and_compress_libdeflate`).
+/// A session large enough to cross that threshold falls back to the
+/// streaming `GzEncoder` path so the full JSON is never held in memory twice.
+pub fn build_and_compress_with(
+    session_id: &str,
+    events: &[ParsedEvent],
+    metadata: &SessionMetadata,
+    source_path: &str,
+    provider: &str,
+    algo: CompressionAlgo,
+) -> anyhow::Result<Vec<u8>> {
+    let payload = build_payload(session_id, events, metadata, source_path, provider);
+
+    match algo {
+        CompressionAlgo::Gzip => {
+            let estimated_bytes: usize = events
+                .iter()
+                .map(|e| event_ingest_size(e, source_path))
+                .sum();
+            if estimated_bytes <= LIBDEFLATE_MAX_BYTES {
+                let (compressed, _) = gzip_with_libdeflate(&payload, LIBDEFLATE_GZIP_LEVEL)?;
+                Ok(compressed)
+            } else {
+                let mut gz = GzEncoder::new(Vec::with_capacity(64 * 1024), Compression::fast());
+                serde_json::to_writer(&mut gz, &payload)?;
+                Ok(gz.finish()?)
+            }
+        }
+        CompressionAlgo::Zstd => {
+            let mut encoder = zstd::stream::write::Encoder::new(
+                Vec::with_capacity(64 * 1024),
+                ZSTD_LEVEL,
+            )?;
+            serde_json::to_writer(&mut encoder, &payload)?;
+            Ok(encoder.finish()?)
+        }
+        CompressionAlgo::Brotli => {
+            let mut writer = brotli::CompressorWriter::new(
+                Vec::with_capacity(64 * 1024),
+                BROTLI_BUFFER_SIZE,
+                BROTLI_QUALITY,
+                BROTLI_LGWIN,
+            );
+            serde_json::to_writer(&mut writer, &payload)?;
+            writer.flush()?;
+            Ok(writer.into_inner())
+        }
+        CompressionAlgo::Lz4 => {
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::with_capacity(64 * 1024));
+            serde_json::to_writer(&mut encoder, &payload)?;
+            Ok(encoder.finish()?)
+        }
+        CompressionAlgo::Identity => {
+            let mut buf = Vec::with_capacity(64 * 1024);
+            serde_json::to_writer(&mut buf, &payload)?;
+            Ok(buf)
+        }
+    }
+}
+
+/// Build an IngestPayload and stream-compress it, stamping `spool_seq` so the
+/// server can dedupe a re-ship of this exact spool entry. Used only by
+/// `shipper::replay_spool_batch` — entries shipped directly off the watcher
+/// don't go through the spool and have no sequence number to stamp.
+pub fn build_and_compress_with_seq(
+    session_id: &str,
+    events: &[ParsedEvent],
+    metadata: &SessionMetadata,
+    source_path: &str,
+    provider: &str,
+    algo: CompressionAlgo,
+    spool_seq: i64,
+) -> anyhow::Result<Vec<u8>> {
+    let mut payload = build_payload(session_id, events, metadata, source_path, provider);
+    payload.spool_seq = Some(spool_seq);
+
+    match algo {
+        CompressionAlgo::Gzip => {
+            let estimated_bytes: usize = events
+                .iter()
+                .map(|e| event_ingest_size(e, source_path))
+                .sum();
+            if estimated_bytes <= LIBDEFLATE_MAX_BYTES {
+                let (compressed, _) = gzip_with_libdeflate(&payload, LIBDEFLATE_GZIP_LEVEL)?;
+                Ok(compressed)
+            } else {
+                let mut gz = GzEncoder::new(Vec::with_capacity(64 * 1024), Compression::fast());
+                serde_json::to_writer(&mut gz, &payload)?;
+                Ok(gz.finish()?)
+            }
+        }
+        CompressionAlgo::Zstd => {
+            let mut encoder = zstd::stream::write::Encoder::new(
+                Vec::with_capacity(64 * 1024),
+                ZSTD_LEVEL,
+            )?;
+            serde_json::to_writer(&mut encoder, &payload)?;
+            Ok(encoder.finish()?)
+        }
+        CompressionAlgo::Brotli => {
+            let mut writer = brotli::CompressorWriter::new(
+                Vec::with_capacity(64 * 1024),
+                BROTLI_BUFFER_SIZE,
+                BROTLI_QUALITY,
+                BROTLI_LGWIN,
+            );
+            serde_json::to_writer(&mut writer, &payload)?;
+            writer.flush()?;
+            Ok(writer.into_inner())
+        }
+        CompressionAlgo::Lz4 => {
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::with_capacity(64 * 1024));
+            serde_json::to_writer(&mut encoder, &payload)?;
+            Ok(encoder.finish()?)
+        }
+        CompressionAlgo::Identity => {
+            let mut buf = Vec::with_capacity(64 * 1024);
+            serde_json::to_writer(&mut buf, &payload)?;
+            Ok(buf)
+        }
+    }
+}
+
+/// Build an IngestPayload, zstd-compress it against a trained dictionary, and
+/// stamp `dictionary_id` so the server knows which dictionary to decode with.
+///
+/// Only worth using for small payloads (few events) where a shared
+/// dictionary recovers the cross-payload redundancy a standalone zstd stream
+/// can't see — see `pipeline::dictionary`.
+pub fn build_and_compress_with_dictionary(
+    session_id: &str,
+    events: &[ParsedEvent],
+    metadata: &SessionMetadata,
+    source_path: &str,
+    provider: &str,
+    dictionary: &super::dictionary::Dictionary,
+) -> anyhow::Result<Vec<u8>> {
+    let mut payload = build_payload(session_id, events, metadata, source_path, provider);
+    payload.dictionary_id = Some(dictionary.id);
+
+    let mut encoder = zstd::stream::write::Encoder::with_dictionary(
+        Vec::with_capacity(64 * 1024),
+        ZSTD_LEVEL,
+        &dictionary.bytes,
+    )?;
+    serde_json::to_writer(&mut encoder, &payload)?;
+    Ok(encoder.finish()?)
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -196,6 +747,7 @@ mod tests {
                 tool_name: None,
                 tool_input_json: None,
                 tool_output_text: None,
+                tool_call_id: None,
                 source_offset: 0,
                 raw_type: "user".to_string(),
                 raw_line: Some(r#"{"type":"user","message":{"content":"Hello world"}}"#.to_string()),
@@ -209,6 +761,7 @@ mod tests {
                 tool_name: None,
                 tool_input_json: None,
                 tool_output_text: None,
+                tool_call_id: None,
                 source_offset: 100,
                 raw_type: "assistant".to_string(),
                 raw_line: None,
@@ -237,6 +790,73 @@ mod tests {
         assert!(payload.events[1].raw_json.is_none());
     }
 
+    #[test]
+    fn test_build_batches_empty_events_yields_one_header_only_batch() {
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let batches = build_batches("test-id", &[], &meta, "/path/to/file", "claude", 1024);
+        assert_eq!(batches.len(), 1);
+        assert!(batches[0].events.is_empty());
+    }
+
+    #[test]
+    fn test_build_batches_under_budget_is_one_batch() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let batches = build_batches("test-id", &events, &meta, "/path/to/file", "claude", 1024 * 1024);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].events.len(), 2);
+    }
+
+    #[test]
+    fn test_build_batches_splits_on_budget_and_shares_header() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            cwd: Some("/home/user/proj".to_string()),
+            project: Some("proj".to_string()),
+            ..Default::default()
+        };
+
+        // Budget smaller than either event's own serialized size forces
+        // each event into its own batch.
+        let batches = build_batches("test-id", &events, &meta, "/path/to/file", "claude", 1);
+        assert_eq!(batches.len(), 2);
+        for batch in &batches {
+            assert_eq!(batch.id, "test-id");
+            assert_eq!(batch.provider, "claude");
+            assert_eq!(batch.provider_session_id, "s1");
+            assert_eq!(batch.events.len(), 1);
+        }
+        assert_eq!(batches[0].events[0].role, "user");
+        assert_eq!(batches[0].events[0].source_offset, events[0].source_offset);
+        assert_eq!(batches[1].events[0].role, "assistant");
+        assert_eq!(batches[1].events[0].source_offset, events[1].source_offset);
+    }
+
+    #[test]
+    fn test_build_batches_never_drops_an_oversized_single_event() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        // A zero-byte budget still can't produce an empty batch — the first
+        // event ends up alone rather than being dropped.
+        let batches = build_batches("test-id", &events, &meta, "/path/to/file", "claude", 0);
+        let total_events: usize = batches.iter().map(|b| b.events.len()).sum();
+        assert_eq!(total_events, 2);
+        assert!(batches.iter().all(|b| !b.events.is_empty()));
+    }
+
     #[test]
     fn test_streaming_compress_roundtrip() {
         let events = make_test_events();
@@ -247,11 +867,39 @@ mod tests {
             ..Default::default()
         };
 
-        let compressed =
+        let encoded =
             build_and_compress("test-id", &events, &meta, "/path/to/file", "claude").unwrap();
 
         // Decompress and verify valid JSON
-        let mut decoder = GzDecoder::new(&compressed[..]);
+        let mut decoder = GzDecoder::new(&encoded.payload[..]);
+        let mut json_str = String::new();
+        decoder.read_to_string(&mut json_str).unwrap();
+
+        let parsed: serde_json::Value = serde_json::from_str(&json_str).unwrap();
+        assert_eq!(parsed["id"], "test-id");
+        assert_eq!(parsed["provider"], "claude");
+        assert_eq!(parsed["events"].as_array().unwrap().len(), 2);
+        assert_eq!(encoded.event_count, 2);
+        assert_eq!(encoded.compressed_bytes, encoded.payload.len());
+        assert_eq!(encoded.codec, CompressionAlgo::Gzip);
+        assert_eq!(encoded.uncompressed_bytes, json_str.len());
+    }
+
+    #[test]
+    fn test_build_and_compress_libdeflate_roundtrips() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            cwd: Some("/proj".to_string()),
+            project: Some("proj".to_string()),
+            ..Default::default()
+        };
+
+        let encoded =
+            build_and_compress_libdeflate("test-id", &events, &meta, "/path/to/file", "claude", 6)
+                .unwrap();
+
+        let mut decoder = GzDecoder::new(&encoded.payload[..]);
         let mut json_str = String::new();
         decoder.read_to_string(&mut json_str).unwrap();
 
@@ -259,6 +907,165 @@ mod tests {
         assert_eq!(parsed["id"], "test-id");
         assert_eq!(parsed["provider"], "claude");
         assert_eq!(parsed["events"].as_array().unwrap().len(), 2);
+        assert_eq!(encoded.event_count, 2);
+        assert_eq!(encoded.compressed_bytes, encoded.payload.len());
+        assert_eq!(encoded.codec, CompressionAlgo::Gzip);
+        assert_eq!(encoded.uncompressed_bytes, json_str.len());
+    }
+
+    #[test]
+    fn test_build_and_compress_libdeflate_reuses_state_across_same_level_calls() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        // Two calls at the same level reuse the cached compressor/buffer;
+        // a third at a different level forces a rebuild. None of this
+        // should affect the output.
+        for level in [6, 6, 9] {
+            let encoded = build_and_compress_libdeflate(
+                "test-id",
+                &events,
+                &meta,
+                "/path/to/file",
+                "claude",
+                level,
+            )
+            .unwrap();
+            assert_eq!(encoded.event_count, 2);
+        }
+    }
+
+    #[test]
+    fn test_build_and_compress_libdeflate_rejects_invalid_level() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let result =
+            build_and_compress_libdeflate("test-id", &events, &meta, "/path/to/file", "claude", 99);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_libdeflate_compression_ratio() {
+        let mut events = Vec::new();
+        for i in 0..100 {
+            events.push(ParsedEvent {
+                uuid: format!("e{}", i),
+                session_id: "s1".to_string(),
+                timestamp: Utc::now(),
+                role: Role::Assistant,
+                content_text: Some(format!(
+                    "This is response number {} with some repeated text to help compression.",
+                    i
+                )),
+                tool_name: None,
+                tool_input_json: None,
+                tool_output_text: None,
+                tool_call_id: None,
+                source_offset: i * 100,
+                raw_type: "assistant".to_string(),
+                raw_line: if i == 0 { Some("raw".to_string()) } else { None },
+            });
+        }
+
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let encoded =
+            build_and_compress_libdeflate("test-id", &events, &meta, "/path", "claude", 6).unwrap();
+
+        let ratio = encoded.uncompressed_bytes as f64 / encoded.compressed_bytes as f64;
+        assert!(
+            ratio > 2.0,
+            "Expected compression ratio > 2x, got {:.1}x ({} → {} bytes)",
+            ratio,
+            encoded.uncompressed_bytes,
+            encoded.compressed_bytes
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_build_and_compress_async_gzip_roundtrips() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let encoded = build_and_compress_async(
+            "test-id",
+            &events,
+            &meta,
+            "/path/to/file",
+            "claude",
+            CompressionAlgo::Gzip,
+        )
+        .await
+        .unwrap();
+
+        let mut decoder = GzDecoder::new(&encoded.payload[..]);
+        let mut json_str = String::new();
+        decoder.read_to_string(&mut json_str).unwrap();
+
+        let parsed: serde_json::Value = serde_json::from_str(&json_str).unwrap();
+        assert_eq!(parsed["id"], "test-id");
+        assert_eq!(parsed["events"].as_array().unwrap().len(), 2);
+        assert_eq!(encoded.event_count, 2);
+        assert_eq!(encoded.codec, CompressionAlgo::Gzip);
+        assert_eq!(encoded.uncompressed_bytes, json_str.len());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_build_and_compress_async_zstd_roundtrips() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let encoded = build_and_compress_async(
+            "test-id",
+            &events,
+            &meta,
+            "/path/to/file",
+            "claude",
+            CompressionAlgo::Zstd,
+        )
+        .await
+        .unwrap();
+
+        let decompressed = zstd::stream::decode_all(&encoded.payload[..]).unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&decompressed).unwrap();
+        assert_eq!(parsed["id"], "test-id");
+        assert_eq!(encoded.codec, CompressionAlgo::Zstd);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_build_and_compress_async_rejects_unsupported_algo() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let result = build_and_compress_async(
+            "test-id",
+            &events,
+            &meta,
+            "/path/to/file",
+            "claude",
+            CompressionAlgo::Brotli,
+        )
+        .await;
+        assert!(result.is_err());
     }
 
     #[test]
@@ -275,6 +1082,7 @@ mod tests {
                 tool_name: None,
                 tool_input_json: None,
                 tool_output_text: None,
+                tool_call_id: None,
                 source_offset: i * 100,
                 raw_type: "assistant".to_string(),
                 raw_line: if i == 0 { Some("raw".to_string()) } else { None },
@@ -286,18 +1094,221 @@ mod tests {
             ..Default::default()
         };
 
-        let compressed =
+        let encoded =
             build_and_compress("test-id", &events, &meta, "/path", "claude").unwrap();
-        let uncompressed = serde_json::to_vec(&build_payload("test-id", &events, &meta, "/path", "claude")).unwrap();
 
         // Compressed should be significantly smaller
-        let ratio = uncompressed.len() as f64 / compressed.len() as f64;
+        let ratio = encoded.uncompressed_bytes as f64 / encoded.compressed_bytes as f64;
         assert!(
             ratio > 2.0,
             "Expected compression ratio > 2x, got {:.1}x ({} → {} bytes)",
             ratio,
-            uncompressed.len(),
-            compressed.len()
+            encoded.uncompressed_bytes,
+            encoded.compressed_bytes
         );
     }
+
+    #[test]
+    fn test_content_encoding_names() {
+        assert_eq!(content_encoding(CompressionAlgo::Gzip), "gzip");
+        assert_eq!(content_encoding(CompressionAlgo::Zstd), "zstd");
+        assert_eq!(content_encoding(CompressionAlgo::Brotli), "br");
+        assert_eq!(content_encoding(CompressionAlgo::Lz4), "lz4");
+        assert_eq!(content_encoding(CompressionAlgo::Identity), "identity");
+    }
+
+    #[test]
+    fn test_build_and_compress_with_zstd_roundtrips() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let compressed = build_and_compress_with(
+            "test-id",
+            &events,
+            &meta,
+            "/path",
+            "claude",
+            CompressionAlgo::Zstd,
+        )
+        .unwrap();
+
+        let decompressed = zstd::stream::decode_all(&compressed[..]).unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&decompressed).unwrap();
+        assert_eq!(parsed["id"], "test-id");
+        assert_eq!(parsed["events"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_build_and_compress_with_gzip_matches_build_and_compress() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let a = build_and_compress("test-id", &events, &meta, "/path", "claude").unwrap();
+        let b = build_and_compress_with(
+            "test-id",
+            &events,
+            &meta,
+            "/path",
+            "claude",
+            CompressionAlgo::Gzip,
+        )
+        .unwrap();
+
+        let mut a_decoder = GzDecoder::new(&a.payload[..]);
+        let mut a_json = String::new();
+        a_decoder.read_to_string(&mut a_json).unwrap();
+
+        let mut b_decoder = GzDecoder::new(&b[..]);
+        let mut b_json = String::new();
+        b_decoder.read_to_string(&mut b_json).unwrap();
+
+        assert_eq!(a_json, b_json);
+    }
+
+    #[test]
+    fn test_build_and_compress_with_gzip_falls_back_to_streaming_above_libdeflate_threshold() {
+        // A session over LIBDEFLATE_MAX_BYTES must still round-trip via the
+        // streaming GzEncoder fallback rather than the libdeflate path.
+        let big_text = "x".repeat(9 * 1024 * 1024);
+        let events = vec![ParsedEvent {
+            uuid: "e1".to_string(),
+            session_id: "s1".to_string(),
+            timestamp: Utc::now(),
+            role: Role::Assistant,
+            content_text: Some(big_text.clone()),
+            tool_name: None,
+            tool_input_json: None,
+            tool_output_text: None,
+            tool_call_id: None,
+            source_offset: 0,
+            raw_type: "assistant".to_string(),
+            raw_line: None,
+        }];
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let compressed = build_and_compress_with(
+            "test-id",
+            &events,
+            &meta,
+            "/path",
+            "claude",
+            CompressionAlgo::Gzip,
+        )
+        .unwrap();
+
+        let mut decoder = GzDecoder::new(&compressed[..]);
+        let mut json_str = String::new();
+        decoder.read_to_string(&mut json_str).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json_str).unwrap();
+        assert_eq!(parsed["events"][0]["content_text"], big_text);
+    }
+
+    #[test]
+    fn test_build_and_compress_with_brotli_roundtrips() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let compressed = build_and_compress_with(
+            "test-id",
+            &events,
+            &meta,
+            "/path",
+            "claude",
+            CompressionAlgo::Brotli,
+        )
+        .unwrap();
+
+        let mut decompressed = Vec::new();
+        brotli::BrotliDecompress(&mut &compressed[..], &mut decompressed).unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&decompressed).unwrap();
+        assert_eq!(parsed["id"], "test-id");
+        assert_eq!(parsed["events"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_build_and_compress_with_lz4_roundtrips() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let compressed = build_and_compress_with(
+            "test-id",
+            &events,
+            &meta,
+            "/path",
+            "claude",
+            CompressionAlgo::Lz4,
+        )
+        .unwrap();
+
+        let mut decoder = lz4_flex::frame::FrameDecoder::new(&compressed[..]);
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&decompressed).unwrap();
+        assert_eq!(parsed["id"], "test-id");
+        assert_eq!(parsed["events"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_build_and_compress_with_identity_is_plain_json() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let built = build_and_compress_with(
+            "test-id",
+            &events,
+            &meta,
+            "/path",
+            "claude",
+            CompressionAlgo::Identity,
+        )
+        .unwrap();
+
+        let parsed: serde_json::Value = serde_json::from_slice(&built).unwrap();
+        assert_eq!(parsed["id"], "test-id");
+        assert_eq!(parsed["events"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_build_and_compress_with_seq_stamps_spool_seq() {
+        let events = make_test_events();
+        let meta = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+
+        let compressed = build_and_compress_with_seq(
+            "test-id",
+            &events,
+            &meta,
+            "/path",
+            "claude",
+            CompressionAlgo::Gzip,
+            42,
+        )
+        .unwrap();
+
+        let mut decoder = GzDecoder::new(&compressed[..]);
+        let mut json_str = String::new();
+        decoder.read_to_string(&mut json_str).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json_str).unwrap();
+        assert_eq!(parsed["spool_seq"], 42);
+    }
 }
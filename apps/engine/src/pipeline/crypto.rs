@@ -0,0 +1,149 @@
+//! Sealed-box encryption of compressed payloads before POST.
+//!
+//! Sits between `compressor::build_and_compress[_with]` and the HTTP
+//! client: each payload is encrypted to a recipient's long-term X25519
+//! public key using a fresh ephemeral keypair per payload (an
+//! Noise/libsodium-style "sealed box"), so a relay that only forwards
+//! ciphertext can never read transcript contents, and no shared secret
+//! ever needs to leave the recipient's machine.
+//!
+//! Wire format (all payloads this module produces):
+//! `ephemeral_pubkey (32 bytes) || nonce (24 bytes) || ciphertext`.
+
+use anyhow::{bail, Context, Result};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+const PUBKEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+
+/// A recipient's long-term X25519 public key, as configured via
+/// `--recipient-key` / `LONGHOUSE_RECIPIENT_KEY`.
+#[derive(Clone, Copy)]
+pub struct RecipientKey(pub [u8; PUBKEY_LEN]);
+
+impl RecipientKey {
+    /// Parse a hex-encoded 32-byte X25519 public key.
+    pub fn from_hex(s: &str) -> Result<Self> {
+        let bytes = hex::decode(s.trim()).context("recipient key is not valid hex")?;
+        if bytes.len() != PUBKEY_LEN {
+            bail!(
+                "recipient key must be {} bytes (got {})",
+                PUBKEY_LEN,
+                bytes.len()
+            );
+        }
+        let mut key = [0u8; PUBKEY_LEN];
+        key.copy_from_slice(&bytes);
+        Ok(Self(key))
+    }
+}
+
+/// Generate a fresh X25519 keypair for `keygen`. Returns (secret_hex, public_hex).
+///
+/// The secret half must be kept off the shipping host — only the public
+/// half is ever given to `ShipperConfig::recipient_key`.
+pub fn generate_keypair() -> (String, String) {
+    let secret = StaticSecret::random_from_rng(rand::thread_rng());
+    let public = PublicKey::from(&secret);
+    (hex::encode(secret.to_bytes()), hex::encode(public.as_bytes()))
+}
+
+/// Seal `plaintext` to `recipient`. Each call uses a fresh ephemeral
+/// keypair and nonce, so sealing the same plaintext twice yields unlinkable
+/// ciphertexts.
+pub fn seal(plaintext: &[u8], recipient: &RecipientKey) -> Result<Vec<u8>> {
+    let ephemeral_secret = StaticSecret::random_from_rng(rand::thread_rng());
+    let ephemeral_public = PublicKey::from(&ephemeral_secret);
+
+    let shared_secret = ephemeral_secret.diffie_hellman(&PublicKey::from(recipient.0));
+    let key = blake3::hash(shared_secret.as_bytes());
+
+    let cipher = XChaCha20Poly1305::new(key.as_bytes().into());
+
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce_bytes);
+    let nonce = XNonce::from_slice(&nonce_bytes);
+
+    let ciphertext = cipher
+        .encrypt(nonce, plaintext)
+        .map_err(|e| anyhow::anyhow!("sealing payload: {e}"))?;
+
+    let mut envelope = Vec::with_capacity(PUBKEY_LEN + NONCE_LEN + ciphertext.len());
+    envelope.extend_from_slice(ephemeral_public.as_bytes());
+    envelope.extend_from_slice(&nonce_bytes);
+    envelope.extend_from_slice(&ciphertext);
+    Ok(envelope)
+}
+
+/// Open an envelope produced by `seal`, given the recipient's secret key.
+/// Not used by the shipper itself (the secret stays server-side) but kept
+/// alongside `seal` so the format has one source of truth, and for tests.
+#[cfg(test)]
+fn open(envelope: &[u8], recipient_secret: &StaticSecret) -> Result<Vec<u8>> {
+    if envelope.len() < PUBKEY_LEN + NONCE_LEN {
+        bail!("envelope too short to contain a pubkey and nonce");
+    }
+    let ephemeral_public = PublicKey::from(<[u8; PUBKEY_LEN]>::try_from(&envelope[..PUBKEY_LEN])?);
+    let nonce_bytes = &envelope[PUBKEY_LEN..PUBKEY_LEN + NONCE_LEN];
+    let ciphertext = &envelope[PUBKEY_LEN + NONCE_LEN..];
+
+    let shared_secret = recipient_secret.diffie_hellman(&ephemeral_public);
+    let key = blake3::hash(shared_secret.as_bytes());
+    let cipher = XChaCha20Poly1305::new(key.as_bytes().into());
+    let nonce = XNonce::from_slice(nonce_bytes);
+
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|e| anyhow::anyhow!("opening sealed payload: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open_round_trip() {
+        let secret = StaticSecret::random_from_rng(rand::thread_rng());
+        let public = PublicKey::from(&secret);
+        let recipient = RecipientKey(*public.as_bytes());
+
+        let plaintext = b"hello from the shipper";
+        let envelope = seal(plaintext, &recipient).unwrap();
+        let opened = open(&envelope, &secret).unwrap();
+        assert_eq!(opened, plaintext);
+    }
+
+    #[test]
+    fn test_seal_is_not_deterministic() {
+        let secret = StaticSecret::random_from_rng(rand::thread_rng());
+        let public = PublicKey::from(&secret);
+        let recipient = RecipientKey(*public.as_bytes());
+
+        let plaintext = b"same content twice";
+        let a = seal(plaintext, &recipient).unwrap();
+        let b = seal(plaintext, &recipient).unwrap();
+        assert_ne!(a, b, "ephemeral keypair + nonce must differ per call");
+    }
+
+    #[test]
+    fn test_from_hex_rejects_wrong_length() {
+        assert!(RecipientKey::from_hex("abcd").is_err());
+    }
+
+    #[test]
+    fn test_generate_keypair_round_trips() {
+        let (secret_hex, public_hex) = generate_keypair();
+        let secret_bytes = hex::decode(secret_hex).unwrap();
+        assert_eq!(secret_bytes.len(), PUBKEY_LEN);
+        let recipient = RecipientKey::from_hex(&public_hex).unwrap();
+
+        let envelope = seal(b"keygen round trip", &recipient).unwrap();
+        let mut secret_arr = [0u8; PUBKEY_LEN];
+        secret_arr.copy_from_slice(&secret_bytes);
+        let opened = open(&envelope, &StaticSecret::from(secret_arr)).unwrap();
+        assert_eq!(opened, b"keygen round trip");
+    }
+}
@@ -0,0 +1,107 @@
+//! Shared zstd dictionary training for small session payloads.
+//!
+//! A single session is too small for zstd to find much redundancy in, but
+//! the *shape* of payloads (repeated JSON keys, tool names, common phrases)
+//! is highly redundant across sessions. Training a dictionary on a sample of
+//! past sessions and compressing against it recovers that cross-payload
+//! redundancy — see `compressor::build_and_compress_with_dictionary`.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// A trained zstd dictionary plus the stable id the server uses to pick the
+/// matching dictionary back out when decompressing.
+pub struct Dictionary {
+    pub id: u32,
+    pub bytes: Vec<u8>,
+}
+
+impl Dictionary {
+    fn new(bytes: Vec<u8>) -> Self {
+        let id = dictionary_id(&bytes);
+        Dictionary { id, bytes }
+    }
+}
+
+/// Train a dictionary from a set of sample payloads.
+///
+/// `max_size` bounds the trained dictionary in bytes; zstd recommends around
+/// 100x the expected sample size, capped well below the payloads themselves.
+pub fn train(samples: &[Vec<u8>], max_size: usize) -> Result<Dictionary> {
+    anyhow::ensure!(!samples.is_empty(), "cannot train a dictionary from zero samples");
+    let bytes = zstd::dict::from_samples(samples, max_size)
+        .context("zstd dictionary training failed")?;
+    Ok(Dictionary::new(bytes))
+}
+
+/// Persist a trained dictionary to disk.
+pub fn save(dict: &Dictionary, path: &Path) -> Result<()> {
+    std::fs::write(path, &dict.bytes)
+        .with_context(|| format!("writing dictionary to {}", path.display()))?;
+    Ok(())
+}
+
+/// Load a previously trained dictionary from disk.
+pub fn load(path: &Path) -> Result<Dictionary> {
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("reading dictionary from {}", path.display()))?;
+    Ok(Dictionary::new(bytes))
+}
+
+/// Derive a stable id for a dictionary from its contents, so the server can
+/// be handed the same id every time this exact dictionary is loaded.
+fn dictionary_id(bytes: &[u8]) -> u32 {
+    let hash = blake3::hash(bytes);
+    u32::from_le_bytes(hash.as_bytes()[0..4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_payloads() -> Vec<Vec<u8>> {
+        (0..50)
+            .map(|i| {
+                format!(
+                    r#"{{"role":"assistant","content_text":"This is response number {} with repeated structure.","tool_name":null}}"#,
+                    i
+                )
+                .into_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_train_produces_nonempty_dictionary() {
+        let dict = train(&sample_payloads(), 8 * 1024).unwrap();
+        assert!(!dict.bytes.is_empty());
+    }
+
+    #[test]
+    fn test_dictionary_id_is_stable() {
+        let dict_a = train(&sample_payloads(), 8 * 1024).unwrap();
+        let dict_b = Dictionary::new(dict_a.bytes.clone());
+        assert_eq!(dict_a.id, dict_b.id);
+    }
+
+    #[test]
+    fn test_train_rejects_empty_samples() {
+        assert!(train(&[], 8 * 1024).is_err());
+    }
+
+    #[test]
+    fn test_save_load_round_trips() {
+        let dict = train(&sample_payloads(), 8 * 1024).unwrap();
+        let dir = std::env::temp_dir().join(format!("longhouse-dict-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("session.dict");
+
+        save(&dict, &path).unwrap();
+        let loaded = load(&path).unwrap();
+
+        assert_eq!(loaded.bytes, dict.bytes);
+        assert_eq!(loaded.id, dict.id);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
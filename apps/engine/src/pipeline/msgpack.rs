@@ -0,0 +1,242 @@
+//! Length-prefixed MessagePack encoding for [`ParsedEvent`] streams.
+//!
+//! `ParsedEvent` only derives `Serialize` for JSON today, which is fine for
+//! the ingest payload but costlier than it needs to be for shipping the
+//! normalized event stream itself over the wire (re-emitting JSONL, or a
+//! JSON array, re-stringifies every field on every hop). This gives
+//! shippers a compact binary alternative behind the same event model,
+//! analogous to the text/msgpack codec pair some log crates expose.
+//!
+//! Each record is written as a little-endian `u32` byte length followed by
+//! that many bytes of msgpack — never msgpack's own array/map framing for
+//! the whole stream — so a reader can resume mid-stream from a byte offset
+//! the same way [`super::parser::ParseResult::last_good_offset`] lets JSONL
+//! parsing resume, and a writer can append further records without
+//! rewriting anything already flushed.
+//!
+//! `tool_input_json` is a `RawValue` holding raw JSON text; rather than
+//! writing that text as a bare msgpack string (which would mean every
+//! consumer does a second JSON parse to get structure back out), it's
+//! decoded once into a generic [`serde_json::Value`] and msgpack-encodes
+//! that natively as nested maps/arrays/scalars.
+
+use std::io::{Read, Write};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::parser::{ParsedEvent, Role};
+
+/// On-the-wire mirror of [`ParsedEvent`] with `tool_input_json` decoded to a
+/// generic `Value` instead of carried as raw JSON text, so `rmp_serde` can
+/// encode it as a real msgpack sub-value.
+#[derive(Serialize, Deserialize)]
+struct WireEvent {
+    uuid: String,
+    session_id: String,
+    timestamp: DateTime<Utc>,
+    role: Role,
+    content_text: Option<String>,
+    tool_name: Option<String>,
+    tool_input_json: Option<Value>,
+    tool_output_text: Option<String>,
+    tool_call_id: Option<String>,
+    source_offset: u64,
+    raw_type: String,
+    raw_line: Option<String>,
+}
+
+fn to_wire(event: &ParsedEvent) -> Result<WireEvent> {
+    let tool_input_json = match &event.tool_input_json {
+        Some(raw) => Some(
+            serde_json::from_str(raw.get())
+                .context("tool_input_json was not valid JSON while encoding to msgpack")?,
+        ),
+        None => None,
+    };
+
+    Ok(WireEvent {
+        uuid: event.uuid.clone(),
+        session_id: event.session_id.clone(),
+        timestamp: event.timestamp,
+        role: event.role.clone(),
+        content_text: event.content_text.clone(),
+        tool_name: event.tool_name.clone(),
+        tool_input_json,
+        tool_output_text: event.tool_output_text.clone(),
+        tool_call_id: event.tool_call_id.clone(),
+        source_offset: event.source_offset,
+        raw_type: event.raw_type.clone(),
+        raw_line: event.raw_line.clone(),
+    })
+}
+
+fn from_wire(wire: WireEvent) -> Result<ParsedEvent> {
+    let tool_input_json = match wire.tool_input_json {
+        Some(value) => Some(
+            serde_json::value::to_raw_value(&value)
+                .context("failed to re-encode tool_input_json while decoding msgpack")?,
+        ),
+        None => None,
+    };
+
+    Ok(ParsedEvent {
+        uuid: wire.uuid,
+        session_id: wire.session_id,
+        timestamp: wire.timestamp,
+        role: wire.role,
+        content_text: wire.content_text,
+        tool_name: wire.tool_name,
+        tool_input_json,
+        tool_output_text: wire.tool_output_text,
+        tool_call_id: wire.tool_call_id,
+        source_offset: wire.source_offset,
+        raw_type: wire.raw_type,
+        raw_line: wire.raw_line,
+    })
+}
+
+/// Write `events` as a stream of length-prefixed msgpack records.
+pub fn write_events_msgpack<W: Write>(events: &[ParsedEvent], w: &mut W) -> Result<()> {
+    for event in events {
+        let wire = to_wire(event)?;
+        let bytes = rmp_serde::to_vec(&wire).context("failed to encode event as msgpack")?;
+        w.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        w.write_all(&bytes)?;
+    }
+    Ok(())
+}
+
+/// Result of [`read_events_msgpack`]: the decoded events plus the byte
+/// offset just past the last complete record, mirroring
+/// `ParseResult::last_good_offset` — a truncated trailing record (e.g. a
+/// write still in flight) is left unconsumed rather than erroring, so a
+/// caller can resume from `last_good_offset` once more bytes arrive.
+pub struct MsgpackEvents {
+    pub events: Vec<ParsedEvent>,
+    pub last_good_offset: u64,
+}
+
+/// Read a stream of length-prefixed msgpack records written by
+/// [`write_events_msgpack`], stopping at EOF or at the first incomplete
+/// trailing record.
+pub fn read_events_msgpack<R: Read>(r: &mut R) -> Result<MsgpackEvents> {
+    let mut events = Vec::new();
+    let mut offset = 0u64;
+
+    loop {
+        let mut len_buf = [0u8; 4];
+        if let Err(e) = r.read_exact(&mut len_buf) {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                break;
+            }
+            return Err(e).context("failed to read msgpack record length prefix");
+        }
+        let len = u32::from_le_bytes(len_buf) as usize;
+
+        let mut body = vec![0u8; len];
+        if let Err(e) = r.read_exact(&mut body) {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                break; // Partial trailing record — leave it for the next read.
+            }
+            return Err(e).context("failed to read msgpack record body");
+        }
+
+        let wire: WireEvent =
+            rmp_serde::from_slice(&body).context("failed to decode msgpack event")?;
+        events.push(from_wire(wire)?);
+        offset += 4 + len as u64;
+    }
+
+    Ok(MsgpackEvents {
+        events,
+        last_good_offset: offset,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_events() -> Vec<ParsedEvent> {
+        vec![
+            ParsedEvent {
+                uuid: "e1".to_string(),
+                session_id: "s1".to_string(),
+                timestamp: Utc::now(),
+                role: Role::User,
+                content_text: Some("hello".to_string()),
+                tool_name: None,
+                tool_input_json: None,
+                tool_output_text: None,
+                tool_call_id: None,
+                source_offset: 0,
+                raw_type: "user".to_string(),
+                raw_line: Some(r#"{"type":"user"}"#.to_string()),
+            },
+            ParsedEvent {
+                uuid: "e2".to_string(),
+                session_id: "s1".to_string(),
+                timestamp: Utc::now(),
+                role: Role::Assistant,
+                content_text: None,
+                tool_name: Some("Read".to_string()),
+                tool_input_json: Some(
+                    serde_json::value::RawValue::from_string(r#"{"path":"/tmp/a"}"#.to_string())
+                        .unwrap(),
+                ),
+                tool_output_text: None,
+                tool_call_id: Some("t1".to_string()),
+                source_offset: 100,
+                raw_type: "assistant".to_string(),
+                raw_line: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let events = sample_events();
+        let mut buf = Vec::new();
+        write_events_msgpack(&events, &mut buf).unwrap();
+
+        let decoded = read_events_msgpack(&mut &buf[..]).unwrap();
+        assert_eq!(decoded.events.len(), 2);
+        assert_eq!(decoded.last_good_offset, buf.len() as u64);
+        assert_eq!(decoded.events[0].uuid, "e1");
+        assert_eq!(decoded.events[0].content_text.as_deref(), Some("hello"));
+        assert_eq!(decoded.events[1].tool_name.as_deref(), Some("Read"));
+        assert_eq!(
+            decoded.events[1]
+                .tool_input_json
+                .as_ref()
+                .map(|raw| raw.get()),
+            Some(r#"{"path":"/tmp/a"}"#)
+        );
+    }
+
+    #[test]
+    fn test_read_stops_before_truncated_trailing_record() {
+        let events = sample_events();
+        let mut buf = Vec::new();
+        write_events_msgpack(&events, &mut buf).unwrap();
+
+        // Simulate a writer that hasn't finished flushing the second record.
+        buf.truncate(buf.len() - 3);
+
+        let decoded = read_events_msgpack(&mut &buf[..]).unwrap();
+        assert_eq!(decoded.events.len(), 1);
+        assert!(decoded.last_good_offset < buf.len() as u64);
+
+        // Resuming from last_good_offset and re-reading the full buffer
+        // should recover the second record too.
+        let mut full = Vec::new();
+        write_events_msgpack(&events, &mut full).unwrap();
+        let rest = &full[decoded.last_good_offset as usize..];
+        let resumed = read_events_msgpack(&mut &rest[..]).unwrap();
+        assert_eq!(resumed.events.len(), 1);
+        assert_eq!(resumed.events[0].uuid, "e2");
+    }
+}
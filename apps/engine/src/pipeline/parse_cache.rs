@@ -0,0 +1,247 @@
+//! Binary sidecar cache for [`parser::parse_session_file`], keyed by the
+//! source file's size and mtime.
+//!
+//! Mmap-parsing a multi-megabyte session on every reload is wasteful once
+//! the file has stopped changing — and even once it's still growing, only
+//! the new tail needs a fresh parse. This mirrors the binary/msgpack
+//! fast-path formats `pipeline::msgpack` already gives shippers for the
+//! event stream itself, applied here as an on-disk cache: a `.parsecache`
+//! sidecar next to the source file holds a small header (size, mtime,
+//! metadata, last_good_offset) followed by the event stream in the same
+//! length-prefixed msgpack framing `pipeline::msgpack` uses.
+//!
+//! Three outcomes on load:
+//! - Sidecar's stored size/mtime match the file exactly: deserialize and
+//!   skip JSONL parsing entirely.
+//! - The file has only grown (size larger, mtime not older): reuse the cached
+//!   events and parse only the tail from `last_good_offset`, via the
+//!   existing offset-resume path, then refresh the sidecar.
+//! - Anything else (missing sidecar, file shrank, or mtime went backward —
+//!   e.g. the file was replaced): treat the cache as stale and parse from
+//!   scratch.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::msgpack::{read_events_msgpack, write_events_msgpack};
+use super::parser::{self, ParseResult, SessionMetadata};
+
+fn cache_path(source: &Path) -> PathBuf {
+    let mut name = source.file_name().unwrap_or_default().to_os_string();
+    name.push(".parsecache");
+    source.with_file_name(name)
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheHeader {
+    file_size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    last_good_offset: u64,
+    metadata: SessionMetadata,
+}
+
+fn mtime_parts(mtime: SystemTime) -> Result<(u64, u32)> {
+    let since_epoch = mtime
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .context("file mtime is before the Unix epoch")?;
+    Ok((since_epoch.as_secs(), since_epoch.subsec_nanos()))
+}
+
+/// Write (or overwrite) `path`'s sidecar cache from an already-parsed result.
+fn write_cache(path: &Path, file_size: u64, mtime: SystemTime, result: &ParseResult) -> Result<()> {
+    let (mtime_secs, mtime_nanos) = mtime_parts(mtime)?;
+    let header = CacheHeader {
+        file_size,
+        mtime_secs,
+        mtime_nanos,
+        last_good_offset: result.last_good_offset,
+        metadata: result.metadata.clone(),
+    };
+
+    let mut buf = rmp_serde::to_vec(&header).context("failed to encode parse cache header")?;
+    let header_len = buf.len() as u32;
+
+    let mut out = header_len.to_le_bytes().to_vec();
+    out.append(&mut buf);
+    write_events_msgpack(&result.events, &mut out).context("failed to encode parse cache events")?;
+
+    std::fs::write(cache_path(path), out).context("failed to write parse cache sidecar")?;
+    Ok(())
+}
+
+struct LoadedCache {
+    header: CacheHeader,
+    events: Vec<super::parser::ParsedEvent>,
+}
+
+/// Read and decode `path`'s sidecar cache, if one exists and isn't corrupt.
+fn read_cache(path: &Path) -> Option<LoadedCache> {
+    let bytes = std::fs::read(cache_path(path)).ok()?;
+    if bytes.len() < 4 {
+        return None;
+    }
+    let header_len = u32::from_le_bytes(bytes[0..4].try_into().ok()?) as usize;
+    let rest = bytes.get(4..)?;
+    let header_bytes = rest.get(..header_len)?;
+    let header: CacheHeader = rmp_serde::from_slice(header_bytes).ok()?;
+    let event_bytes = rest.get(header_len..)?;
+
+    let decoded = read_events_msgpack(&mut &event_bytes[..]).ok()?;
+    Some(LoadedCache {
+        header,
+        events: decoded.events,
+    })
+}
+
+/// Cached wrapper around [`parser::parse_session_file`] for the common case
+/// of reloading the same, possibly-growing, session file repeatedly.
+/// Always parses from scratch (and still writes a fresh sidecar) if no
+/// usable cache exists for `path`.
+pub fn parse_session_file_cached(path: &Path) -> Result<ParseResult> {
+    let fs_metadata = std::fs::metadata(path)
+        .with_context(|| format!("Failed to stat {}", path.display()))?;
+    let file_size = fs_metadata.len();
+    let mtime = fs_metadata
+        .modified()
+        .with_context(|| format!("Failed to read mtime of {}", path.display()))?;
+
+    if let Some(cached) = read_cache(path) {
+        let (mtime_secs, mtime_nanos) = mtime_parts(mtime)?;
+
+        if cached.header.file_size == file_size
+            && cached.header.mtime_secs == mtime_secs
+            && cached.header.mtime_nanos == mtime_nanos
+        {
+            return Ok(ParseResult {
+                events: cached.events,
+                last_good_offset: cached.header.last_good_offset,
+                metadata: cached.header.metadata,
+            });
+        }
+
+        let grew_in_place = file_size > cached.header.file_size
+            && (mtime_secs, mtime_nanos) >= (cached.header.mtime_secs, cached.header.mtime_nanos);
+
+        if grew_in_place {
+            let tail = parser::parse_session_file(path, cached.header.last_good_offset)?;
+
+            let mut events = cached.events;
+            events.extend(tail.events);
+
+            let mut metadata = cached.header.metadata;
+            metadata.started_at = metadata.started_at.into_iter().chain(tail.metadata.started_at).min();
+            metadata.ended_at = metadata.ended_at.into_iter().chain(tail.metadata.ended_at).max();
+            if tail.metadata.cwd.is_some() {
+                metadata.cwd = tail.metadata.cwd;
+                metadata.project = tail.metadata.project;
+            }
+            if tail.metadata.git_branch.is_some() {
+                metadata.git_branch = tail.metadata.git_branch;
+            }
+            if tail.metadata.version.is_some() {
+                metadata.version = tail.metadata.version;
+            }
+
+            let result = ParseResult {
+                events,
+                last_good_offset: tail.last_good_offset,
+                metadata,
+            };
+            let _ = write_cache(path, file_size, mtime, &result);
+            return Ok(result);
+        }
+    }
+
+    let result = parser::parse_session_file(path, 0)?;
+    let _ = write_cache(path, file_size, mtime, &result);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_session_file(path: &Path, lines: &[&str]) {
+        let mut f = std::fs::File::create(path).unwrap();
+        for line in lines {
+            writeln!(f, "{}", line).unwrap();
+        }
+    }
+
+    const LINE1: &str = r#"{"type":"user","uuid":"u1","timestamp":"2026-01-01T00:00:00Z","message":{"content":"first"}}"#;
+    const LINE2: &str = r#"{"type":"user","uuid":"u2","timestamp":"2026-01-01T00:00:01Z","message":{"content":"second"}}"#;
+    const LINE3: &str = r#"{"type":"user","uuid":"u3","timestamp":"2026-01-01T00:00:02Z","message":{"content":"third"}}"#;
+
+    #[test]
+    fn test_cold_cache_parses_and_writes_sidecar() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        write_session_file(&path, &[LINE1, LINE2]);
+
+        let result = parse_session_file_cached(&path).unwrap();
+        assert_eq!(result.events.len(), 2);
+        assert!(cache_path(&path).exists());
+    }
+
+    #[test]
+    fn test_warm_cache_round_trips_same_result_for_unchanged_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        write_session_file(&path, &[LINE1, LINE2]);
+
+        let first = parse_session_file_cached(&path).unwrap();
+        let second = parse_session_file_cached(&path).unwrap();
+        assert_eq!(second.events.len(), first.events.len());
+        assert_eq!(
+            second.events[0].content_text,
+            first.events[0].content_text
+        );
+        assert_eq!(second.last_good_offset, first.last_good_offset);
+    }
+
+    #[test]
+    fn test_grown_file_reparses_only_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        write_session_file(&path, &[LINE1, LINE2]);
+
+        let first = parse_session_file_cached(&path).unwrap();
+        assert_eq!(first.events.len(), 2);
+
+        // Ensure the mtime actually advances on filesystems with coarse
+        // mtime resolution.
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        write_session_file(&path, &[LINE1, LINE2, LINE3]);
+
+        let second = parse_session_file_cached(&path).unwrap();
+        assert_eq!(second.events.len(), 3);
+        assert_eq!(second.events[2].content_text.as_deref(), Some("third"));
+        assert_eq!(
+            second.metadata.started_at,
+            first.metadata.started_at
+        );
+        assert!(second.metadata.ended_at > first.metadata.ended_at);
+    }
+
+    #[test]
+    fn test_replaced_file_discards_stale_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        write_session_file(&path, &[LINE1, LINE2, LINE3]);
+        let _ = parse_session_file_cached(&path).unwrap();
+
+        // A shorter replacement (e.g. the session was cleared/restarted)
+        // must not be treated as a tail-append of the old one.
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        write_session_file(&path, &[LINE1]);
+
+        let result = parse_session_file_cached(&path).unwrap();
+        assert_eq!(result.events.len(), 1);
+        assert_eq!(result.events[0].content_text.as_deref(), Some("first"));
+    }
+}
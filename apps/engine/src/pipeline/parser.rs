@@ -3,6 +3,15 @@
 //! Mirrors the Python parser at `zerg/services/shipper/parser.py`.
 //! Extracts meaningful events (user messages, assistant text, tool calls,
 //! tool results) from JSONL files and converts them to a normalized format.
+//!
+//! The three providers' JSONL lines don't share a shape — format-specific
+//! decoding lives in `pipeline::session_format` behind the `SessionFormat`
+//! trait, sniffed from the opening non-empty lines of the range being parsed
+//! (which starts at the resume offset, not necessarily at the file's first
+//! line). This module owns
+//! only what all three have in common: the offset-tracking line splitter
+//! and the mmap/buffered/sharded I/O strategies that feed lines to
+//! whichever format matched.
 
 use std::io::{BufRead, BufReader};
 use std::path::Path;
@@ -10,9 +19,12 @@ use std::path::Path;
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use memmap2::Mmap;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_json::value::RawValue;
-use uuid::Uuid;
+
+use super::session_format::{self, SessionFormat};
+use super::stats::{compute_stats, SessionStats};
 
 /// Threshold for switching from buffered read to mmap (1 MB).
 const MMAP_THRESHOLD: u64 = 1_048_576;
@@ -43,6 +55,14 @@ pub struct ParsedEvent {
     pub tool_input_json: Option<Box<RawValue>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_output_text: Option<String>,
+    /// For a `tool_use` event, the call's own id; for a `tool_result` event,
+    /// the id of the call it answers. Set by the `SessionFormat` that
+    /// produced the event from whatever correlator its provider uses (an
+    /// explicit call id, or the tool name where the provider has no id).
+    /// Lets [`ParseResult::tool_calls`] join calls to results without
+    /// string-parsing `uuid`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
     pub source_offset: u64,
     pub raw_type: String,
     /// Only the first event per source line carries raw_line (dedup).
@@ -50,7 +70,7 @@ pub struct ParsedEvent {
     pub raw_line: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct SessionMetadata {
     pub session_id: String,
     pub cwd: Option<String>,
@@ -67,47 +87,70 @@ pub struct ParseResult {
     pub metadata: SessionMetadata,
 }
 
-// ---------------------------------------------------------------------------
-// Raw deserialization types (minimal — only fields we need)
-// ---------------------------------------------------------------------------
-
-#[derive(Deserialize)]
-struct RawLine {
-    r#type: Option<String>,
-    timestamp: Option<String>,
-    uuid: Option<String>,
-    cwd: Option<String>,
-    #[serde(rename = "gitBranch")]
-    git_branch: Option<String>,
-    version: Option<String>,
-    message: Option<RawMessage>,
+/// A `tool_use` call joined to its `tool_result`, via [`ParseResult::tool_calls`].
+///
+/// An orphaned call — no matching result has been seen yet, e.g. because
+/// the session file ends mid-turn or a streaming re-parse hasn't reached
+/// the result line yet — has `result_offset`/`output_text`/`latency` all
+/// `None`; check `result_offset.is_none()` to tell it apart from a call
+/// whose result legitimately carried no output text.
+#[derive(Debug, Clone)]
+pub struct ToolCall {
+    pub name: String,
+    pub input_json: Option<Box<RawValue>>,
+    pub output_text: Option<String>,
+    pub call_offset: u64,
+    pub result_offset: Option<u64>,
+    pub latency: Option<std::time::Duration>,
 }
 
-#[derive(Deserialize)]
-struct RawMessage {
-    /// Kept as raw JSON — avoids building a full serde_json::Value DOM tree.
-    /// Parsed on-demand in extraction functions via ContentItem.
-    content: Box<RawValue>,
-}
+impl ParseResult {
+    /// Join each `tool_use` event to the later `tool_result` event sharing
+    /// its `tool_call_id`, in call order. Mirrors the request/response
+    /// pairing agent CLIs need to reconstruct a turn: a `tool_use` alone is
+    /// only half the story.
+    pub fn tool_calls(&self) -> Vec<ToolCall> {
+        let results: std::collections::HashMap<&str, &ParsedEvent> = self
+            .events
+            .iter()
+            .filter(|e| e.role == Role::Tool)
+            .filter_map(|e| e.tool_call_id.as_deref().map(|id| (id, e)))
+            .collect();
+
+        self.events
+            .iter()
+            .filter(|e| e.role == Role::Assistant && e.tool_name.is_some())
+            .map(|call| {
+                let result = call.tool_call_id.as_deref().and_then(|id| results.get(id));
+                let latency = result.map(|r| r.timestamp - call.timestamp).and_then(|d| d.to_std().ok());
+
+                ToolCall {
+                    name: call.tool_name.clone().unwrap_or_default(),
+                    input_json: call.tool_input_json.clone(),
+                    output_text: result.and_then(|r| r.tool_output_text.clone()),
+                    call_offset: call.source_offset,
+                    result_offset: result.map(|r| r.source_offset),
+                    latency,
+                }
+            })
+            .collect()
+    }
 
-/// Targeted deserialization of a single content array item.
-/// Only the fields we actually use are extracted; everything else is skipped.
-#[derive(Deserialize)]
-struct ContentItem {
-    r#type: Option<String>,
-    /// Text content (for "text" items)
-    text: Option<String>,
-    /// Tool name (for "tool_use" items)
-    name: Option<String>,
-    /// Tool call ID (for "tool_use" items)
-    id: Option<String>,
-    /// Tool input — kept as raw JSON, never parsed into a Value tree.
-    input: Option<Box<RawValue>>,
-    /// Tool use ID (for "tool_result" items)
-    tool_use_id: Option<String>,
-    /// Tool result content — kept as raw JSON, parsed lazily for text extraction.
-    #[serde(rename = "content")]
-    result_content: Option<Box<RawValue>>,
+    /// Roll this session's events up into frequency metrics for a dashboard
+    /// summary, so callers don't have to walk `events` themselves. The
+    /// wall-clock span is taken from `metadata.started_at`/`ended_at` rather
+    /// than recomputed from the events, since metadata already carries the
+    /// authoritative min/max timestamps seen across the whole file (not just
+    /// whatever slice of `events` this `ParseResult` happens to hold after a
+    /// resumed parse).
+    pub fn stats(&self) -> SessionStats {
+        let mut stats = compute_stats(&self.events);
+        stats.wall_clock_span = match (self.metadata.started_at, self.metadata.ended_at) {
+            (Some(start), Some(end)) => (end - start).to_std().ok(),
+            _ => None,
+        };
+        stats
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -174,6 +217,66 @@ fn parse_mmap(path: &Path, offset: u64, session_id: &str) -> Result<ParseResult>
         });
     };
 
+    let format = session_format::detect_format(data);
+    let shard = parse_byte_range(data, offset, session_id, format);
+    Ok(shard_to_parse_result(shard))
+}
+
+/// Parse a JSONL byte range already in memory — the owned-blob spool replay
+/// path (see `state::spool::Spool::read_payload`), which has no source file
+/// left to `mmap`/seek into, only the bytes it copied out at enqueue time.
+pub fn parse_bytes(data: &[u8], offset: u64, session_id: &str) -> ParseResult {
+    let format = session_format::detect_format(data);
+    let shard = parse_byte_range(data, offset, session_id, format);
+    shard_to_parse_result(shard)
+}
+
+/// Finish a [`ShardResult`] into a [`ParseResult`]: fold `min_ts`/`max_ts`
+/// into `started_at`/`ended_at` and derive `project` from `cwd`'s final path
+/// component. Shared by [`parse_mmap`] and [`parse_bytes`], which both parse
+/// a single unsharded range and so need no cross-shard merging.
+fn shard_to_parse_result(shard: ShardResult) -> ParseResult {
+    let mut metadata = shard.metadata;
+    metadata.started_at = shard.min_ts;
+    metadata.ended_at = shard.max_ts;
+    if let Some(ref cwd) = metadata.cwd {
+        metadata.project = Path::new(cwd)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .map(|s| s.to_string());
+    }
+
+    ParseResult {
+        events: shard.events,
+        last_good_offset: shard.last_good_offset,
+        metadata,
+    }
+}
+
+/// Result of decoding one line-aligned byte range (a whole file for the
+/// single-threaded path, or one shard of it for [`parse_session_file_sharded`]).
+struct ShardResult {
+    events: Vec<ParsedEvent>,
+    last_good_offset: u64,
+    metadata: SessionMetadata,
+    min_ts: Option<DateTime<Utc>>,
+    max_ts: Option<DateTime<Utc>>,
+}
+
+/// Decode every complete JSONL line in `data`, which starts at file byte
+/// `base_offset`. Shared by the single-shard mmap path and the multi-shard
+/// parallel path so both go through identical event-decoding logic — only
+/// the range of bytes handed in differs. `format` is sniffed once by the
+/// caller from the opening non-empty lines of the whole post-offset range
+/// (see `session_format::detect_format`) and reused for every line in
+/// `data`, including in the sharded path where a shard may not itself
+/// contain those lines.
+fn parse_byte_range(
+    data: &[u8],
+    base_offset: u64,
+    session_id: &str,
+    format: &dyn SessionFormat,
+) -> ShardResult {
     let mut events = Vec::new();
     let mut metadata = SessionMetadata {
         session_id: session_id.to_string(),
@@ -181,7 +284,7 @@ fn parse_mmap(path: &Path, offset: u64, session_id: &str) -> Result<ParseResult>
     };
     let mut min_ts: Option<DateTime<Utc>> = None;
     let mut max_ts: Option<DateTime<Utc>> = None;
-    let mut last_good_offset = offset;
+    let mut last_good_offset = base_offset;
 
     let mut pos: usize = 0;
     while pos < data.len() {
@@ -195,8 +298,8 @@ fn parse_mmap(path: &Path, offset: u64, session_id: &str) -> Result<ParseResult>
             }
         };
 
-        let line_offset = offset + line_start as u64;
-        let after_line = offset + line_end as u64 + 1; // past the \n
+        let line_offset = base_offset + line_start as u64;
+        let after_line = base_offset + line_end as u64 + 1; // past the \n
 
         let line_bytes = &data[line_start..line_end];
         pos = line_end + 1;
@@ -208,34 +311,143 @@ fn parse_mmap(path: &Path, offset: u64, session_id: &str) -> Result<ParseResult>
             continue;
         }
 
-        // Parse JSON
-        let obj: RawLine = match serde_json::from_slice(trimmed) {
-            Ok(v) => v,
-            Err(e) => {
-                tracing::warn!(offset = line_offset, error = %e, "Failed to parse JSON line");
-                // Still advance — the line is complete, just malformed
-                last_good_offset = after_line;
-                continue;
-            }
-        };
-
         last_good_offset = after_line;
 
-        // Collect metadata
-        collect_metadata(&obj, &mut metadata, &mut min_ts, &mut max_ts);
-
-        // Extract events — pass raw bytes, convert to string only when needed
+        // Decode + extract in one pass — pass raw bytes, convert to string
+        // only when needed. Malformed/unrecognized lines are logged and
+        // skipped by the format itself; the line is still "complete" so the
+        // offset already advanced above regardless.
         let line_str = std::str::from_utf8(trimmed).unwrap_or("");
-        extract_events(
-            &obj,
+        format.process_line(
+            line_str,
             session_id,
             line_offset,
-            line_str,
+            &mut metadata,
+            &mut min_ts,
+            &mut max_ts,
             &mut events,
         );
     }
 
-    // Finalize metadata
+    ShardResult {
+        events,
+        last_good_offset,
+        metadata,
+        min_ts,
+        max_ts,
+    }
+}
+
+/// Split `data` into `shard_count` pieces, each boundary (other than the
+/// very last) walked backward from an even split point to the preceding
+/// `\n` so no shard starts or ends mid-line. Falls back to a single shard
+/// if the data is too small to usefully split.
+fn shard_boundaries(data: &[u8], shard_count: usize) -> Vec<(usize, usize)> {
+    if shard_count <= 1 || data.len() < shard_count * 2 {
+        return vec![(0, data.len())];
+    }
+
+    let approx = data.len() / shard_count;
+    let mut bounds = Vec::with_capacity(shard_count);
+    let mut start = 0usize;
+    for i in 0..shard_count {
+        if i == shard_count - 1 {
+            bounds.push((start, data.len()));
+            break;
+        }
+        let mut cut = (start + approx).min(data.len());
+        while cut > start && data[cut - 1] != b'\n' {
+            cut -= 1;
+        }
+        if cut <= start {
+            // One line spans the whole target shard width — don't split here,
+            // let this shard absorb it (through its `\n`, or to EOF) and try
+            // again from the next target.
+            let from = (start + approx).min(data.len());
+            cut = data[from..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(data.len(), |nl| from + nl + 1);
+        }
+        bounds.push((start, cut));
+        start = cut;
+    }
+    bounds
+}
+
+/// Parallel mmap parsing: split the post-offset byte range into line-aligned
+/// shards and decode them concurrently with rayon, merging results in file
+/// order so output (event order, first-seen metadata) matches the
+/// single-threaded path exactly.
+pub fn parse_session_file_sharded(
+    path: &Path,
+    offset: u64,
+    shard_count: usize,
+) -> Result<ParseResult> {
+    let session_id = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mmap = unsafe { Mmap::map(&file) }
+        .with_context(|| format!("Failed to mmap {}", path.display()))?;
+
+    if (offset as usize) >= mmap.len() {
+        return Ok(ParseResult {
+            events: Vec::new(),
+            last_good_offset: offset,
+            metadata: SessionMetadata {
+                session_id,
+                ..Default::default()
+            },
+        });
+    }
+
+    let data = &mmap[offset as usize..];
+    let format = session_format::detect_format(data);
+    let bounds = shard_boundaries(data, shard_count.max(1));
+
+    let shards: Vec<ShardResult> = bounds
+        .par_iter()
+        .map(|&(s, e)| parse_byte_range(&data[s..e], offset + s as u64, &session_id, format))
+        .collect();
+
+    let mut events = Vec::new();
+    let mut last_good_offset = offset;
+    let mut metadata = SessionMetadata {
+        session_id: session_id.clone(),
+        ..Default::default()
+    };
+    let mut min_ts: Option<DateTime<Utc>> = None;
+    let mut max_ts: Option<DateTime<Utc>> = None;
+
+    for shard in shards {
+        events.extend(shard.events);
+        last_good_offset = last_good_offset.max(shard.last_good_offset);
+        if metadata.cwd.is_none() {
+            metadata.cwd = shard.metadata.cwd;
+        }
+        if metadata.git_branch.is_none() {
+            metadata.git_branch = shard.metadata.git_branch;
+        }
+        if metadata.version.is_none() {
+            metadata.version = shard.metadata.version;
+        }
+        min_ts = match (min_ts, shard.min_ts) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, None) => a,
+            (None, b) => b,
+        };
+        max_ts = match (max_ts, shard.max_ts) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, None) => a,
+            (None, b) => b,
+        };
+    }
+
     metadata.started_at = min_ts;
     metadata.ended_at = max_ts;
     if let Some(ref cwd) = metadata.cwd {
@@ -252,6 +464,138 @@ fn parse_mmap(path: &Path, offset: u64, session_id: &str) -> Result<ParseResult>
     })
 }
 
+// ---------------------------------------------------------------------------
+// Timestamp seek (binary search over byte offsets)
+// ---------------------------------------------------------------------------
+
+/// Find a byte offset usable with [`parse_session_file`]'s `offset` parameter
+/// that skips every line whose `timestamp` is known to precede `target`,
+/// without parsing the file in full.
+///
+/// Session timestamps are effectively monotonic non-decreasing, so this
+/// binary-searches byte positions in `[0, len)`: at each midpoint it aligns
+/// *backward* to the start of the line containing it (so the probe always
+/// reads that line's own timestamp, never skips past it), then scans forward
+/// from there (past any undated lines — summaries, progress markers, ...) to
+/// the next line that does carry a `timestamp`, and narrows toward the first
+/// such line whose timestamp is `>= target`. Returns `file_size` if every
+/// dated event precedes `target`, and `0` if `target` precedes the first
+/// one. A truncated trailing line with no `\n` is never treated as a line
+/// boundary to land on, matching how [`parse_byte_range`] leaves a partial
+/// final line for the next resume rather than parsing it.
+pub fn seek_to_timestamp(path: &Path, target: DateTime<Utc>) -> Result<u64> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mmap = unsafe { Mmap::map(&file) }
+        .with_context(|| format!("Failed to mmap {}", path.display()))?;
+    let data: &[u8] = &mmap;
+
+    let mut lo = 0usize;
+    let mut hi = data.len();
+
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let probe_start = line_start_containing(data, mid);
+
+        match next_dated_line(data, probe_start) {
+            Some((dated_start, ts)) => {
+                if ts >= target {
+                    // `probe_start` itself already qualifies (the forward
+                    // scan from it lands on a `>= target` timestamp), not
+                    // just `dated_start` — any undated lines between them
+                    // are equally safe to include, so narrow to the
+                    // earlier, tighter bound rather than `dated_start`
+                    // (which can equal the current `hi` and stall the
+                    // search when the probe's own line is undated).
+                    hi = probe_start;
+                } else {
+                    lo = line_start_at_or_after(data, dated_start + 1);
+                }
+            }
+            // No dated line anywhere at or after this probe — everything
+            // left to search is undated/empty, so the answer lies before it.
+            None => hi = mid,
+        }
+    }
+
+    Ok(lo as u64)
+}
+
+/// Start of the line containing `pos`: 0 if `pos` is 0 or no earlier `\n`
+/// exists (the first line always starts there), otherwise the
+/// position just past the preceding `\n`. Unlike [`line_start_at_or_after`],
+/// this never skips past `pos`'s own line — `seek_to_timestamp` needs to
+/// read that line's timestamp, not the next one's, or a probe landing
+/// mid-line would never narrow the search and the loop would never
+/// terminate.
+fn line_start_containing(data: &[u8], pos: usize) -> usize {
+    if pos == 0 {
+        return 0;
+    }
+    match data[..pos].iter().rposition(|&b| b == b'\n') {
+        Some(nl) => nl + 1,
+        None => 0,
+    }
+}
+
+/// Start of the line containing or following `pos`: `pos` itself if it's
+/// already 0 (the first line always starts there), otherwise the position
+/// just past the next `\n` at or after `pos`, or `data.len()` if `pos`'s
+/// line runs to EOF with no trailing newline.
+fn line_start_at_or_after(data: &[u8], pos: usize) -> usize {
+    if pos == 0 {
+        return 0;
+    }
+    match data[pos..].iter().position(|&b| b == b'\n') {
+        Some(nl) => pos + nl + 1,
+        None => data.len(),
+    }
+}
+
+/// Scan forward from `start` (already a line start) for the first
+/// non-empty line that carries a parseable `timestamp`, skipping undated
+/// lines along the way. Returns that line's start offset and timestamp, or
+/// `None` if no dated line remains before EOF. A trailing line with no `\n`
+/// is never a candidate, even if it happens to parse — a writer still
+/// mid-flush could still be appending to it, so it's not a stable boundary
+/// to compare against or land on (matching [`parse_byte_range`]'s handling
+/// of the same partial-final-line case).
+fn next_dated_line(data: &[u8], mut start: usize) -> Option<(usize, DateTime<Utc>)> {
+    while start < data.len() {
+        let end = match data[start..].iter().position(|&b| b == b'\n') {
+            Some(nl) => start + nl,
+            None => return None,
+        };
+
+        let trimmed = trim_bytes(&data[start..end]);
+        if !trimmed.is_empty() {
+            if let Ok(s) = std::str::from_utf8(trimmed) {
+                if let Some(ts) = line_timestamp(s) {
+                    return Some((start, ts));
+                }
+            }
+        }
+
+        start = end + 1;
+    }
+    None
+}
+
+/// Pull just the top-level `timestamp` field out of a JSONL line, without
+/// caring which `SessionFormat` produced it — every provider's line shape
+/// carries this field under the same key.
+fn line_timestamp(line: &str) -> Option<DateTime<Utc>> {
+    #[derive(Deserialize)]
+    struct TimestampProbe {
+        timestamp: Option<String>,
+    }
+    serde_json::from_str::<TimestampProbe>(line)
+        .ok()?
+        .timestamp
+        .as_deref()
+        .and_then(parse_timestamp)
+}
+
 // ---------------------------------------------------------------------------
 // Buffered reader parser (small files)
 // ---------------------------------------------------------------------------
@@ -275,6 +619,10 @@ fn parse_buffered(path: &Path, offset: u64, session_id: &str) -> Result<ParseRes
     let mut min_ts: Option<DateTime<Utc>> = None;
     let mut max_ts: Option<DateTime<Utc>> = None;
     let mut current_offset = offset;
+    // Sniffed from the first non-empty line, then reused for the rest of
+    // the file — `BufReader::lines()` only yields one line at a time, so
+    // unlike the mmap paths this can't peek ahead before the loop starts.
+    let mut format: Option<&'static dyn SessionFormat> = None;
 
     for line_result in reader.lines() {
         let line = match line_result {
@@ -294,21 +642,15 @@ fn parse_buffered(path: &Path, offset: u64, session_id: &str) -> Result<ParseRes
             continue;
         }
 
-        let obj: RawLine = match serde_json::from_str(trimmed) {
-            Ok(v) => v,
-            Err(e) => {
-                tracing::warn!(offset = line_offset, error = %e, "Failed to parse JSON line");
-                continue;
-            }
-        };
-
-        collect_metadata(&obj, &mut metadata, &mut min_ts, &mut max_ts);
+        let format = *format.get_or_insert_with(|| session_format::detect_format(trimmed.as_bytes()));
 
-        extract_events(
-            &obj,
+        format.process_line(
+            trimmed,
             session_id,
             line_offset,
-            trimmed,
+            &mut metadata,
+            &mut min_ts,
+            &mut max_ts,
             &mut events,
         );
     }
@@ -329,399 +671,11 @@ fn parse_buffered(path: &Path, offset: u64, session_id: &str) -> Result<ParseRes
     })
 }
 
-// ---------------------------------------------------------------------------
-// Shared extraction logic
-// ---------------------------------------------------------------------------
-
-fn collect_metadata(
-    obj: &RawLine,
-    meta: &mut SessionMetadata,
-    min_ts: &mut Option<DateTime<Utc>>,
-    max_ts: &mut Option<DateTime<Utc>>,
-) {
-    if meta.cwd.is_none() {
-        if let Some(ref cwd) = obj.cwd {
-            meta.cwd = Some(cwd.clone());
-        }
-    }
-    if meta.git_branch.is_none() {
-        if let Some(ref branch) = obj.git_branch {
-            meta.git_branch = Some(branch.clone());
-        }
-    }
-    if meta.version.is_none() {
-        if let Some(ref ver) = obj.version {
-            meta.version = Some(ver.clone());
-        }
-    }
-    if let Some(ts) = obj.timestamp.as_deref().and_then(parse_timestamp) {
-        match min_ts {
-            Some(ref existing) if ts < *existing => *min_ts = Some(ts),
-            None => *min_ts = Some(ts),
-            _ => {}
-        }
-        match max_ts {
-            Some(ref existing) if ts > *existing => *max_ts = Some(ts),
-            None => *max_ts = Some(ts),
-            _ => {}
-        }
-    }
-}
-
-fn extract_events(
-    obj: &RawLine,
-    session_id: &str,
-    line_offset: u64,
-    raw_line: &str,
-    events: &mut Vec<ParsedEvent>,
-) {
-    let event_type = obj.r#type.as_deref().unwrap_or("");
-
-    // Skip metadata-only types
-    match event_type {
-        "summary" | "file-history-snapshot" | "progress" => return,
-        _ => {}
-    }
-
-    let timestamp = obj
-        .timestamp
-        .as_deref()
-        .and_then(parse_timestamp)
-        .unwrap_or_else(Utc::now);
-
-    let msg_uuid = obj
-        .uuid
-        .as_deref()
-        .unwrap_or("")
-        .to_string();
-    let msg_uuid = if msg_uuid.is_empty() {
-        Uuid::new_v4().to_string()
-    } else {
-        msg_uuid
-    };
-
-    let content_raw = match &obj.message {
-        Some(m) => &m.content,
-        None => return,
-    };
-
-    // Parse content items from raw JSON on-demand.
-    // This is where the RawValue optimization pays off: the initial RawLine
-    // parse skipped building a Value tree for content entirely. Now we parse
-    // only the fields we need via ContentItem.
-    let content_str = content_raw.get();
-
-    match event_type {
-        "user" => {
-            extract_user_events(
-                content_str,
-                session_id,
-                &msg_uuid,
-                timestamp,
-                line_offset,
-                raw_line,
-                events,
-            );
-        }
-        "assistant" => {
-            extract_assistant_events(
-                content_str,
-                session_id,
-                &msg_uuid,
-                timestamp,
-                line_offset,
-                raw_line,
-                events,
-            );
-        }
-        _ => {
-            // Unknown type — skip
-        }
-    }
-}
-
-fn extract_user_events(
-    content_str: &str,
-    session_id: &str,
-    msg_uuid: &str,
-    timestamp: DateTime<Utc>,
-    line_offset: u64,
-    raw_line: &str,
-    events: &mut Vec<ParsedEvent>,
-) {
-    // Try parsing as array of ContentItems
-    if let Ok(items) = serde_json::from_str::<Vec<ContentItem>>(content_str) {
-        // Check if any items are tool_results
-        let has_tool_result = items.iter().any(|item| {
-            item.r#type.as_deref() == Some("tool_result")
-        });
-
-        if has_tool_result {
-            extract_tool_results_from_items(
-                &items,
-                session_id,
-                msg_uuid,
-                timestamp,
-                line_offset,
-                raw_line,
-                events,
-            );
-        } else {
-            // Regular user message — extract text from items
-            let text = extract_user_content_from_items(&items);
-            if let Some(text) = text {
-                if !text.trim().is_empty() {
-                    events.push(ParsedEvent {
-                        uuid: msg_uuid.to_string(),
-                        session_id: session_id.to_string(),
-                        timestamp,
-                        role: Role::User,
-                        content_text: Some(text),
-                        tool_name: None,
-                        tool_input_json: None,
-                        tool_output_text: None,
-                        source_offset: line_offset,
-                        raw_type: "user".to_string(),
-                        raw_line: Some(raw_line.to_string()),
-                    });
-                }
-            }
-        }
-    } else if let Ok(text) = serde_json::from_str::<String>(content_str) {
-        // Plain string content
-        if !text.trim().is_empty() {
-            events.push(ParsedEvent {
-                uuid: msg_uuid.to_string(),
-                session_id: session_id.to_string(),
-                timestamp,
-                role: Role::User,
-                content_text: Some(text),
-                tool_name: None,
-                tool_input_json: None,
-                tool_output_text: None,
-                source_offset: line_offset,
-                raw_type: "user".to_string(),
-                raw_line: Some(raw_line.to_string()),
-            });
-        }
-    }
-}
-
-fn extract_assistant_events(
-    content_str: &str,
-    session_id: &str,
-    msg_uuid: &str,
-    timestamp: DateTime<Utc>,
-    line_offset: u64,
-    raw_line: &str,
-    events: &mut Vec<ParsedEvent>,
-) {
-    let items: Vec<ContentItem> = match serde_json::from_str(content_str) {
-        Ok(v) => v,
-        Err(_) => return,
-    };
-
-    let mut first = true;
-    for (idx, item) in items.iter().enumerate() {
-        let item_type = item.r#type.as_deref().unwrap_or("");
-
-        match item_type {
-            "text" => {
-                let text = item.text.as_deref().unwrap_or("");
-                if !text.trim().is_empty() {
-                    events.push(ParsedEvent {
-                        uuid: format!("{}-text-{}", msg_uuid, idx),
-                        session_id: session_id.to_string(),
-                        timestamp,
-                        role: Role::Assistant,
-                        content_text: Some(text.to_string()),
-                        tool_name: None,
-                        tool_input_json: None,
-                        tool_output_text: None,
-                        source_offset: line_offset,
-                        raw_type: "assistant".to_string(),
-                        raw_line: if first {
-                            first = false;
-                            Some(raw_line.to_string())
-                        } else {
-                            None
-                        },
-                    });
-                }
-            }
-            "tool_use" => {
-                let tool_name = item.name.as_deref().unwrap_or("").to_string();
-                let tool_id = item.id.as_deref().unwrap_or("");
-                let uuid_suffix = if tool_id.is_empty() {
-                    format!("{}", idx)
-                } else {
-                    tool_id.to_string()
-                };
-
-                // tool_input stays as Box<RawValue> — zero-copy pass-through
-                let tool_input = item.input.as_ref().and_then(|raw| {
-                    // Only keep if it's a JSON object (starts with '{')
-                    let s = raw.get().trim();
-                    if s.starts_with('{') {
-                        // Clone the RawValue box (just copies the string, not a DOM tree)
-                        Some(raw.clone())
-                    } else {
-                        None
-                    }
-                });
-
-                events.push(ParsedEvent {
-                    uuid: format!("{}-tool-{}", msg_uuid, uuid_suffix),
-                    session_id: session_id.to_string(),
-                    timestamp,
-                    role: Role::Assistant,
-                    content_text: None,
-                    tool_name: Some(tool_name),
-                    tool_input_json: tool_input,
-                    tool_output_text: None,
-                    source_offset: line_offset,
-                    raw_type: "assistant".to_string(),
-                    raw_line: if first {
-                        first = false;
-                        Some(raw_line.to_string())
-                    } else {
-                        None
-                    },
-                });
-            }
-            _ => {
-                // thinking, etc. — skip
-            }
-        }
-    }
-}
-
-fn extract_tool_results_from_items(
-    items: &[ContentItem],
-    session_id: &str,
-    msg_uuid: &str,
-    timestamp: DateTime<Utc>,
-    line_offset: u64,
-    raw_line: &str,
-    events: &mut Vec<ParsedEvent>,
-) {
-    let mut first = true;
-    for (idx, item) in items.iter().enumerate() {
-        if item.r#type.as_deref() != Some("tool_result") {
-            continue;
-        }
-
-        let tool_use_id = item.tool_use_id.as_deref().unwrap_or("");
-        let uuid_suffix = if tool_use_id.is_empty() {
-            format!("{}", idx)
-        } else {
-            tool_use_id.to_string()
-        };
-
-        let result_text = item.result_content.as_ref().and_then(|raw| {
-            extract_text_from_raw_content(raw.get())
-        });
-
-        if let Some(text) = result_text {
-            if !text.is_empty() {
-                events.push(ParsedEvent {
-                    uuid: format!("{}-result-{}", msg_uuid, uuid_suffix),
-                    session_id: session_id.to_string(),
-                    timestamp,
-                    role: Role::Tool,
-                    content_text: None,
-                    tool_name: None,
-                    tool_input_json: None,
-                    tool_output_text: Some(text),
-                    source_offset: line_offset,
-                    raw_type: "tool_result".to_string(),
-                    raw_line: if first {
-                        first = false;
-                        Some(raw_line.to_string())
-                    } else {
-                        None
-                    },
-                });
-            }
-        }
-    }
-}
-
-// ---------------------------------------------------------------------------
-// Content extraction helpers
-// ---------------------------------------------------------------------------
-
-fn extract_user_content_from_items(items: &[ContentItem]) -> Option<String> {
-    let mut parts = Vec::new();
-    for item in items {
-        match item.r#type.as_deref() {
-            Some("text") => {
-                if let Some(ref text) = item.text {
-                    parts.push(text.clone());
-                }
-            }
-            Some("tool_result") => {
-                if let Some(ref raw) = item.result_content {
-                    if let Some(text) = extract_text_from_raw_content(raw.get()) {
-                        parts.push(text);
-                    }
-                }
-            }
-            _ => {}
-        }
-    }
-    if parts.is_empty() {
-        None
-    } else {
-        Some(parts.join("\n"))
-    }
-}
-
-/// Extract text from a raw JSON content field (tool_result content).
-/// Handles: plain string, array of {type: "text", text: "..."}, or fallback to raw JSON.
-fn extract_text_from_raw_content(raw_json: &str) -> Option<String> {
-    let trimmed = raw_json.trim();
-
-    // Plain string: "some text"
-    if trimmed.starts_with('"') {
-        if let Ok(s) = serde_json::from_str::<String>(trimmed) {
-            return Some(s);
-        }
-    }
-
-    // Array of content parts
-    if trimmed.starts_with('[') {
-        #[derive(Deserialize)]
-        struct TextPart {
-            r#type: Option<String>,
-            text: Option<String>,
-        }
-
-        if let Ok(parts) = serde_json::from_str::<Vec<TextPart>>(trimmed) {
-            let mut texts = Vec::new();
-            for part in &parts {
-                if part.r#type.as_deref() == Some("text") {
-                    if let Some(ref text) = part.text {
-                        texts.push(text.clone());
-                    }
-                }
-            }
-            if texts.is_empty() {
-                return None;
-            }
-            return Some(texts.join("\n"));
-        }
-    }
-
-    // Fallback: raw JSON as string
-    Some(trimmed.to_string())
-}
-
 // ---------------------------------------------------------------------------
 // Timestamp parsing
 // ---------------------------------------------------------------------------
 
-fn parse_timestamp(ts: &str) -> Option<DateTime<Utc>> {
+pub(crate) fn parse_timestamp(ts: &str) -> Option<DateTime<Utc>> {
     if ts.is_empty() {
         return None;
     }
@@ -747,7 +701,7 @@ fn parse_timestamp(ts: &str) -> Option<DateTime<Utc>> {
 // Byte utilities
 // ---------------------------------------------------------------------------
 
-fn trim_bytes(bytes: &[u8]) -> &[u8] {
+pub(crate) fn trim_bytes(bytes: &[u8]) -> &[u8] {
     let start = bytes.iter().position(|&b| !b.is_ascii_whitespace()).unwrap_or(bytes.len());
     let end = bytes.iter().rposition(|&b| !b.is_ascii_whitespace()).map_or(start, |p| p + 1);
     &bytes[start..end]
@@ -900,6 +854,18 @@ mod tests {
         assert_eq!(result.events[0].content_text.as_deref(), Some("complete"));
     }
 
+    #[test]
+    fn test_parse_bytes_matches_parse_session_file() {
+        let line = r#"{"type":"user","uuid":"u1","timestamp":"2026-01-01T00:00:00Z","message":{"content":"from a blob"},"cwd":"/home/user/project"}"#;
+        let data = format!("{}\n", line);
+
+        let result = parse_bytes(data.as_bytes(), 0, "test-session");
+        assert_eq!(result.events.len(), 1);
+        assert_eq!(result.events[0].content_text.as_deref(), Some("from a blob"));
+        assert_eq!(result.metadata.project.as_deref(), Some("project"));
+        assert_eq!(result.last_good_offset, data.len() as u64);
+    }
+
     #[test]
     fn test_metadata_timestamps() {
         let dir = tempfile::tempdir().unwrap();
@@ -918,4 +884,229 @@ mod tests {
         assert!(result.metadata.started_at.unwrap() < result.metadata.ended_at.unwrap());
         assert_eq!(result.metadata.version.as_deref(), Some("1.0"));
     }
+
+    #[test]
+    fn test_tool_calls_joins_use_and_result() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = make_jsonl_file(
+            dir.path(),
+            "test-session.jsonl",
+            &[
+                r#"{"type":"assistant","uuid":"a1","timestamp":"2026-01-01T00:00:00Z","message":{"content":[{"type":"tool_use","id":"t1","name":"Read","input":{"file_path":"/tmp/foo"}}]}}"#,
+                r#"{"type":"user","uuid":"u1","timestamp":"2026-01-01T00:00:02Z","message":{"content":[{"type":"tool_result","tool_use_id":"t1","content":"file contents"}]}}"#,
+            ],
+        );
+
+        let result = parse_session_file(&path, 0).unwrap();
+        let calls = result.tool_calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "Read");
+        assert_eq!(calls[0].output_text.as_deref(), Some("file contents"));
+        assert!(calls[0].result_offset.is_some());
+        assert_eq!(calls[0].latency, Some(std::time::Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_tool_calls_flags_orphaned_call() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = make_jsonl_file(
+            dir.path(),
+            "test-session.jsonl",
+            &[r#"{"type":"assistant","uuid":"a1","timestamp":"2026-01-01T00:00:00Z","message":{"content":[{"type":"tool_use","id":"t1","name":"Read","input":{"file_path":"/tmp/foo"}}]}}"#],
+        );
+
+        let result = parse_session_file(&path, 0).unwrap();
+        let calls = result.tool_calls();
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].result_offset.is_none());
+        assert!(calls[0].output_text.is_none());
+        assert!(calls[0].latency.is_none());
+    }
+
+    #[test]
+    fn test_seek_to_timestamp_finds_exact_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let line1 = r#"{"type":"user","uuid":"u1","timestamp":"2026-01-01T00:00:00Z","message":{"content":"first"}}"#;
+        let line2 = r#"{"type":"user","uuid":"u2","timestamp":"2026-01-01T00:05:00Z","message":{"content":"second"}}"#;
+        let line3 = r#"{"type":"user","uuid":"u3","timestamp":"2026-01-01T00:10:00Z","message":{"content":"third"}}"#;
+        let path = make_jsonl_file(dir.path(), "test-session.jsonl", &[line1, line2, line3]);
+
+        let target = parse_timestamp("2026-01-01T00:05:00Z").unwrap();
+        let offset = seek_to_timestamp(&path, target).unwrap();
+
+        let result = parse_session_file(&path, offset).unwrap();
+        assert_eq!(result.events.len(), 2);
+        assert_eq!(result.events[0].content_text.as_deref(), Some("second"));
+        assert_eq!(result.events[1].content_text.as_deref(), Some("third"));
+    }
+
+    #[test]
+    fn test_seek_to_timestamp_between_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let line1 = r#"{"type":"user","uuid":"u1","timestamp":"2026-01-01T00:00:00Z","message":{"content":"first"}}"#;
+        let line2 = r#"{"type":"user","uuid":"u2","timestamp":"2026-01-01T00:10:00Z","message":{"content":"second"}}"#;
+        let path = make_jsonl_file(dir.path(), "test-session.jsonl", &[line1, line2]);
+
+        let target = parse_timestamp("2026-01-01T00:05:00Z").unwrap();
+        let offset = seek_to_timestamp(&path, target).unwrap();
+
+        let result = parse_session_file(&path, offset).unwrap();
+        assert_eq!(result.events.len(), 1);
+        assert_eq!(result.events[0].content_text.as_deref(), Some("second"));
+    }
+
+    #[test]
+    fn test_seek_to_timestamp_skips_undated_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let line1 = r#"{"type":"user","uuid":"u1","timestamp":"2026-01-01T00:00:00Z","message":{"content":"first"}}"#;
+        let line2 = r#"{"type":"summary"}"#;
+        let line3 = r#"{"type":"user","uuid":"u2","timestamp":"2026-01-01T00:10:00Z","message":{"content":"second"}}"#;
+        let path = make_jsonl_file(dir.path(), "test-session.jsonl", &[line1, line2, line3]);
+
+        let target = parse_timestamp("2026-01-01T00:05:00Z").unwrap();
+        let offset = seek_to_timestamp(&path, target).unwrap();
+
+        let result = parse_session_file(&path, offset).unwrap();
+        assert_eq!(result.events.len(), 1);
+        assert_eq!(result.events[0].content_text.as_deref(), Some("second"));
+    }
+
+    #[test]
+    fn test_seek_to_timestamp_boundary_line_not_first() {
+        // Regression test: the boundary line (the first with ts >= target)
+        // must not be the first line in the file, so a probe landing inside
+        // the preceding dated line actually reads that line's own
+        // timestamp instead of being skipped over.
+        let dir = tempfile::tempdir().unwrap();
+        let line1 = r#"{"type":"user","uuid":"u1","timestamp":"2026-01-01T00:00:00Z","message":{"content":"first"}}"#;
+        let line2 = r#"{"type":"user","uuid":"u2","timestamp":"2026-01-01T00:05:00Z","message":{"content":"second"}}"#;
+        let line3 = r#"{"type":"user","uuid":"u3","timestamp":"2026-01-01T00:10:00Z","message":{"content":"third"}}"#;
+        let line4 = r#"{"type":"user","uuid":"u4","timestamp":"2026-01-01T00:15:00Z","message":{"content":"fourth"}}"#;
+        let path = make_jsonl_file(dir.path(), "test-session.jsonl", &[line1, line2, line3, line4]);
+
+        let target = parse_timestamp("2026-01-01T00:10:00Z").unwrap();
+        let offset = seek_to_timestamp(&path, target).unwrap();
+
+        let result = parse_session_file(&path, offset).unwrap();
+        assert_eq!(result.events.len(), 2);
+        assert_eq!(result.events[0].content_text.as_deref(), Some("third"));
+        assert_eq!(result.events[1].content_text.as_deref(), Some("fourth"));
+    }
+
+    #[test]
+    fn test_seek_to_timestamp_returns_file_size_when_target_is_after_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        let line1 = r#"{"type":"user","uuid":"u1","timestamp":"2026-01-01T00:00:00Z","message":{"content":"first"}}"#;
+        let path = make_jsonl_file(dir.path(), "test-session.jsonl", &[line1]);
+        let file_size = std::fs::metadata(&path).unwrap().len();
+
+        let target = parse_timestamp("2026-01-02T00:00:00Z").unwrap();
+        let offset = seek_to_timestamp(&path, target).unwrap();
+        assert_eq!(offset, file_size);
+    }
+
+    #[test]
+    fn test_seek_to_timestamp_returns_zero_when_target_is_before_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        let line1 = r#"{"type":"user","uuid":"u1","timestamp":"2026-01-01T00:00:00Z","message":{"content":"first"}}"#;
+        let path = make_jsonl_file(dir.path(), "test-session.jsonl", &[line1]);
+
+        let target = parse_timestamp("2025-01-01T00:00:00Z").unwrap();
+        let offset = seek_to_timestamp(&path, target).unwrap();
+        assert_eq!(offset, 0);
+    }
+
+    #[test]
+    fn test_seek_to_timestamp_ignores_partial_trailing_line() {
+        // A writer still mid-flush can leave a truncated final line with no
+        // trailing `\n` — seek_to_timestamp must treat it the same way
+        // parse_byte_range does: never a candidate, never advanced past.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test-session.jsonl");
+        let line1 = r#"{"type":"user","uuid":"u1","timestamp":"2026-01-01T00:00:00Z","message":{"content":"first"}}"#;
+        let line2 = r#"{"type":"user","uuid":"u2","timestamp":"2026-01-01T00:10:00Z","message":{"content":"second"}}"#;
+        let partial = r#"{"type":"user","uuid":"u3","timestamp":"2026-01-01T00:"#;
+        std::fs::write(&path, format!("{line1}\n{line2}\n{partial}")).unwrap();
+
+        let target = parse_timestamp("2026-01-01T00:05:00Z").unwrap();
+        let offset = seek_to_timestamp(&path, target).unwrap();
+
+        let result = parse_session_file(&path, offset).unwrap();
+        assert_eq!(result.events.len(), 1);
+        assert_eq!(result.events[0].content_text.as_deref(), Some("second"));
+    }
+
+    #[test]
+    fn test_seek_to_timestamp_ignores_trailing_line_missing_newline() {
+        // Unlike the truncated-mid-object case above, a writer can also
+        // flush a *complete* JSON object and not yet have appended its `\n`.
+        // That line is fully parseable — including its timestamp — but
+        // still isn't a stable boundary (the writer could still be about to
+        // append more after it), so it must never be read as a seek
+        // candidate, even though it parses successfully.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test-session.jsonl");
+        let line1 = r#"{"type":"user","uuid":"u1","timestamp":"2026-01-01T00:00:00Z","message":{"content":"first"}}"#;
+        let trailing = r#"{"type":"user","uuid":"u2","timestamp":"2026-01-01T00:10:00Z","message":{"content":"second"}}"#;
+        std::fs::write(&path, format!("{line1}\n{trailing}")).unwrap();
+
+        let target = parse_timestamp("2026-01-01T00:05:00Z").unwrap();
+        let offset = seek_to_timestamp(&path, target).unwrap();
+
+        // The only complete, newline-terminated line is line1, whose
+        // timestamp precedes target — so the trailing line's timestamp must
+        // never be consulted, and the seek must not skip past it.
+        assert!(offset as usize <= format!("{line1}\n").len());
+    }
+
+    #[test]
+    fn test_seek_to_timestamp_exhaustive_targets_terminate_and_agree_with_linear_scan() {
+        // Both prior seek_to_timestamp bugs (an infinite loop when a probe
+        // landed mid-line, and treating a pre-`\n` line as a candidate) only
+        // showed up for *some* binary-search paths through a file, not
+        // every target — so rather than one fixture per bug, probe every
+        // whole minute from 00:00 to 01:39 (each line's own timestamp, the
+        // gaps between lines, and past the last one), and check the result
+        // against a trivial linear scan.
+        let dir = tempfile::tempdir().unwrap();
+        let lines: Vec<String> = (0..9)
+            .map(|i| {
+                format!(
+                    r#"{{"type":"user","uuid":"u{i}","timestamp":"2026-01-01T00:{:02}:00Z","message":{{"content":"line{i}"}}}}"#,
+                    i * 10
+                )
+            })
+            .collect();
+        let line_refs: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let path = make_jsonl_file(dir.path(), "test-session.jsonl", &line_refs);
+
+        let timestamps: Vec<DateTime<Utc>> = (0..9)
+            .map(|i| parse_timestamp(&format!("2026-01-01T00:{:02}:00Z", i * 10)).unwrap())
+            .collect();
+
+        for minute in 0..100 {
+            let target = parse_timestamp(&format!(
+                "2026-01-01T{:02}:{:02}:00Z",
+                minute / 60,
+                minute % 60
+            ))
+            .unwrap();
+
+            // `seek_to_timestamp` must terminate (the old bug could loop
+            // forever) and land exactly where a naive linear scan would.
+            let offset = seek_to_timestamp(&path, target).unwrap();
+            let expected_line = timestamps.iter().position(|ts| *ts >= target);
+            let expected_offset: u64 = match expected_line {
+                Some(idx) => line_refs[..idx]
+                    .iter()
+                    .map(|l| l.len() as u64 + 1)
+                    .sum(),
+                None => std::fs::metadata(&path).unwrap().len(),
+            };
+            assert_eq!(
+                offset, expected_offset,
+                "mismatch for target minute {minute}"
+            );
+        }
+    }
 }
@@ -0,0 +1,1075 @@
+//! Per-provider JSONL line decoding, behind the `SessionFormat` trait.
+//!
+//! Claude Code, Codex, and Gemini each write a structurally different JSONL
+//! line — different key names, different nesting for tool calls/results —
+//! but `pipeline::parser` needs all three to land in the same
+//! `ParsedEvent`/`SessionMetadata` shape. Each format owns its own raw
+//! deserialization types and decoding logic; `parser` only needs to sniff
+//! the right one (via `detect_format`) and then feed it lines one at a time.
+//! This is the same format-abstraction pattern log converters use to
+//! support many on-disk encodings behind one normalized event type.
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use uuid::Uuid;
+
+use super::parser::{parse_timestamp, trim_bytes, ParsedEvent, Role, SessionMetadata};
+
+/// Decodes one provider's JSONL line shape into `ParsedEvent`s/`SessionMetadata`.
+///
+/// Implementors are cheap, stateless, zero-sized marker types — `detect` is
+/// probed against one of the session file's first few non-empty lines (see
+/// `detect_format`), and `process_line` does one `serde_json` parse per
+/// line into the format's own raw struct, same as the single hardcoded
+/// `RawLine` this trait replaced.
+pub trait SessionFormat: Send + Sync {
+    /// Human-readable name, for logging/diagnostics only.
+    #[allow(dead_code)]
+    fn name(&self) -> &'static str;
+
+    /// Cheap probe against one of the session file's opening non-empty
+    /// lines. Must never panic on malformed or foreign-format input — just
+    /// return `false`.
+    fn detect(&self, first_line: &str) -> bool;
+
+    /// Decode one already-trimmed JSONL line: fold any metadata it carries
+    /// into `metadata`/`min_ts`/`max_ts`, and push zero or more
+    /// `ParsedEvent`s onto `events`. A line that fails to parse as this
+    /// format is logged and otherwise ignored — the caller still advances
+    /// past it, same as the old single-format parser did for malformed JSON.
+    #[allow(clippy::too_many_arguments)]
+    fn process_line(
+        &self,
+        line: &str,
+        session_id: &str,
+        line_offset: u64,
+        metadata: &mut SessionMetadata,
+        min_ts: &mut Option<DateTime<Utc>>,
+        max_ts: &mut Option<DateTime<Utc>>,
+        events: &mut Vec<ParsedEvent>,
+    );
+}
+
+/// Registered formats, probed in order against the first non-empty line.
+/// Claude first since it's the overwhelming majority of sessions; Gemini
+/// before Codex since Gemini's `parts` field lets it be detected precisely,
+/// while Codex's probe (`role` alone) would otherwise also match Gemini.
+fn formats() -> &'static [&'static dyn SessionFormat] {
+    static CLAUDE: ClaudeFormat = ClaudeFormat;
+    static GEMINI: GeminiFormat = GeminiFormat;
+    static CODEX: CodexFormat = CodexFormat;
+    &[&CLAUDE, &GEMINI, &CODEX]
+}
+
+/// Number of leading non-empty lines `detect_format` will probe before
+/// giving up. Claude Code session files routinely open with one or more
+/// metadata-only rows (`summary`, `file-history-snapshot`, `progress`) that
+/// carry no `message` field and so fail every format's `detect` — a single
+/// first-line probe would wrongly treat those files as unrecognized.
+const DETECT_PROBE_LINES: usize = 5;
+
+/// Sniff the first few non-empty lines in `data` to pick a `SessionFormat`,
+/// falling back to Claude (the original hardcoded behavior) if none of them
+/// match any registered format — a session file with no recognizable line
+/// in its opening run is far more likely to be a Claude edge case than a
+/// genuinely unsupported format.
+pub fn detect_format(data: &[u8]) -> &'static dyn SessionFormat {
+    let mut probed = 0;
+    for line in data.split(|&b| b == b'\n') {
+        if probed >= DETECT_PROBE_LINES {
+            break;
+        }
+        let trimmed = trim_bytes(line);
+        if trimmed.is_empty() {
+            continue;
+        }
+        if let Ok(s) = std::str::from_utf8(trimmed) {
+            if let Some(fmt) = formats().iter().find(|f| f.detect(s)) {
+                return *fmt;
+            }
+        }
+        probed += 1;
+    }
+    formats()[0]
+}
+
+// ---------------------------------------------------------------------------
+// Claude Code
+// ---------------------------------------------------------------------------
+
+/// Claude Code's `~/.claude/projects/*/*.jsonl` transcript format: a
+/// top-level `message: { content }` where `content` is either a plain
+/// string or an array of typed content blocks (`text`, `tool_use`,
+/// `tool_result`).
+struct ClaudeFormat;
+
+#[derive(Deserialize)]
+struct ClaudeRawLine {
+    r#type: Option<String>,
+    timestamp: Option<String>,
+    uuid: Option<String>,
+    cwd: Option<String>,
+    #[serde(rename = "gitBranch")]
+    git_branch: Option<String>,
+    version: Option<String>,
+    message: Option<ClaudeRawMessage>,
+}
+
+#[derive(Deserialize)]
+struct ClaudeRawMessage {
+    /// Kept as raw JSON — avoids building a full serde_json::Value DOM tree.
+    content: Box<RawValue>,
+}
+
+/// Targeted deserialization of a single content array item. Only the
+/// fields we actually use are extracted; everything else is skipped.
+#[derive(Deserialize)]
+struct ClaudeContentItem {
+    r#type: Option<String>,
+    /// Text content (for "text" items)
+    text: Option<String>,
+    /// Tool name (for "tool_use" items)
+    name: Option<String>,
+    /// Tool call ID (for "tool_use" items)
+    id: Option<String>,
+    /// Tool input — kept as raw JSON, never parsed into a Value tree.
+    input: Option<Box<RawValue>>,
+    /// Tool use ID (for "tool_result" items)
+    tool_use_id: Option<String>,
+    /// Tool result content — kept as raw JSON, parsed lazily for text extraction.
+    #[serde(rename = "content")]
+    result_content: Option<Box<RawValue>>,
+}
+
+impl SessionFormat for ClaudeFormat {
+    fn name(&self) -> &'static str {
+        "claude"
+    }
+
+    fn detect(&self, first_line: &str) -> bool {
+        #[derive(Deserialize)]
+        struct Probe {
+            message: serde::de::IgnoredAny,
+        }
+        serde_json::from_str::<Probe>(first_line).is_ok()
+    }
+
+    fn process_line(
+        &self,
+        line: &str,
+        session_id: &str,
+        line_offset: u64,
+        metadata: &mut SessionMetadata,
+        min_ts: &mut Option<DateTime<Utc>>,
+        max_ts: &mut Option<DateTime<Utc>>,
+        events: &mut Vec<ParsedEvent>,
+    ) {
+        let obj: ClaudeRawLine = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::warn!(offset = line_offset, error = %e, "Failed to parse Claude JSON line");
+                return;
+            }
+        };
+
+        if metadata.cwd.is_none() {
+            if let Some(ref cwd) = obj.cwd {
+                metadata.cwd = Some(cwd.clone());
+            }
+        }
+        if metadata.git_branch.is_none() {
+            if let Some(ref branch) = obj.git_branch {
+                metadata.git_branch = Some(branch.clone());
+            }
+        }
+        if metadata.version.is_none() {
+            if let Some(ref ver) = obj.version {
+                metadata.version = Some(ver.clone());
+            }
+        }
+        if let Some(ts) = obj.timestamp.as_deref().and_then(parse_timestamp) {
+            update_ts_range(min_ts, max_ts, ts);
+        }
+
+        let event_type = obj.r#type.as_deref().unwrap_or("");
+        match event_type {
+            "summary" | "file-history-snapshot" | "progress" => return,
+            _ => {}
+        }
+
+        let timestamp = obj
+            .timestamp
+            .as_deref()
+            .and_then(parse_timestamp)
+            .unwrap_or_else(Utc::now);
+
+        let msg_uuid = obj.uuid.as_deref().unwrap_or("").to_string();
+        let msg_uuid = if msg_uuid.is_empty() {
+            Uuid::new_v4().to_string()
+        } else {
+            msg_uuid
+        };
+
+        let content_raw = match &obj.message {
+            Some(m) => &m.content,
+            None => return,
+        };
+        let content_str = content_raw.get();
+
+        match event_type {
+            "user" => claude_extract_user_events(
+                content_str,
+                session_id,
+                &msg_uuid,
+                timestamp,
+                line_offset,
+                line,
+                events,
+            ),
+            "assistant" => claude_extract_assistant_events(
+                content_str,
+                session_id,
+                &msg_uuid,
+                timestamp,
+                line_offset,
+                line,
+                events,
+            ),
+            _ => {
+                // Unknown type — skip
+            }
+        }
+    }
+}
+
+fn claude_extract_user_events(
+    content_str: &str,
+    session_id: &str,
+    msg_uuid: &str,
+    timestamp: DateTime<Utc>,
+    line_offset: u64,
+    raw_line: &str,
+    events: &mut Vec<ParsedEvent>,
+) {
+    if let Ok(items) = serde_json::from_str::<Vec<ClaudeContentItem>>(content_str) {
+        let has_tool_result = items
+            .iter()
+            .any(|item| item.r#type.as_deref() == Some("tool_result"));
+
+        if has_tool_result {
+            claude_extract_tool_results_from_items(
+                &items,
+                session_id,
+                msg_uuid,
+                timestamp,
+                line_offset,
+                raw_line,
+                events,
+            );
+        } else {
+            let text = claude_extract_user_content_from_items(&items);
+            if let Some(text) = text {
+                if !text.trim().is_empty() {
+                    events.push(ParsedEvent {
+                        uuid: msg_uuid.to_string(),
+                        session_id: session_id.to_string(),
+                        timestamp,
+                        role: Role::User,
+                        content_text: Some(text),
+                        tool_name: None,
+                        tool_input_json: None,
+                        tool_output_text: None,
+                        tool_call_id: None,
+                        source_offset: line_offset,
+                        raw_type: "user".to_string(),
+                        raw_line: Some(raw_line.to_string()),
+                    });
+                }
+            }
+        }
+    } else if let Ok(text) = serde_json::from_str::<String>(content_str) {
+        if !text.trim().is_empty() {
+            events.push(ParsedEvent {
+                uuid: msg_uuid.to_string(),
+                session_id: session_id.to_string(),
+                timestamp,
+                role: Role::User,
+                content_text: Some(text),
+                tool_name: None,
+                tool_input_json: None,
+                tool_output_text: None,
+                tool_call_id: None,
+                source_offset: line_offset,
+                raw_type: "user".to_string(),
+                raw_line: Some(raw_line.to_string()),
+            });
+        }
+    }
+}
+
+fn claude_extract_assistant_events(
+    content_str: &str,
+    session_id: &str,
+    msg_uuid: &str,
+    timestamp: DateTime<Utc>,
+    line_offset: u64,
+    raw_line: &str,
+    events: &mut Vec<ParsedEvent>,
+) {
+    let items: Vec<ClaudeContentItem> = match serde_json::from_str(content_str) {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+
+    let mut first = true;
+    for (idx, item) in items.iter().enumerate() {
+        let item_type = item.r#type.as_deref().unwrap_or("");
+
+        match item_type {
+            "text" => {
+                let text = item.text.as_deref().unwrap_or("");
+                if !text.trim().is_empty() {
+                    events.push(ParsedEvent {
+                        uuid: format!("{}-text-{}", msg_uuid, idx),
+                        session_id: session_id.to_string(),
+                        timestamp,
+                        role: Role::Assistant,
+                        content_text: Some(text.to_string()),
+                        tool_name: None,
+                        tool_input_json: None,
+                        tool_output_text: None,
+                        tool_call_id: None,
+                        source_offset: line_offset,
+                        raw_type: "assistant".to_string(),
+                        raw_line: if first {
+                            first = false;
+                            Some(raw_line.to_string())
+                        } else {
+                            None
+                        },
+                    });
+                }
+            }
+            "tool_use" => {
+                let tool_name = item.name.as_deref().unwrap_or("").to_string();
+                let tool_id = item.id.as_deref().unwrap_or("");
+                let uuid_suffix = if tool_id.is_empty() {
+                    format!("{}", idx)
+                } else {
+                    tool_id.to_string()
+                };
+
+                let tool_input = item.input.as_ref().and_then(|raw| {
+                    let s = raw.get().trim();
+                    if s.starts_with('{') {
+                        Some(raw.clone())
+                    } else {
+                        None
+                    }
+                });
+
+                events.push(ParsedEvent {
+                    uuid: format!("{}-tool-{}", msg_uuid, uuid_suffix),
+                    session_id: session_id.to_string(),
+                    timestamp,
+                    role: Role::Assistant,
+                    content_text: None,
+                    tool_name: Some(tool_name),
+                    tool_input_json: tool_input,
+                    tool_output_text: None,
+                    tool_call_id: if tool_id.is_empty() {
+                        None
+                    } else {
+                        Some(tool_id.to_string())
+                    },
+                    source_offset: line_offset,
+                    raw_type: "assistant".to_string(),
+                    raw_line: if first {
+                        first = false;
+                        Some(raw_line.to_string())
+                    } else {
+                        None
+                    },
+                });
+            }
+            _ => {
+                // thinking, etc. — skip
+            }
+        }
+    }
+}
+
+fn claude_extract_tool_results_from_items(
+    items: &[ClaudeContentItem],
+    session_id: &str,
+    msg_uuid: &str,
+    timestamp: DateTime<Utc>,
+    line_offset: u64,
+    raw_line: &str,
+    events: &mut Vec<ParsedEvent>,
+) {
+    let mut first = true;
+    for (idx, item) in items.iter().enumerate() {
+        if item.r#type.as_deref() != Some("tool_result") {
+            continue;
+        }
+
+        let tool_use_id = item.tool_use_id.as_deref().unwrap_or("");
+        let uuid_suffix = if tool_use_id.is_empty() {
+            format!("{}", idx)
+        } else {
+            tool_use_id.to_string()
+        };
+
+        let result_text = item
+            .result_content
+            .as_ref()
+            .and_then(|raw| extract_text_from_raw_content(raw.get()));
+
+        if let Some(text) = result_text {
+            if !text.is_empty() {
+                events.push(ParsedEvent {
+                    uuid: format!("{}-result-{}", msg_uuid, uuid_suffix),
+                    session_id: session_id.to_string(),
+                    timestamp,
+                    role: Role::Tool,
+                    content_text: None,
+                    tool_name: None,
+                    tool_input_json: None,
+                    tool_output_text: Some(text),
+                    tool_call_id: if tool_use_id.is_empty() {
+                        None
+                    } else {
+                        Some(tool_use_id.to_string())
+                    },
+                    source_offset: line_offset,
+                    raw_type: "tool_result".to_string(),
+                    raw_line: if first {
+                        first = false;
+                        Some(raw_line.to_string())
+                    } else {
+                        None
+                    },
+                });
+            }
+        }
+    }
+}
+
+fn claude_extract_user_content_from_items(items: &[ClaudeContentItem]) -> Option<String> {
+    let mut parts = Vec::new();
+    for item in items {
+        match item.r#type.as_deref() {
+            Some("text") => {
+                if let Some(ref text) = item.text {
+                    parts.push(text.clone());
+                }
+            }
+            Some("tool_result") => {
+                if let Some(ref raw) = item.result_content {
+                    if let Some(text) = extract_text_from_raw_content(raw.get()) {
+                        parts.push(text);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("\n"))
+    }
+}
+
+/// Extract text from a raw JSON content field (tool_result content).
+/// Handles: plain string, array of {type: "text", text: "..."}, or
+/// fallback to the raw JSON itself. Shared by Claude's tool_result
+/// extraction and Codex's tool-role content, which use the same shapes.
+fn extract_text_from_raw_content(raw_json: &str) -> Option<String> {
+    let trimmed = raw_json.trim();
+
+    if trimmed.starts_with('"') {
+        if let Ok(s) = serde_json::from_str::<String>(trimmed) {
+            return Some(s);
+        }
+    }
+
+    if trimmed.starts_with('[') {
+        #[derive(Deserialize)]
+        struct TextPart {
+            r#type: Option<String>,
+            text: Option<String>,
+        }
+
+        if let Ok(parts) = serde_json::from_str::<Vec<TextPart>>(trimmed) {
+            let mut texts = Vec::new();
+            for part in &parts {
+                if part.r#type.as_deref() == Some("text") {
+                    if let Some(ref text) = part.text {
+                        texts.push(text.clone());
+                    }
+                }
+            }
+            if texts.is_empty() {
+                return None;
+            }
+            return Some(texts.join("\n"));
+        }
+    }
+
+    Some(trimmed.to_string())
+}
+
+fn update_ts_range(min_ts: &mut Option<DateTime<Utc>>, max_ts: &mut Option<DateTime<Utc>>, ts: DateTime<Utc>) {
+    match min_ts {
+        Some(ref existing) if ts < *existing => *min_ts = Some(ts),
+        None => *min_ts = Some(ts),
+        _ => {}
+    }
+    match max_ts {
+        Some(ref existing) if ts > *existing => *max_ts = Some(ts),
+        None => *max_ts = Some(ts),
+        _ => {}
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Codex
+// ---------------------------------------------------------------------------
+
+/// OpenAI Codex CLI's JSONL format: a flat `role`/`content` pair (content a
+/// plain string for `user`/`assistant`/`tool` turns) plus a single
+/// `function_call` or a `tool_calls` array for assistant tool invocations.
+struct CodexFormat;
+
+#[derive(Deserialize)]
+struct CodexRawLine {
+    timestamp: Option<String>,
+    id: Option<String>,
+    cwd: Option<String>,
+    version: Option<String>,
+    role: Option<String>,
+    content: Option<Box<RawValue>>,
+    function_call: Option<CodexFunctionCall>,
+    tool_calls: Option<Vec<CodexToolCall>>,
+    tool_call_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CodexFunctionCall {
+    name: Option<String>,
+    arguments: Option<Box<RawValue>>,
+}
+
+#[derive(Deserialize)]
+struct CodexToolCall {
+    id: Option<String>,
+    function: Option<CodexFunctionCall>,
+}
+
+impl SessionFormat for CodexFormat {
+    fn name(&self) -> &'static str {
+        "codex"
+    }
+
+    fn detect(&self, first_line: &str) -> bool {
+        #[derive(Deserialize)]
+        struct Probe {
+            role: String,
+        }
+        serde_json::from_str::<Probe>(first_line).is_ok()
+    }
+
+    fn process_line(
+        &self,
+        line: &str,
+        session_id: &str,
+        line_offset: u64,
+        metadata: &mut SessionMetadata,
+        min_ts: &mut Option<DateTime<Utc>>,
+        max_ts: &mut Option<DateTime<Utc>>,
+        events: &mut Vec<ParsedEvent>,
+    ) {
+        let obj: CodexRawLine = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::warn!(offset = line_offset, error = %e, "Failed to parse Codex JSON line");
+                return;
+            }
+        };
+
+        if metadata.cwd.is_none() {
+            if let Some(ref cwd) = obj.cwd {
+                metadata.cwd = Some(cwd.clone());
+            }
+        }
+        if metadata.version.is_none() {
+            if let Some(ref ver) = obj.version {
+                metadata.version = Some(ver.clone());
+            }
+        }
+        if let Some(ts) = obj.timestamp.as_deref().and_then(parse_timestamp) {
+            update_ts_range(min_ts, max_ts, ts);
+        }
+
+        let timestamp = obj
+            .timestamp
+            .as_deref()
+            .and_then(parse_timestamp)
+            .unwrap_or_else(Utc::now);
+        let msg_uuid = obj.id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        let role = match obj.role.as_deref() {
+            Some("user") => Role::User,
+            Some("assistant") => Role::Assistant,
+            Some("tool") => Role::Tool,
+            _ => return, // "system", or unrecognized — skip
+        };
+
+        let content_text = obj
+            .content
+            .as_ref()
+            .and_then(|raw| extract_text_from_raw_content(raw.get()))
+            .filter(|t| !t.trim().is_empty());
+
+        let mut first = true;
+
+        match role {
+            Role::User => {
+                if let Some(text) = content_text {
+                    events.push(ParsedEvent {
+                        uuid: msg_uuid,
+                        session_id: session_id.to_string(),
+                        timestamp,
+                        role: Role::User,
+                        content_text: Some(text),
+                        tool_name: None,
+                        tool_input_json: None,
+                        tool_output_text: None,
+                        tool_call_id: None,
+                        source_offset: line_offset,
+                        raw_type: "user".to_string(),
+                        raw_line: Some(line.to_string()),
+                    });
+                }
+            }
+            Role::Tool => {
+                if let Some(text) = content_text {
+                    let suffix = obj.tool_call_id.clone().unwrap_or_default();
+                    events.push(ParsedEvent {
+                        uuid: if suffix.is_empty() {
+                            format!("{}-result", msg_uuid)
+                        } else {
+                            format!("{}-result-{}", msg_uuid, suffix)
+                        },
+                        session_id: session_id.to_string(),
+                        timestamp,
+                        role: Role::Tool,
+                        content_text: None,
+                        tool_name: None,
+                        tool_input_json: None,
+                        tool_output_text: Some(text),
+                        tool_call_id: obj.tool_call_id.clone(),
+                        source_offset: line_offset,
+                        raw_type: "tool_result".to_string(),
+                        raw_line: Some(line.to_string()),
+                    });
+                }
+            }
+            Role::Assistant => {
+                if let Some(text) = content_text {
+                    events.push(ParsedEvent {
+                        uuid: format!("{}-text", msg_uuid),
+                        session_id: session_id.to_string(),
+                        timestamp,
+                        role: Role::Assistant,
+                        content_text: Some(text),
+                        tool_name: None,
+                        tool_input_json: None,
+                        tool_output_text: None,
+                        tool_call_id: None,
+                        source_offset: line_offset,
+                        raw_type: "assistant".to_string(),
+                        raw_line: if first {
+                            first = false;
+                            Some(line.to_string())
+                        } else {
+                            None
+                        },
+                    });
+                }
+
+                let calls: Vec<(&str, &CodexFunctionCall)> = obj
+                    .function_call
+                    .as_ref()
+                    .map(|c| vec![("", c)])
+                    .unwrap_or_default()
+                    .into_iter()
+                    .chain(obj.tool_calls.iter().flatten().filter_map(|tc| {
+                        tc.function
+                            .as_ref()
+                            .map(|f| (tc.id.as_deref().unwrap_or(""), f))
+                    }))
+                    .collect();
+
+                for (idx, (call_id, call)) in calls.iter().enumerate() {
+                    let tool_name = call.name.clone().unwrap_or_default();
+                    let suffix = if call_id.is_empty() {
+                        format!("{}", idx)
+                    } else {
+                        call_id.to_string()
+                    };
+                    events.push(ParsedEvent {
+                        uuid: format!("{}-tool-{}", msg_uuid, suffix),
+                        session_id: session_id.to_string(),
+                        timestamp,
+                        role: Role::Assistant,
+                        content_text: None,
+                        tool_name: Some(tool_name),
+                        tool_input_json: call.arguments.clone(),
+                        tool_output_text: None,
+                        tool_call_id: if call_id.is_empty() {
+                            None
+                        } else {
+                            Some(call_id.to_string())
+                        },
+                        source_offset: line_offset,
+                        raw_type: "assistant".to_string(),
+                        raw_line: if first {
+                            first = false;
+                            Some(line.to_string())
+                        } else {
+                            None
+                        },
+                    });
+                }
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Gemini
+// ---------------------------------------------------------------------------
+
+/// Gemini CLI's JSONL format: a top-level `role` (`user`/`model`) with a
+/// `parts` array, each part either plain `text`, a `functionCall`
+/// (assistant tool invocation), or a `functionResponse` (tool result).
+struct GeminiFormat;
+
+#[derive(Deserialize)]
+struct GeminiRawLine {
+    timestamp: Option<String>,
+    cwd: Option<String>,
+    version: Option<String>,
+    role: Option<String>,
+    parts: Option<Vec<GeminiPart>>,
+}
+
+#[derive(Deserialize)]
+struct GeminiPart {
+    text: Option<String>,
+    #[serde(rename = "functionCall")]
+    function_call: Option<GeminiFunctionCall>,
+    #[serde(rename = "functionResponse")]
+    function_response: Option<GeminiFunctionResponse>,
+}
+
+#[derive(Deserialize)]
+struct GeminiFunctionCall {
+    name: Option<String>,
+    args: Option<Box<RawValue>>,
+}
+
+#[derive(Deserialize)]
+struct GeminiFunctionResponse {
+    name: Option<String>,
+    response: Option<Box<RawValue>>,
+}
+
+impl SessionFormat for GeminiFormat {
+    fn name(&self) -> &'static str {
+        "gemini"
+    }
+
+    fn detect(&self, first_line: &str) -> bool {
+        #[derive(Deserialize)]
+        struct Probe {
+            role: String,
+            parts: serde::de::IgnoredAny,
+        }
+        serde_json::from_str::<Probe>(first_line).is_ok()
+    }
+
+    fn process_line(
+        &self,
+        line: &str,
+        session_id: &str,
+        line_offset: u64,
+        metadata: &mut SessionMetadata,
+        min_ts: &mut Option<DateTime<Utc>>,
+        max_ts: &mut Option<DateTime<Utc>>,
+        events: &mut Vec<ParsedEvent>,
+    ) {
+        let obj: GeminiRawLine = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::warn!(offset = line_offset, error = %e, "Failed to parse Gemini JSON line");
+                return;
+            }
+        };
+
+        if metadata.cwd.is_none() {
+            if let Some(ref cwd) = obj.cwd {
+                metadata.cwd = Some(cwd.clone());
+            }
+        }
+        if metadata.version.is_none() {
+            if let Some(ref ver) = obj.version {
+                metadata.version = Some(ver.clone());
+            }
+        }
+        if let Some(ts) = obj.timestamp.as_deref().and_then(parse_timestamp) {
+            update_ts_range(min_ts, max_ts, ts);
+        }
+
+        let timestamp = obj
+            .timestamp
+            .as_deref()
+            .and_then(parse_timestamp)
+            .unwrap_or_else(Utc::now);
+
+        let role = match obj.role.as_deref() {
+            Some("user") => Role::User,
+            Some("model") => Role::Assistant,
+            _ => return,
+        };
+
+        let msg_uuid = Uuid::new_v4().to_string();
+        let parts = match obj.parts {
+            Some(p) => p,
+            None => return,
+        };
+
+        let mut first = true;
+        for (idx, part) in parts.iter().enumerate() {
+            if let Some(ref text) = part.text {
+                if !text.trim().is_empty() {
+                    events.push(ParsedEvent {
+                        uuid: format!("{}-text-{}", msg_uuid, idx),
+                        session_id: session_id.to_string(),
+                        timestamp,
+                        role: role.clone(),
+                        content_text: Some(text.clone()),
+                        tool_name: None,
+                        tool_input_json: None,
+                        tool_output_text: None,
+                        tool_call_id: None,
+                        source_offset: line_offset,
+                        raw_type: match &role {
+                            Role::User => "user".to_string(),
+                            _ => "assistant".to_string(),
+                        },
+                        raw_line: if first {
+                            first = false;
+                            Some(line.to_string())
+                        } else {
+                            None
+                        },
+                    });
+                }
+            }
+            if let Some(ref call) = part.function_call {
+                // Gemini's functionCall/functionResponse parts carry no id —
+                // the tool name is the only correlator available for this
+                // provider.
+                events.push(ParsedEvent {
+                    uuid: format!("{}-tool-{}", msg_uuid, idx),
+                    session_id: session_id.to_string(),
+                    timestamp,
+                    role: Role::Assistant,
+                    content_text: None,
+                    tool_name: Some(call.name.clone().unwrap_or_default()),
+                    tool_input_json: call.args.clone(),
+                    tool_output_text: None,
+                    tool_call_id: call.name.clone(),
+                    source_offset: line_offset,
+                    raw_type: "assistant".to_string(),
+                    raw_line: if first {
+                        first = false;
+                        Some(line.to_string())
+                    } else {
+                        None
+                    },
+                });
+            }
+            if let Some(ref response) = part.function_response {
+                let text = response
+                    .response
+                    .as_ref()
+                    .and_then(|raw| extract_text_from_raw_content(raw.get()));
+                if let Some(text) = text {
+                    if !text.is_empty() {
+                        events.push(ParsedEvent {
+                            uuid: format!("{}-result-{}", msg_uuid, idx),
+                            session_id: session_id.to_string(),
+                            timestamp,
+                            role: Role::Tool,
+                            content_text: None,
+                            tool_name: None,
+                            tool_input_json: None,
+                            tool_output_text: Some(text),
+                            tool_call_id: response.name.clone(),
+                            source_offset: line_offset,
+                            raw_type: "tool_result".to_string(),
+                            raw_line: if first {
+                                first = false;
+                                Some(line.to_string())
+                            } else {
+                                None
+                            },
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(format: &dyn SessionFormat, line: &str) -> (SessionMetadata, Vec<ParsedEvent>) {
+        let mut metadata = SessionMetadata {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+        let mut min_ts = None;
+        let mut max_ts = None;
+        let mut events = Vec::new();
+        format.process_line(line, "s1", 0, &mut metadata, &mut min_ts, &mut max_ts, &mut events);
+        (metadata, events)
+    }
+
+    #[test]
+    fn test_detect_claude() {
+        let line = r#"{"type":"user","uuid":"u1","message":{"content":"hi"}}"#;
+        assert!(formats()[0].detect(line));
+        assert_eq!(detect_format(line.as_bytes()).name(), "claude");
+    }
+
+    #[test]
+    fn test_detect_codex() {
+        let line = r#"{"role":"user","content":"hi"}"#;
+        assert_eq!(detect_format(line.as_bytes()).name(), "codex");
+    }
+
+    #[test]
+    fn test_detect_gemini() {
+        let line = r#"{"role":"user","parts":[{"text":"hi"}]}"#;
+        assert_eq!(detect_format(line.as_bytes()).name(), "gemini");
+    }
+
+    #[test]
+    fn test_detect_falls_back_to_claude() {
+        let line = r#"{"unrelated":true}"#;
+        assert_eq!(detect_format(line.as_bytes()).name(), "claude");
+    }
+
+    #[test]
+    fn test_detect_skips_leading_metadata_lines() {
+        // Claude Code sessions often open with a summary/progress row that
+        // has no `message` field, before the first real message line.
+        let data = concat!(
+            r#"{"type":"summary","timestamp":"2026-01-01T00:00:00Z"}"#,
+            "\n",
+            r#"{"type":"file-history-snapshot","timestamp":"2026-01-01T00:00:01Z"}"#,
+            "\n",
+            r#"{"role":"user","parts":[{"text":"hi"}]}"#,
+        );
+        assert_eq!(detect_format(data.as_bytes()).name(), "gemini");
+    }
+
+    #[test]
+    fn test_detect_probes_up_to_but_not_past_probe_line_limit() {
+        // DETECT_PROBE_LINES is 5 non-empty lines: a match on line 5 is
+        // still found, but one that only shows up on line 6 falls back to
+        // Claude instead — regression coverage for the literal probe count,
+        // not just "more than one leading line is skipped".
+        let unrelated = r#"{"unrelated":true}"#;
+        let gemini = r#"{"role":"user","parts":[{"text":"hi"}]}"#;
+
+        let matches_on_line_5 = vec![unrelated; 4]
+            .into_iter()
+            .chain(std::iter::once(gemini))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(detect_format(matches_on_line_5.as_bytes()).name(), "gemini");
+
+        let matches_on_line_6 = vec![unrelated; 5]
+            .into_iter()
+            .chain(std::iter::once(gemini))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(detect_format(matches_on_line_6.as_bytes()).name(), "claude");
+    }
+
+    #[test]
+    fn test_codex_user_message() {
+        let (_, events) = run(&CodexFormat, r#"{"role":"user","content":"hello codex"}"#);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].role, Role::User);
+        assert_eq!(events[0].content_text.as_deref(), Some("hello codex"));
+    }
+
+    #[test]
+    fn test_codex_function_call() {
+        let (_, events) = run(
+            &CodexFormat,
+            r#"{"role":"assistant","function_call":{"name":"Read","arguments":{"path":"/tmp/a"}}}"#,
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].tool_name.as_deref(), Some("Read"));
+    }
+
+    #[test]
+    fn test_codex_tool_result() {
+        let (_, events) = run(
+            &CodexFormat,
+            r#"{"role":"tool","tool_call_id":"t1","content":"file contents"}"#,
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].role, Role::Tool);
+        assert_eq!(events[0].tool_output_text.as_deref(), Some("file contents"));
+    }
+
+    #[test]
+    fn test_gemini_text_and_function_call() {
+        let (_, events) = run(
+            &GeminiFormat,
+            r#"{"role":"model","parts":[{"text":"let me check"},{"functionCall":{"name":"Read","args":{"path":"/tmp/a"}}}]}"#,
+        );
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].role, Role::Assistant);
+        assert_eq!(events[0].content_text.as_deref(), Some("let me check"));
+        assert_eq!(events[1].tool_name.as_deref(), Some("Read"));
+    }
+
+    #[test]
+    fn test_gemini_function_response() {
+        let (_, events) = run(
+            &GeminiFormat,
+            r#"{"role":"user","parts":[{"functionResponse":{"name":"Read","response":"file contents"}}]}"#,
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].role, Role::Tool);
+        assert_eq!(events[0].tool_output_text.as_deref(), Some("file contents"));
+    }
+}
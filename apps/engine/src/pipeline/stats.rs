@@ -0,0 +1,143 @@
+//! Frequency-analysis rollup over a parsed session's events.
+//!
+//! The frequency-analysis subcommand pattern from log-cruncher tools,
+//! adapted to agent-session events: callers that just want a dashboard
+//! summary (event mix, tool usage, response cadence) shouldn't have to walk
+//! `ParsedEvent`s themselves.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::parser::{ParsedEvent, Role};
+
+#[derive(Debug, Clone, Default)]
+pub struct SessionStats {
+    pub user_count: usize,
+    pub assistant_count: usize,
+    pub tool_count: usize,
+    /// Tool name -> number of `tool_use` calls seen.
+    pub tool_usage: HashMap<String, usize>,
+    pub assistant_char_count: usize,
+    /// Mean gap between consecutive events' timestamps, `None` if fewer
+    /// than two events.
+    pub mean_gap: Option<Duration>,
+    /// 95th-percentile gap between consecutive events' timestamps, `None`
+    /// if fewer than two events.
+    pub p95_gap: Option<Duration>,
+    /// `max(timestamp) - min(timestamp)` across all events, `None` if
+    /// `events` is empty.
+    pub wall_clock_span: Option<Duration>,
+}
+
+/// Roll `events` up into [`SessionStats`]. Events are assumed to already be
+/// in source order (as `pipeline::parser` produces them) for the
+/// consecutive-gap calculation — it is not itself sorted by timestamp.
+pub fn compute_stats(events: &[ParsedEvent]) -> SessionStats {
+    let mut stats = SessionStats::default();
+
+    for event in events {
+        match event.role {
+            Role::User => stats.user_count += 1,
+            Role::Assistant => {
+                stats.assistant_count += 1;
+                if let Some(ref text) = event.content_text {
+                    stats.assistant_char_count += text.chars().count();
+                }
+            }
+            Role::Tool => stats.tool_count += 1,
+        }
+
+        if let Some(ref name) = event.tool_name {
+            *stats.tool_usage.entry(name.clone()).or_insert(0) += 1;
+        }
+    }
+
+    let mut gaps: Vec<Duration> = events
+        .windows(2)
+        .filter_map(|pair| (pair[1].timestamp - pair[0].timestamp).to_std().ok())
+        .collect();
+
+    if !gaps.is_empty() {
+        let total: Duration = gaps.iter().sum();
+        stats.mean_gap = Some(total / gaps.len() as u32);
+
+        gaps.sort();
+        let p95_idx = (((gaps.len() - 1) as f64) * 0.95).round() as usize;
+        stats.p95_gap = Some(gaps[p95_idx]);
+    }
+
+    let min_ts = events.iter().map(|e| e.timestamp).min();
+    let max_ts = events.iter().map(|e| e.timestamp).max();
+    if let (Some(min), Some(max)) = (min_ts, max_ts) {
+        stats.wall_clock_span = (max - min).to_std().ok();
+    }
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+
+    fn event_at(role: Role, timestamp: DateTime<Utc>, tool_name: Option<&str>) -> ParsedEvent {
+        ParsedEvent {
+            uuid: "e".to_string(),
+            session_id: "s1".to_string(),
+            timestamp,
+            role,
+            content_text: Some("hello".to_string()),
+            tool_name: tool_name.map(|s| s.to_string()),
+            tool_input_json: None,
+            tool_output_text: None,
+            tool_call_id: None,
+            source_offset: 0,
+            raw_type: "x".to_string(),
+            raw_line: None,
+        }
+    }
+
+    #[test]
+    fn test_empty_events() {
+        let stats = compute_stats(&[]);
+        assert_eq!(stats.user_count, 0);
+        assert!(stats.mean_gap.is_none());
+        assert!(stats.p95_gap.is_none());
+        assert!(stats.wall_clock_span.is_none());
+    }
+
+    #[test]
+    fn test_counts_and_tool_histogram() {
+        let t0: DateTime<Utc> = "2026-01-01T00:00:00Z".parse().unwrap();
+        let events = vec![
+            event_at(Role::User, t0, None),
+            event_at(Role::Assistant, t0, Some("Read")),
+            event_at(Role::Assistant, t0, Some("Read")),
+            event_at(Role::Tool, t0, None),
+        ];
+
+        let stats = compute_stats(&events);
+        assert_eq!(stats.user_count, 1);
+        assert_eq!(stats.assistant_count, 2);
+        assert_eq!(stats.tool_count, 1);
+        assert_eq!(stats.tool_usage.get("Read"), Some(&2));
+        assert_eq!(stats.assistant_char_count, "hello".len() * 2);
+    }
+
+    #[test]
+    fn test_gaps_and_wall_clock_span() {
+        let t0: DateTime<Utc> = "2026-01-01T00:00:00Z".parse().unwrap();
+        let t1: DateTime<Utc> = "2026-01-01T00:00:10Z".parse().unwrap();
+        let t2: DateTime<Utc> = "2026-01-01T00:00:40Z".parse().unwrap();
+        let events = vec![
+            event_at(Role::User, t0, None),
+            event_at(Role::Assistant, t1, None),
+            event_at(Role::Assistant, t2, None),
+        ];
+
+        let stats = compute_stats(&events);
+        assert_eq!(stats.mean_gap, Some(Duration::from_secs(20)));
+        assert_eq!(stats.p95_gap, Some(Duration::from_secs(30)));
+        assert_eq!(stats.wall_clock_span, Some(Duration::from_secs(40)));
+    }
+}
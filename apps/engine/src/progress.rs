@@ -0,0 +1,35 @@
+//! Structured progress reporting for long-running file-processing jobs
+//! (see `state::jobs`), replacing ad hoc `eprintln!` so a caller can observe
+//! live progress (files/bytes done, throughput, the latest non-fatal error)
+//! instead of only reading terminal output.
+//!
+//! `tokio::sync::watch` fits this better than an mpsc channel: there's only
+//! ever one "current" progress snapshot a consumer cares about, and `watch`
+//! naturally coalesces updates a slow consumer hasn't read yet.
+
+use tokio::sync::watch;
+
+/// A snapshot of a job's progress so far.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobProgress {
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_done: u64,
+    pub events_done: usize,
+    pub throughput_mb_s: f64,
+    /// The most recent non-fatal per-file error, if any (path, message).
+    pub last_error: Option<(String, String)>,
+}
+
+/// Sending half of a progress channel (see `JobProgress`).
+pub type ProgressSender = watch::Sender<JobProgress>;
+/// Receiving half; `borrow()` reads the latest snapshot without blocking.
+pub type ProgressReceiver = watch::Receiver<JobProgress>;
+
+/// Create a progress channel seeded with `files_total` known up front.
+pub fn channel(files_total: usize) -> (ProgressSender, ProgressReceiver) {
+    watch::channel(JobProgress {
+        files_total,
+        ..Default::default()
+    })
+}
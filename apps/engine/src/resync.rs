@@ -0,0 +1,119 @@
+//! Background resync worker for stalled unacked file gaps.
+//!
+//! `prepare_file`/`ship_and_record` only run in response to a watcher event
+//! or a periodic full scan, so a file whose gap (`queued_offset >
+//! acked_offset`, see `state::file_state::FileState::get_unacked_files`)
+//! opened on its last write and then went quiet never gets another chance —
+//! its spool entry can back off, go dead, or simply outlive a process
+//! restart with nothing left to re-enqueue it. This module re-runs the same
+//! prepare/ship pipeline against those files on a timer instead, so a
+//! stalled gap eventually closes even without new writes.
+//!
+//! Self-throttled by a "tranquility" knob (see
+//! `config::ShipperConfig::tranquility`) so catch-up never competes with
+//! live shipping for priority: after each file's gap is resynced, the pass
+//! sleeps `tranquility * last_op_duration` before moving to the next one.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::circuit_breaker::CircuitBreaker;
+use crate::pipeline::chunker::ChunkerParams;
+use crate::pipeline::compressor::CompressionAlgo;
+use crate::pipeline::crypto::RecipientKey;
+use crate::pipeline::dictionary::Dictionary;
+use crate::shipper;
+use crate::shipping::target::ShipTarget;
+use crate::state::file_state::FileState;
+use crate::workunit::{LogSink, Span};
+
+/// Files resynced per pass. Bounds one timer tick even with a large backlog
+/// of stalled files — the rest get picked up on the next tick, oldest
+/// `last_updated` first.
+const MAX_FILES_PER_PASS: usize = 20;
+
+/// Scan for unacked files, oldest `last_updated` first, and re-run
+/// `prepare_file`/`ship_and_record` against each one's gap.
+///
+/// Opens its own `resync.pass` root span (see `workunit::Span`), same as
+/// `shipper::run_startup_recovery`/`shipper::replay_spool_batch`.
+///
+/// Returns `(had_connect_error, files_resynced)` — same shape as
+/// `daemon::ship_batch` — so the caller can enter offline mode exactly as it
+/// would for a live shipment that hit a connect error.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_resync_pass(
+    conn: &Connection,
+    client: &dyn ShipTarget,
+    algo: CompressionAlgo,
+    recipient_key: Option<&RecipientKey>,
+    chunk_dedup: bool,
+    chunker_params: &ChunkerParams,
+    owned_blobs: bool,
+    tranquility: u8,
+    breaker: Option<&CircuitBreaker>,
+    dictionary: Option<&Dictionary>,
+    max_uncompressed_bytes: usize,
+) -> Result<(bool, usize)> {
+    let root = Span::root("resync.pass", Arc::new(LogSink));
+    let file_state = FileState::new(conn);
+    let mut unacked = file_state.get_unacked_files()?;
+    unacked.sort_by_key(|f| f.last_updated);
+    root.add_rows(unacked.len() as u64);
+
+    let mut resynced = 0usize;
+    for f in unacked.into_iter().take(MAX_FILES_PER_PASS) {
+        let op_start = Instant::now();
+
+        let prepared = shipper::prepare_file(Path::new(&f.path), &f.provider, algo, conn, recipient_key, chunk_dedup, chunker_params, dictionary, max_uncompressed_bytes);
+        // A stalled gap can come back as several batches (see
+        // `compressor::build_batches`); ship each in order and stop at the
+        // first connect error, same as a single-item gap would.
+        let mut had_connect_error = false;
+        let mut any_resynced = false;
+        match prepared {
+            Ok(items) => {
+                for item in items {
+                    match shipper::ship_and_record(item, client, conn, None, breaker, owned_blobs, Some(&root)).await {
+                        Ok((events, connect_error)) => {
+                            if events > 0 {
+                                any_resynced = true;
+                            }
+                            if connect_error {
+                                had_connect_error = true;
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            tracing::warn!("Resync ship error for {}: {}", f.path, e);
+                            break;
+                        }
+                    }
+                }
+            }
+            // gap already closed, or file unreadable right now
+            Err(e) => {
+                tracing::warn!("Resync prepare error for {}: {}", f.path, e);
+            }
+        };
+
+        if any_resynced {
+            resynced += 1;
+            tracing::info!("Resynced stalled gap for {}: acked={}, queued={}", f.path, f.acked_offset, f.queued_offset);
+        }
+
+        if had_connect_error {
+            return Ok((true, resynced));
+        }
+
+        if tranquility > 0 {
+            tokio::time::sleep(op_start.elapsed() * tranquility as u32).await;
+        }
+    }
+
+    Ok((false, resynced))
+}
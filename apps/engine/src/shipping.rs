@@ -0,0 +1,3 @@
+pub mod client;
+pub mod s3;
+pub mod target;
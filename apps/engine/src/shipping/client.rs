@@ -1,17 +1,54 @@
 //! HTTP client for shipping compressed payloads to the Longhouse API.
 //!
-//! POST `{api_url}/api/agents/ingest` with gzip-compressed JSON body.
-//! Handles 429 rate limiting with exponential backoff + Retry-After.
+//! POST `{api_url}/api/agents/ingest` with a compressed JSON body. When
+//! `ShipperConfig::recipient_key` is set, the body has already been sealed
+//! by `pipeline::crypto::seal` before it reaches this client — `ship`/
+//! `ship_batch` just need to swap `Content-Encoding` for `identity` and add
+//! [`ENCRYPTION_HEADER_NAME`] so the server knows to decrypt before
+//! decompressing.
+//! Retries transient failures (429 rate limiting honoring `Retry-After`,
+//! 5xx, and connect errors) in place with exponential backoff + full jitter
+//! before surfacing them to the caller to spool.
+//!
+//! The auth token lives behind a shared `RwLock` rather than baked into the
+//! client's default headers, so it can be rotated without rebuilding the
+//! connection pool: `ship`/`ship_batch` attach it per-request, a 401/403
+//! triggers one `refresh_token` round trip + retry (see
+//! `ShipperConfig::token_refresh_url`), and a daemon timer can call
+//! `refresh_token` proactively ahead of expiry.
+//!
+//! `Content-Encoding` is likewise never baked into a default header — `ship`/
+//! `ship_batch` take the algorithm to label per call, since `choose_algo`
+//! (see `pipeline::adaptive_compression`) can pick a different one for each
+//! payload based on its size, what the server's `Accept-Encoding` advertised
+//! (`health_check`), and which algorithm is actually paying off on this link
+//! so far (`record_compression_outcome`).
 
-use std::time::Duration;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use rand::Rng;
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE};
 
 use crate::config::ShipperConfig;
+use crate::metrics::Metrics;
+use crate::pipeline::adaptive_compression::{self, AdaptiveCompressor};
 use crate::pipeline::compressor::{CompressionAlgo, content_encoding};
 
+/// One compressed payload's outcome within a `ship_batch` response.
+#[derive(serde::Deserialize)]
+struct BatchItemResult {
+    status: String,
+    #[serde(default)]
+    code: Option<u16>,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    body: Option<serde_json::Value>,
+}
+
 /// Result of a shipping attempt.
 #[derive(Debug)]
 pub enum ShipResult {
@@ -27,14 +64,163 @@ pub enum ShipResult {
     ConnectError(String),
 }
 
+/// Short label for a `ShipResult` variant, for `Metrics::record_ship_outcome`.
+pub fn outcome_label(result: &ShipResult) -> &'static str {
+    match result {
+        ShipResult::Ok(_) => "ok",
+        ShipResult::RateLimited => "rate_limited",
+        ShipResult::ServerError(_, _) => "server_error",
+        ShipResult::ClientError(_, _) => "client_error",
+        ShipResult::ConnectError(_) => "connect_error",
+    }
+}
+
+/// Optional fields a `ShipResult::Ok` body may carry when the server only
+/// durably accepted a prefix of the item's byte range (e.g. it rejected one
+/// malformed record mid-batch rather than the whole request).
+#[derive(Debug, Clone, Copy, Default, serde::Deserialize)]
+struct AckBody {
+    #[serde(default)]
+    acked_offset: Option<u64>,
+}
+
+/// Pull a server-confirmed partial-ack offset out of a `ShipResult::Ok`
+/// body, if present. A server that doesn't send `acked_offset` (or any
+/// server predating this field) means the caller should assume the whole
+/// item was accepted — callers treat `None` that way, not as an error.
+pub fn acked_offset(body: &serde_json::Value) -> Option<u64> {
+    serde_json::from_value::<AckBody>(body.clone())
+        .ok()
+        .and_then(|a| a.acked_offset)
+}
+
+/// Hard ceiling on any single backoff sleep, regardless of attempt count or
+/// a server-supplied `Retry-After`.
+const MAX_BACKOFF_SECS: f64 = 30.0;
+
+/// AWS-style "full jitter" backoff: uniform random in `[0, min(cap, base * 2^attempt))`.
+/// Spreads out retries from many clients instead of having them all wake up
+/// and hammer the server at the same moment (thundering herd).
+fn full_jitter_backoff(base_backoff: f64, attempt: u32) -> f64 {
+    let max_wait = (base_backoff * 2f64.powi(attempt as i32)).min(MAX_BACKOFF_SECS);
+    rand::thread_rng().gen::<f64>() * max_wait
+}
+
+/// Protocol major version this build of the client was written against. A
+/// server advertising a different major version via `/api/capabilities` is
+/// incompatible — see `Capabilities::is_compatible`.
+const CLIENT_PROTOCOL_MAJOR: u32 = 1;
+
+/// How long a fetched `/api/capabilities` document stays valid before
+/// `capabilities()` fetches it again — long enough that a drain pass every
+/// few seconds doesn't round-trip every time, short enough to notice a
+/// server upgrade within a long-running process.
+const CAPABILITIES_TTL: Duration = Duration::from_secs(300);
+
+/// What `/api/capabilities` advertises: the server's protocol major version
+/// and the named optional features it understands (e.g. extra fields a
+/// presence payload may carry).
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Capabilities {
+    pub protocol_version: u32,
+    #[serde(default)]
+    pub features: std::collections::HashSet<String>,
+}
+
+impl Capabilities {
+    /// Assumed when `/api/capabilities` is unreachable or absent (e.g. a
+    /// server predating this endpoint) — protocol version 1, no optional
+    /// features, matching this client's original behavior before capability
+    /// negotiation existed.
+    fn baseline() -> Self {
+        Self {
+            protocol_version: CLIENT_PROTOCOL_MAJOR,
+            features: Default::default(),
+        }
+    }
+
+    /// Whether this client can talk to a server advertising this document.
+    /// `false` on any major version mismatch — callers should refuse to
+    /// POST rather than send a payload the server can't parse.
+    pub fn is_compatible(&self) -> bool {
+        self.protocol_version == CLIENT_PROTOCOL_MAJOR
+    }
+
+    /// Whether the server has advertised support for the named optional
+    /// feature.
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.contains(feature)
+    }
+}
+
+/// Header announcing that the body is a `pipeline::crypto::seal` envelope
+/// rather than a raw compressed payload, so the server decrypts before
+/// decompressing.
+const ENCRYPTION_HEADER_NAME: &str = "X-Agents-Encryption";
+const ENCRYPTION_HEADER_VALUE: &str = "x25519-xchacha20poly1305";
+
+/// `Content-Encoding` value and, when sealed, the extra `(name, value)`
+/// encryption header to attach to an outbound POST. Pulled out of
+/// `ship`/`ship_batch` so the two call sites can't drift and the choice is
+/// unit-testable without a live server.
+fn outbound_headers(
+    encrypted: bool,
+    compression: CompressionAlgo,
+) -> (&'static str, Option<(&'static str, &'static str)>) {
+    if encrypted {
+        ("identity", Some((ENCRYPTION_HEADER_NAME, ENCRYPTION_HEADER_VALUE)))
+    } else {
+        (content_encoding(compression), None)
+    }
+}
+
+/// Build a `reqwest::Identity` from a PEM client cert + PEM private key for
+/// mutual TLS. `reqwest::Identity::from_pem` expects both concatenated in
+/// one buffer, so read and join them here rather than at every call site.
+fn build_client_identity(cert_path: &Path, key_path: &Path) -> Result<reqwest::Identity> {
+    let mut combined = std::fs::read(cert_path)
+        .with_context(|| format!("reading client cert {}", cert_path.display()))?;
+    let key_pem = std::fs::read(key_path)
+        .with_context(|| format!("reading client key {}", key_path.display()))?;
+    combined.push(b'\n');
+    combined.extend_from_slice(&key_pem);
+    reqwest::Identity::from_pem(&combined).context("parsing client cert/key as TLS identity")
+}
+
 /// HTTP client with connection pooling and retry logic.
 pub struct ShipperClient {
     client: reqwest::Client,
     ingest_url: String,
-    api_token: Option<String>,
-    max_retries_429: u32,
+    /// Current bearer token, behind a lock so a 401/403-triggered or
+    /// background `refresh_token` can rotate it without rebuilding `client`
+    /// (and thus without tearing down its connection pool).
+    current_token: Arc<RwLock<Option<String>>>,
+    /// Endpoint `refresh_token` POSTs the current token to for a new one.
+    /// `None` means a 401/403 is terminal, same as before token refresh existed.
+    token_refresh_url: Option<String>,
+    max_retries_transient: u32,
     base_backoff: f64,
     compression: CompressionAlgo,
+    /// Whether `config.recipient_key` was set at construction — bodies
+    /// passed to `ship`/`ship_batch` are assumed already sealed in that
+    /// case (see `pipeline::crypto::seal`), so the `Content-Encoding` sent
+    /// on the wire needs to say `identity`, not the compression algo.
+    encrypted: bool,
+    /// Optional metrics handle (see `with_metrics`) — when set, every
+    /// `ship`/`ship_batch` attempt records its outcome, compressed bytes,
+    /// and request latency, and every retry records its backoff sleep.
+    metrics: Option<Metrics>,
+    /// Algorithms the server has advertised support for, parsed from its
+    /// `Accept-Encoding` response header (see `health_check`). `[compression]`
+    /// until the first successful health check populates it for real.
+    negotiated_encodings: Arc<RwLock<Vec<CompressionAlgo>>>,
+    /// Learns which algorithm is actually paying off on this link (see
+    /// `choose_algo`/`record_compression_outcome`).
+    adaptive: AdaptiveCompressor,
+    /// Last `/api/capabilities` fetch and when it was fetched, so
+    /// `capabilities()` can serve from cache within `CAPABILITIES_TTL`
+    /// instead of round-tripping on every call.
+    capabilities: Arc<RwLock<Option<(Capabilities, Instant)>>>,
 }
 
 impl ShipperClient {
@@ -44,27 +230,37 @@ impl ShipperClient {
     }
 
     /// Create a new client with specific compression algorithm.
+    ///
+    /// Wires up mutual TLS when `config.client_cert_path`/`client_key_path`
+    /// (and optionally `ca_bundle_path`) are set — for gateways that
+    /// authenticate by client certificate instead of, or in addition to,
+    /// the bearer token.
     pub fn with_compression(config: &ShipperConfig, compression: CompressionAlgo) -> Result<Self> {
+        // No default Content-Encoding header: `ship`/`ship_batch` always set
+        // it per-request from the algorithm that specific payload actually
+        // used (see `choose_algo`), since different payloads can now use
+        // different algorithms.
         let mut default_headers = HeaderMap::new();
         default_headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        default_headers.insert(
-            CONTENT_ENCODING,
-            HeaderValue::from_static(content_encoding(compression)),
-        );
-
-        if let Some(ref token) = config.api_token {
-            default_headers.insert(
-                "X-Agents-Token",
-                HeaderValue::from_str(token).context("invalid token header value")?,
-            );
-        }
 
-        let client = reqwest::Client::builder()
+        let mut builder = reqwest::Client::builder()
             .default_headers(default_headers)
             .timeout(Duration::from_secs(config.timeout_seconds))
-            .pool_max_idle_per_host(4)
-            .build()
-            .context("building HTTP client")?;
+            .pool_max_idle_per_host(4);
+
+        if let (Some(cert_path), Some(key_path)) = (&config.client_cert_path, &config.client_key_path) {
+            let identity = build_client_identity(cert_path, key_path)?;
+            builder = builder.identity(identity);
+        }
+
+        if let Some(ca_path) = &config.ca_bundle_path {
+            let ca_pem = std::fs::read(ca_path)
+                .with_context(|| format!("reading CA bundle {}", ca_path.display()))?;
+            let ca_cert = reqwest::Certificate::from_pem(&ca_pem).context("parsing CA bundle")?;
+            builder = builder.add_root_certificate(ca_cert);
+        }
+
+        let client = builder.build().context("building HTTP client")?;
 
         let ingest_url = format!(
             "{}/api/agents/ingest",
@@ -74,96 +270,390 @@ impl ShipperClient {
         Ok(Self {
             client,
             ingest_url,
-            api_token: config.api_token.clone(),
-            max_retries_429: config.max_retries_429,
+            current_token: Arc::new(RwLock::new(config.api_token.clone())),
+            token_refresh_url: config.token_refresh_url.clone(),
+            max_retries_transient: config.max_retries_transient,
             base_backoff: config.base_backoff_seconds,
             compression,
+            encrypted: config.recipient_key.is_some(),
+            metrics: None,
+            negotiated_encodings: Arc::new(RwLock::new(vec![compression])),
+            adaptive: AdaptiveCompressor::new(),
+            capabilities: Arc::new(RwLock::new(None)),
         })
     }
 
+    /// Attach a metrics handle so `ship`/`ship_batch` record per-attempt
+    /// outcomes, compressed bytes, request latency, and retry backoff (see
+    /// `Metrics::record_ship_outcome` and friends).
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Get the compression algorithm being used.
     pub fn compression(&self) -> CompressionAlgo {
         self.compression
     }
 
-    /// Ship a gzip-compressed payload. Handles 429 retries internally.
-    pub async fn ship(&self, compressed_payload: Vec<u8>) -> ShipResult {
+    /// Algorithms the server has advertised support for — a snapshot, since
+    /// `health_check` can refresh it concurrently. `[compression]` (the
+    /// configured default) until the first successful health check.
+    pub fn negotiated_encodings(&self) -> Vec<CompressionAlgo> {
+        self.negotiated_encodings.read().unwrap().clone()
+    }
+
+    /// Pick the best compression algorithm for a payload of `payload_len`
+    /// bytes: the learned per-algorithm rate (see `record_compression_outcome`)
+    /// among whatever the server has advertised support for, falling back to
+    /// `adaptive_compression::choose_for_size` until there's history or
+    /// negotiation to go on.
+    pub fn choose_algo(&self, payload_len: usize) -> CompressionAlgo {
+        let candidates = self.negotiated_encodings();
+        self.adaptive.choose(&candidates, payload_len)
+    }
+
+    /// Feed one compression outcome into the adaptive algorithm tracker —
+    /// call after compressing a payload with the algorithm `choose_algo`
+    /// returned, so future calls bias toward whatever is actually paying off
+    /// on this link.
+    pub fn record_compression_outcome(
+        &self,
+        algo: CompressionAlgo,
+        original_len: usize,
+        compressed_len: usize,
+        elapsed: Duration,
+    ) {
+        self.adaptive.record(algo, original_len, compressed_len, elapsed);
+    }
+
+    /// Current bearer token, if any — a snapshot, since `current_token` can
+    /// be rotated concurrently by `refresh_token`.
+    pub fn current_token(&self) -> Option<String> {
+        self.current_token.read().unwrap().clone()
+    }
+
+    /// `X-Agents-Token` header value for the current token, if set.
+    fn token_header(&self) -> Option<HeaderValue> {
+        self.current_token()
+            .and_then(|t| HeaderValue::from_str(&t).ok())
+    }
+
+    /// POST the current token to `token_refresh_url` for a fresh short-lived
+    /// one, store it in `current_token`, and return it. Callable both
+    /// reactively (`ship` on a 401/403) and proactively (a daemon timer,
+    /// ahead of expiry) — either way the connection pool is untouched since
+    /// only the token behind the lock changes.
+    ///
+    /// Errors (including a missing `token_refresh_url`) leave the stored
+    /// token as-is.
+    pub async fn refresh_token(&self) -> Result<String> {
+        let Some(url) = &self.token_refresh_url else {
+            bail!("no token_refresh_url configured");
+        };
+
+        #[derive(serde::Serialize)]
+        struct RefreshRequest<'a> {
+            current_token: Option<&'a str>,
+        }
+        #[derive(serde::Deserialize)]
+        struct RefreshResponse {
+            token: String,
+        }
+
+        let current = self.current_token();
+        let resp = self
+            .client
+            .post(url)
+            .json(&RefreshRequest { current_token: current.as_deref() })
+            .send()
+            .await
+            .context("token refresh request failed")?;
+
+        if !resp.status().is_success() {
+            bail!("token refresh returned {}", resp.status());
+        }
+
+        let parsed: RefreshResponse = resp.json().await.context("parsing token refresh response")?;
+        *self.current_token.write().unwrap() = Some(parsed.token.clone());
+        Ok(parsed.token)
+    }
+
+    /// Ship a compressed payload, retrying transient failures (429, 5xx,
+    /// connect errors) in place with exponential backoff + full jitter
+    /// before giving up — only once the retry budget is exhausted does the
+    /// caller see a `RateLimited`/`ServerError`/`ConnectError` to spool (see
+    /// `shipper::ship_and_record`). A 429's `Retry-After` header, when
+    /// present, overrides the computed backoff (still jittered) since the
+    /// server knows better than our guess how long to wait.
+    ///
+    /// `algo` labels `Content-Encoding` for this specific payload — callers
+    /// whose items were compressed with different algorithms (see
+    /// `choose_algo`) pass their own, rather than this client assuming one
+    /// fixed algorithm for every call.
+    pub async fn ship(&self, compressed_payload: Vec<u8>, algo: CompressionAlgo) -> ShipResult {
         let mut retries = 0u32;
-        let mut backoff = self.base_backoff;
+        let mut refreshed_once = false;
+        let (content_encoding, encryption_header) = outbound_headers(self.encrypted, algo);
+        let compressed_len = compressed_payload.len() as u64;
 
-        loop {
-            let result = self
+        let result = loop {
+            let mut request = self
                 .client
                 .post(&self.ingest_url)
-                .body(compressed_payload.clone())
-                .send()
-                .await;
+                .header(CONTENT_ENCODING, content_encoding);
+            if let Some((name, value)) = encryption_header {
+                request = request.header(name, value);
+            }
+            if let Some(token) = self.token_header() {
+                request = request.header("X-Agents-Token", token);
+            }
+            let attempt_start = Instant::now();
+            let result = request.body(compressed_payload.clone()).send().await;
 
-            match result {
+            let response = match result {
                 Err(e) => {
-                    return ShipResult::ConnectError(e.to_string());
+                    self.observe_request_latency(attempt_start.elapsed());
+                    if retries >= self.max_retries_transient {
+                        break ShipResult::ConnectError(e.to_string());
+                    }
+                    let wait = full_jitter_backoff(self.base_backoff, retries);
+                    tracing::info!(
+                        "Connect error ({}), retry {}/{}, waiting {:.1}s",
+                        e,
+                        retries + 1,
+                        self.max_retries_transient,
+                        wait
+                    );
+                    self.sleep_backoff(wait).await;
+                    retries += 1;
+                    continue;
                 }
-                Ok(response) => {
-                    let status = response.status().as_u16();
-
-                    match status {
-                        200..=299 => {
-                            let body = response
-                                .json::<serde_json::Value>()
-                                .await
-                                .unwrap_or(serde_json::Value::Null);
-                            return ShipResult::Ok(body);
-                        }
-                        429 => {
-                            if retries >= self.max_retries_429 {
-                                tracing::warn!(
-                                    "Rate limited after {} retries, giving up",
-                                    retries
-                                );
-                                return ShipResult::RateLimited;
-                            }
+                Ok(r) => r,
+            };
+            self.observe_request_latency(attempt_start.elapsed());
 
-                            // Check Retry-After header
-                            let base_wait = response
-                                .headers()
-                                .get("Retry-After")
-                                .and_then(|v| v.to_str().ok())
-                                .and_then(|s| s.parse::<f64>().ok())
-                                .unwrap_or(backoff);
+            let status = response.status().as_u16();
 
-                            // Add jitter (50%–100% of base_wait) and cap at 30s
+            match status {
+                200..=299 => {
+                    let body = response
+                        .json::<serde_json::Value>()
+                        .await
+                        .unwrap_or(serde_json::Value::Null);
+                    break ShipResult::Ok(body);
+                }
+                429 => {
+                    if retries >= self.max_retries_transient {
+                        tracing::warn!("Rate limited after {} retries, giving up", retries);
+                        break ShipResult::RateLimited;
+                    }
+
+                    // Honor Retry-After if the server sent one; still jitter it
+                    // so a thundering herd of clients doesn't retry in lockstep.
+                    let wait = match response
+                        .headers()
+                        .get("Retry-After")
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|s| s.parse::<f64>().ok())
+                    {
+                        Some(retry_after) => {
                             let jitter_factor = 0.5 + rand::thread_rng().gen::<f64>() * 0.5;
-                            let wait = (base_wait * jitter_factor).min(30.0);
-
-                            tracing::info!(
-                                "Rate limited (429), retry {}/{}, waiting {:.1}s",
-                                retries + 1,
-                                self.max_retries_429,
-                                wait
-                            );
-
-                            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
-                            retries += 1;
-                            backoff *= 2.0;
-                        }
-                        401 | 403 => {
-                            let body = response.text().await.unwrap_or_default();
-                            return ShipResult::ClientError(status, body);
-                        }
-                        400..=499 => {
-                            let body = response.text().await.unwrap_or_default();
-                            return ShipResult::ClientError(status, body);
-                        }
-                        500..=599 => {
-                            let body = response.text().await.unwrap_or_default();
-                            return ShipResult::ServerError(status, body);
+                            (retry_after * jitter_factor).min(MAX_BACKOFF_SECS)
                         }
-                        _ => {
-                            let body = response.text().await.unwrap_or_default();
-                            return ShipResult::ClientError(status, body);
+                        None => full_jitter_backoff(self.base_backoff, retries),
+                    };
+
+                    tracing::info!(
+                        "Rate limited (429), retry {}/{}, waiting {:.1}s",
+                        retries + 1,
+                        self.max_retries_transient,
+                        wait
+                    );
+
+                    if let Some(metrics) = &self.metrics {
+                        metrics.record_rate_limit_retry();
+                    }
+                    self.sleep_backoff(wait).await;
+                    retries += 1;
+                }
+                401 | 403 => {
+                    if !refreshed_once && self.token_refresh_url.is_some() {
+                        refreshed_once = true;
+                        match self.refresh_token().await {
+                            Ok(_) => {
+                                tracing::info!(
+                                    "Token refresh succeeded after {} response, retrying once",
+                                    status
+                                );
+                                continue;
+                            }
+                            Err(e) => {
+                                tracing::warn!("Token refresh failed after {} response: {}", status, e);
+                            }
                         }
                     }
+                    let body = response.text().await.unwrap_or_default();
+                    break ShipResult::ClientError(status, body);
                 }
+                400..=499 => {
+                    let body = response.text().await.unwrap_or_default();
+                    break ShipResult::ClientError(status, body);
+                }
+                500..=599 => {
+                    if retries >= self.max_retries_transient {
+                        let body = response.text().await.unwrap_or_default();
+                        break ShipResult::ServerError(status, body);
+                    }
+                    let wait = full_jitter_backoff(self.base_backoff, retries);
+                    tracing::info!(
+                        "Server error ({}), retry {}/{}, waiting {:.1}s",
+                        status,
+                        retries + 1,
+                        self.max_retries_transient,
+                        wait
+                    );
+                    self.sleep_backoff(wait).await;
+                    retries += 1;
+                }
+                _ => {
+                    let body = response.text().await.unwrap_or_default();
+                    break ShipResult::ClientError(status, body);
+                }
+            }
+        };
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_ship_outcome(outcome_label(&result));
+            if matches!(result, ShipResult::Ok(_)) {
+                metrics.record_bytes_compressed(compressed_len);
+            }
+        }
+        result
+    }
+
+    /// Sleep out a backoff wait, recording it to `metrics` if attached.
+    async fn sleep_backoff(&self, wait_secs: f64) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_backoff(Duration::from_secs_f64(wait_secs));
+        }
+        tokio::time::sleep(Duration::from_secs_f64(wait_secs)).await;
+    }
+
+    /// Record one HTTP round-trip observation, if a metrics handle is attached.
+    fn observe_request_latency(&self, elapsed: Duration) {
+        if let Some(metrics) = &self.metrics {
+            metrics.observe_request_latency(elapsed);
+        }
+    }
+
+    /// Ship multiple compressed payloads in a single HTTP request.
+    ///
+    /// Each payload is framed with a 4-byte little-endian length prefix and
+    /// concatenated into one request body (cheaper than re-encoding already
+    /// gzip/zstd-compressed bytes as base64 JSON). The server replies with a
+    /// same-length JSON array of per-item results, so one session rate-limited
+    /// or erroring doesn't block the rest of the batch — callers should
+    /// still spool/advance offsets per-item from the returned `Vec<ShipResult>`.
+    ///
+    /// The whole batch ships under one `Content-Encoding` (`algo`) since
+    /// it's one HTTP request — a caller whose items used different
+    /// algorithms picks one for the group (see `ShipTarget::put_batch`'s
+    /// `ShipperClient` impl).
+    pub async fn ship_batch(&self, payloads: Vec<Vec<u8>>, algo: CompressionAlgo) -> Vec<ShipResult> {
+        if payloads.is_empty() {
+            return Vec::new();
+        }
+
+        let mut body = Vec::new();
+        for payload in &payloads {
+            body.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            body.extend_from_slice(payload);
+        }
+
+        let batch_url = self
+            .ingest_url
+            .replace("/api/agents/ingest", "/api/agents/ingest/batch");
+
+        let (content_encoding, encryption_header) = outbound_headers(self.encrypted, algo);
+        let mut request = self
+            .client
+            .post(&batch_url)
+            .header(CONTENT_ENCODING, content_encoding)
+            .header(CONTENT_TYPE, "application/octet-stream");
+        if let Some((name, value)) = encryption_header {
+            request = request.header(name, value);
+        }
+        if let Some(token) = self.token_header() {
+            request = request.header("X-Agents-Token", token);
+        }
+        let attempt_start = Instant::now();
+        let result = request.body(body).send().await;
+
+        let response = match result {
+            Err(e) => {
+                self.observe_request_latency(attempt_start.elapsed());
+                let msg = e.to_string();
+                let results: Vec<ShipResult> = payloads.iter().map(|_| ShipResult::ConnectError(msg.clone())).collect();
+                self.record_batch_outcomes(&results, &payloads);
+                return results;
+            }
+            Ok(r) => r,
+        };
+        self.observe_request_latency(attempt_start.elapsed());
+
+        let status = response.status().as_u16();
+        if !(200..=299).contains(&status) {
+            let body_text = response.text().await.unwrap_or_default();
+            let results: Vec<ShipResult> = payloads
+                .iter()
+                .map(|_| match status {
+                    429 => ShipResult::RateLimited,
+                    500..=599 => ShipResult::ServerError(status, body_text.clone()),
+                    _ => ShipResult::ClientError(status, body_text.clone()),
+                })
+                .collect();
+            self.record_batch_outcomes(&results, &payloads);
+            return results;
+        }
+
+        let items: Vec<BatchItemResult> = match response.json().await {
+            Ok(v) => v,
+            Err(_) => {
+                let results: Vec<ShipResult> = payloads
+                    .iter()
+                    .map(|_| ShipResult::ServerError(status, "invalid batch response body".to_string()))
+                    .collect();
+                self.record_batch_outcomes(&results, &payloads);
+                return results;
+            }
+        };
+
+        let results: Vec<ShipResult> = items
+            .into_iter()
+            .map(|item| match item.status.as_str() {
+                "ok" => ShipResult::Ok(item.body.unwrap_or(serde_json::Value::Null)),
+                "rate_limited" => ShipResult::RateLimited,
+                "server_error" => ShipResult::ServerError(item.code.unwrap_or(500), item.error.unwrap_or_default()),
+                "client_error" => ShipResult::ClientError(item.code.unwrap_or(400), item.error.unwrap_or_default()),
+                other => ShipResult::ServerError(status, format!("unknown batch item status: {}", other)),
+            })
+            .collect();
+        self.record_batch_outcomes(&results, &payloads);
+        results
+    }
+
+    /// Record per-item outcome + compressed bytes for a `ship_batch` response,
+    /// if a metrics handle is attached. On a whole-request failure `results`
+    /// is built by mapping over `payloads`, so the two line up one-to-one; on
+    /// a parsed response `results` has as many items as the server returned,
+    /// and pairs are recorded only up to the shorter of the two.
+    fn record_batch_outcomes(&self, results: &[ShipResult], payloads: &[Vec<u8>]) {
+        let Some(metrics) = &self.metrics else { return };
+        for (result, payload) in results.iter().zip(payloads.iter()) {
+            metrics.record_ship_outcome(outcome_label(result));
+            if matches!(result, ShipResult::Ok(_)) {
+                metrics.record_bytes_compressed(payload.len() as u64);
             }
         }
     }
@@ -173,15 +663,16 @@ impl ShipperClient {
         let url = self
             .ingest_url
             .replace("/api/agents/ingest", path_suffix);
-        self.client
+        let mut request = self
+            .client
             .post(&url)
             .header(reqwest::header::CONTENT_TYPE, "application/json")
             // Remove Content-Encoding for uncompressed requests
-            .header(reqwest::header::CONTENT_ENCODING, "identity")
-            .body(body)
-            .send()
-            .await
-            .context("heartbeat POST failed")?;
+            .header(reqwest::header::CONTENT_ENCODING, "identity");
+        if let Some(token) = self.token_header() {
+            request = request.header("X-Agents-Token", token);
+        }
+        request.body(body).send().await.context("heartbeat POST failed")?;
         Ok(())
     }
 
@@ -190,16 +681,60 @@ impl ShipperClient {
         &self.ingest_url
     }
 
-    /// Check if the API is reachable (health check).
+    /// Check if the API is reachable (health check). Also refreshes
+    /// `negotiated_encodings` from the response's `Accept-Encoding` header,
+    /// if present, so `choose_algo` learns what the server actually
+    /// supports instead of assuming only the configured default.
     pub async fn health_check(&self) -> Result<bool> {
         let health_url = self
             .ingest_url
             .replace("/api/agents/ingest", "/api/health");
         match self.client.get(&health_url).send().await {
-            Ok(resp) => Ok(resp.status().is_success()),
+            Ok(resp) => {
+                let ok = resp.status().is_success();
+                if let Some(accept_encoding) = resp
+                    .headers()
+                    .get(ACCEPT_ENCODING)
+                    .and_then(|v| v.to_str().ok())
+                {
+                    let negotiated = adaptive_compression::negotiate_server_support(accept_encoding);
+                    if !negotiated.is_empty() {
+                        *self.negotiated_encodings.write().unwrap() = negotiated;
+                    }
+                }
+                Ok(ok)
+            }
             Err(_) => Ok(false),
         }
     }
+
+    /// Fetch (or serve from cache) the server's `/api/capabilities`
+    /// document. Cached for `CAPABILITIES_TTL` so a drain pass doesn't
+    /// round-trip on every call; falls back to [`Capabilities::baseline`] on
+    /// any fetch/parse failure, so a server predating this endpoint (or a
+    /// transient network hiccup) degrades to "assume compatible" rather than
+    /// blocking draining outright.
+    pub async fn capabilities(&self) -> Capabilities {
+        if let Some((cached, fetched_at)) = self.capabilities.read().unwrap().as_ref() {
+            if fetched_at.elapsed() < CAPABILITIES_TTL {
+                return cached.clone();
+            }
+        }
+
+        let capabilities_url = self
+            .ingest_url
+            .replace("/api/agents/ingest", "/api/capabilities");
+        let fetched = match self.client.get(&capabilities_url).send().await {
+            Ok(resp) if resp.status().is_success() => resp
+                .json::<Capabilities>()
+                .await
+                .unwrap_or_else(|_| Capabilities::baseline()),
+            _ => Capabilities::baseline(),
+        };
+
+        *self.capabilities.write().unwrap() = Some((fetched.clone(), Instant::now()));
+        fetched
+    }
 }
 
 /// Read API URL from the standard location.
@@ -222,6 +757,119 @@ pub fn has_valid_config() -> bool {
 mod tests {
     use rand::Rng;
 
+    use super::{
+        acked_offset, full_jitter_backoff, outbound_headers, outcome_label, Capabilities,
+        CompressionAlgo, ShipResult, ShipperClient, CLIENT_PROTOCOL_MAJOR, ENCRYPTION_HEADER_NAME,
+        ENCRYPTION_HEADER_VALUE,
+    };
+    use crate::config::ShipperConfig;
+
+    #[test]
+    fn test_current_token_reflects_config_api_token() {
+        let config = ShipperConfig {
+            api_token: Some("secret-token".to_string()),
+            ..ShipperConfig::default()
+        };
+        let client = ShipperClient::new(&config).unwrap();
+        assert_eq!(client.current_token(), Some("secret-token".to_string()));
+    }
+
+    #[test]
+    fn test_with_mtls_missing_cert_file_errors() {
+        let config = ShipperConfig {
+            client_cert_path: Some(std::path::PathBuf::from("/nonexistent/cert.pem")),
+            client_key_path: Some(std::path::PathBuf::from("/nonexistent/key.pem")),
+            ..ShipperConfig::default()
+        };
+        assert!(ShipperClient::new(&config).is_err());
+    }
+
+    #[test]
+    fn test_negotiated_encodings_defaults_to_configured_compression() {
+        let config = ShipperConfig::default();
+        let client = ShipperClient::with_compression(&config, CompressionAlgo::Zstd).unwrap();
+        assert_eq!(client.negotiated_encodings(), vec![CompressionAlgo::Zstd]);
+    }
+
+    #[test]
+    fn test_choose_algo_uses_negotiated_encodings() {
+        let config = ShipperConfig::default();
+        let client = ShipperClient::with_compression(&config, CompressionAlgo::Gzip).unwrap();
+        // Only Gzip is negotiated, so a small payload still gets Gzip rather
+        // than the size heuristic's usual Identity pick.
+        assert_eq!(client.choose_algo(100), CompressionAlgo::Gzip);
+    }
+
+    #[test]
+    fn test_record_compression_outcome_biases_future_choose_algo() {
+        let config = ShipperConfig::default();
+        let client = ShipperClient::with_compression(&config, CompressionAlgo::Gzip).unwrap();
+        *client.negotiated_encodings.write().unwrap() =
+            vec![CompressionAlgo::Gzip, CompressionAlgo::Zstd];
+
+        client.record_compression_outcome(
+            CompressionAlgo::Zstd,
+            10_000,
+            2_000,
+            std::time::Duration::from_millis(1),
+        );
+
+        assert_eq!(client.choose_algo(10 * 1024), CompressionAlgo::Zstd);
+    }
+
+    #[test]
+    fn test_outcome_label_matches_variant() {
+        assert_eq!(outcome_label(&ShipResult::Ok(serde_json::Value::Null)), "ok");
+        assert_eq!(outcome_label(&ShipResult::RateLimited), "rate_limited");
+        assert_eq!(outcome_label(&ShipResult::ServerError(500, String::new())), "server_error");
+        assert_eq!(outcome_label(&ShipResult::ClientError(400, String::new())), "client_error");
+        assert_eq!(outcome_label(&ShipResult::ConnectError(String::new())), "connect_error");
+    }
+
+    #[test]
+    fn test_acked_offset_reads_partial_ack_field() {
+        let body = serde_json::json!({"acked_offset": 4096});
+        assert_eq!(acked_offset(&body), Some(4096));
+    }
+
+    #[test]
+    fn test_acked_offset_absent_is_none() {
+        let body = serde_json::json!({"status": "ok"});
+        assert_eq!(acked_offset(&body), None);
+    }
+
+    #[test]
+    fn test_outbound_headers_unencrypted_uses_compression_encoding() {
+        let (encoding, encryption) = outbound_headers(false, CompressionAlgo::Zstd);
+        assert_eq!(encoding, "zstd");
+        assert_eq!(encryption, None);
+    }
+
+    #[test]
+    fn test_outbound_headers_encrypted_overrides_to_identity() {
+        let (encoding, encryption) = outbound_headers(true, CompressionAlgo::Gzip);
+        assert_eq!(encoding, "identity");
+        assert_eq!(encryption, Some((ENCRYPTION_HEADER_NAME, ENCRYPTION_HEADER_VALUE)));
+    }
+
+    #[test]
+    fn test_full_jitter_backoff_in_range_and_doubles() {
+        for attempt in 0..6 {
+            for _ in 0..100 {
+                let wait = full_jitter_backoff(1.0, attempt);
+                assert!(wait >= 0.0, "full jitter must never be negative");
+                let cap = (1.0_f64 * 2f64.powi(attempt as i32)).min(30.0);
+                assert!(wait <= cap, "wait {:.2} should be <= cap {:.2}", wait, cap);
+            }
+        }
+    }
+
+    #[test]
+    fn test_full_jitter_backoff_capped_at_max() {
+        let wait = full_jitter_backoff(100.0, 10);
+        assert!(wait <= 30.0, "wait {:.2} should be capped at 30s", wait);
+    }
+
     #[test]
     fn test_429_jitter_in_range() {
         // Verify the jitter formula produces values in [0.5 * base, base] and <= 30s
@@ -251,4 +899,113 @@ mod tests {
         let wait = (large_base * jitter_factor).min(30.0);
         assert_eq!(wait, 30.0, "Large base_wait should be capped at 30s");
     }
+
+    #[test]
+    fn test_capabilities_is_compatible_matches_major_version() {
+        let matching = Capabilities { protocol_version: CLIENT_PROTOCOL_MAJOR, features: Default::default() };
+        assert!(matching.is_compatible());
+
+        let mismatched = Capabilities { protocol_version: CLIENT_PROTOCOL_MAJOR + 1, features: Default::default() };
+        assert!(!mismatched.is_compatible());
+    }
+
+    #[test]
+    fn test_capabilities_supports_checks_feature_set() {
+        let caps = Capabilities {
+            protocol_version: CLIENT_PROTOCOL_MAJOR,
+            features: ["batch_presence".to_string()].into_iter().collect(),
+        };
+        assert!(caps.supports("batch_presence"));
+        assert!(!caps.supports("unknown_feature"));
+    }
+
+    // -----------------------------------------------------------------------
+    // `capabilities()` against a real in-process HTTP server, confirming the
+    // fetch/cache/fallback behavior end to end (mirrors the one integration
+    // test in `outbox::drain`).
+    // -----------------------------------------------------------------------
+
+    async fn serve_once(addr: std::net::SocketAddr, response: &'static str) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0u8; 4096];
+        let mut total = 0;
+        loop {
+            let n = socket.read(&mut buf[total..]).await.unwrap_or(0);
+            if n == 0 {
+                break;
+            }
+            total += n;
+            if buf[..total].windows(4).any(|w| w == b"\r\n\r\n") {
+                break;
+            }
+        }
+        let _ = socket.write_all(response.as_bytes()).await;
+        let _ = socket.shutdown().await;
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_capabilities_fetches_and_caches_within_ttl() {
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let body = r#"{"protocol_version":1,"features":["batch_presence"]}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let server = tokio::spawn(serve_once(addr, Box::leak(response.into_boxed_str())));
+
+        let url = format!("http://{}", addr);
+        let cfg = ShipperConfig::default().with_overrides(Some(&url), None, None, None);
+        let client = ShipperClient::new(&cfg).unwrap();
+
+        let caps = client.capabilities().await;
+        assert_eq!(caps.protocol_version, 1);
+        assert!(caps.supports("batch_presence"));
+        server.await.unwrap();
+
+        // Cached — a second call must not need another server response.
+        let cached = client.capabilities().await;
+        assert_eq!(cached.protocol_version, 1);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_capabilities_falls_back_to_baseline_when_unreachable() {
+        let cfg = ShipperConfig::default().with_overrides(Some("http://127.0.0.1:1"), None, None, None);
+        let client = ShipperClient::new(&cfg).unwrap();
+
+        let caps = client.capabilities().await;
+        assert_eq!(caps.protocol_version, CLIENT_PROTOCOL_MAJOR);
+        assert!(caps.is_compatible());
+        assert!(caps.features.is_empty());
+    }
+
+    #[test]
+    fn test_batch_framing_round_trips_lengths() {
+        // Mirrors the length-prefix framing in ShipperClient::ship_batch.
+        let payloads: Vec<Vec<u8>> = vec![vec![1, 2, 3], vec![], vec![9; 10]];
+
+        let mut body = Vec::new();
+        for payload in &payloads {
+            body.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            body.extend_from_slice(payload);
+        }
+
+        let mut cursor = 0usize;
+        for payload in &payloads {
+            let len = u32::from_le_bytes(body[cursor..cursor + 4].try_into().unwrap()) as usize;
+            cursor += 4;
+            assert_eq!(&body[cursor..cursor + len], payload.as_slice());
+            cursor += len;
+        }
+        assert_eq!(cursor, body.len());
+    }
 }
@@ -0,0 +1,128 @@
+//! Pluggable ship destinations.
+//!
+//! `ShipperClient` (HTTP POST to a bespoke ingest server) and `S3Target`
+//! (direct writes to an S3-compatible bucket) both implement `ShipTarget`,
+//! so `prepare_file`/`ship_and_record`-style callers can upload a compressed
+//! payload without caring which backend is configured.
+
+use async_trait::async_trait;
+
+use super::client::{ShipResult, ShipperClient};
+use super::s3::S3Target;
+use crate::config::ShipperConfig;
+use crate::pipeline::compressor::CompressionAlgo;
+
+/// A destination a compressed, already-encrypted payload can be uploaded to.
+///
+/// `manifest_key` identifies the payload (see `s3::manifest_key`) — HTTP
+/// targets ignore it today since the ingest server assigns its own identity
+/// from the payload body, but object-storage targets use it as the object
+/// key. `algo` is the algorithm `bytes` was actually compressed with, so an
+/// HTTP target can label `Content-Encoding` correctly even when different
+/// items used different algorithms (see `ShipItem::algo`).
+#[async_trait]
+pub trait ShipTarget: Send + Sync {
+    async fn put(&self, manifest_key: &str, bytes: Vec<u8>, algo: CompressionAlgo) -> ShipResult;
+
+    /// Upload several payloads in one round-trip where the target supports
+    /// it. The default falls back to one `put` per item — correct for any
+    /// target, just not as cheap as a real batch endpoint — so only
+    /// `ShipperClient` needs to override it (see `ShipperClient::ship_batch`).
+    async fn put_batch(&self, items: Vec<(String, Vec<u8>, CompressionAlgo)>) -> Vec<ShipResult> {
+        let mut results = Vec::with_capacity(items.len());
+        for (key, bytes, algo) in items {
+            results.push(self.put(&key, bytes, algo).await);
+        }
+        results
+    }
+}
+
+#[async_trait]
+impl ShipTarget for ShipperClient {
+    async fn put(&self, _manifest_key: &str, bytes: Vec<u8>, algo: CompressionAlgo) -> ShipResult {
+        self.ship(bytes, algo).await
+    }
+
+    async fn put_batch(&self, items: Vec<(String, Vec<u8>, CompressionAlgo)>) -> Vec<ShipResult> {
+        // A real HTTP batch is one request with one Content-Encoding for the
+        // whole framed body (see `ShipperClient::ship_batch`), so a group
+        // mixing algorithms ships under its first item's — groups bigger
+        // than one item are the less common path (see `shipper::ship_group`);
+        // the adaptive per-item choice is exact for the common single-item
+        // `put`.
+        let algo = items
+            .first()
+            .map(|(_, _, algo)| *algo)
+            .unwrap_or(self.compression());
+        let payloads = items.into_iter().map(|(_, bytes, _)| bytes).collect();
+        self.ship_batch(payloads, algo).await
+    }
+}
+
+/// `s3://bucket/prefix` vs. an HTTP(S) ingest URL.
+enum TargetAddr {
+    Http,
+    S3 { bucket: String, prefix: String },
+}
+
+fn parse_target_addr(api_url: &str) -> TargetAddr {
+    match api_url.strip_prefix("s3://") {
+        Some(rest) => {
+            let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+            TargetAddr::S3 {
+                bucket: bucket.to_string(),
+                prefix: prefix.trim_end_matches('/').to_string(),
+            }
+        }
+        None => TargetAddr::Http,
+    }
+}
+
+/// Build the configured `ShipTarget` — an `S3Target` when `config.api_url`
+/// is `s3://bucket/prefix`, otherwise the existing `ShipperClient`.
+pub async fn resolve(
+    config: &ShipperConfig,
+    algo: crate::pipeline::compressor::CompressionAlgo,
+) -> anyhow::Result<Box<dyn ShipTarget>> {
+    match parse_target_addr(&config.api_url) {
+        TargetAddr::S3 { bucket, prefix } => {
+            Ok(Box::new(S3Target::new(&bucket, &prefix).await?))
+        }
+        TargetAddr::Http => Ok(Box::new(ShipperClient::with_compression(config, algo)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_s3_url_splits_bucket_and_prefix() {
+        match parse_target_addr("s3://my-bucket/sessions/claude") {
+            TargetAddr::S3 { bucket, prefix } => {
+                assert_eq!(bucket, "my-bucket");
+                assert_eq!(prefix, "sessions/claude");
+            }
+            TargetAddr::Http => panic!("expected S3 target"),
+        }
+    }
+
+    #[test]
+    fn test_parse_s3_url_without_prefix() {
+        match parse_target_addr("s3://my-bucket") {
+            TargetAddr::S3 { bucket, prefix } => {
+                assert_eq!(bucket, "my-bucket");
+                assert_eq!(prefix, "");
+            }
+            TargetAddr::Http => panic!("expected S3 target"),
+        }
+    }
+
+    #[test]
+    fn test_parse_http_url_is_http_target() {
+        assert!(matches!(
+            parse_target_addr("https://longhouse.example.com"),
+            TargetAddr::Http
+        ));
+    }
+}
@@ -0,0 +1,54 @@
+//! Cooperative cancellation tripwire for the daemon's graceful-shutdown drain.
+//!
+//! A plain `Arc<AtomicBool>` rather than pulling in `tokio-util` for a single
+//! flag — mirrors the repo's other cheap-clone `Arc`-wrapped shared state
+//! (`circuit_breaker::CircuitBreaker`, `error_tracker::ConsecutiveErrorTracker`).
+//! `daemon::run`'s final drain pass flips this once `shutdown_grace` elapses;
+//! `shipper::full_scan`/`shipper::ship_batch` check it between units of work
+//! so a slow catch-up winds down between a network round-trip instead of
+//! being severed mid-request.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Cheap-clone cancellation flag threaded into `shipper::full_scan` and
+/// `shipper::ship_batch` as `Option<&ShutdownToken>` — `None` on the live
+/// event-driven paths that never need to stop early.
+#[derive(Clone, Default)]
+pub struct ShutdownToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ShutdownToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trip the tripwire. Every clone observes this immediately.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starts_uncancelled() {
+        let token = ShutdownToken::new();
+        assert!(!token.is_cancelled());
+    }
+
+    #[test]
+    fn test_cancel_is_visible_on_clone() {
+        let token = ShutdownToken::new();
+        let clone = token.clone();
+        token.cancel();
+        assert!(clone.is_cancelled());
+    }
+}
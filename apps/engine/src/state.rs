@@ -0,0 +1,7 @@
+pub mod chunks;
+pub mod db;
+pub mod file_state;
+pub mod jobs;
+pub mod parse_errors;
+pub mod spool;
+pub mod store;
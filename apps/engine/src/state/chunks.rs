@@ -0,0 +1,94 @@
+//! Dedup ledger of content-defined chunk hashes already accepted by the server.
+//!
+//! Shares the shipper's SQLite DB (see `state/db.rs`). Hashes are stored as
+//! the raw 32 blake3 bytes rather than hex to keep the table compact.
+
+use anyhow::Result;
+use rusqlite::{Connection, OptionalExtension};
+
+/// Chunk hash bookkeeping on the shared SQLite connection.
+pub struct ChunkStore<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> ChunkStore<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Returns, for each hash in order, whether the server has already seen it.
+    pub fn seen(&self, hashes: &[[u8; 32]]) -> Result<Vec<bool>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT 1 FROM chunks WHERE hash = ?")?;
+        let mut result = Vec::with_capacity(hashes.len());
+        for hash in hashes {
+            let known = stmt
+                .query_row([hash.as_slice()], |_| Ok(()))
+                .optional()?
+                .is_some();
+            result.push(known);
+        }
+        Ok(result)
+    }
+
+    /// Record a batch of newly-shipped chunk hashes as seen.
+    pub fn mark_seen(&self, hashes: &[[u8; 32]]) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        for hash in hashes {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO chunks (hash, first_seen) VALUES (?1, ?2)",
+                rusqlite::params![hash.as_slice(), now],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Count of distinct chunk hashes ever accepted.
+    pub fn count(&self) -> Result<usize> {
+        let count: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM chunks", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::db::open_db;
+
+    fn setup() -> (tempfile::NamedTempFile, Connection) {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let conn = open_db(Some(tmp.path())).unwrap();
+        (tmp, conn)
+    }
+
+    #[test]
+    fn test_unseen_by_default() {
+        let (_tmp, conn) = setup();
+        let store = ChunkStore::new(&conn);
+        let hash = [1u8; 32];
+        assert_eq!(store.seen(&[hash]).unwrap(), vec![false]);
+    }
+
+    #[test]
+    fn test_mark_seen_then_known() {
+        let (_tmp, conn) = setup();
+        let store = ChunkStore::new(&conn);
+        let hash = [2u8; 32];
+        store.mark_seen(&[hash]).unwrap();
+        assert_eq!(store.seen(&[hash]).unwrap(), vec![true]);
+        assert_eq!(store.count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_mark_seen_is_idempotent() {
+        let (_tmp, conn) = setup();
+        let store = ChunkStore::new(&conn);
+        let hash = [3u8; 32];
+        store.mark_seen(&[hash]).unwrap();
+        store.mark_seen(&[hash]).unwrap();
+        assert_eq!(store.count().unwrap(), 1);
+    }
+}
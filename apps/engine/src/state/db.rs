@@ -11,6 +11,141 @@ use rusqlite::Connection;
 /// Default DB filename (same as Python).
 const DB_FILENAME: &str = "longhouse-shipper.db";
 
+/// Current schema version, tracked via `PRAGMA user_version`. Bump this and
+/// append a migration to `MIGRATIONS` whenever a new column or index is
+/// needed — never edit the base `CREATE TABLE` statements for existing
+/// installs, since `CREATE TABLE IF NOT EXISTS` is a no-op against an
+/// already-created table.
+const DB_VERSION: u32 = 6;
+
+/// A single migration step: the version it brings the DB to, and the
+/// function that applies it. Functions must be safe to re-run against a DB
+/// that already has their changes (e.g. the Python shipper independently
+/// created the same column) — check `pragma_table_info` before `ALTER
+/// TABLE` rather than relying on the error it raises for a duplicate
+/// column. Migrations may only add nullable columns and indexes, never drop
+/// or rename, so the Python shipper sharing this file stays compatible.
+type MigrationFn = fn(&Connection) -> Result<()>;
+const MIGRATIONS: &[(u32, MigrationFn)] = &[
+    (1, migrate_v1_add_file_state_inode),
+    (2, migrate_v2_add_spool_last_backoff),
+    (3, migrate_v3_add_parse_errors),
+    (4, migrate_v4_add_spool_seq),
+    (5, migrate_v5_add_spool_truncated_from),
+    (6, migrate_v6_add_spool_payload),
+];
+
+/// v1: rotation detection (see `shipper::prepare_file`) needs to tell a
+/// reused path apart from a truly-appended file, which requires recording
+/// the inode alongside the byte offset.
+fn migrate_v1_add_file_state_inode(conn: &Connection) -> Result<()> {
+    if !column_exists(conn, "file_state", "inode")? {
+        conn.execute("ALTER TABLE file_state ADD COLUMN inode INTEGER", [])?;
+    }
+    Ok(())
+}
+
+/// v2: decorrelated-jitter backoff (see `state::spool::BackoffStrategy`)
+/// needs each entry's previously chosen delay to build the next one on.
+fn migrate_v2_add_spool_last_backoff(conn: &Connection) -> Result<()> {
+    if !column_exists(conn, "spool_queue", "last_backoff_secs")? {
+        conn.execute("ALTER TABLE spool_queue ADD COLUMN last_backoff_secs REAL", [])?;
+    }
+    Ok(())
+}
+
+/// v3: sliding-window error rate tracking (see `state::parse_errors`) needs
+/// its own table — a new table is additive by nature, so `CREATE TABLE IF
+/// NOT EXISTS` is already idempotent without a `pragma_table_info` check.
+fn migrate_v3_add_parse_errors(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS parse_errors (
+            ts TEXT NOT NULL,
+            provider TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            detail TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_parse_errors_ts ON parse_errors(ts);",
+    )?;
+    Ok(())
+}
+
+/// v4: deterministic total order and dedupe-by-sequence for spool delivery
+/// (see `state::spool::Spool::enqueue`) needs a monotonic counter that
+/// survives concurrent Python+Rust writers — a single-row table incremented
+/// in the same transaction as the insert, rather than relying on
+/// `created_at` timestamps, which can collide at sub-millisecond resolution.
+fn migrate_v4_add_spool_seq(conn: &Connection) -> Result<()> {
+    if !column_exists(conn, "spool_queue", "seq")? {
+        conn.execute("ALTER TABLE spool_queue ADD COLUMN seq INTEGER", [])?;
+    }
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS spool_seq (id INTEGER PRIMARY KEY CHECK (id = 1), value INTEGER NOT NULL);
+         INSERT OR IGNORE INTO spool_seq (id, value) VALUES (1, 0);
+         CREATE UNIQUE INDEX IF NOT EXISTS idx_spool_queue_seq ON spool_queue(seq);",
+    )?;
+    Ok(())
+}
+
+/// v5: record-boundary validation on enqueue (see
+/// `state::spool::Spool::enqueue_with_truncation`) needs somewhere to note
+/// that a candidate range was clamped because a complete line failed
+/// UTF-8/JSON validation, so offline triage can tell "truncated for
+/// corruption" apart from an ordinary trailing-partial-line clamp.
+fn migrate_v5_add_spool_truncated_from(conn: &Connection) -> Result<()> {
+    if !column_exists(conn, "spool_queue", "truncated_from")? {
+        conn.execute("ALTER TABLE spool_queue ADD COLUMN truncated_from INTEGER", [])?;
+    }
+    Ok(())
+}
+
+/// v6: "owned-blob" spool mode (see `state::spool::Spool::with_owned_blobs`)
+/// copies a spooled range's bytes into the row itself via SQLite's
+/// incremental blob I/O, rather than only ever pointing at the source
+/// file — this column holds that copy. `NULL` for every row spooled in the
+/// default "reference" mode.
+fn migrate_v6_add_spool_payload(conn: &Connection) -> Result<()> {
+    if !column_exists(conn, "spool_queue", "payload")? {
+        conn.execute("ALTER TABLE spool_queue ADD COLUMN payload BLOB", [])?;
+    }
+    Ok(())
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let sql = format!("SELECT COUNT(*) FROM pragma_table_info('{table}') WHERE name = ?1");
+    let count: i64 = conn.query_row(&sql, [column], |row| row.get(0))?;
+    Ok(count > 0)
+}
+
+/// Bring the DB from its on-disk `user_version` up to `DB_VERSION`,
+/// applying each outstanding migration in its own transaction. Errors out
+/// if the on-disk version is newer than this binary knows about (e.g. a
+/// future Python shipper release wrote a schema we don't understand) —
+/// better to fail loud than silently corrupt a newer schema.
+fn run_migrations(conn: &Connection) -> Result<()> {
+    let current: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if current > DB_VERSION {
+        anyhow::bail!(
+            "DB schema version {} is newer than this binary supports (max {}); refusing to open",
+            current,
+            DB_VERSION
+        );
+    }
+
+    for (version, migration) in MIGRATIONS {
+        if *version > current {
+            let tx = conn.unchecked_transaction()?;
+            migration(&tx)?;
+            tx.pragma_update(None, "user_version", version)?;
+            tx.commit()?;
+            tracing::debug!("Migrated shipper DB to schema version {}", version);
+        }
+    }
+
+    Ok(())
+}
+
 /// Open (or create) the shipper database with WAL mode and proper pragmas.
 pub fn open_db(db_path: Option<&Path>) -> Result<Connection> {
     let path = match db_path {
@@ -43,7 +178,8 @@ pub fn open_db(db_path: Option<&Path>) -> Result<Connection> {
             acked_offset INTEGER NOT NULL DEFAULT 0,
             session_id TEXT,
             provider_session_id TEXT,
-            last_updated TEXT NOT NULL
+            last_updated TEXT NOT NULL,
+            inode INTEGER
         );
 
         CREATE TABLE IF NOT EXISTS spool_queue (
@@ -57,13 +193,50 @@ pub fn open_db(db_path: Option<&Path>) -> Result<Connection> {
             retry_count INTEGER DEFAULT 0,
             next_retry_at TEXT NOT NULL,
             last_error TEXT,
-            status TEXT DEFAULT 'pending'
+            status TEXT DEFAULT 'pending',
+            last_backoff_secs REAL,
+            seq INTEGER,
+            truncated_from INTEGER,
+            payload BLOB
         );
 
         CREATE INDEX IF NOT EXISTS idx_spool_status
-        ON spool_queue(status, next_retry_at);",
+        ON spool_queue(status, next_retry_at);
+
+        CREATE TABLE IF NOT EXISTS spool_seq (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            value INTEGER NOT NULL
+        );
+
+        INSERT OR IGNORE INTO spool_seq (id, value) VALUES (1, 0);
+
+        CREATE TABLE IF NOT EXISTS chunks (
+            hash BLOB PRIMARY KEY,
+            first_seen INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS job_files (
+            job_id INTEGER NOT NULL,
+            path TEXT NOT NULL,
+            state TEXT NOT NULL DEFAULT 'pending',
+            error TEXT,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (job_id, path)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_job_files_state
+        ON job_files(job_id, state);",
     )?;
 
+    // Bring older DBs (including Python-shipper-era ones) up to DB_VERSION.
+    run_migrations(&conn)?;
+
     tracing::debug!("Opened shipper DB: {}", path.display());
     Ok(conn)
 }
@@ -110,4 +283,228 @@ mod tests {
             .unwrap();
         assert_eq!(mode, "wal");
     }
+
+    #[test]
+    fn test_fresh_db_lands_on_current_version() {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let conn = open_db(Some(tmp.path())).unwrap();
+
+        let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, DB_VERSION);
+        assert!(column_exists(&conn, "file_state", "inode").unwrap());
+    }
+
+    #[test]
+    fn test_migrates_pre_inode_db() {
+        // Simulate a Python-shipper-era DB: file_state without the inode
+        // column and no user_version set yet.
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        {
+            let conn = Connection::open(tmp.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE file_state (
+                    path TEXT PRIMARY KEY,
+                    provider TEXT NOT NULL,
+                    queued_offset INTEGER NOT NULL DEFAULT 0,
+                    acked_offset INTEGER NOT NULL DEFAULT 0,
+                    session_id TEXT,
+                    provider_session_id TEXT,
+                    last_updated TEXT NOT NULL
+                );",
+            )
+            .unwrap();
+        }
+
+        let conn = open_db(Some(tmp.path())).unwrap();
+        assert!(column_exists(&conn, "file_state", "inode").unwrap());
+        let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, DB_VERSION);
+    }
+
+    #[test]
+    fn test_migrates_pre_backoff_db() {
+        // Simulate a DB that already had the v1 inode column but predates
+        // decorrelated-jitter backoff.
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        {
+            let conn = Connection::open(tmp.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE file_state (
+                    path TEXT PRIMARY KEY,
+                    provider TEXT NOT NULL,
+                    queued_offset INTEGER NOT NULL DEFAULT 0,
+                    acked_offset INTEGER NOT NULL DEFAULT 0,
+                    session_id TEXT,
+                    provider_session_id TEXT,
+                    last_updated TEXT NOT NULL,
+                    inode INTEGER
+                );
+                CREATE TABLE spool_queue (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    provider TEXT NOT NULL,
+                    file_path TEXT NOT NULL,
+                    start_offset INTEGER NOT NULL,
+                    end_offset INTEGER NOT NULL,
+                    session_id TEXT,
+                    created_at TEXT NOT NULL,
+                    retry_count INTEGER DEFAULT 0,
+                    next_retry_at TEXT NOT NULL,
+                    last_error TEXT,
+                    status TEXT DEFAULT 'pending'
+                );",
+            )
+            .unwrap();
+            conn.pragma_update(None, "user_version", 1u32).unwrap();
+        }
+
+        let conn = open_db(Some(tmp.path())).unwrap();
+        assert!(column_exists(&conn, "spool_queue", "last_backoff_secs").unwrap());
+        let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, DB_VERSION);
+    }
+
+    #[test]
+    fn test_migrates_pre_seq_db() {
+        // Simulate a DB that predates the spool sequence counter.
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        {
+            let conn = Connection::open(tmp.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE file_state (
+                    path TEXT PRIMARY KEY,
+                    provider TEXT NOT NULL,
+                    queued_offset INTEGER NOT NULL DEFAULT 0,
+                    acked_offset INTEGER NOT NULL DEFAULT 0,
+                    session_id TEXT,
+                    provider_session_id TEXT,
+                    last_updated TEXT NOT NULL,
+                    inode INTEGER
+                );
+                CREATE TABLE spool_queue (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    provider TEXT NOT NULL,
+                    file_path TEXT NOT NULL,
+                    start_offset INTEGER NOT NULL,
+                    end_offset INTEGER NOT NULL,
+                    session_id TEXT,
+                    created_at TEXT NOT NULL,
+                    retry_count INTEGER DEFAULT 0,
+                    next_retry_at TEXT NOT NULL,
+                    last_error TEXT,
+                    status TEXT DEFAULT 'pending',
+                    last_backoff_secs REAL
+                );",
+            )
+            .unwrap();
+            conn.pragma_update(None, "user_version", 2u32).unwrap();
+        }
+
+        let conn = open_db(Some(tmp.path())).unwrap();
+        assert!(column_exists(&conn, "spool_queue", "seq").unwrap());
+        let value: i64 = conn
+            .query_row("SELECT value FROM spool_seq WHERE id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(value, 0);
+        let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, DB_VERSION);
+    }
+
+    #[test]
+    fn test_migrates_pre_truncated_from_db() {
+        // Simulate a DB that predates record-boundary truncation tracking.
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        {
+            let conn = Connection::open(tmp.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE file_state (
+                    path TEXT PRIMARY KEY,
+                    provider TEXT NOT NULL,
+                    queued_offset INTEGER NOT NULL DEFAULT 0,
+                    acked_offset INTEGER NOT NULL DEFAULT 0,
+                    session_id TEXT,
+                    provider_session_id TEXT,
+                    last_updated TEXT NOT NULL,
+                    inode INTEGER
+                );
+                CREATE TABLE spool_queue (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    provider TEXT NOT NULL,
+                    file_path TEXT NOT NULL,
+                    start_offset INTEGER NOT NULL,
+                    end_offset INTEGER NOT NULL,
+                    session_id TEXT,
+                    created_at TEXT NOT NULL,
+                    retry_count INTEGER DEFAULT 0,
+                    next_retry_at TEXT NOT NULL,
+                    last_error TEXT,
+                    status TEXT DEFAULT 'pending',
+                    last_backoff_secs REAL,
+                    seq INTEGER
+                );",
+            )
+            .unwrap();
+            conn.pragma_update(None, "user_version", 4u32).unwrap();
+        }
+
+        let conn = open_db(Some(tmp.path())).unwrap();
+        assert!(column_exists(&conn, "spool_queue", "truncated_from").unwrap());
+        let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, DB_VERSION);
+    }
+
+    #[test]
+    fn test_migrates_pre_payload_db() {
+        // Simulate a DB that predates owned-blob spool storage.
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        {
+            let conn = Connection::open(tmp.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE file_state (
+                    path TEXT PRIMARY KEY,
+                    provider TEXT NOT NULL,
+                    queued_offset INTEGER NOT NULL DEFAULT 0,
+                    acked_offset INTEGER NOT NULL DEFAULT 0,
+                    session_id TEXT,
+                    provider_session_id TEXT,
+                    last_updated TEXT NOT NULL,
+                    inode INTEGER
+                );
+                CREATE TABLE spool_queue (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    provider TEXT NOT NULL,
+                    file_path TEXT NOT NULL,
+                    start_offset INTEGER NOT NULL,
+                    end_offset INTEGER NOT NULL,
+                    session_id TEXT,
+                    created_at TEXT NOT NULL,
+                    retry_count INTEGER DEFAULT 0,
+                    next_retry_at TEXT NOT NULL,
+                    last_error TEXT,
+                    status TEXT DEFAULT 'pending',
+                    last_backoff_secs REAL,
+                    seq INTEGER,
+                    truncated_from INTEGER
+                );",
+            )
+            .unwrap();
+            conn.pragma_update(None, "user_version", 5u32).unwrap();
+        }
+
+        let conn = open_db(Some(tmp.path())).unwrap();
+        assert!(column_exists(&conn, "spool_queue", "payload").unwrap());
+        let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, DB_VERSION);
+    }
+
+    #[test]
+    fn test_rejects_newer_on_disk_version() {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        {
+            let conn = Connection::open(tmp.path()).unwrap();
+            conn.pragma_update(None, "user_version", DB_VERSION + 1).unwrap();
+        }
+
+        let err = open_db(Some(tmp.path())).unwrap_err();
+        assert!(err.to_string().contains("newer than this binary supports"));
+    }
 }
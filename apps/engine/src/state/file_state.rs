@@ -118,6 +118,31 @@ impl<'a> FileState<'a> {
         Ok(())
     }
 
+    /// Get the inode last recorded for a file, for rotation detection.
+    /// Returns `None` if the file isn't tracked yet or no inode was recorded.
+    pub fn get_inode(&self, file_path: &str) -> Result<Option<u64>> {
+        let result = self.conn.query_row(
+            "SELECT inode FROM file_state WHERE path = ?",
+            [file_path],
+            |row| row.get::<_, Option<i64>>(0),
+        );
+        match result {
+            Ok(v) => Ok(v.map(|i| i as u64)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Record the current inode for an already-tracked file (no-op if the
+    /// file has no `file_state` row yet — call after `set_offset`).
+    pub fn set_inode(&self, file_path: &str, inode: u64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE file_state SET inode = ?1 WHERE path = ?2",
+            rusqlite::params![inode as i64, file_path],
+        )?;
+        Ok(())
+    }
+
     /// Reset both offsets to 0 (e.g., after file truncation).
     pub fn reset_offsets(&self, file_path: &str) -> Result<()> {
         let now = Utc::now().to_rfc3339();
@@ -182,6 +207,32 @@ impl<'a> FileState<'a> {
         }
     }
 
+    /// List all tracked files (used by the admin `/status` endpoint).
+    pub fn list_all(&self) -> Result<Vec<TrackedFile>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path, provider, queued_offset, acked_offset, session_id, provider_session_id, last_updated
+             FROM file_state",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(TrackedFile {
+                path: row.get(0)?,
+                provider: row.get(1)?,
+                queued_offset: row.get::<_, i64>(2)? as u64,
+                acked_offset: row.get::<_, i64>(3)? as u64,
+                session_id: row.get(4)?,
+                provider_session_id: row.get(5)?,
+                last_updated: row
+                    .get::<_, String>(6)
+                    .map(|s| DateTime::parse_from_rfc3339(&s).map(|d| d.with_timezone(&Utc)).unwrap_or_else(|_| Utc::now()))?,
+            })
+        })?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
     /// Count all tracked files.
     pub fn count(&self) -> Result<usize> {
         let count: i64 = self
@@ -284,6 +335,26 @@ mod tests {
         assert_eq!(unacked[0].path, "/a");
     }
 
+    #[test]
+    fn test_inode_roundtrip() {
+        let (_tmp, conn) = setup();
+        let fs = FileState::new(&conn);
+        assert_eq!(fs.get_inode("/f").unwrap(), None);
+
+        fs.set_offset("/f", 100, "s1", "ps1", "claude").unwrap();
+        fs.set_inode("/f", 42).unwrap();
+        assert_eq!(fs.get_inode("/f").unwrap(), Some(42));
+    }
+
+    #[test]
+    fn test_set_inode_is_noop_for_untracked_file() {
+        let (_tmp, conn) = setup();
+        let fs = FileState::new(&conn);
+        // No file_state row exists yet — the UPDATE should affect no rows.
+        fs.set_inode("/nope", 7).unwrap();
+        assert_eq!(fs.get_inode("/nope").unwrap(), None);
+    }
+
     #[test]
     fn test_reset_offsets() {
         let (_tmp, conn) = setup();
@@ -294,6 +365,17 @@ mod tests {
         assert_eq!(fs.get_queued_offset("/f").unwrap(), 0);
     }
 
+    #[test]
+    fn test_list_all() {
+        let (_tmp, conn) = setup();
+        let fs = FileState::new(&conn);
+        fs.set_offset("/a", 100, "s1", "ps1", "claude").unwrap();
+        fs.set_offset("/b", 200, "s2", "ps2", "codex").unwrap();
+
+        let all = fs.list_all().unwrap();
+        assert_eq!(all.len(), 2);
+    }
+
     #[test]
     fn test_get_session() {
         let (_tmp, conn) = setup();
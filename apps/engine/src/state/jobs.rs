@@ -0,0 +1,165 @@
+//! Resumable job tracking: a named job's file list plus per-file state.
+//!
+//! Shares the shipper's SQLite DB (see `state/db.rs`). A job is identified by
+//! name (callers reuse a stable name, e.g. `"bench-L3-zstd"`, to resume the
+//! same run across restarts); `start` seeds any files not already tracked as
+//! `Pending` so a restart only reprocesses what's left. Unlike `FileState`'s
+//! byte-offset tracking (built for incremental re-shipment of growing files),
+//! a job's unit of work is a whole file processed exactly once.
+
+use anyhow::Result;
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension};
+
+/// Per-file outcome within a job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobFileState {
+    Pending,
+    Done,
+    /// Non-fatal processing error — recorded rather than silently skipped.
+    Failed(String),
+}
+
+impl JobFileState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JobFileState::Pending => "pending",
+            JobFileState::Done => "done",
+            JobFileState::Failed(_) => "failed",
+        }
+    }
+}
+
+/// Job tracking operations on a shared SQLite connection.
+pub struct Jobs<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> Jobs<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Start (or resume) the named job: if a job with this name already
+    /// exists, reuse it and leave existing per-file state untouched; if not,
+    /// create it and seed every path as `Pending`. Returns the job id.
+    pub fn start(&self, name: &str, paths: &[String]) -> Result<i64> {
+        let existing: Option<i64> = self
+            .conn
+            .query_row("SELECT id FROM jobs WHERE name = ?1", [name], |row| {
+                row.get(0)
+            })
+            .optional()?;
+
+        let job_id = match existing {
+            Some(id) => id,
+            None => {
+                let now = Utc::now().to_rfc3339();
+                self.conn.execute(
+                    "INSERT INTO jobs (name, created_at) VALUES (?1, ?2)",
+                    rusqlite::params![name, now],
+                )?;
+                self.conn.last_insert_rowid()
+            }
+        };
+
+        let now = Utc::now().to_rfc3339();
+        for path in paths {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO job_files (job_id, path, state, updated_at)
+                 VALUES (?1, ?2, 'pending', ?3)",
+                rusqlite::params![job_id, path, now],
+            )?;
+        }
+
+        Ok(job_id)
+    }
+
+    /// Paths still `Pending` for this job, in the order `start` inserted them.
+    pub fn pending_paths(&self, job_id: i64) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path FROM job_files WHERE job_id = ?1 AND state = 'pending' ORDER BY rowid",
+        )?;
+        let rows = stmt.query_map([job_id], |row| row.get::<_, String>(0))?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
+    /// Record the outcome of processing one file.
+    pub fn set_state(&self, job_id: i64, path: &str, state: &JobFileState) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        let error = match state {
+            JobFileState::Failed(msg) => Some(msg.as_str()),
+            _ => None,
+        };
+        self.conn.execute(
+            "UPDATE job_files SET state = ?1, error = ?2, updated_at = ?3
+             WHERE job_id = ?4 AND path = ?5",
+            rusqlite::params![state.as_str(), error, now, job_id, path],
+        )?;
+        Ok(())
+    }
+
+    /// Count of files in each state, as `(pending, done, failed)`, for a
+    /// completion summary.
+    pub fn counts(&self, job_id: i64) -> Result<(usize, usize, usize)> {
+        let mut count_for = |state: &str| -> Result<usize> {
+            let n: i64 = self.conn.query_row(
+                "SELECT COUNT(*) FROM job_files WHERE job_id = ?1 AND state = ?2",
+                rusqlite::params![job_id, state],
+                |row| row.get(0),
+            )?;
+            Ok(n as usize)
+        };
+        Ok((count_for("pending")?, count_for("done")?, count_for("failed")?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::db::open_db;
+
+    fn setup() -> (tempfile::NamedTempFile, Connection) {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let conn = open_db(Some(tmp.path())).unwrap();
+        (tmp, conn)
+    }
+
+    #[test]
+    fn test_start_seeds_pending_files() {
+        let (_tmp, conn) = setup();
+        let jobs = Jobs::new(&conn);
+        let job_id = jobs.start("test-job", &["a.jsonl".to_string(), "b.jsonl".to_string()]).unwrap();
+        let pending = jobs.pending_paths(job_id).unwrap();
+        assert_eq!(pending, vec!["a.jsonl", "b.jsonl"]);
+    }
+
+    #[test]
+    fn test_resume_skips_done_files() {
+        let (_tmp, conn) = setup();
+        let jobs = Jobs::new(&conn);
+        let job_id = jobs.start("test-job", &["a.jsonl".to_string(), "b.jsonl".to_string()]).unwrap();
+        jobs.set_state(job_id, "a.jsonl", &JobFileState::Done).unwrap();
+
+        // Re-starting the same named job (simulating a restart) must not
+        // reset "a.jsonl" back to pending.
+        let job_id2 = jobs.start("test-job", &["a.jsonl".to_string(), "b.jsonl".to_string()]).unwrap();
+        assert_eq!(job_id, job_id2);
+        assert_eq!(jobs.pending_paths(job_id2).unwrap(), vec!["b.jsonl"]);
+    }
+
+    #[test]
+    fn test_failed_files_recorded_with_reason() {
+        let (_tmp, conn) = setup();
+        let jobs = Jobs::new(&conn);
+        let job_id = jobs.start("test-job", &["a.jsonl".to_string()]).unwrap();
+        jobs.set_state(job_id, "a.jsonl", &JobFileState::Failed("bad json".to_string())).unwrap();
+
+        let (pending, done, failed) = jobs.counts(job_id).unwrap();
+        assert_eq!((pending, done, failed), (0, 0, 1));
+    }
+}
@@ -0,0 +1,110 @@
+//! Sliding-window log of parse/ship errors, for the heartbeat's
+//! `parse_error_count_1h` and `elevated_parse_error_rate` fields.
+//!
+//! Shares the shipper's SQLite DB (see `state/db.rs`). A row per error keeps
+//! this simple and queryable by time window, unlike an in-memory counter
+//! that would reset on daemon restart and miss errors from the one-shot
+//! `ship`/`parse` CLI paths.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use rusqlite::Connection;
+
+/// Parse/ship error bookkeeping on the shared SQLite connection.
+pub struct ParseErrorLog<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> ParseErrorLog<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Record a parse or ship error against a file.
+    pub fn record(&self, provider: &str, file_path: &str, kind: &str, detail: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO parse_errors (ts, provider, file_path, kind, detail) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![Utc::now().to_rfc3339(), provider, file_path, kind, detail],
+        )?;
+        Ok(())
+    }
+
+    /// Count of errors recorded within the last `window`.
+    pub fn count_since(&self, window: Duration) -> Result<u32> {
+        let cutoff = (Utc::now() - chrono::Duration::from_std(window)?).to_rfc3339();
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM parse_errors WHERE ts >= ?1",
+            [&cutoff],
+            |row| row.get(0),
+        )?;
+        Ok(count as u32)
+    }
+
+    /// Remove errors older than `max_age`. Returns count removed.
+    pub fn cleanup(&self, max_age: Duration) -> Result<usize> {
+        let cutoff = (Utc::now() - chrono::Duration::from_std(max_age)?).to_rfc3339();
+        let deleted = self
+            .conn
+            .execute("DELETE FROM parse_errors WHERE ts < ?1", [&cutoff])?;
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::db::open_db;
+
+    fn setup() -> (tempfile::NamedTempFile, Connection) {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let conn = open_db(Some(tmp.path())).unwrap();
+        (tmp, conn)
+    }
+
+    #[test]
+    fn test_record_and_count_since() {
+        let (_tmp, conn) = setup();
+        let log = ParseErrorLog::new(&conn);
+        assert_eq!(log.count_since(Duration::from_secs(3600)).unwrap(), 0);
+
+        log.record("claude", "/a.jsonl", "parse_error", "bad json").unwrap();
+        log.record("codex", "/b.jsonl", "parse_error", "truncated line").unwrap();
+
+        assert_eq!(log.count_since(Duration::from_secs(3600)).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_count_since_excludes_old_errors() {
+        let (_tmp, conn) = setup();
+        conn.execute(
+            "INSERT INTO parse_errors (ts, provider, file_path, kind, detail) VALUES (?1, 'claude', '/old.jsonl', 'parse_error', 'stale')",
+            [(Utc::now() - chrono::Duration::hours(2)).to_rfc3339()],
+        )
+        .unwrap();
+
+        let log = ParseErrorLog::new(&conn);
+        assert_eq!(log.count_since(Duration::from_secs(3600)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_cleanup_removes_old_rows() {
+        let (_tmp, conn) = setup();
+        conn.execute(
+            "INSERT INTO parse_errors (ts, provider, file_path, kind, detail) VALUES (?1, 'claude', '/old.jsonl', 'parse_error', 'stale')",
+            [(Utc::now() - chrono::Duration::days(2)).to_rfc3339()],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO parse_errors (ts, provider, file_path, kind, detail) VALUES (?1, 'claude', '/new.jsonl', 'parse_error', 'fresh')",
+            [Utc::now().to_rfc3339()],
+        )
+        .unwrap();
+
+        let log = ParseErrorLog::new(&conn);
+        let removed = log.cleanup(Duration::from_secs(24 * 3600)).unwrap();
+        assert_eq!(removed, 1);
+        assert_eq!(log.count_since(Duration::from_secs(365 * 24 * 3600)).unwrap(), 1);
+    }
+}
@@ -1,16 +1,31 @@
 //! Pointer-based offline spool for retry resilience.
 //!
-//! Stores byte-range pointers (NOT payloads) into source files.
-//! On retry, the source file is re-read and re-parsed.
+//! Stores byte-range pointers (NOT payloads) into source files by default.
+//! On retry, the source file is re-read and re-parsed. Opt into
+//! "owned-blob" mode (see [`Spool::with_owned_blobs`]) to instead copy each
+//! range's bytes into the row itself, decoupling replay from the source
+//! file's lifetime.
 //! Max queue size: 10,000 entries (backpressure).
 
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use rand::Rng;
 use rusqlite::Connection;
 
+use super::file_state::FileState;
+use crate::workunit::Span;
+
 /// Maximum spool entries before backpressure kicks in.
 const MAX_QUEUE_SIZE: usize = 10_000;
 
+/// Chunk size for incremental blob reads/writes in "owned-blob" mode — bytes
+/// are streamed in and out of the `payload` column this many at a time
+/// rather than the whole range at once.
+const BLOB_CHUNK_SIZE: usize = 64 * 1024;
+
 /// Base backoff in seconds.
 const BACKOFF_BASE: f64 = 5.0;
 
@@ -20,10 +35,37 @@ const BACKOFF_MAX: f64 = 3600.0;
 /// Default max retries before marking dead.
 const DEFAULT_MAX_RETRIES: u32 = 50;
 
+/// How `mark_failed` schedules the next retry.
+///
+/// A pure exponential schedule is deterministic: when the server recovers,
+/// every entry across every agent that failed at the same retry count wakes
+/// at the exact same instant and stampedes the endpoint. The jittered modes
+/// spread that out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackoffStrategy {
+    /// `min(BACKOFF_BASE * 2^retry, BACKOFF_MAX)` — no randomness.
+    Exponential,
+    /// `random_between(0, min(BACKOFF_BASE * 2^retry, BACKOFF_MAX))`.
+    FullJitter,
+    /// `min(BACKOFF_MAX, random_between(BACKOFF_BASE, prev_sleep * 3))` —
+    /// AWS's "decorrelated jitter": each delay builds on the last one
+    /// actually chosen, so the spread widens over successive failures
+    /// instead of just the upper bound.
+    #[default]
+    DecorrelatedJitter,
+}
+
 /// A spool entry — pointer to a byte range in a source file.
 #[derive(Debug, Clone)]
 pub struct SpoolEntry {
     pub id: i64,
+    /// Monotonic delivery sequence, assigned atomically with insertion via
+    /// the `spool_seq` counter table. Included in the shipped payload so the
+    /// server can dedupe idempotently after a crash between a successful
+    /// ship and `mark_shipped`, and used (instead of `created_at`, which can
+    /// collide at sub-millisecond resolution) to give `dequeue_batch` a
+    /// deterministic total order.
+    pub seq: i64,
     pub provider: String,
     pub file_path: String,
     pub start_offset: u64,
@@ -32,16 +74,99 @@ pub struct SpoolEntry {
     pub created_at: DateTime<Utc>,
     pub retry_count: u32,
     pub last_error: Option<String>,
+    /// Byte offset where a complete-but-invalid (bad UTF-8 or malformed
+    /// JSON) record was found and this entry's range got clamped back to
+    /// the last well-formed one — see `Spool::enqueue_with_truncation`.
+    /// `None` for entries that needed no corruption-recovery truncation
+    /// (including the ordinary case of clamping to a trailing partial
+    /// line, which isn't corruption).
+    pub truncated_from: Option<u64>,
+    /// Whether this entry's bytes were copied into the row's `payload` blob
+    /// at enqueue time (see [`Spool::with_owned_blobs`]), rather than only
+    /// ever being a pointer into `file_path`. Read it out with
+    /// [`Spool::read_payload`] — `file_path`/`start_offset`/`end_offset`
+    /// are kept either way, as metadata.
+    pub has_payload: bool,
+}
+
+/// Result of one [`Spool::checkpoint`] sweep.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CheckpointReport {
+    /// Total rows deleted across every file swept.
+    pub reclaimed: usize,
+    /// `(file_path, watermark)` for each file that had at least one row
+    /// reclaimed — the watermark it was reclaimed against, not just every
+    /// file considered.
+    pub watermarks: Vec<(String, u64)>,
+}
+
+/// Copy `len` bytes starting at `start_offset` in `source_path` into the
+/// just-inserted `spool_queue` row's `payload` blob (already sized to `len`
+/// via `zeroblob`), `BLOB_CHUNK_SIZE` bytes at a time — bounded memory use
+/// regardless of how large the range is.
+fn copy_range_into_blob(
+    conn: &Connection,
+    row_id: i64,
+    source_path: &Path,
+    start_offset: u64,
+    len: u64,
+) -> Result<()> {
+    let mut source = std::fs::File::open(source_path)?;
+    source.seek(SeekFrom::Start(start_offset))?;
+    let mut blob = conn.blob_open(rusqlite::DatabaseName::Main, "spool_queue", "payload", row_id, false)?;
+
+    let mut remaining = len as usize;
+    let mut buf = [0u8; BLOB_CHUNK_SIZE];
+    while remaining > 0 {
+        let want = remaining.min(BLOB_CHUNK_SIZE);
+        source.read_exact(&mut buf[..want])?;
+        blob.write_all(&buf[..want])?;
+        remaining -= want;
+    }
+    Ok(())
 }
 
 /// Spool operations on a shared SQLite connection.
 pub struct Spool<'a> {
     conn: &'a Connection,
+    backoff: BackoffStrategy,
+    /// "Owned-blob" mode switch — see [`Self::with_owned_blobs`].
+    owned_blobs: bool,
 }
 
 impl<'a> Spool<'a> {
     pub fn new(conn: &'a Connection) -> Self {
-        Self { conn }
+        Self {
+            conn,
+            backoff: BackoffStrategy::default(),
+            owned_blobs: false,
+        }
+    }
+
+    /// Construct with an explicit retry-scheduling strategy.
+    pub fn with_backoff(conn: &'a Connection, backoff: BackoffStrategy) -> Self {
+        Self {
+            conn,
+            backoff,
+            owned_blobs: false,
+        }
+    }
+
+    /// Construct with "owned-blob" mode set explicitly (see
+    /// `ShipperConfig::spool_owned_blobs`). When `true`, every enqueue
+    /// through this instance copies the validated range's bytes into the
+    /// row's `payload` column via SQLite's incremental blob I/O instead of
+    /// only recording `file_path`/`start_offset`/`end_offset` as a
+    /// pointer — the row then survives the source file being rotated,
+    /// truncated, or deleted before replay gets to it. When `false`
+    /// (the default, via [`Self::new`]), enqueue behaves exactly as
+    /// before: reference-only.
+    pub fn with_owned_blobs(conn: &'a Connection, owned_blobs: bool) -> Self {
+        Self {
+            conn,
+            backoff: BackoffStrategy::default(),
+            owned_blobs,
+        }
     }
 
     /// Enqueue a byte-range pointer. Returns false if at capacity.
@@ -53,35 +178,117 @@ impl<'a> Spool<'a> {
         end_offset: u64,
         session_id: Option<&str>,
     ) -> Result<bool> {
+        self.enqueue_with_truncation(provider, file_path, start_offset, end_offset, session_id, None, None)
+    }
+
+    /// Same as [`Self::enqueue`], but also records `truncated_from` — the
+    /// byte offset where a corrupt/malformed record was found and the
+    /// caller clamped the candidate range back to the last well-formed one
+    /// before calling this (see `shipper::validate_spool_range`). `None`
+    /// when no such clamp happened.
+    ///
+    /// `workunit`, if set, is the enclosing span (e.g. `shipper.flush_group`)
+    /// this enqueue should nest a `spool.enqueue` child span under — see
+    /// `workunit::Span`.
+    pub fn enqueue_with_truncation(
+        &self,
+        provider: &str,
+        file_path: &str,
+        start_offset: u64,
+        end_offset: u64,
+        session_id: Option<&str>,
+        truncated_from: Option<u64>,
+        workunit: Option<&Span>,
+    ) -> Result<bool> {
+        let span = workunit.map(|p| p.child("spool.enqueue"));
+
         if self.total_size()? >= MAX_QUEUE_SIZE {
             tracing::warn!("Spool at capacity ({} entries), rejecting enqueue", MAX_QUEUE_SIZE);
+            if let Some(s) = &span {
+                s.mark_spool_full();
+            }
             return Ok(false);
         }
 
         let now = Utc::now().to_rfc3339();
-        self.conn.execute(
-            "INSERT INTO spool_queue (provider, file_path, start_offset, end_offset, session_id, created_at, next_retry_at, status)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, 'pending')",
-            rusqlite::params![
-                provider,
-                file_path,
-                start_offset as i64,
-                end_offset as i64,
-                session_id,
-                now,
-            ],
-        )?;
+        let payload_len = end_offset.saturating_sub(start_offset);
+
+        // Assign the next sequence value and insert in the same transaction
+        // so concurrent Python+Rust writers to the shared DB never reuse one.
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("UPDATE spool_seq SET value = value + 1 WHERE id = 1", [])?;
+        let seq: i64 = tx.query_row("SELECT value FROM spool_seq WHERE id = 1", [], |row| row.get(0))?;
+        if self.owned_blobs {
+            tx.execute(
+                "INSERT INTO spool_queue (provider, file_path, start_offset, end_offset, session_id, created_at, next_retry_at, status, seq, truncated_from, payload)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, 'pending', ?7, ?8, zeroblob(?9))",
+                rusqlite::params![
+                    provider,
+                    file_path,
+                    start_offset as i64,
+                    end_offset as i64,
+                    session_id,
+                    now,
+                    seq,
+                    truncated_from.map(|v| v as i64),
+                    payload_len as i64,
+                ],
+            )?;
+            let row_id = tx.last_insert_rowid();
+            copy_range_into_blob(&tx, row_id, Path::new(file_path), start_offset, payload_len)?;
+        } else {
+            tx.execute(
+                "INSERT INTO spool_queue (provider, file_path, start_offset, end_offset, session_id, created_at, next_retry_at, status, seq, truncated_from)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, 'pending', ?7, ?8)",
+                rusqlite::params![
+                    provider,
+                    file_path,
+                    start_offset as i64,
+                    end_offset as i64,
+                    session_id,
+                    now,
+                    seq,
+                    truncated_from.map(|v| v as i64),
+                ],
+            )?;
+        }
+        tx.commit()?;
+        if let Some(s) = &span {
+            s.add_bytes(payload_len);
+            s.add_rows(1);
+        }
         Ok(true)
     }
 
+    /// Stream bytes out of an owned-blob entry's `payload` column in
+    /// bounded chunks, for a replay that needs to re-parse them (see
+    /// `shipper::replay_spool_batch`'s `has_payload` branch). Only
+    /// meaningful for an entry with `has_payload == true`.
+    pub fn read_payload(&self, entry_id: i64) -> Result<Vec<u8>> {
+        let mut blob = self
+            .conn
+            .blob_open(rusqlite::DatabaseName::Main, "spool_queue", "payload", entry_id, true)?;
+        let len = blob.len();
+        let mut out = Vec::with_capacity(len);
+        let mut remaining = len;
+        let mut buf = [0u8; BLOB_CHUNK_SIZE];
+        while remaining > 0 {
+            let want = remaining.min(BLOB_CHUNK_SIZE);
+            blob.read_exact(&mut buf[..want])?;
+            out.extend_from_slice(&buf[..want]);
+            remaining -= want;
+        }
+        Ok(out)
+    }
+
     /// Get pending entries ready for retry (next_retry_at <= now).
     pub fn dequeue_batch(&self, limit: usize) -> Result<Vec<SpoolEntry>> {
         let now = Utc::now().to_rfc3339();
         let mut stmt = self.conn.prepare(
-            "SELECT id, provider, file_path, start_offset, end_offset, session_id, created_at, retry_count, last_error
+            "SELECT id, provider, file_path, start_offset, end_offset, session_id, created_at, retry_count, last_error, seq, truncated_from, payload IS NOT NULL
              FROM spool_queue
              WHERE status = 'pending' AND next_retry_at <= ?1
-             ORDER BY created_at ASC
+             ORDER BY seq ASC
              LIMIT ?2",
         )?;
         let rows = stmt.query_map(rusqlite::params![now, limit as i64], |row| {
@@ -101,6 +308,60 @@ impl<'a> Spool<'a> {
                     })?,
                 retry_count: row.get::<_, i32>(7)? as u32,
                 last_error: row.get(8)?,
+                seq: row.get(9)?,
+                truncated_from: row.get::<_, Option<i64>>(10)?.map(|v| v as u64),
+                has_payload: row.get(11)?,
+            })
+        })?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
+    /// Fetch every spool entry with `seq` greater than `after_seq`, in `seq`
+    /// order, regardless of status (pending, mid-backoff, or dead).
+    ///
+    /// `dequeue_batch` answers "what's ready to retry right now" — this
+    /// answers "everything that's happened since idx N", the read a
+    /// multi-host or multi-provider consumer needs to resync a total order
+    /// that's independent of any one file's byte offsets or rotations. A
+    /// resuming consumer just remembers the last `seq` it saw (see
+    /// [`Self::current_seq`] for where to start a fresh one) and passes it
+    /// back in here on the next call. Rows reclaimed by
+    /// [`Self::checkpoint`] or [`Self::mark_shipped`] leave permanent holes
+    /// in the sequence — that's expected, since `seq` numbers a write, not
+    /// a still-live row; a resuming consumer should treat a jump in `seq`
+    /// as already-delivered data, not a gap to fill.
+    pub fn entries_since(&self, after_seq: i64, limit: usize) -> Result<Vec<SpoolEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, provider, file_path, start_offset, end_offset, session_id, created_at, retry_count, last_error, seq, truncated_from, payload IS NOT NULL
+             FROM spool_queue
+             WHERE seq > ?1
+             ORDER BY seq ASC
+             LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![after_seq, limit as i64], |row| {
+            Ok(SpoolEntry {
+                id: row.get(0)?,
+                provider: row.get(1)?,
+                file_path: row.get(2)?,
+                start_offset: row.get::<_, i64>(3)? as u64,
+                end_offset: row.get::<_, i64>(4)? as u64,
+                session_id: row.get(5)?,
+                created_at: row
+                    .get::<_, String>(6)
+                    .map(|s| {
+                        DateTime::parse_from_rfc3339(&s)
+                            .map(|d| d.with_timezone(&Utc))
+                            .unwrap_or_else(|_| Utc::now())
+                    })?,
+                retry_count: row.get::<_, i32>(7)? as u32,
+                last_error: row.get(8)?,
+                seq: row.get(9)?,
+                truncated_from: row.get::<_, Option<i64>>(10)?.map(|v| v as u64),
+                has_payload: row.get(11)?,
             })
         })?;
         let mut result = Vec::new();
@@ -110,6 +371,17 @@ impl<'a> Spool<'a> {
         Ok(result)
     }
 
+    /// The highest `seq` assigned so far (the `spool_seq` counter's current
+    /// value) — a fresh consumer's starting point for [`Self::entries_since`]
+    /// when it wants to skip everything already enqueued and see only new
+    /// writes going forward.
+    pub fn current_seq(&self) -> Result<i64> {
+        let seq: i64 = self
+            .conn
+            .query_row("SELECT value FROM spool_seq WHERE id = 1", [], |row| row.get(0))?;
+        Ok(seq)
+    }
+
     /// Remove a successfully shipped entry.
     pub fn mark_shipped(&self, entry_id: i64) -> Result<()> {
         self.conn
@@ -118,17 +390,30 @@ impl<'a> Spool<'a> {
     }
 
     /// Mark entry as failed with exponential backoff. Returns true if now permanently dead.
-    pub fn mark_failed(&self, entry_id: i64, error: &str) -> Result<bool> {
-        self.mark_failed_with_max(entry_id, error, DEFAULT_MAX_RETRIES)
+    ///
+    /// `workunit`, if set, is the enclosing span this failure's
+    /// `spool.retry` child span should nest under.
+    pub fn mark_failed(&self, entry_id: i64, error: &str, workunit: Option<&Span>) -> Result<bool> {
+        self.mark_failed_with_max(entry_id, error, DEFAULT_MAX_RETRIES, workunit)
     }
 
     /// Mark failed with custom max retries.
-    pub fn mark_failed_with_max(&self, entry_id: i64, error: &str, max_retries: u32) -> Result<bool> {
-        // Get current retry count
-        let retry_count: i32 = self.conn.query_row(
-            "SELECT retry_count FROM spool_queue WHERE id = ?",
+    pub fn mark_failed_with_max(
+        &self,
+        entry_id: i64,
+        error: &str,
+        max_retries: u32,
+        workunit: Option<&Span>,
+    ) -> Result<bool> {
+        let span = workunit.map(|p| p.child("spool.retry"));
+        if let Some(s) = &span {
+            s.add_retry();
+        }
+        // Get current retry count and the previously chosen backoff.
+        let (retry_count, prev_backoff_secs): (i32, Option<f64>) = self.conn.query_row(
+            "SELECT retry_count, last_backoff_secs FROM spool_queue WHERE id = ?",
             [entry_id],
-            |row| row.get(0),
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )?;
         let new_count = retry_count + 1;
 
@@ -142,18 +427,33 @@ impl<'a> Spool<'a> {
             return Ok(true);
         }
 
-        // Exponential backoff: min(5 * 2^retry, 3600)
-        let backoff_secs = (BACKOFF_BASE * 2.0_f64.powi(new_count)).min(BACKOFF_MAX);
+        let backoff_secs = self.next_backoff_secs(new_count, prev_backoff_secs);
         let next_retry = Utc::now() + chrono::Duration::seconds(backoff_secs as i64);
 
         self.conn.execute(
-            "UPDATE spool_queue SET retry_count = ?1, last_error = ?2, next_retry_at = ?3
-             WHERE id = ?4",
-            rusqlite::params![new_count, error, next_retry.to_rfc3339(), entry_id],
+            "UPDATE spool_queue SET retry_count = ?1, last_error = ?2, next_retry_at = ?3, last_backoff_secs = ?4
+             WHERE id = ?5",
+            rusqlite::params![new_count, error, next_retry.to_rfc3339(), backoff_secs, entry_id],
         )?;
         Ok(false)
     }
 
+    /// Compute the next retry delay per `self.backoff`. `prev_backoff_secs`
+    /// is the delay this entry was last scheduled with (`None` on its first
+    /// failure).
+    fn next_backoff_secs(&self, new_count: i32, prev_backoff_secs: Option<f64>) -> f64 {
+        let exponential_ceiling = (BACKOFF_BASE * 2.0_f64.powi(new_count)).min(BACKOFF_MAX);
+        match self.backoff {
+            BackoffStrategy::Exponential => exponential_ceiling,
+            BackoffStrategy::FullJitter => rand::thread_rng().gen_range(0.0..=exponential_ceiling),
+            BackoffStrategy::DecorrelatedJitter => {
+                let prev = prev_backoff_secs.unwrap_or(BACKOFF_BASE);
+                let hi = (prev * 3.0).max(BACKOFF_BASE); // guard against prev < BACKOFF_BASE/3
+                rand::thread_rng().gen_range(BACKOFF_BASE..=hi).min(BACKOFF_MAX)
+            }
+        }
+    }
+
     /// Count pending (retryable) entries.
     pub fn pending_count(&self) -> Result<usize> {
         let count: i64 = self.conn.query_row(
@@ -164,6 +464,16 @@ impl<'a> Spool<'a> {
         Ok(count as usize)
     }
 
+    /// Count entries given up on after exhausting their retries.
+    pub fn dead_count(&self) -> Result<usize> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM spool_queue WHERE status = 'dead'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
     /// Total entries (for backpressure check).
     pub fn total_size(&self) -> Result<usize> {
         let count: i64 = self.conn.query_row(
@@ -188,6 +498,159 @@ impl<'a> Spool<'a> {
         )?;
         Ok(deleted + deleted2)
     }
+
+    /// Reclaim spool rows stranded behind an already-advanced acked
+    /// watermark.
+    ///
+    /// `mark_shipped` deletes a row the instant its ship succeeds, but that
+    /// delete and the `FileState::set_acked_offset` call recording the
+    /// watermark it was shipped against are two separate writes — a crash
+    /// between them leaves an already-acked row behind. `run_startup_recovery`
+    /// re-enqueues from `acked_offset` rather than 0, so replay never reships
+    /// that data, but the orphaned row itself just sits there, counting
+    /// against `MAX_QUEUE_SIZE`, until something reclaims it.
+    ///
+    /// For every distinct `file_path` with spool rows, this reads
+    /// `file_state`'s durable `acked_offset` as that file's watermark and
+    /// deletes every row whose `end_offset <= watermark` in one transaction.
+    /// A row that straddles the watermark (a partially-acked range) is never
+    /// touched — the filter is on the whole row's `end_offset`, never a
+    /// trimmed sub-range, so reclamation can only ever remove fully-acked
+    /// pointers.
+    pub fn checkpoint(&self, file_state: &FileState) -> Result<CheckpointReport> {
+        let file_paths: Vec<String> = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT DISTINCT file_path FROM spool_queue")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            let mut paths = Vec::new();
+            for row in rows {
+                paths.push(row?);
+            }
+            paths
+        };
+
+        let mut report = CheckpointReport::default();
+        let tx = self.conn.unchecked_transaction()?;
+        for file_path in file_paths {
+            let watermark = file_state.get_offset(&file_path)?;
+            let reclaimed = tx.execute(
+                "DELETE FROM spool_queue WHERE file_path = ?1 AND end_offset <= ?2",
+                rusqlite::params![file_path, watermark as i64],
+            )?;
+            if reclaimed > 0 {
+                report.reclaimed += reclaimed;
+                report.watermarks.push((file_path, watermark));
+            }
+        }
+        tx.commit()?;
+        Ok(report)
+    }
+
+    /// Fetch all entries given up on after exhausting their retries, for
+    /// offline triage (see [`Self::export_dead_jsonl`]).
+    pub fn export_dead(&self) -> Result<Vec<SpoolEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, provider, file_path, start_offset, end_offset, session_id, created_at, retry_count, last_error, seq, truncated_from, payload IS NOT NULL
+             FROM spool_queue
+             WHERE status = 'dead'
+             ORDER BY seq ASC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(SpoolEntry {
+                id: row.get(0)?,
+                provider: row.get(1)?,
+                file_path: row.get(2)?,
+                start_offset: row.get::<_, i64>(3)? as u64,
+                end_offset: row.get::<_, i64>(4)? as u64,
+                session_id: row.get(5)?,
+                created_at: row
+                    .get::<_, String>(6)
+                    .map(|s| {
+                        DateTime::parse_from_rfc3339(&s)
+                            .map(|d| d.with_timezone(&Utc))
+                            .unwrap_or_else(|_| Utc::now())
+                    })?,
+                retry_count: row.get::<_, i32>(7)? as u32,
+                last_error: row.get(8)?,
+                seq: row.get(9)?,
+                truncated_from: row.get::<_, Option<i64>>(10)?.map(|v| v as u64),
+                has_payload: row.get(11)?,
+            })
+        })?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
+    /// Stream dead entries to `path` as JSONL for offline triage, one
+    /// [`SpoolEntry`] per line plus a `source_missing` flag noting whether
+    /// `file_path` still exists on disk — a dead entry whose source file was
+    /// since rotated or deleted can never be recovered by `requeue`, unless
+    /// it's an owned-blob entry (`has_payload`), which doesn't need the
+    /// source file at all.
+    pub fn export_dead_jsonl(&self, path: &std::path::Path) -> Result<usize> {
+        use std::io::Write;
+
+        let entries = self.export_dead()?;
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        for entry in &entries {
+            let source_missing = !std::path::Path::new(&entry.file_path).exists();
+            let line = serde_json::json!({
+                "id": entry.id,
+                "seq": entry.seq,
+                "provider": entry.provider,
+                "file_path": entry.file_path,
+                "start_offset": entry.start_offset,
+                "end_offset": entry.end_offset,
+                "retry_count": entry.retry_count,
+                "last_error": entry.last_error,
+                "created_at": entry.created_at.to_rfc3339(),
+                "source_missing": source_missing,
+                "truncated_from": entry.truncated_from,
+                "has_payload": entry.has_payload,
+            });
+            writeln!(out, "{}", line)?;
+        }
+        out.flush()?;
+        Ok(entries.len())
+    }
+
+    /// Flip a dead entry back to `'pending'`, resetting its retry schedule
+    /// so the next replay picks it up immediately.
+    pub fn requeue(&self, entry_id: i64) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            "UPDATE spool_queue
+             SET status = 'pending', retry_count = 0, last_backoff_secs = NULL, next_retry_at = ?1
+             WHERE id = ?2 AND status = 'dead'",
+            rusqlite::params![now, entry_id],
+        )?;
+        Ok(())
+    }
+
+    /// Requeue every dead entry. Returns the count requeued.
+    pub fn requeue_all_dead(&self) -> Result<usize> {
+        let now = Utc::now().to_rfc3339();
+        let updated = self.conn.execute(
+            "UPDATE spool_queue
+             SET status = 'pending', retry_count = 0, last_backoff_secs = NULL, next_retry_at = ?1
+             WHERE status = 'dead'",
+            [&now],
+        )?;
+        Ok(updated)
+    }
+
+    /// Delete all dead entries immediately, independent of the 7-day
+    /// `cleanup` timer. Returns the count removed.
+    pub fn purge_dead(&self) -> Result<usize> {
+        let deleted = self
+            .conn
+            .execute("DELETE FROM spool_queue WHERE status = 'dead'", [])?;
+        Ok(deleted)
+    }
 }
 
 #[cfg(test)]
@@ -217,6 +680,22 @@ mod tests {
         assert_eq!(batch[0].file_path, "/path/a.jsonl");
         assert_eq!(batch[0].start_offset, 0);
         assert_eq!(batch[0].end_offset, 1000);
+        assert_eq!(batch[0].truncated_from, None, "plain enqueue records no truncation");
+        assert!(!batch[0].has_payload, "reference-mode entries carry no payload blob");
+    }
+
+    #[test]
+    fn test_enqueue_with_truncation_roundtrips_through_dequeue() {
+        let (_tmp, conn) = setup();
+        let spool = Spool::new(&conn);
+
+        spool
+            .enqueue_with_truncation("claude", "/f", 0, 400, Some("s1"), Some(512), None)
+            .unwrap();
+
+        let batch = spool.dequeue_batch(10).unwrap();
+        assert_eq!(batch[0].end_offset, 400);
+        assert_eq!(batch[0].truncated_from, Some(512));
     }
 
     #[test]
@@ -241,7 +720,7 @@ mod tests {
         let id = batch[0].id;
 
         // First failure — not dead yet
-        let dead = spool.mark_failed(id, "connection refused").unwrap();
+        let dead = spool.mark_failed(id, "connection refused", None).unwrap();
         assert!(!dead);
         assert_eq!(spool.pending_count().unwrap(), 1);
 
@@ -273,7 +752,7 @@ mod tests {
         // Fail 3 times with max_retries=3
         for i in 0..3 {
             let dead = spool
-                .mark_failed_with_max(id, &format!("err {}", i), 3)
+                .mark_failed_with_max(id, &format!("err {}", i), 3, None)
                 .unwrap();
             if i < 2 {
                 assert!(!dead);
@@ -285,6 +764,68 @@ mod tests {
         // Should now be dead, not pending
         assert_eq!(spool.pending_count().unwrap(), 0);
         assert_eq!(spool.total_size().unwrap(), 1); // still in DB as dead
+        assert_eq!(spool.dead_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_decorrelated_jitter_stays_within_bounds() {
+        let (_tmp, conn) = setup();
+        let spool = Spool::with_backoff(&conn, BackoffStrategy::DecorrelatedJitter);
+
+        spool.enqueue("claude", "/f", 0, 100, None).unwrap();
+        let batch = spool.dequeue_batch(10).unwrap();
+        let id = batch[0].id;
+
+        let mut prev_next_retry = Utc::now();
+        for i in 0..10 {
+            spool.mark_failed_with_max(id, &format!("err {i}"), 50, None).unwrap();
+            let (next_retry_at, last_backoff): (String, f64) = conn
+                .query_row(
+                    "SELECT next_retry_at, last_backoff_secs FROM spool_queue WHERE id = ?",
+                    [id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .unwrap();
+            assert!(
+                (BACKOFF_BASE..=BACKOFF_MAX).contains(&last_backoff),
+                "backoff {} out of [{}, {}]",
+                last_backoff,
+                BACKOFF_BASE,
+                BACKOFF_MAX
+            );
+            let next_retry: DateTime<Utc> = DateTime::parse_from_rfc3339(&next_retry_at)
+                .unwrap()
+                .with_timezone(&Utc);
+            assert!(next_retry > prev_next_retry);
+            prev_next_retry = next_retry;
+        }
+    }
+
+    #[test]
+    fn test_decorrelated_jitter_spreads_simultaneous_failures() {
+        let (_tmp, conn) = setup();
+        let spool = Spool::with_backoff(&conn, BackoffStrategy::DecorrelatedJitter);
+
+        spool.enqueue("claude", "/a", 0, 100, None).unwrap();
+        spool.enqueue("claude", "/b", 0, 100, None).unwrap();
+        let batch = spool.dequeue_batch(10).unwrap();
+
+        // Many entries failing "at the same instant" (same retry count,
+        // same starting last_backoff_secs) should not all choose the same
+        // delay — that's the whole point of the jitter.
+        let mut backoffs = Vec::new();
+        for entry in &batch {
+            spool.mark_failed_with_max(entry.id, "server error", 50, None).unwrap();
+            let last_backoff: f64 = conn
+                .query_row(
+                    "SELECT last_backoff_secs FROM spool_queue WHERE id = ?",
+                    [entry.id],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            backoffs.push(last_backoff);
+        }
+        assert_ne!(backoffs[0], backoffs[1], "jittered backoffs should diverge");
     }
 
     #[test]
@@ -299,6 +840,142 @@ mod tests {
         assert!(ok);
     }
 
+    #[test]
+    fn test_enqueue_assigns_monotonic_seq() {
+        let (_tmp, conn) = setup();
+        let spool = Spool::new(&conn);
+
+        spool.enqueue("claude", "/a", 0, 100, None).unwrap();
+        spool.enqueue("claude", "/b", 0, 100, None).unwrap();
+        spool.enqueue("claude", "/c", 0, 100, None).unwrap();
+
+        let batch = spool.dequeue_batch(10).unwrap();
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch[0].file_path, "/a");
+        assert_eq!(batch[1].file_path, "/b");
+        assert_eq!(batch[2].file_path, "/c");
+        assert!(batch[0].seq < batch[1].seq);
+        assert!(batch[1].seq < batch[2].seq);
+    }
+
+    #[test]
+    fn test_entries_since_returns_total_order_across_files_regardless_of_status() {
+        let (_tmp, conn) = setup();
+        let spool = Spool::new(&conn);
+
+        spool.enqueue("claude", "/a", 0, 100, None).unwrap();
+        spool.enqueue("claude", "/b", 0, 100, None).unwrap();
+        spool.enqueue("claude", "/c", 0, 100, None).unwrap();
+
+        // Mark /a dead and /b shipped — entries_since must still surface
+        // /a (dead rows aren't retry-ready, unlike dequeue_batch) and must
+        // not surface /b at all (it's gone, not merely filtered).
+        let all = spool.dequeue_batch(10).unwrap();
+        spool.mark_failed_with_max(all[0].id, "down", 1, None).unwrap();
+        spool.mark_shipped(all[1].id).unwrap();
+
+        let since_zero = spool.entries_since(0, 10).unwrap();
+        assert_eq!(since_zero.len(), 2, "shipped row is gone, dead row still counts");
+        assert_eq!(since_zero[0].file_path, "/a");
+        assert_eq!(since_zero[1].file_path, "/c");
+
+        // Resuming from the first entry's seq should only surface what's
+        // strictly after it.
+        let since_a = spool.entries_since(since_zero[0].seq, 10).unwrap();
+        assert_eq!(since_a.len(), 1);
+        assert_eq!(since_a[0].file_path, "/c");
+    }
+
+    #[test]
+    fn test_current_seq_tracks_highest_assigned_regardless_of_deletes() {
+        let (_tmp, conn) = setup();
+        let spool = Spool::new(&conn);
+        assert_eq!(spool.current_seq().unwrap(), 0);
+
+        spool.enqueue("claude", "/a", 0, 100, None).unwrap();
+        spool.enqueue("claude", "/b", 0, 100, None).unwrap();
+        let head = spool.current_seq().unwrap();
+        assert!(head >= 2);
+
+        // Deleting rows (reclamation) must not roll the counter back —
+        // a resuming consumer's watermark would otherwise see old writes
+        // as "new" again.
+        let batch = spool.dequeue_batch(10).unwrap();
+        spool.mark_shipped(batch[0].id).unwrap();
+        spool.mark_shipped(batch[1].id).unwrap();
+        assert_eq!(spool.current_seq().unwrap(), head);
+
+        let since_head = spool.entries_since(head, 10).unwrap();
+        assert!(since_head.is_empty());
+    }
+
+    #[test]
+    fn test_export_requeue_purge_dead() {
+        let (_tmp, conn) = setup();
+        let spool = Spool::new(&conn);
+
+        spool.enqueue("claude", "/f", 0, 100, None).unwrap();
+        let batch = spool.dequeue_batch(10).unwrap();
+        let id = batch[0].id;
+        for i in 0..3 {
+            spool.mark_failed_with_max(id, &format!("err {i}"), 3, None).unwrap();
+        }
+        assert_eq!(spool.dead_count().unwrap(), 1);
+
+        let dead = spool.export_dead().unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].id, id);
+        assert_eq!(dead[0].retry_count, 3);
+
+        spool.requeue(id).unwrap();
+        assert_eq!(spool.dead_count().unwrap(), 0);
+        assert_eq!(spool.pending_count().unwrap(), 1);
+        let entry: (i32, Option<f64>) = conn
+            .query_row(
+                "SELECT retry_count, last_backoff_secs FROM spool_queue WHERE id = ?",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(entry.0, 0);
+        assert_eq!(entry.1, None);
+
+        // Kill it again, then requeue_all_dead + purge_dead.
+        for i in 0..3 {
+            spool.mark_failed_with_max(id, &format!("err {i}"), 3, None).unwrap();
+        }
+        assert_eq!(spool.requeue_all_dead().unwrap(), 1);
+        assert_eq!(spool.dead_count().unwrap(), 0);
+
+        for i in 0..3 {
+            spool.mark_failed_with_max(id, &format!("err {i}"), 3, None).unwrap();
+        }
+        assert_eq!(spool.purge_dead().unwrap(), 1);
+        assert_eq!(spool.total_size().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_export_dead_jsonl_flags_missing_source() {
+        let (_tmp, conn) = setup();
+        let spool = Spool::new(&conn);
+
+        spool.enqueue("claude", "/does/not/exist.jsonl", 0, 100, None).unwrap();
+        let batch = spool.dequeue_batch(10).unwrap();
+        let id = batch[0].id;
+        for i in 0..3 {
+            spool.mark_failed_with_max(id, &format!("err {i}"), 3, None).unwrap();
+        }
+
+        let out = tempfile::NamedTempFile::new().unwrap();
+        let count = spool.export_dead_jsonl(out.path()).unwrap();
+        assert_eq!(count, 1);
+
+        let contents = std::fs::read_to_string(out.path()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+        assert_eq!(line["file_path"], "/does/not/exist.jsonl");
+        assert_eq!(line["source_missing"], true);
+    }
+
     #[test]
     fn test_cleanup() {
         let (_tmp, conn) = setup();
@@ -318,4 +995,100 @@ mod tests {
         assert_eq!(cleaned, 1);
         assert_eq!(spool.total_size().unwrap(), 0);
     }
+
+    // ---------------------------------------------------------------
+    // checkpoint: watermark-based reclamation of stranded acked rows
+    // ---------------------------------------------------------------
+
+    #[test]
+    fn test_checkpoint_reclaims_rows_fully_behind_watermark() {
+        let (_tmp, conn) = setup();
+        let spool = Spool::new(&conn);
+        let fs = FileState::new(&conn);
+
+        // Simulate a crash between `mark_shipped` and `set_acked_offset`:
+        // the watermark has already advanced past this row, but the row
+        // itself was never deleted.
+        spool.enqueue("claude", "/f", 0, 100, Some("s1")).unwrap();
+        fs.set_queued_offset("/f", 100, "claude", "s1", "ps1").unwrap();
+        fs.set_acked_offset("/f", 100).unwrap();
+
+        let report = spool.checkpoint(&fs).unwrap();
+        assert_eq!(report.reclaimed, 1);
+        assert_eq!(report.watermarks, vec![("/f".to_string(), 100)]);
+        assert_eq!(spool.total_size().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_checkpoint_never_reclaims_partially_acked_range() {
+        let (_tmp, conn) = setup();
+        let spool = Spool::new(&conn);
+        let fs = FileState::new(&conn);
+
+        // Watermark sits inside this row's range — only part of it is
+        // acked, so the whole pointer must survive.
+        spool.enqueue("claude", "/f", 0, 100, Some("s1")).unwrap();
+        fs.set_queued_offset("/f", 100, "claude", "s1", "ps1").unwrap();
+        fs.set_acked_offset("/f", 50).unwrap();
+
+        let report = spool.checkpoint(&fs).unwrap();
+        assert_eq!(report.reclaimed, 0);
+        assert_eq!(spool.total_size().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_checkpoint_skips_files_with_no_recorded_watermark() {
+        let (_tmp, conn) = setup();
+        let spool = Spool::new(&conn);
+        let fs = FileState::new(&conn);
+
+        // No FileState entry at all — watermark defaults to 0, so nothing
+        // is reclaimed.
+        spool.enqueue("claude", "/untracked", 0, 100, Some("s1")).unwrap();
+
+        let report = spool.checkpoint(&fs).unwrap();
+        assert_eq!(report.reclaimed, 0);
+        assert_eq!(spool.total_size().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_owned_blobs_round_trip_through_read_payload() {
+        let (_tmp, conn) = setup();
+        let mut source = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut source, b"line one\nline two\nline three\n").unwrap();
+
+        let spool = Spool::with_owned_blobs(&conn, true);
+        spool
+            .enqueue(
+                "claude",
+                source.path().to_str().unwrap(),
+                9,
+                18,
+                Some("s1"),
+            )
+            .unwrap();
+
+        let batch = spool.dequeue_batch(10).unwrap();
+        assert_eq!(batch.len(), 1);
+        assert!(batch[0].has_payload);
+        assert_eq!(spool.read_payload(batch[0].id).unwrap(), b"line two\n");
+    }
+
+    #[test]
+    fn test_owned_blobs_survive_source_file_deletion() {
+        let (_tmp, conn) = setup();
+        let source = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut std::fs::File::create(source.path()).unwrap(), b"hello world").unwrap();
+
+        let spool = Spool::with_owned_blobs(&conn, true);
+        spool
+            .enqueue("claude", source.path().to_str().unwrap(), 0, 5, Some("s1"))
+            .unwrap();
+        let batch = spool.dequeue_batch(10).unwrap();
+        let entry_id = batch[0].id;
+
+        std::fs::remove_file(source.path()).unwrap();
+
+        assert_eq!(spool.read_payload(entry_id).unwrap(), b"hello");
+    }
 }
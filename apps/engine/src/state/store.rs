@@ -0,0 +1,90 @@
+//! Dispatches a `--db` connection string to a concrete state backend.
+//!
+//! Only SQLite is implemented today (`open_db` in `state::db`), but the
+//! address grammar reserves `sled://` and `postgres://`/`postgresql://` so a
+//! fleet of engines can eventually share one remote store without changing
+//! `cmd_ship`'s call sites — they'd just resolve to a different `StoreAddr`
+//! variant here. A bare filesystem path (no `scheme://`) keeps working
+//! exactly as before, for backward compatibility with existing `--db` usage.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+
+/// A parsed `--db` address, resolved to a concrete backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreAddr {
+    /// `sqlite:///path` or a bare filesystem path.
+    Sqlite(PathBuf),
+}
+
+/// Parse a `--db` connection string into a `StoreAddr`.
+///
+/// Recognized schemes: `sqlite://`. `sled://` and `postgres://`/`postgresql://`
+/// are recognized but not yet backed by an implementation — they return an
+/// error naming the missing backend rather than silently falling back to
+/// SQLite. Anything without a `scheme://` prefix is treated as a bare
+/// filesystem path, matching the pre-existing `--db <path>` behavior.
+pub fn parse_addr(addr: &str) -> Result<StoreAddr> {
+    if let Some(path) = addr.strip_prefix("sqlite://") {
+        return Ok(StoreAddr::Sqlite(PathBuf::from(path)));
+    }
+    if addr.starts_with("sled://") {
+        bail!("sled state backend is not yet implemented (got {addr:?}); use sqlite:// or a bare path");
+    }
+    if addr.starts_with("postgres://") || addr.starts_with("postgresql://") {
+        bail!("postgres state backend is not yet implemented (got {addr:?}); use sqlite:// or a bare path");
+    }
+    Ok(StoreAddr::Sqlite(PathBuf::from(addr)))
+}
+
+/// Resolve an optional `--db` argument to the SQLite path `open_db` expects.
+///
+/// `None` keeps the existing default-path behavior. `Some` is parsed via
+/// [`parse_addr`] and, since SQLite is the only implemented backend, always
+/// yields a filesystem path (or an error for an unimplemented scheme).
+pub fn resolve_sqlite_path(addr: Option<&str>) -> Result<Option<PathBuf>> {
+    match addr {
+        None => Ok(None),
+        Some(addr) => match parse_addr(addr)? {
+            StoreAddr::Sqlite(path) => Ok(Some(path)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bare_path_defaults_to_sqlite() {
+        assert_eq!(
+            parse_addr("/home/user/.claude/longhouse-shipper.db").unwrap(),
+            StoreAddr::Sqlite(PathBuf::from("/home/user/.claude/longhouse-shipper.db"))
+        );
+    }
+
+    #[test]
+    fn test_sqlite_scheme_strips_prefix() {
+        assert_eq!(
+            parse_addr("sqlite:///tmp/state.db").unwrap(),
+            StoreAddr::Sqlite(PathBuf::from("/tmp/state.db"))
+        );
+    }
+
+    #[test]
+    fn test_sled_scheme_errors() {
+        assert!(parse_addr("sled:///tmp/state").is_err());
+    }
+
+    #[test]
+    fn test_postgres_scheme_errors() {
+        assert!(parse_addr("postgres://user@host/db").is_err());
+        assert!(parse_addr("postgresql://user@host/db").is_err());
+    }
+
+    #[test]
+    fn test_resolve_sqlite_path_passes_through_none() {
+        assert_eq!(resolve_sqlite_path(None).unwrap(), None);
+    }
+}
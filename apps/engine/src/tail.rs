@@ -0,0 +1,164 @@
+//! Single-file tail-follow for a live-rendering UI.
+//!
+//! `watcher::SessionWatcher` batches changed *paths* across whole provider
+//! directories for the shipping pipeline; `SessionTail` is the per-file
+//! counterpart a UI wants instead — wake on every append to one known path
+//! and hand back only the newly appended, already-parsed `ParsedEvent`s, via
+//! the same offset-resume `parse_session_file` already uses for batch
+//! shipping. A trailing partial line (the writer hasn't flushed its closing
+//! newline yet) is never emitted early — `parse_session_file` already holds
+//! it back until a later call sees the complete line (see
+//! `pipeline::parser`'s `test_partial_line_at_eof`).
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::pipeline::parser::{self, ParsedEvent};
+
+/// Bounded to 1: only "something changed since last drain" needs to survive
+/// between polls, not a count of how many times.
+const TAIL_CHANNEL_CAPACITY: usize = 1;
+
+/// Follows one session file from the moment it's opened, parsing only the
+/// bytes appended since the last call.
+pub struct SessionTail {
+    // Must stay alive — dropping stops the watcher.
+    watcher: RecommendedWatcher,
+    rx: mpsc::Receiver<()>,
+    path: PathBuf,
+    offset: u64,
+}
+
+impl SessionTail {
+    /// Start following `path` from its current end of file — only events
+    /// appended after this call are ever emitted. Watches the parent
+    /// directory (like `SessionWatcher`) rather than the file itself, so a
+    /// writer that replaces the file via rename is still picked up.
+    pub fn open(path: &Path) -> Result<Self> {
+        let offset = std::fs::metadata(path)
+            .with_context(|| format!("Failed to stat {}", path.display()))?
+            .len();
+
+        let (tx, rx) = mpsc::channel(TAIL_CHANNEL_CAPACITY);
+        let target = path.to_path_buf();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else { return };
+            if event.paths.iter().any(|p| p == &target) {
+                let _ = tx.try_send(());
+            }
+        })?;
+
+        let parent = path.parent().unwrap_or_else(|| Path::new("."));
+        watcher
+            .watch(parent, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {}", parent.display()))?;
+
+        Ok(Self {
+            watcher,
+            rx,
+            path: path.to_path_buf(),
+            offset,
+        })
+    }
+
+    /// Wait for the next filesystem notification on this file, then parse
+    /// and return whatever complete lines have been appended since the last
+    /// call (empty if the notification turned out to be a no-op, e.g. a
+    /// metadata-only touch). Returns `None` once the underlying watcher
+    /// channel closes.
+    pub async fn next_events(&mut self) -> Result<Option<Vec<ParsedEvent>>> {
+        if self.rx.recv().await.is_none() {
+            return Ok(None);
+        }
+        // Coalesce any further notifications already queued, the same
+        // throttle `SessionWatcher::next_batch` applies, rather than
+        // re-parsing once per raw OS event under sustained writes.
+        while self.rx.try_recv().is_ok() {}
+
+        let result = parser::parse_session_file(&self.path, self.offset)?;
+        self.offset = result.last_good_offset;
+        Ok(Some(result.events))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn test_follow_emits_only_appended_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        std::fs::write(
+            &path,
+            "{\"type\":\"user\",\"uuid\":\"u1\",\"timestamp\":\"2026-01-01T00:00:00Z\",\"message\":{\"content\":\"first\"}}\n",
+        )
+        .unwrap();
+
+        let mut tail = SessionTail::open(&path).unwrap();
+
+        let mut f = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(
+            f,
+            "{{\"type\":\"user\",\"uuid\":\"u2\",\"timestamp\":\"2026-01-01T00:00:01Z\",\"message\":{{\"content\":\"second\"}}}}"
+        )
+        .unwrap();
+        drop(f);
+
+        let events = tokio::time::timeout(std::time::Duration::from_secs(5), tail.next_events())
+            .await
+            .expect("next_events should not hang")
+            .unwrap()
+            .expect("channel should still be open");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].content_text.as_deref(), Some("second"));
+    }
+
+    #[tokio::test]
+    async fn test_follow_holds_back_partial_trailing_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        std::fs::write(
+            &path,
+            "{\"type\":\"user\",\"uuid\":\"u1\",\"timestamp\":\"2026-01-01T00:00:00Z\",\"message\":{\"content\":\"first\"}}\n",
+        )
+        .unwrap();
+
+        let mut tail = SessionTail::open(&path).unwrap();
+
+        // Append a line with no trailing newline yet, as if the writer is
+        // still mid-flush.
+        let mut f = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        write!(
+            f,
+            "{{\"type\":\"user\",\"uuid\":\"u2\",\"timestamp\":\"2026-01-01T00:00:01Z\",\"message\":{{\"con"
+        )
+        .unwrap();
+        drop(f);
+
+        let events = tokio::time::timeout(std::time::Duration::from_secs(5), tail.next_events())
+            .await
+            .expect("next_events should not hang")
+            .unwrap()
+            .expect("channel should still be open");
+        assert!(events.is_empty(), "partial trailing line must not be emitted early");
+
+        // Now the writer finishes the line.
+        let mut f = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(f, "tent\":\"second\"}}}}").unwrap();
+        drop(f);
+
+        let events = tokio::time::timeout(std::time::Duration::from_secs(5), tail.next_events())
+            .await
+            .expect("next_events should not hang")
+            .unwrap()
+            .expect("channel should still be open");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].content_text.as_deref(), Some("second"));
+    }
+}
@@ -5,18 +5,19 @@
 //! interval (throttle pattern, not debounce) to handle rapid JSONL appends
 //! without starving.
 
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicU64;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::Result;
 use notify::event::{CreateKind, DataChange, EventKind, ModifyKind};
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 
 use crate::discovery::ProviderConfig;
+use crate::ignore::IgnoreMatcher;
 
 /// Bounded channel capacity for file events.
 const WATCHER_CHANNEL_CAPACITY: usize = 2048;
@@ -38,21 +39,66 @@ fn is_temp_file(path: &std::path::Path) -> bool {
         || name.contains(".#")
 }
 
+/// Prefix for the sentinel files `flush_cookie` writes into a watched
+/// provider directory — named so `is_temp_file`'s own dot-prefix rule would
+/// already exclude them from `SESSION_EXTENSIONS` matching, but they're
+/// actually intercepted even earlier, before any of that filtering runs
+/// (see the `notify` callback in `SessionWatcher::new`).
+const COOKIE_PREFIX: &str = ".longhouse-cookie-";
+
+fn cookie_file_name(seq: u64) -> String {
+    format!("{COOKIE_PREFIX}{seq}")
+}
+
+/// Extracts the sequence number from a cookie file's path, if it is one.
+fn parse_cookie_seq(path: &Path) -> Option<u64> {
+    path.file_name()?.to_str()?.strip_prefix(COOKIE_PREFIX)?.parse().ok()
+}
+
+/// Whether `path` is a `flush_cookie` sentinel file rather than real session
+/// data — exposed so callers that receive paths from somewhere other than
+/// `next_batch`/`drain_ready` (e.g. `daemon::ship_batch`) can defensively
+/// skip one that slipped through.
+pub fn is_cookie_file(path: &Path) -> bool {
+    parse_cookie_seq(path).is_some()
+}
+
 /// File watcher that delivers batches of changed session file paths.
 pub struct SessionWatcher {
     // Must stay alive — dropping stops the watcher.
-    _watcher: RecommendedWatcher,
+    watcher: RecommendedWatcher,
     rx: mpsc::Receiver<PathBuf>,
     dropped_events: Arc<AtomicU64>,
+    /// Provider roots currently registered with `watcher`. `notify`'s OS
+    /// backends watch an inode, not a path, so a root that's removed and
+    /// recreated under the same path needs an explicit re-`watch()` call —
+    /// this set is what `reestablish_watches` diffs against to find those.
+    watching: HashSet<PathBuf>,
+    /// Monotonic counter for cookie filenames — just needs to be unique per
+    /// process, not globally, so `Relaxed` ordering is fine.
+    cookie_seq: Arc<AtomicU64>,
+    /// Waiters registered by `flush_cookie`, resolved from inside the
+    /// `notify` callback when the matching cookie path is observed.
+    pending_cookies: Arc<Mutex<HashMap<u64, oneshot::Sender<()>>>>,
 }
 
 impl SessionWatcher {
-    /// Start watching all provider directories.
-    pub fn new(providers: &[ProviderConfig]) -> Result<Self> {
+    /// Start watching all provider directories. `ignore` is compiled once
+    /// up front (not per event) from each provider root's `.longhouseignore`
+    /// plus global config patterns — see `crate::ignore`.
+    pub fn new(providers: &[ProviderConfig], ignore: &IgnoreMatcher) -> Result<Self> {
         let (tx, rx) = mpsc::channel(WATCHER_CHANNEL_CAPACITY);
         let dropped_events = Arc::new(AtomicU64::new(0));
         let dropped_clone = dropped_events.clone();
+        let cookie_seq = Arc::new(AtomicU64::new(0));
+        let pending_cookies: Arc<Mutex<HashMap<u64, oneshot::Sender<()>>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+        let pending_cookies_clone = pending_cookies.clone();
 
+        // `notify`'s callback runs on its own background thread for the
+        // lifetime of the watcher, so the matcher is cloned in rather than
+        // borrowed (its GlobSet is cheap to clone and immutable after gather).
+        let ignore = ignore.clone();
         let watcher_tx = tx.clone();
         let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
             let event = match res {
@@ -72,6 +118,17 @@ impl SessionWatcher {
             }
 
             for path in event.paths {
+                // Cookie files never reach the session channel — resolving
+                // a waiter here, before any of the filters below, is what
+                // lets `flush_cookie` prove everything earlier in this same
+                // directory's notify stream has already been seen.
+                if let Some(seq) = parse_cookie_seq(&path) {
+                    if let Some(waiter) = pending_cookies_clone.lock().unwrap().remove(&seq) {
+                        let _ = waiter.send(());
+                    }
+                    continue;
+                }
+
                 // Filter by extension
                 let ext_ok = path
                     .extension()
@@ -86,6 +143,11 @@ impl SessionWatcher {
                     continue;
                 }
 
+                // Skip paths excluded by .longhouseignore / global ignore patterns
+                if ignore.is_ignored(&path) {
+                    continue;
+                }
+
                 // Bounded send — silently drop if channel full (fallback scan will catch it)
                 if watcher_tx.try_send(path).is_err() {
                     let n = dropped_clone.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
@@ -101,20 +163,118 @@ impl SessionWatcher {
         })?;
 
         // Watch all provider root directories recursively
+        let mut watching = HashSet::new();
         for provider in providers {
             if provider.root.exists() {
                 watcher.watch(&provider.root, RecursiveMode::Recursive)?;
                 tracing::info!("Watching {} for {} sessions", provider.root.display(), provider.name);
+                watching.insert(provider.root.clone());
             }
         }
 
         Ok(Self {
-            _watcher: watcher,
+            watcher,
             rx,
             dropped_events,
+            watching,
+            cookie_seq,
+            pending_cookies,
         })
     }
 
+    /// Re-register watches for provider roots that weren't present at
+    /// startup (or at the last call) and now exist — e.g. a directory that
+    /// was deleted and recreated, or a provider root that didn't exist yet
+    /// when the daemon started. Call this periodically (the fallback full
+    /// scan's cadence is a natural fit) alongside the event stream, the same
+    /// way `distant`'s watcher re-establishes watches after path loss.
+    pub fn reestablish_watches(&mut self, providers: &[ProviderConfig]) {
+        for provider in providers {
+            let root_exists = provider.root.exists();
+            if self.watching.contains(&provider.root) {
+                if !root_exists {
+                    // The watch itself is now dangling (inotify drops it when
+                    // the inode disappears); stop tracking so a later
+                    // recreate gets picked back up below.
+                    self.watching.remove(&provider.root);
+                }
+                continue;
+            }
+            if root_exists {
+                match self.watcher.watch(&provider.root, RecursiveMode::Recursive) {
+                    Ok(()) => {
+                        tracing::info!(
+                            "Re-established watch on {} for {} sessions",
+                            provider.root.display(),
+                            provider.name
+                        );
+                        self.watching.insert(provider.root.clone());
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to watch {}: {}", provider.root.display(), e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Roots currently registered with the OS watcher, for tests/diagnostics.
+    pub fn is_watching(&self, root: &Path) -> bool {
+        self.watching.contains(root)
+    }
+
+    /// Bound on how long `flush_cookie` waits for a single cookie to be
+    /// observed before giving up on it — a watched root that vanished
+    /// mid-write, or a saturated inotify queue, shouldn't hang shutdown
+    /// forever.
+    const COOKIE_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Block until every filesystem event enqueued, in any currently-watched
+    /// provider directory, before this call is guaranteed to have been
+    /// delivered into the event channel. Writes one uniquely-named sentinel
+    /// ("cookie") file per watched root and waits for the `notify` callback
+    /// to observe each one — `notify`/inotify preserve per-directory
+    /// ordering, so seeing the cookie proves every real event queued ahead
+    /// of it in that directory was already seen too.
+    ///
+    /// Used by the daemon's shutdown path to drain in-flight changes before
+    /// exiting, and lets tests assert "everything written
+    /// so far has been shipped" without guessing at a sleep duration.
+    pub async fn flush_cookie(&self) -> Result<()> {
+        let mut pending = Vec::with_capacity(self.watching.len());
+        for root in &self.watching {
+            let seq = self.cookie_seq.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+            let (tx, rx) = oneshot::channel();
+            self.pending_cookies.lock().unwrap().insert(seq, tx);
+            let cookie_path = root.join(cookie_file_name(seq));
+            std::fs::write(&cookie_path, b"")?;
+            pending.push((seq, cookie_path, rx));
+        }
+
+        for (seq, cookie_path, rx) in pending {
+            if tokio::time::timeout(Self::COOKIE_TIMEOUT, rx).await.is_err() {
+                tracing::warn!("Timed out waiting for watcher cookie {} to be observed", seq);
+                self.pending_cookies.lock().unwrap().remove(&seq);
+            }
+            let _ = std::fs::remove_file(&cookie_path);
+        }
+
+        Ok(())
+    }
+
+    /// Non-blocking drain of everything currently buffered in the event
+    /// channel, deduplicated the same way `next_batch` dedupes a timed
+    /// batch. Pairs with `flush_cookie`: once a cookie write is observed,
+    /// everything it vouches for is already sitting in the channel, so this
+    /// collects it without waiting out a `flush_interval`.
+    pub fn drain_ready(&mut self) -> Vec<PathBuf> {
+        let mut batch = HashSet::new();
+        while let Ok(path) = self.rx.try_recv() {
+            batch.insert(path);
+        }
+        batch.into_iter().collect()
+    }
+
     /// Collect changed paths for `flush_interval`, then return the deduplicated batch.
     ///
     /// This implements throttling (not debouncing): we always flush after the
@@ -180,4 +340,87 @@ mod tests {
         assert_eq!(rx.try_recv().unwrap(), PathBuf::from("/b"));
         assert!(rx.try_recv().is_err(), "Channel should be empty after drain");
     }
+
+    #[test]
+    fn test_reestablish_watches_picks_up_recreated_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("provider-root");
+        // Root doesn't exist yet at construction time — `new` skips it.
+        let providers = vec![ProviderConfig {
+            name: "test",
+            root: root.clone(),
+            extension: "jsonl",
+        }];
+        let ignore = IgnoreMatcher::gather(&providers, &[]).unwrap();
+        let mut watcher = SessionWatcher::new(&providers, &ignore).unwrap();
+        assert!(!watcher.is_watching(&root));
+
+        // Directory appears later — reestablish should pick it up.
+        std::fs::create_dir_all(&root).unwrap();
+        watcher.reestablish_watches(&providers);
+        assert!(watcher.is_watching(&root));
+
+        // Directory removed and recreated — still tracked as watched until
+        // the removal is observed.
+        std::fs::remove_dir_all(&root).unwrap();
+        watcher.reestablish_watches(&providers);
+        assert!(!watcher.is_watching(&root));
+
+        std::fs::create_dir_all(&root).unwrap();
+        watcher.reestablish_watches(&providers);
+        assert!(watcher.is_watching(&root));
+    }
+
+    #[test]
+    fn test_is_cookie_file_matches_only_cookie_names() {
+        assert!(is_cookie_file(Path::new("/tmp/sessions/.longhouse-cookie-42")));
+        assert!(!is_cookie_file(Path::new("/tmp/sessions/session.jsonl")));
+        assert!(!is_cookie_file(Path::new("/tmp/sessions/.swp")));
+    }
+
+    #[test]
+    fn test_drain_ready_collects_buffered_paths_without_blocking() {
+        let (tx, rx) = mpsc::channel(4);
+        tx.try_send(PathBuf::from("/a")).unwrap();
+        tx.try_send(PathBuf::from("/b")).unwrap();
+
+        let mut watcher = SessionWatcher {
+            watcher: notify::recommended_watcher(|_: notify::Result<notify::Event>| {}).unwrap(),
+            rx,
+            dropped_events: Arc::new(AtomicU64::new(0)),
+            watching: HashSet::new(),
+            cookie_seq: Arc::new(AtomicU64::new(0)),
+            pending_cookies: Arc::new(Mutex::new(HashMap::new())),
+        };
+
+        let mut paths = watcher.drain_ready();
+        paths.sort();
+        assert_eq!(paths, vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+        assert!(watcher.drain_ready().is_empty(), "channel should now be empty");
+    }
+
+    #[tokio::test]
+    async fn test_flush_cookie_resolves_once_observed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("provider-root");
+        std::fs::create_dir_all(&root).unwrap();
+        let providers = vec![ProviderConfig {
+            name: "test",
+            root: root.clone(),
+            extension: "jsonl",
+        }];
+        let ignore = IgnoreMatcher::gather(&providers, &[]).unwrap();
+        let watcher = SessionWatcher::new(&providers, &ignore).unwrap();
+
+        // Well inside COOKIE_TIMEOUT — a real filesystem + notify round trip
+        // that never resolves would hang this test at the outer timeout instead.
+        tokio::time::timeout(Duration::from_secs(5), watcher.flush_cookie())
+            .await
+            .expect("flush_cookie should not hang")
+            .unwrap();
+
+        // The cookie file is cleaned up once observed, not left behind.
+        let leftover: Vec<_> = std::fs::read_dir(&root).unwrap().collect();
+        assert!(leftover.is_empty(), "cookie file should be removed after flush_cookie");
+    }
 }
@@ -0,0 +1,242 @@
+//! Hierarchical workunit/span instrumentation for spool operations.
+//!
+//! Time spent validating a range, enqueuing it, waiting on backpressure, or
+//! backing off a retry is otherwise invisible — a stall could be downstream
+//! I/O or it could be `next_retry_at` backoff, and nothing distinguishes
+//! them. A [`Span`] wraps one such operation (enqueue, flush-to-sink,
+//! retry-scheduling) and records its name, nesting level, parent id, and a
+//! handful of cheap counters; completed spans are handed to a pluggable
+//! [`WorkunitSink`] on drop.
+//!
+//! Call sites opt in by accepting `workunit: Option<&Span>` (the enclosing
+//! span, if any) and calling [`Span::child`] to open their own nested span —
+//! the same `Option<&T>` shape already used for `tracker`/`breaker`, so
+//! adding a span anywhere new costs one parameter and one `.child()` call.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Process-unique id assigned to each [`Span`] at creation time.
+pub type WorkunitId = u64;
+
+/// Counters accumulated over a span's lifetime, snapshotted into its
+/// [`CompletedWorkunit`] when it finishes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WorkunitMetrics {
+    pub bytes: u64,
+    pub rows: u64,
+    pub retries: u64,
+    pub spool_full: bool,
+}
+
+/// A finished [`Span`], as handed to a [`WorkunitSink`].
+#[derive(Debug, Clone)]
+pub struct CompletedWorkunit {
+    pub id: WorkunitId,
+    pub parent_id: Option<WorkunitId>,
+    pub name: &'static str,
+    pub level: u32,
+    pub duration: Duration,
+    pub metrics: WorkunitMetrics,
+}
+
+/// Receives completed workunits for export to logs or metrics.
+pub trait WorkunitSink: Send + Sync {
+    fn record(&self, workunit: CompletedWorkunit);
+}
+
+/// Sink that logs each completed span at debug level via `tracing` — the
+/// default for call sites that don't need aggregation beyond what the log
+/// line itself carries.
+#[derive(Default)]
+pub struct LogSink;
+
+impl WorkunitSink for LogSink {
+    fn record(&self, workunit: CompletedWorkunit) {
+        tracing::debug!(
+            workunit_id = workunit.id,
+            parent_id = workunit.parent_id,
+            level = workunit.level,
+            duration_ms = workunit.duration.as_millis() as u64,
+            bytes = workunit.metrics.bytes,
+            rows = workunit.metrics.rows,
+            retries = workunit.metrics.retries,
+            spool_full = workunit.metrics.spool_full,
+            "{}",
+            workunit.name
+        );
+    }
+}
+
+/// RAII span covering one operation. Metrics are plain atomics (the same
+/// cheap-clone-free sharing `Metrics` uses in `metrics.rs`) so a `&Span`
+/// passed down through several layers of `Option<&Span>` parameters can
+/// still be written to without needing `&mut` at every call site.
+///
+/// Dropping the span (or calling [`Span::finish`]) records its elapsed time
+/// and accumulated metrics to the sink it was created with. A child opened
+/// via [`Span::child`] carries the parent's id as `parent_id`, so a sink can
+/// reconstruct the tree and attribute, say, a stall to `spool.retry` backoff
+/// rather than `shipper.flush_group` I/O.
+pub struct Span {
+    id: WorkunitId,
+    parent_id: Option<WorkunitId>,
+    name: &'static str,
+    level: u32,
+    started_at: Instant,
+    bytes: AtomicU64,
+    rows: AtomicU64,
+    retries: AtomicU64,
+    spool_full: AtomicBool,
+    sink: Arc<dyn WorkunitSink>,
+}
+
+impl Span {
+    /// Start a new root span (level 0, no parent) reporting to `sink` —
+    /// typically one per poll cycle, replay batch, or startup-recovery pass.
+    pub fn root(name: &'static str, sink: Arc<dyn WorkunitSink>) -> Self {
+        Self::new(name, 0, None, sink)
+    }
+
+    /// Open a child span nested one level under `self`, reporting to the
+    /// same sink.
+    pub fn child(&self, name: &'static str) -> Self {
+        Self::new(name, self.level + 1, Some(self.id), self.sink.clone())
+    }
+
+    fn new(name: &'static str, level: u32, parent_id: Option<WorkunitId>, sink: Arc<dyn WorkunitSink>) -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            parent_id,
+            name,
+            level,
+            started_at: Instant::now(),
+            bytes: AtomicU64::new(0),
+            rows: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            spool_full: AtomicBool::new(false),
+            sink,
+        }
+    }
+
+    pub fn id(&self) -> WorkunitId {
+        self.id
+    }
+
+    /// Add to this span's bytes-processed counter.
+    pub fn add_bytes(&self, n: u64) {
+        self.bytes.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Add to this span's rows-touched counter.
+    pub fn add_rows(&self, n: u64) {
+        self.rows.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Record one retry against this span.
+    pub fn add_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Mark that this span's operation found the spool at capacity.
+    pub fn mark_spool_full(&self) {
+        self.spool_full.store(true, Ordering::Relaxed);
+    }
+
+    fn metrics(&self) -> WorkunitMetrics {
+        WorkunitMetrics {
+            bytes: self.bytes.load(Ordering::Relaxed),
+            rows: self.rows.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            spool_full: self.spool_full.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Finish the span now instead of waiting for drop. Equivalent to
+    /// letting it go out of scope — provided for call sites that want to
+    /// close a span explicitly before doing more work in the same block.
+    pub fn finish(self) {
+        drop(self);
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        self.sink.record(CompletedWorkunit {
+            id: self.id,
+            parent_id: self.parent_id,
+            name: self.name,
+            level: self.level,
+            duration: self.started_at.elapsed(),
+            metrics: self.metrics(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct CollectingSink {
+        collected: Mutex<Vec<CompletedWorkunit>>,
+    }
+
+    impl WorkunitSink for CollectingSink {
+        fn record(&self, workunit: CompletedWorkunit) {
+            self.collected.lock().unwrap().push(workunit);
+        }
+    }
+
+    #[test]
+    fn test_root_span_records_on_drop() {
+        let sink = Arc::new(CollectingSink::default());
+        {
+            let span = Span::root("spool.enqueue", sink.clone());
+            span.add_bytes(128);
+            span.add_rows(1);
+        }
+        let collected = sink.collected.lock().unwrap();
+        assert_eq!(collected.len(), 1);
+        assert_eq!(collected[0].name, "spool.enqueue");
+        assert_eq!(collected[0].level, 0);
+        assert_eq!(collected[0].parent_id, None);
+        assert_eq!(collected[0].metrics.bytes, 128);
+        assert_eq!(collected[0].metrics.rows, 1);
+    }
+
+    #[test]
+    fn test_child_span_nests_under_parent() {
+        let sink = Arc::new(CollectingSink::default());
+        let root = Span::root("shipper.poll_cycle", sink.clone());
+        let root_id = root.id();
+        {
+            let child = root.child("shipper.flush_group");
+            child.add_retry();
+            assert_eq!(child.level, 1);
+        }
+        root.finish();
+
+        let collected = sink.collected.lock().unwrap();
+        assert_eq!(collected.len(), 2);
+        assert_eq!(collected[0].name, "shipper.flush_group");
+        assert_eq!(collected[0].parent_id, Some(root_id));
+        assert_eq!(collected[0].metrics.retries, 1);
+        assert_eq!(collected[1].name, "shipper.poll_cycle");
+        assert_eq!(collected[1].parent_id, None);
+    }
+
+    #[test]
+    fn test_mark_spool_full() {
+        let sink = Arc::new(CollectingSink::default());
+        {
+            let span = Span::root("spool.enqueue", sink.clone());
+            span.mark_spool_full();
+        }
+        assert!(sink.collected.lock().unwrap()[0].metrics.spool_full);
+    }
+}